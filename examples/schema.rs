@@ -1,11 +1,14 @@
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 use proofpay_contract::msg::{
     ExecuteMsg, InstantiateMsg, QueryMsg, UserResponse, UsersResponse, PaymentResponse, PaymentsResponse,
-    UsernameResponse, WalletResponse, HasUsernameResponse, UsernameAvailableResponse, TaskResponse, TasksResponse
+    UsernameResponse, WalletResponse, HasUsernameResponse, UsernameAvailableResponse, TaskResponse, TasksResponse,
+    CallbackExecuteMsg, YieldAdapterExecuteMsg, YieldAdapterQueryMsg, AdapterPositionResponse, OracleQueryMsg,
+    OraclePriceResponse, SignedPayload, ReceiptResponse, ErrorCode, ErrorCodesResponse
 };
-use proofpay_contract::state::{User, Payment, PaymentStatus, ProofType, State, Task, TaskStatus};
+use proofpay_contract::ContractError;
+use proofpay_contract::state::{User, Payment, PaymentStatus, ProofType, Receipt, State, Task, TaskStatus};
 use std::env::current_dir;
-use std::fs::create_dir_all;
+use std::fs::{create_dir_all, write};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -27,10 +30,32 @@ fn main() {
     export_schema(&schema_for!(User), &out_dir);
     export_schema(&schema_for!(Payment), &out_dir);
     export_schema(&schema_for!(PaymentStatus), &out_dir);
+    export_schema(&schema_for!(Receipt), &out_dir);
+    export_schema(&schema_for!(ReceiptResponse), &out_dir);
     export_schema(&schema_for!(ProofType), &out_dir);
     export_schema(&schema_for!(State), &out_dir);
     export_schema(&schema_for!(TaskResponse), &out_dir);
     export_schema(&schema_for!(TasksResponse), &out_dir);
     export_schema(&schema_for!(Task), &out_dir);
     export_schema(&schema_for!(TaskStatus), &out_dir);
+    export_schema(&schema_for!(CallbackExecuteMsg), &out_dir);
+    export_schema(&schema_for!(YieldAdapterExecuteMsg), &out_dir);
+    export_schema(&schema_for!(YieldAdapterQueryMsg), &out_dir);
+    export_schema(&schema_for!(AdapterPositionResponse), &out_dir);
+    export_schema(&schema_for!(OracleQueryMsg), &out_dir);
+    export_schema(&schema_for!(OraclePriceResponse), &out_dir);
+    export_schema(&schema_for!(SignedPayload), &out_dir);
+    export_schema(&schema_for!(ErrorCodesResponse), &out_dir);
+
+    // The error code catalog itself, matching the GetErrorCodes query, for SDKs mapping failures offline
+    let errors = ContractError::all()
+        .into_iter()
+        .map(|error| ErrorCode {
+            code: error.code(),
+            name: format!("{:?}", error),
+            message: error.to_string(),
+        })
+        .collect();
+    let catalog = cosmwasm_std::to_json_vec(&ErrorCodesResponse { errors }).unwrap();
+    write(out_dir.join("error_codes.json"), catalog).unwrap();
 }
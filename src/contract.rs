@@ -1,9 +1,10 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, BankMsg, Binary, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Order, Addr,
+    from_json, to_json_binary, to_json_string, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut, Empty, Env, MessageInfo, QuerierWrapper, Reply, Response, StdError, StdResult, Order, Addr, Storage, SubMsg, Uint128, WasmMsg,
 };
 use cw2::set_contract_version;
+use cw_storage_plus::{Bound, Map, PrimaryKey};
 
 use crate::error::ContractError;
 use crate::msg::*;
@@ -21,6 +22,7 @@ pub fn instantiate(
 ) -> Result<Response, ContractError> {
     let state = State {
         owner: info.sender.clone(),
+        pending_owner: None,
         next_payment_id: 1,
         next_task_id: 1,
     };
@@ -35,6 +37,27 @@ pub fn instantiate(
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    let sender = info.sender.clone();
+    let funds = info.funds.clone();
+    // Expired usernames can only be renewed or deactivated
+    if !matches!(msg, ExecuteMsg::RenewUsername {} | ExecuteMsg::DeactivateUser {}) {
+        ensure_username_current(deps.as_ref(), &env, &sender)?;
+    }
+    let response = dispatch_execute(deps.branch(), env.clone(), info, msg)?;
+    ensure_policy_allows(deps.as_ref(), &sender, &funds, &response)?;
+    
+    // Every successful execute is appended to the activity log for analytics export
+    record_activity(deps.storage, &env, &sender, &response)?;
+    
+    Ok(response)
+}
+
+fn dispatch_execute(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
@@ -45,13 +68,43 @@ pub fn execute(
         ExecuteMsg::RegisterUser { username, display_name } => {
             execute_register_user(deps, env, info, username, display_name)
         }
-        ExecuteMsg::UpdateUserProfile { display_name, profile_picture } => {
-            execute_update_user_profile(deps, env, info, display_name, profile_picture)
+        ExecuteMsg::UpdateUserProfile {
+            display_name,
+            profile_picture,
+            require_friendship_for_requests,
+            default_visibility,
+            bio,
+            links,
+            country,
+            timezone,
+            preferred_denom,
+            payout_slippage_bps,
+        } => execute_update_user_profile(
+            deps,
+            env,
+            info,
+            display_name,
+            profile_picture,
+            require_friendship_for_requests,
+            default_visibility,
+            bio,
+            links,
+            country,
+            timezone,
+            preferred_denom,
+            payout_slippage_bps,
+        ),
+        ExecuteMsg::RegisterPubkey { pubkey, proof_signature } => {
+            execute_register_pubkey(deps, env, info, pubkey, proof_signature)
         }
+        ExecuteMsg::RemovePubkey {} => execute_remove_pubkey(deps, env, info),
+        ExecuteMsg::DeactivateUser {} => execute_deactivate_user(deps, env, info),
+        ExecuteMsg::RenewUsername {} => execute_renew_username(deps, env, info),
+        ExecuteMsg::ReapExpiredUsernames { limit } => execute_reap_expired_usernames(deps, env, info, limit),
         
         // Friends System
-        ExecuteMsg::SendFriendRequest { to_username } => {
-            execute_send_friend_request(deps, env, info, to_username)
+        ExecuteMsg::SendFriendRequest { to_username, message } => {
+            execute_send_friend_request(deps, env, info, to_username, message)
         }
         ExecuteMsg::AcceptFriendRequest { from_username } => {
             execute_accept_friend_request(deps, env, info, from_username)
@@ -59,20 +112,40 @@ pub fn execute(
         ExecuteMsg::DeclineFriendRequest { from_username } => {
             execute_decline_friend_request(deps, env, info, from_username)
         }
-        ExecuteMsg::RemoveFriend { username } => {
-            execute_remove_friend(deps, env, info, username)
+        ExecuteMsg::RemoveFriend { username, leave_shared_groups } => {
+            execute_remove_friend(deps, env, info, username, leave_shared_groups)
+        }
+        
+        // Friend Groups
+        ExecuteMsg::CreateGroup { name } => execute_create_group(deps, env, info, name),
+        ExecuteMsg::AddToGroup { group_id, username } => {
+            execute_add_to_group(deps, env, info, group_id, username)
+        }
+        ExecuteMsg::RemoveFromGroup { group_id, username } => {
+            execute_remove_from_group(deps, env, info, group_id, username)
+        }
+        ExecuteMsg::RecordGroupExpense { group_id, amount, description, split_among } => {
+            execute_record_group_expense(deps, env, info, group_id, amount, description, split_among)
         }
+        ExecuteMsg::SettleUp { group_id } => execute_settle_up(deps, env, info, group_id),
         
         // Payment System
-        ExecuteMsg::SendDirectPayment { to_username, amount, description, proof_type } => {
-            execute_send_direct_payment(deps, env, info, to_username, amount, description, proof_type)
+        ExecuteMsg::SendDirectPayment { to_username, amount, description, proof_type, category, tags, visibility, external_ref, idempotency_key, release_delay_secs } => {
+            execute_send_direct_payment(deps, env, info, to_username, amount, description, proof_type, category, tags, visibility, external_ref, idempotency_key, release_delay_secs)
         }
-        ExecuteMsg::CreatePaymentRequest { to_username, amount, description, proof_type } => {
-            execute_create_payment_request(deps, env, info, to_username, amount, description, proof_type)
+        ExecuteMsg::CreatePaymentRequest { to_username, amount, description, proof_type, category, tags, visibility, price_quote, external_ref, idempotency_key } => {
+            execute_create_payment_request(deps, env, info, RequestPayer::Username(to_username), amount, description, proof_type, category, tags, visibility, price_quote, external_ref, idempotency_key)
+        }
+        ExecuteMsg::CreateWalletPaymentRequest { to_wallet, amount, description, proof_type, category, tags, visibility, price_quote, external_ref, idempotency_key } => {
+            let payer = request_payer_for_wallet(deps.as_ref(), &to_wallet)?;
+            execute_create_payment_request(deps, env, info, payer, amount, description, proof_type, category, tags, visibility, price_quote, external_ref, idempotency_key)
         }
         // Task System
-        ExecuteMsg::CreateTask { to_username, amount, description, proof_type, deadline_ts, review_window_secs, endpoint } => {
-            execute_create_task(deps, env, info, to_username, amount, description, proof_type, deadline_ts, review_window_secs, endpoint)
+        ExecuteMsg::CreateTask { to_username, amount, description, proof_type, deadline_ts, review_window_secs, endpoint, category, tags, auto_approve_after_secs, escrow_upfront, co_approvers, approvals_required, price_quote, external_ref, idempotency_key, arbiter, depends_on, payout_swap, release_delay_secs } => {
+            execute_create_task(deps, env, info, to_username, amount, description, proof_type, deadline_ts, review_window_secs, endpoint, category, tags, auto_approve_after_secs, escrow_upfront, co_approvers, approvals_required, price_quote, external_ref, idempotency_key, arbiter, depends_on, payout_swap, release_delay_secs)
+        }
+        ExecuteMsg::CoApproveTask { task_id } => {
+            execute_co_approve_task(deps, env, info, task_id)
         }
         ExecuteMsg::SubmitSoftEvidence { task_id, evidence_hash } => {
             execute_submit_soft_evidence(deps, env, info, task_id, evidence_hash)
@@ -83,18 +156,33 @@ pub fn execute(
         ExecuteMsg::ApproveTask { task_id } => {
             execute_approve_task(deps, env, info, task_id)
         }
-        ExecuteMsg::DisputeTask { task_id, reason_hash } => {
-            execute_dispute_task(deps, env, info, task_id, reason_hash)
+        ExecuteMsg::DisputeTask { task_id, reason, reason_hash } => {
+            execute_dispute_task(deps, env, info, task_id, reason, reason_hash)
         }
-        ExecuteMsg::ResolveDispute { task_id, decision } => {
-            execute_resolve_dispute(deps, env, info, task_id, decision)
+        ExecuteMsg::ResolveDispute { task_id, worker_bps } => {
+            execute_resolve_dispute(deps, env, info, task_id, worker_bps)
         }
         ExecuteMsg::RefundIfExpired { task_id } => {
             execute_refund_if_expired(deps, env, info, task_id)
         }
+        ExecuteMsg::AutoApproveIfSilent { task_id } => {
+            execute_auto_approve_if_silent(deps, env, info, task_id)
+        }
         ExecuteMsg::ReleaseIfWindowElapsed { task_id } => {
             execute_release_if_window_elapsed(deps, env, info, task_id)
         }
+        ExecuteMsg::CancelTask { task_id } => execute_cancel_task(deps, env, info, task_id),
+        ExecuteMsg::CreateTaskBatch { template, workers } => execute_create_task_batch(deps, env, info, template, workers),
+        ExecuteMsg::CancelTaskBatch { batch_id } => execute_cancel_task_batch(deps, env, info, batch_id),
+        ExecuteMsg::AcceptTask { task_id } => execute_accept_task(deps, env, info, task_id),
+        ExecuteMsg::DeclineTask { task_id } => execute_decline_task(deps, env, info, task_id),
+        ExecuteMsg::CounterOffer { task_id, amount } => execute_counter_offer(deps, env, info, task_id, amount),
+        ExecuteMsg::AcceptCounterOffer { task_id } => execute_accept_counter_offer(deps, env, info, task_id),
+        ExecuteMsg::AmendTask { task_id, additional_amount, new_description_hash, new_deadline } => {
+            execute_amend_task(deps, env, info, task_id, additional_amount, new_description_hash, new_deadline)
+        }
+        ExecuteMsg::AcceptAmendment { task_id } => execute_accept_amendment(deps, env, info, task_id),
+        ExecuteMsg::WithdrawAmendment { task_id } => execute_withdraw_amendment(deps, env, info, task_id),
         ExecuteMsg::SubmitProof { payment_id, proof_data } => {
             execute_submit_proof(deps, env, info, payment_id, proof_data)
         }
@@ -107,6 +195,148 @@ pub fn execute(
         ExecuteMsg::CancelPayment { payment_id } => {
             execute_cancel_payment(deps, env, info, payment_id)
         }
+        ExecuteMsg::DisputePayment { payment_id } => {
+            execute_dispute_payment(deps, env, info, payment_id)
+        }
+        ExecuteMsg::ReleasePayment { payment_id } => {
+            execute_release_payment(deps, env, info, payment_id)
+        }
+        ExecuteMsg::RemindPaymentRequest { payment_id } => {
+            execute_remind_payment_request(deps, env, info, payment_id)
+        }
+        
+        // Campaigns
+        ExecuteMsg::CreateCampaign { title, goal, deadline, beneficiary_username, all_or_nothing } => {
+            execute_create_campaign(deps, env, info, title, goal, deadline, beneficiary_username, all_or_nothing)
+        }
+        ExecuteMsg::Donate { campaign_id } => execute_donate(deps, env, info, campaign_id),
+        ExecuteMsg::WithdrawCampaign { campaign_id } => execute_withdraw_campaign(deps, env, info, campaign_id),
+        ExecuteMsg::ClaimCampaignRefund { campaign_id } => {
+            execute_claim_campaign_refund(deps, env, info, campaign_id)
+        }
+        
+        // Subscriptions
+        ExecuteMsg::CreateSubscription { to_username, amount, period, periods } => {
+            execute_create_subscription(deps, env, info, to_username, amount, period, periods)
+        }
+        ExecuteMsg::ClaimSubscription { subscription_id } => {
+            execute_claim_subscription(deps, env, info, subscription_id)
+        }
+        ExecuteMsg::CancelSubscription { subscription_id } => {
+            execute_cancel_subscription(deps, env, info, subscription_id)
+        }
+        
+        // Invoices
+        ExecuteMsg::CreateInvoice { to_username, line_items, due_ts } => {
+            execute_create_invoice(deps, env, info, to_username, line_items, due_ts)
+        }
+        ExecuteMsg::PayInvoice { invoice_id } => execute_pay_invoice(deps, env, info, invoice_id),
+        ExecuteMsg::CancelInvoice { invoice_id } => execute_cancel_invoice(deps, env, info, invoice_id),
+        
+        // Payment links
+        ExecuteMsg::CreatePaymentLink { amount, description, max_uses, expires_at } => {
+            execute_create_payment_link(deps, env, info, amount, description, max_uses, expires_at)
+        }
+        ExecuteMsg::PayLink { code } => execute_pay_link(deps, env, info, code),
+        ExecuteMsg::DisablePaymentLink { code } => execute_disable_payment_link(deps, env, info, code),
+        
+        // Scheduled payments
+        ExecuteMsg::SchedulePayment { to_username, amount, description, execute_at } => {
+            execute_schedule_payment(deps, env, info, to_username, amount, description, execute_at)
+        }
+        ExecuteMsg::ExecuteScheduledPayment { scheduled_id } => {
+            execute_execute_scheduled_payment(deps, env, info, scheduled_id)
+        }
+        ExecuteMsg::CancelScheduledPayment { scheduled_id } => {
+            execute_cancel_scheduled_payment(deps, env, info, scheduled_id)
+        }
+        
+        // Gift codes
+        ExecuteMsg::CreateGiftCode { amount, code_hash, expires_at } => {
+            execute_create_gift_code(deps, env, info, amount, code_hash, expires_at)
+        }
+        ExecuteMsg::ClaimGiftCode { code } => execute_claim_gift_code(deps, env, info, code),
+        ExecuteMsg::RefundGiftCode { gift_id } => execute_refund_gift_code(deps, env, info, gift_id),
+        
+        // Hash-timelocked payments
+        ExecuteMsg::CreateHtlc { to_username, amount, hashlock, timelock } => {
+            execute_create_htlc(deps, env, info, to_username, amount, hashlock, timelock)
+        }
+        ExecuteMsg::ClaimHtlc { htlc_id, preimage } => execute_claim_htlc(deps, env, info, htlc_id, preimage),
+        ExecuteMsg::RefundHtlc { htlc_id } => execute_refund_htlc(deps, env, info, htlc_id),
+        
+        // Admin
+        ExecuteMsg::AdminResolvePayment { payment_id, outcome, reason } => {
+            execute_admin_resolve_payment(deps, env, info, payment_id, outcome, reason)
+        }
+        ExecuteMsg::AdminCancelTask { task_id, reason } => {
+            execute_admin_cancel_task(deps, env, info, task_id, reason)
+        }
+        ExecuteMsg::TransferOwnership { new_owner } => execute_transfer_ownership(deps, info, new_owner),
+        ExecuteMsg::AcceptOwnership {} => execute_accept_ownership(deps, info),
+        ExecuteMsg::CancelOwnershipTransfer {} => execute_cancel_ownership_transfer(deps, info),
+        ExecuteMsg::GrantRole { role, address } => execute_grant_role(deps, info, role, address),
+        ExecuteMsg::RevokeRole { role, address } => execute_revoke_role(deps, info, role, address),
+        ExecuteMsg::SetUserBadge { username, badge } => execute_set_user_badge(deps, env, info, username, badge),
+        ExecuteMsg::UpdateConfig { co_approval_thresholds, review_window, archive_retention_secs, accepted_denoms, instant_release, friend_requests, text_limits, open_item_limits } => {
+            execute_update_config(deps, info, co_approval_thresholds, review_window, archive_retention_secs, accepted_denoms, instant_release, friend_requests, text_limits, open_item_limits)
+        }
+        ExecuteMsg::SetYieldConfig { config } => execute_set_yield_config(deps, info, config),
+        ExecuteMsg::SetPriceOracle { oracle } => execute_set_price_oracle(deps, info, oracle),
+        ExecuteMsg::SetFeatureFlags { disabled_payment_types, disabled_proof_types } => {
+            execute_set_feature_flags(deps, info, disabled_payment_types, disabled_proof_types)
+        }
+        ExecuteMsg::SetPolicyContract { policy } => execute_set_policy_contract(deps, info, policy),
+        ExecuteMsg::SetDexRouter { router } => execute_set_dex_router(deps, info, router),
+        ExecuteMsg::SetRegistrationFees { config } => execute_set_registration_fees(deps, info, config),
+        ExecuteMsg::SetUsernameExpiry { config } => execute_set_username_expiry(deps, info, config),
+        
+        // Notifications
+        ExecuteMsg::AckNotifications { up_to_id } => execute_ack_notifications(deps, info, up_to_id),
+        
+        // Privacy
+        ExecuteMsg::RedactRecordText { kind, id } => {
+            execute_redact_record_text(deps, env, info, kind, id)
+        }
+        
+        // Completion Callbacks
+        ExecuteMsg::RegisterCallback { kind, id, contract_addr, msg } => {
+            execute_register_callback(deps, env, info, kind, id, contract_addr, msg)
+        }
+        ExecuteMsg::RebuildIndex { index, start_after, limit } => {
+            execute_rebuild_index(deps, env, info, index, start_after, limit)
+        }
+        ExecuteMsg::ArchiveRecords { up_to_ts, limit } => execute_archive_records(deps, env, info, up_to_ts, limit),
+        
+        // Insurance Pool
+        ExecuteMsg::SetInsuranceConfig { config } => execute_set_insurance_config(deps, info, config),
+        ExecuteMsg::FundInsurancePool {} => execute_fund_insurance_pool(deps, info),
+        ExecuteMsg::InsureTask { task_id } => execute_insure_task(deps, env, info, task_id),
+        ExecuteMsg::PayInsuranceClaim { task_id, amount } => {
+            execute_pay_insurance_claim(deps, env, info, task_id, amount)
+        }
+        
+        // Arbiters
+        ExecuteMsg::SetArbiterConfig { config } => execute_set_arbiter_config(deps, info, config),
+        ExecuteMsg::BondArbiter {} => execute_bond_arbiter(deps, info),
+        ExecuteMsg::UnbondArbiter {} => execute_unbond_arbiter(deps, info),
+        ExecuteMsg::AcceptArbitration { task_id } => execute_accept_arbitration(deps, env, info, task_id),
+        ExecuteMsg::DeclineArbitration { task_id } => execute_decline_arbitration(deps, env, info, task_id),
+        
+        // Relayed Execution
+        ExecuteMsg::SetRelayer { address, authorized } => execute_set_relayer(deps, info, address, authorized),
+        ExecuteMsg::ExecuteSigned { payload, signature } => execute_signed(deps, env, info, payload, signature),
+        
+        // Username NFTs
+        ExecuteMsg::SetUsernameCollection { collection } => execute_set_username_collection(deps, info, collection),
+        ExecuteMsg::TokenizeUsername {} => execute_tokenize_username(deps, env, info),
+        ExecuteMsg::ReceiveNft(receive) => execute_receive_username_nft(deps, env, info, receive),
+        ExecuteMsg::FinalizeUsernameTransfer { username } => {
+            execute_finalize_username_transfer(deps, env, info, username)
+        }
+        ExecuteMsg::CancelUsernameTransfer { username } => {
+            execute_cancel_username_transfer(deps, info, username)
+        }
     }
 }
 
@@ -129,6 +359,39 @@ fn validate_username(username: &str) -> Result<(), ContractError> {
     Ok(())
 }
 
+// Free text whose maximum length is set by TextLimits
+#[derive(Clone, Copy)]
+enum TextKind {
+    Description,
+    Evidence,
+    Endpoint,
+}
+
+// Helper function to enforce the configured maximum length of a free-text field
+fn validate_text_length(storage: &dyn Storage, kind: TextKind, text: &str) -> Result<(), ContractError> {
+    let limits = CONFIG.may_load(storage)?.unwrap_or_default().text_limits;
+    let (max, err) = match kind {
+        TextKind::Description => (limits.description, ContractError::DescriptionTooLong {}),
+        TextKind::Evidence => (limits.evidence, ContractError::EvidenceTooLong {}),
+        TextKind::Endpoint => (limits.endpoint, ContractError::EndpointTooLong {}),
+    };
+    if text.chars().count() > max as usize {
+        return Err(err);
+    }
+    Ok(())
+}
+
+// Helper function to validate an address and return its canonical form, e.g. lowercase bech32
+fn validate_address(api: &dyn Api, address: &str) -> Result<Addr, ContractError> {
+    let canonical = api.addr_canonicalize(address).map_err(|_| ContractError::InvalidAddress {})?;
+    api.addr_humanize(&canonical).map_err(|_| ContractError::InvalidAddress {})
+}
+
+// Helper function to validate an address given to a query
+fn validate_query_address(api: &dyn Api, address: &str) -> StdResult<Addr> {
+    validate_address(api, address).map_err(|err| StdError::generic_err(err.to_string()))
+}
+
 // Helper function to normalize username (convert to lowercase for case-insensitive checking)
 fn normalize_username(username: &str) -> String {
     username.to_lowercase()
@@ -140,156 +403,1611 @@ fn get_username_from_wallet(deps: &DepsMut, wallet: &Addr) -> Result<String, Con
         .map_err(|_| ContractError::UserNotRegistered {})
 }
 
-// USER MANAGEMENT FUNCTIONS
-
-pub fn execute_register_user(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-    username: String,
-    display_name: String,
-) -> Result<Response, ContractError> {
-    // Validate username format
-    validate_username(&username)?;
+// Helper function to append a successful execute to the activity log
+fn record_activity(storage: &mut dyn Storage, env: &Env, sender: &Addr, response: &Response) -> StdResult<()> {
+    let seq = ACTIVITY_SEQ.may_load(storage)?.unwrap_or_default() + 1;
+    let action = response.attributes.iter()
+        .find(|attr| attr.key == "action")
+        .map(|attr| attr.value.clone())
+        .unwrap_or_default();
     
-    // Normalize username for case-insensitive checking
-    let normalized_username = normalize_username(&username);
+    let entry = ActivityEntry {
+        seq,
+        action,
+        sender: sender.clone(),
+        attributes: response.attributes.clone(),
+        block_height: env.block.height,
+        timestamp: env.block.time.seconds(),
+    };
     
-    // Check if username is already taken (case-insensitive)
-    if USERS_BY_USERNAME.may_load(deps.storage, normalized_username.clone())?.is_some() {
-        return Err(ContractError::UsernameAlreadyTaken {});
+    ACTIVITY_LOG.save(storage, seq, &entry)?;
+    ACTIVITY_SEQ.save(storage, &seq)
+}
+
+// Helper function to queue a notification in a user's inbox
+fn notify(
+    storage: &mut dyn Storage,
+    env: &Env,
+    username: &str,
+    kind: NotificationKind,
+    from_username: &str,
+    record_id: Option<u64>,
+) -> StdResult<()> {
+    let id = NOTIFICATION_SEQ.may_load(storage)?.unwrap_or_default() + 1;
+    NOTIFICATION_SEQ.save(storage, &id)?;
+    NOTIFICATIONS.save(storage, (username.to_string(), id), &Notification {
+        id,
+        kind,
+        from_username: from_username.to_string(),
+        record_id,
+        created_at: env.block.time.seconds(),
+    })
+}
+
+// Reply id for completion callbacks dispatched to creator-registered contracts
+const CALLBACK_REPLY_ID: u64 = 1;
+// Payout swaps each take their own reply id from this offset so replies find their swap
+const PAYOUT_SWAP_REPLY_ID_START: u64 = 1 << 32;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    // Callbacks are dispatched with reply_on_error so a failing receiver never blocks settlement
+    match msg.id {
+        CALLBACK_REPLY_ID => {
+            let error = msg.result.into_result().err().unwrap_or_default();
+            Ok(Response::new()
+                .add_attribute("action", "callback_failed")
+                .add_attribute("error", error))
+        }
+        id if id >= PAYOUT_SWAP_REPLY_ID_START => {
+            let swap = PENDING_PAYOUT_SWAPS.may_load(deps.storage, id)?
+                .ok_or_else(|| StdError::generic_err("No pending payout swap"))?;
+            PENDING_PAYOUT_SWAPS.remove(deps.storage, id);
+            match msg.result.into_result() {
+                Ok(_) => Ok(Response::new()
+                    .add_attribute("action", "payout_swap")
+                    .add_attribute("task_id", swap.task_id.to_string())),
+                Err(error) => {
+                    let response = Response::new()
+                        .add_message(BankMsg::Send {
+                            to_address: swap.recipient.to_string(),
+                            amount: vec![swap.amount.clone()],
+                        })
+                        .add_attribute("action", "payout_swap_failed")
+                        .add_attribute("task_id", swap.task_id.to_string())
+                        .add_attribute("amount", swap.amount.to_string())
+                        .add_attribute("error", error);
+                    // The unconverted payout is checked like any other, with the contract as sender
+                    ensure_policy_allows(deps.as_ref(), &env.contract.address, &[], &response)?;
+                    Ok(response)
+                }
+            }
+        }
+        _ => Err(ContractError::Std(StdError::generic_err("Unknown reply id"))),
     }
-    
-    // Check if wallet is already registered
-    if USERS_BY_WALLET.may_load(deps.storage, info.sender.clone())?.is_some() {
-        return Err(ContractError::WalletAlreadyRegistered {});
+}
+
+// Helper function to enforce a target user's friends-only setting for incoming requests
+fn ensure_request_allowed(storage: &dyn Storage, from_username: &str, target: &User) -> Result<(), ContractError> {
+    if target.require_friendship_for_requests &&
+       FRIENDSHIPS.may_load(storage, (from_username.to_string(), target.username.clone()))?.is_none() {
+        return Err(ContractError::CannotRequestNonFriend {});
     }
-    
-    let user = User {
-        wallet_address: info.sender.clone(),
-        username: normalized_username.clone(),
-        display_name,
-        profile_picture: None,
-        created_at: env.block.time.seconds(),
-        updated_at: env.block.time.seconds(),
+    Ok(())
+}
+
+// Maximum number of tags on a payment or task
+const MAX_TAGS: usize = 5;
+// Maximum length of a category or tag
+const MAX_LABEL_LENGTH: usize = 32;
+
+// Maximum length of an external reference, e.g. an invoice number
+const MAX_EXTERNAL_REF_LENGTH: usize = 64;
+
+// Helper function to reserve a new record's external reference; references are unique per creator
+fn claim_external_ref(
+    storage: &mut dyn Storage,
+    refs: &Map<(String, String), u64>,
+    creator: &str,
+    external_ref: &Option<String>,
+    id: u64,
+) -> Result<(), ContractError> {
+    let external_ref = match external_ref {
+        Some(external_ref) => external_ref,
+        None => return Ok(()),
     };
-    
-    // Save user data using normalized username
-    USERS_BY_USERNAME.save(deps.storage, normalized_username.clone(), &user)?;
-    USERS_BY_WALLET.save(deps.storage, info.sender.clone(), &normalized_username)?;
-    
-    Ok(Response::new()
-        .add_attribute("action", "register_user")
-        .add_attribute("username", &normalized_username)
-        .add_attribute("wallet", info.sender.as_str())
-        .add_event(
-            cosmwasm_std::Event::new("username_registered")
-                .add_attribute("wallet", info.sender.as_str())
-                .add_attribute("username", &normalized_username)
-        ))
+    if external_ref.trim().is_empty() || external_ref.len() > MAX_EXTERNAL_REF_LENGTH {
+        return Err(ContractError::InvalidExternalRef {});
+    }
+    let key = (creator.to_string(), external_ref.clone());
+    if refs.has(storage, key.clone()) {
+        return Err(ContractError::ExternalRefTaken {});
+    }
+    refs.save(storage, key, &id)?;
+    Ok(())
 }
 
-pub fn execute_update_user_profile(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-    display_name: Option<String>,
-    profile_picture: Option<String>,
-) -> Result<Response, ContractError> {
-    let username = get_username_from_wallet(&deps, &info.sender)?;
-    
-    USERS_BY_USERNAME.update(deps.storage, username.clone(), |user| -> Result<_, ContractError> {
-        let mut user = user.ok_or(ContractError::UserNotFound {})?;
-        
-        if let Some(new_display_name) = display_name {
-            user.display_name = new_display_name;
+// How long an idempotency key keeps returning the record it created
+const IDEMPOTENCY_TTL_SECS: u64 = 86400;
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 64;
+
+// Helper function to find the record a live idempotency key of `sender` already created
+fn replayed_record(
+    storage: &dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    key: &Option<String>,
+    kind: RecordKind,
+) -> Result<Option<u64>, ContractError> {
+    let key = match key {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+    if key.trim().is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+        return Err(ContractError::InvalidIdempotencyKey {});
+    }
+    match IDEMPOTENCY_KEYS.may_load(storage, (sender.clone(), key.clone()))? {
+        Some(record) if record.expires_at > env.block.time.seconds() => {
+            if record.kind != kind {
+                return Err(ContractError::IdempotencyKeyConflict {});
+            }
+            Ok(Some(record.id))
         }
-        
-        if let Some(new_profile_picture) = profile_picture {
-            user.profile_picture = Some(new_profile_picture);
+        _ => Ok(None),
+    }
+}
+
+// Helper function to remember the record an idempotency key created
+fn save_idempotency_key(
+    storage: &mut dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    key: Option<String>,
+    kind: RecordKind,
+    id: u64,
+) -> StdResult<()> {
+    if let Some(key) = key {
+        // An expired record under the same key is replaced along with its expiry entry
+        if let Some(previous) = IDEMPOTENCY_KEYS.may_load(storage, (sender.clone(), key.clone()))? {
+            IDEMPOTENCY_EXPIRIES.remove(storage, (previous.expires_at, sender.clone(), key.clone()));
         }
-        
-        user.updated_at = env.block.time.seconds();
-        
-        Ok(user)
-    })?;
-    
-    Ok(Response::new()
-        .add_attribute("action", "update_user_profile")
-        .add_attribute("username", username))
+        let record = IdempotencyRecord {
+            kind,
+            id,
+            expires_at: env.block.time.seconds() + IDEMPOTENCY_TTL_SECS,
+        };
+        IDEMPOTENCY_EXPIRIES.save(storage, (record.expires_at, sender.clone(), key.clone()), &true)?;
+        IDEMPOTENCY_KEYS.save(storage, (sender.clone(), key), &record)?;
+    }
+    Ok(())
 }
 
-// FRIENDS SYSTEM FUNCTIONS
+// Helper function to delete up to `limit` expired idempotency keys, oldest first
+fn reap_idempotency_keys(storage: &mut dyn Storage, env: &Env, limit: usize) -> StdResult<usize> {
+    let now = env.block.time.seconds();
+    let expired: Vec<(u64, Addr, String)> = IDEMPOTENCY_EXPIRIES
+        .keys(storage, None, Some(Bound::exclusive((now + 1, Addr::unchecked(""), String::new()))), Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+    for (expires_at, sender, key) in &expired {
+        IDEMPOTENCY_EXPIRIES.remove(storage, (*expires_at, sender.clone(), key.clone()));
+        IDEMPOTENCY_KEYS.remove(storage, (sender.clone(), key.clone()));
+    }
+    Ok(expired.len())
+}
 
-pub fn execute_send_friend_request(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-    to_username: String,
-) -> Result<Response, ContractError> {
-    let from_username = get_username_from_wallet(&deps, &info.sender)?;
-    let normalized_to_username = normalize_username(&to_username);
-    
-    // Check if trying to add self
-    if from_username == normalized_to_username {
-        return Err(ContractError::CannotAddSelf {});
+// Helper function to answer a replayed create message with the original record's id
+fn idempotent_replay(info: &MessageInfo, action: &str, id_key: &str, id: u64) -> Response {
+    // The original message already took its funds; return whatever the retry attached
+    let refund = (!info.funds.is_empty()).then(|| BankMsg::Send {
+        to_address: info.sender.to_string(),
+        amount: info.funds.clone(),
+    });
+    Response::new()
+        .add_messages(refund)
+        .add_attribute("action", action)
+        .add_attribute(id_key, id.to_string())
+        .add_attribute("idempotent_replay", "true")
+}
+
+// Helper function to validate payment/task category and tags
+fn validate_labels(category: &Option<String>, tags: &[String]) -> Result<(), ContractError> {
+    if let Some(category) = category {
+        if category.trim().is_empty() || category.len() > MAX_LABEL_LENGTH {
+            return Err(ContractError::InvalidCategory {});
+        }
     }
     
-    // Check if target user exists
-    if USERS_BY_USERNAME.may_load(deps.storage, normalized_to_username.clone())?.is_none() {
-        return Err(ContractError::UserNotFound {});
+    if tags.len() > MAX_TAGS ||
+       tags.iter().any(|tag| tag.trim().is_empty() || tag.len() > MAX_LABEL_LENGTH) {
+        return Err(ContractError::InvalidTags {});
     }
     
-    // Check if already friends
-    let friendship_key1 = (from_username.clone(), normalized_to_username.clone());
-    let friendship_key2 = (normalized_to_username.clone(), from_username.clone());
-    
-    if FRIENDSHIPS.may_load(deps.storage, friendship_key1)?.is_some() ||
-       FRIENDSHIPS.may_load(deps.storage, friendship_key2)?.is_some() {
-        return Err(ContractError::AlreadyFriends {});
+    Ok(())
+}
+
+// Helper function to apply a user's default visibility when none was given
+fn resolve_visibility(storage: &dyn Storage, username: &str, visibility: Option<Visibility>) -> StdResult<Visibility> {
+    match visibility {
+        Some(visibility) => Ok(visibility),
+        None => Ok(USERS_BY_USERNAME.load(storage, username.to_string())?.default_visibility),
     }
-    
-    // Check if friend request already exists
-    let request_key = (from_username.clone(), normalized_to_username.clone());
-    if FRIEND_REQUESTS.may_load(deps.storage, request_key.clone())?.is_some() {
-        return Err(ContractError::FriendRequestAlreadyExists {});
+}
+
+// Helper function to check whether `viewer` (a username, if any) may see a payment.
+// Queries are unauthenticated, so this only shapes what honest clients display.
+fn payment_visible_to(storage: &dyn Storage, payment: &Payment, viewer: Option<&str>) -> StdResult<bool> {
+    parties_visible_to(storage, payment.visibility, &payment.from_username, &payment.to_username, viewer)
+}
+
+// Helper function to apply a visibility setting to a record between two parties
+fn parties_visible_to(
+    storage: &dyn Storage,
+    visibility: Visibility,
+    from_username: &str,
+    to_username: &str,
+    viewer: Option<&str>,
+) -> StdResult<bool> {
+    let viewer = match (visibility, viewer) {
+        (Visibility::Public, _) => return Ok(true),
+        (_, None) => return Ok(false),
+        (_, Some(viewer)) => viewer,
+    };
+    if from_username == viewer || to_username == viewer {
+        return Ok(true);
     }
-    
-    let friend_request = FriendRequest {
-        from_username: from_username.clone(),
-        to_username: normalized_to_username.clone(),
-        status: FriendRequestStatus::Pending,
-        created_at: env.block.time.seconds(),
-        updated_at: env.block.time.seconds(),
+    Ok(matches!(visibility, Visibility::Friends) && (
+        FRIENDSHIPS.has(storage, (viewer.to_string(), from_username.to_string())) ||
+        FRIENDSHIPS.has(storage, (viewer.to_string(), to_username.to_string()))
+    ))
+}
+
+// Helper function to index two users as counterparties of a completed payment or task
+fn record_counterparties(storage: &mut dyn Storage, env: &Env, username_a: &str, username_b: &str) -> StdResult<()> {
+    let now = env.block.time.seconds();
+    COUNTERPARTIES.save(storage, (username_a.to_string(), username_b.to_string()), &now)?;
+    COUNTERPARTIES.save(storage, (username_b.to_string(), username_a.to_string()), &now)?;
+    Ok(())
+}
+
+// Helper function to credit a worker whose task was paid out in full
+fn record_task_paid_in_full(storage: &mut dyn Storage, worker: &str) -> StdResult<()> {
+    WORKER_REPUTATION.update(storage, worker.to_string(), |reputation| -> StdResult<_> {
+        Ok(reputation.unwrap_or_default() + 1)
+    })?;
+    Ok(())
+}
+
+// Helper function to check whether a verified hybrid task can skip its review window
+fn releases_instantly(storage: &dyn Storage, task: &Task) -> StdResult<bool> {
+    if !matches!(task.proof_type, ProofType::Hybrid)
+        || co_approvals_missing(task) > 0
+        || task.agreed_arbiter.is_some()
+        || task.release_delay_secs.is_some()
+    {
+        return Ok(false);
+    }
+    let config = CONFIG.may_load(storage)?.unwrap_or_default().instant_release;
+    let Some(max_amount) = config.max_amounts.iter().find(|max| max.denom == task.amount.denom) else {
+        return Ok(false);
     };
-    
-    FRIEND_REQUESTS.save(deps.storage, request_key, &friend_request)?;
-    
-    Ok(Response::new()
-        .add_attribute("action", "send_friend_request")
-        .add_attribute("from_username", from_username)
-        .add_attribute("to_username", normalized_to_username))
+    let reputation = WORKER_REPUTATION.may_load(storage, task.worker.clone())?.unwrap_or_default();
+    Ok(reputation >= config.min_reputation && task.amount.amount <= max_amount.amount)
 }
 
-pub fn execute_accept_friend_request(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-    from_username: String,
-) -> Result<Response, ContractError> {
-    let to_username = get_username_from_wallet(&deps, &info.sender)?;
-    
-    let request_key = (from_username.clone(), to_username.clone());
-    let _friend_request = FRIEND_REQUESTS.load(deps.storage, request_key.clone())
-        .map_err(|_| ContractError::FriendRequestNotFound {})?;
+// Helper function to store the receipt of a payment that reached a terminal status; returns its hash
+fn issue_receipt(storage: &mut dyn Storage, payment: &Payment) -> StdResult<String> {
+    let receipt = Receipt {
+        payment_id: payment.id,
+        from_username: payment.from_username.clone(),
+        to_username: payment.to_username.clone(),
+        amount: payment.amount.clone(),
+        created_at: payment.created_at,
+        settled_at: payment.updated_at,
+        proof_hash: payment.proof_data.as_deref().map(hash_data),
+        resolution: payment.status.clone(),
+        visibility: payment.visibility,
+    };
+    let hash = hash_data(&to_json_string(&receipt)?);
+    RECEIPTS.save(storage, payment.id, &receipt)?;
+    RECEIPT_HASHES.save(storage, payment.id, &hash)?;
+    Ok(hash)
+}
+
+// Helper function to check a denom is on the accepted list
+fn ensure_denom_accepted(storage: &dyn Storage, denom: &str) -> Result<(), ContractError> {
+    match CONFIG.may_load(storage)?.unwrap_or_default().accepted_denoms {
+        AcceptedDenoms::Only(denoms) if !denoms.iter().any(|accepted| accepted == denom) => {
+            Err(ContractError::DenomNotAccepted {})
+        }
+        _ => Ok(()),
+    }
+}
+
+// Helper function to check attached funds cover the expected coin, returning a refund of any surplus
+fn validate_funds(storage: &dyn Storage, info: &MessageInfo, expected: &Coin) -> Result<Option<BankMsg>, ContractError> {
+    ensure_denom_accepted(storage, &expected.denom)?;
+    if info.funds.iter().any(|coin| coin.denom != expected.denom && !coin.amount.is_zero()) {
+        return Err(ContractError::UnexpectedFunds {});
+    }
     
-    // Update friend request status
-    FRIEND_REQUESTS.update(deps.storage, request_key.clone(), |req| -> Result<_, ContractError> {
-        let mut req = req.ok_or(ContractError::FriendRequestNotFound {})?;
-        req.status = FriendRequestStatus::Accepted;
-        req.updated_at = env.block.time.seconds();
-        Ok(req)
-    })?;
+    let sent_amount: Uint128 = info.funds.iter()
+        .filter(|coin| coin.denom == expected.denom)
+        .map(|coin| coin.amount)
+        .sum();
+    
+    if sent_amount < expected.amount {
+        return Err(ContractError::InsufficientFunds {});
+    }
+    
+    let surplus = sent_amount - expected.amount;
+    Ok((!surplus.is_zero()).then(|| BankMsg::Send {
+        to_address: info.sender.to_string(),
+        amount: vec![Coin { denom: expected.denom.clone(), amount: surplus }],
+    }))
+}
+
+// Helper function to reject funds attached to an execute that takes no payment
+fn ensure_no_funds(info: &MessageInfo) -> Result<(), ContractError> {
+    if info.funds.iter().any(|coin| !coin.amount.is_zero()) {
+        return Err(ContractError::UnexpectedFunds {});
+    }
+    Ok(())
+}
+
+// Helper function to bump a dispute statistics counter
+fn increment_dispute_count(storage: &mut dyn Storage, counts: &Map<String, u64>, key: &str) -> StdResult<()> {
+    counts.update(storage, key.to_string(), |count| -> StdResult<_> {
+        Ok(count.unwrap_or_default() + 1)
+    })?;
+    Ok(())
+}
+
+// Helper function to check whether a task's funds are held by the contract
+fn task_holds_escrow(task: &Task) -> bool {
+    // Soft tasks are funded at approval unless the payer chose to escrow upfront
+    !matches!(task.proof_type, ProofType::Soft) || task.escrow_upfront
+}
+
+// Helper function to check whether a payment's funds are held by the contract
+fn payment_holds_escrow(payment: &Payment) -> bool {
+    // Direct payments without proof are sent immediately; payment requests are funded at approval
+    matches!(payment.payment_type, PaymentType::DirectPayment) &&
+        !matches!(payment.proof_type, ProofType::None)
+}
+
+// Helper function to save a task and move its status index entries to its current status
+fn save_task(storage: &mut dyn Storage, task_id: u64, task: &Task) -> StdResult<()> {
+    let previous_status = TASKS.may_load(storage, task_id)?.map(|previous| {
+        remove_task_status_index(storage, task_id, &previous);
+        previous.status
+    });
+    let was_open = previous_status.as_ref().is_some_and(task_open);
+    if was_open != task_open(&task.status) {
+        adjust_open_count(storage, &OPEN_TASK_COUNTS, task.payer.clone(), !was_open)?;
+    }
+    let status = task.status.key().to_string();
+    TASKS_BY_STATUS.save(storage, (status.clone(), task_id), &true)?;
+    USER_TASKS_BY_STATUS.save(storage, (task.payer.clone(), status.clone(), task_id), &true)?;
+    USER_TASKS_BY_STATUS.save(storage, (task.worker.clone(), status, task_id), &true)?;
+    TASKS.save(storage, task_id, task)?;
+    
+    // Blocked tasks are indexed under their dependency until it is released or they are cancelled
+    if let Some(dependency_id) = task.depends_on {
+        if matches!(task.status, TaskStatus::Blocked) {
+            TASK_DEPENDENTS.save(storage, (dependency_id, task_id), &true)?;
+        } else if matches!(previous_status, Some(TaskStatus::Blocked)) {
+            TASK_DEPENDENTS.remove(storage, (dependency_id, task_id));
+        }
+    }
+    if matches!(task.status, TaskStatus::Released) && !matches!(previous_status, Some(TaskStatus::Released)) {
+        activate_dependent_tasks(storage, task_id, task)?;
+    }
+    Ok(())
+}
+
+// Helper function to tell whether a task counts toward its payer's open task limit
+fn task_open(status: &TaskStatus) -> bool {
+    !matches!(status, TaskStatus::Released | TaskStatus::Refunded)
+}
+
+// Helper function to save a payment, keeping its creator's open payment request counts and
+// the parties' open payment index in step
+fn save_payment(storage: &mut dyn Storage, payment_id: u64, payment: &Payment) -> StdResult<()> {
+    let previous = PAYMENTS.may_load(storage, payment_id)?;
+    let was_open = previous.as_ref().is_some_and(payment_request_open);
+    if was_open != payment_request_open(payment) {
+        adjust_open_count(storage, &OPEN_PAYMENT_REQUEST_COUNTS, payment.from_username.clone(), !was_open)?;
+    }
+    let was_waiting = previous.as_ref().and_then(wallet_request_waiting);
+    let waiting = wallet_request_waiting(payment);
+    if was_waiting != waiting {
+        if let Some(key) = was_waiting {
+            adjust_open_count(storage, &WALLET_PAYMENT_REQUEST_COUNTS, key, false)?;
+        }
+        if let Some(key) = waiting {
+            adjust_open_count(storage, &WALLET_PAYMENT_REQUEST_COUNTS, key, true)?;
+        }
+    }
+    if let Some(previous) = previous.filter(payment_open) {
+        index_open_payment(storage, payment_id, &previous, false)?;
+    }
+    if payment_open(payment) {
+        index_open_payment(storage, payment_id, payment, true)?;
+    } else {
+        close_group_settlement(storage, payment_id, payment)?;
+    }
+    PAYMENTS.save(storage, payment_id, payment)
+}
+
+// Helper function to settle a group ledger once a settle-up request is paid; a rejected or
+// cancelled request leaves the debt on the ledger
+fn close_group_settlement(storage: &mut dyn Storage, payment_id: u64, payment: &Payment) -> StdResult<()> {
+    let Some(group_id) = SETTLEMENT_GROUPS.may_load(storage, payment_id)? else {
+        return Ok(());
+    };
+    SETTLEMENT_GROUPS.remove(storage, payment_id);
+    GROUP_SETTLEMENTS.remove(storage, (group_id, payment_id));
+    if matches!(payment.status, PaymentStatus::Completed) {
+        let amount = payment.amount.amount;
+        update_group_balance(storage, group_id, &payment.to_username, &payment.amount.denom, amount, Uint128::zero())?;
+        update_group_balance(storage, group_id, &payment.from_username, &payment.amount.denom, Uint128::zero(), amount)?;
+    }
+    Ok(())
+}
+
+// Helper function to add or drop a payment's entries in both parties' open payment index
+fn index_open_payment(storage: &mut dyn Storage, payment_id: u64, payment: &Payment, open: bool) -> StdResult<()> {
+    let parties = [
+        (&payment.from_username, &payment.to_username),
+        (&payment.to_username, &payment.from_username),
+    ];
+    for (party, counterparty) in parties {
+        // A request to an unregistered wallet has no payer username until it is bound
+        if party.is_empty() {
+            continue;
+        }
+        let key = (party.clone(), counterparty.clone(), payment_id);
+        if open {
+            OPEN_PAYMENTS.save(storage, key, &true)?;
+        } else {
+            OPEN_PAYMENTS.remove(storage, key);
+        }
+    }
+    Ok(())
+}
+
+// Helper function to update a payment through `save_payment`
+fn update_payment<A, E>(storage: &mut dyn Storage, payment_id: u64, action: A) -> Result<Payment, E>
+where
+    A: FnOnce(Option<Payment>) -> Result<Payment, E>,
+    E: From<StdError>,
+{
+    let payment = action(PAYMENTS.may_load(storage, payment_id)?)?;
+    save_payment(storage, payment_id, &payment)?;
+    Ok(payment)
+}
+
+// Helper function to tell whether a payment is still waiting on either party
+fn payment_open(payment: &Payment) -> bool {
+    matches!(
+        payment.status,
+        PaymentStatus::Pending | PaymentStatus::ProofSubmitted | PaymentStatus::PendingRelease | PaymentStatus::Disputed
+    )
+}
+
+// Helper function to tell whether a payment counts toward its creator's open payment request limit
+fn payment_request_open(payment: &Payment) -> bool {
+    matches!(payment.payment_type, PaymentType::PaymentRequest) && payment_open(payment)
+}
+
+// Helper function to get the (requester, wallet) key an open request to an unregistered wallet counts under
+fn wallet_request_waiting(payment: &Payment) -> Option<(String, Addr)> {
+    payment.to_wallet.clone()
+        .filter(|_| payment_request_open(payment))
+        .map(|to_wallet| (payment.from_username.clone(), to_wallet))
+}
+
+// Helper function to count a record in or out of one of the open item counts
+fn adjust_open_count<'a, K>(storage: &mut dyn Storage, counts: &Map<'a, K, u64>, key: K, open: bool) -> StdResult<()>
+where
+    K: PrimaryKey<'a> + Clone,
+{
+    let count = counts.may_load(storage, key.clone())?.unwrap_or(0);
+    let count = if open { count + 1 } else { count.saturating_sub(1) };
+    if count == 0 {
+        counts.remove(storage, key);
+        return Ok(());
+    }
+    counts.save(storage, key, &count)
+}
+
+// Helper function to reject a new open record once the user holds their configured limit
+fn ensure_open_item_limit(
+    storage: &dyn Storage,
+    counts: &Map<String, u64>,
+    username: &str,
+    limit: impl FnOnce(&OpenItemLimits) -> u64,
+    error: ContractError,
+) -> Result<(), ContractError> {
+    let limits = CONFIG.may_load(storage)?.unwrap_or_default().open_item_limits;
+    if counts.may_load(storage, username.to_string())?.unwrap_or(0) >= limit(&limits) {
+        return Err(error);
+    }
+    Ok(())
+}
+
+// Maximum number of blocked tasks that can wait on one task
+const MAX_TASK_DEPENDENTS: usize = 10;
+
+// Helper function to get the status a task starts in once it is active
+fn task_start_status(proof_type: &ProofType) -> TaskStatus {
+    if matches!(proof_type, ProofType::Soft) {
+        TaskStatus::ProofSubmitted // Soft tasks don't escrow, so they start ready for approval
+    } else {
+        TaskStatus::Escrowed
+    }
+}
+
+// Helper function to start the tasks blocked on a task that was just released
+fn activate_dependent_tasks(storage: &mut dyn Storage, task_id: u64, released: &Task) -> StdResult<()> {
+    let dependent_ids: Vec<u64> = TASK_DEPENDENTS
+        .prefix(task_id)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for dependent_id in dependent_ids {
+        let mut dependent = TASKS.load(storage, dependent_id)?;
+        // The deadline clock starts now: the worker gets the full time the payer allowed at creation
+        dependent.deadline_ts += released.updated_at.saturating_sub(dependent.created_at);
+        dependent.status = task_start_status(&dependent.proof_type);
+        dependent.updated_at = released.updated_at;
+        dependent.updated_at_height = released.updated_at_height;
+        save_task(storage, dependent_id, &dependent)?;
+    }
+    Ok(())
+}
+
+// Helper function to update a task in place, keeping its status index entries current
+fn update_task<A, E>(storage: &mut dyn Storage, task_id: u64, action: A) -> Result<Task, E>
+where
+    A: FnOnce(Option<Task>) -> Result<Task, E>,
+    E: From<StdError>,
+{
+    let task = action(TASKS.may_load(storage, task_id)?)?;
+    save_task(storage, task_id, &task)?;
+    Ok(task)
+}
+
+// Helper function to drop a task's status index entries
+fn remove_task_status_index(storage: &mut dyn Storage, task_id: u64, task: &Task) {
+    let status = task.status.key().to_string();
+    TASKS_BY_STATUS.remove(storage, (status.clone(), task_id));
+    USER_TASKS_BY_STATUS.remove(storage, (task.payer.clone(), status.clone(), task_id));
+    USER_TASKS_BY_STATUS.remove(storage, (task.worker.clone(), status, task_id));
+}
+
+// Helper function to record funds entering escrow on behalf of a user
+fn add_escrow(storage: &mut dyn Storage, username: &str, amount: &Coin) -> Result<(), ContractError> {
+    add_escrow_total(storage, amount)?;
+    USER_ESCROWED.update(storage, (username.to_string(), amount.denom.clone()), |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount.amount)?)
+    })?;
+    Ok(())
+}
+
+// Helper function to record funds leaving escrow (released or refunded)
+fn release_escrow(storage: &mut dyn Storage, username: &str, amount: &Coin) -> Result<(), ContractError> {
+    release_escrow_total(storage, amount)?;
+    
+    let user_key = (username.to_string(), amount.denom.clone());
+    let user_total = USER_ESCROWED.may_load(storage, user_key.clone())?.unwrap_or_default()
+        .checked_sub(amount.amount)
+        .map_err(StdError::from)?;
+    if user_total.is_zero() {
+        USER_ESCROWED.remove(storage, user_key);
+    } else {
+        USER_ESCROWED.save(storage, user_key, &user_total)?;
+    }
+    Ok(())
+}
+
+// Helper function to count funds toward the contract's escrow liabilities
+// (campaign funds and the insurance pool are pooled and not attributed to a single user)
+fn add_escrow_total(storage: &mut dyn Storage, amount: &Coin) -> Result<(), ContractError> {
+    ESCROW_TOTALS.update(storage, amount.denom.clone(), |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount.amount)?)
+    })?;
+    Ok(())
+}
+
+// Helper function to remove funds from the contract's escrow liabilities
+fn release_escrow_total(storage: &mut dyn Storage, amount: &Coin) -> Result<(), ContractError> {
+    let total = ESCROW_TOTALS.may_load(storage, amount.denom.clone())?.unwrap_or_default()
+        .checked_sub(amount.amount)
+        .map_err(StdError::from)?;
+    if total.is_zero() {
+        ESCROW_TOTALS.remove(storage, amount.denom.clone());
+    } else {
+        ESCROW_TOTALS.save(storage, amount.denom.clone(), &total)?;
+    }
+    Ok(())
+}
+
+// Helper function to deposit a newly escrowed task with the yield adapter when routing is enabled
+fn deposit_task_yield(storage: &mut dyn Storage, task_id: u64, amount: &Coin) -> StdResult<Option<CosmosMsg>> {
+    let config = match YIELD_CONFIG.may_load(storage)? {
+        Some(config) if config.denom == amount.denom && amount.amount >= config.threshold => config,
+        _ => return Ok(None),
+    };
+    
+    YIELD_POSITIONS.save(storage, task_id, &YieldPosition {
+        adapter: config.adapter.clone(),
+        principal: amount.clone(),
+        treasury: config.treasury,
+        user_share_bps: config.user_share_bps,
+    })?;
+    Ok(Some(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.adapter.to_string(),
+        msg: to_json_binary(&YieldAdapterExecuteMsg::Deposit { position_id: task_id })?,
+        funds: vec![amount.clone()],
+    })))
+}
+
+// Helper function to pay a task's escrow to `recipient`, first withdrawing it from the yield adapter
+// if it was deposited there. Accrued yield is split between the recipient and the treasury.
+fn pay_out_task_escrow(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    env: &Env,
+    task_id: u64,
+    amount: &Coin,
+    recipient: &Addr,
+) -> Result<(Vec<CosmosMsg>, Vec<cosmwasm_std::Event>), ContractError> {
+    split_task_escrow(storage, querier, env, task_id, amount, &[(recipient.clone(), amount.amount)])
+}
+
+// Default and largest slippage below the oracle price a worker accepts when a payout is swapped
+// into their preferred denom
+const DEFAULT_PAYOUT_SLIPPAGE_BPS: u16 = 100;
+const MAX_PAYOUT_SLIPPAGE_BPS: u16 = 1000;
+// Reference currency both denoms are priced in to value a preferred-denom swap
+const PAYOUT_SWAP_REFERENCE_CURRENCY: &str = "USD";
+
+// Helper function to release a task's escrow to its worker in full like `pay_out_task_escrow`,
+// routing the worker's payout through the DEX router when the task asks for a payout swap or the
+// worker prefers another denom. A failed swap is answered in `reply` by paying the escrow unconverted.
+fn pay_worker_task_escrow(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    env: &Env,
+    task: &Task,
+    worker: &User,
+) -> Result<(Vec<SubMsg>, Vec<cosmwasm_std::Event>), ContractError> {
+    let recipient = &worker.wallet_address;
+    let (msgs, events) = pay_out_task_escrow(storage, querier, env, task.id, &task.amount, recipient)?;
+    let ask_denom = match (&task.payout_swap, &worker.preferred_denom) {
+        (Some(swap), _) => swap.ask_denom.clone(),
+        (None, Some(preferred_denom)) => preferred_denom.clone(),
+        (None, None) => return Ok((msgs.into_iter().map(SubMsg::new).collect(), events)),
+    };
+    let router = match DEX_ROUTER.may_load(storage)? {
+        Some(router) if ask_denom != task.amount.denom => router,
+        _ => return Ok((msgs.into_iter().map(SubMsg::new).collect(), events)),
+    };
+    
+    let mut sub_msgs = vec![];
+    for msg in msgs {
+        let payout = match &msg {
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount })
+                if *to_address == recipient.as_str() && amount.len() == 1 && amount[0].denom == task.amount.denom => amount[0].clone(),
+            _ => {
+                sub_msgs.push(SubMsg::new(msg));
+                continue;
+            }
+        };
+        // A task's payout swap sets its own floor; a preferred denom takes the oracle's value of the
+        // payout less the worker's slippage, and is paid unconverted when no fresh price is available
+        let min_out = match &task.payout_swap {
+            Some(swap) => Some(swap.min_out),
+            None => {
+                let slippage_bps = worker.payout_slippage_bps.unwrap_or(DEFAULT_PAYOUT_SLIPPAGE_BPS);
+                oracle_swap_value(storage, querier, env, &payout, &ask_denom)
+                    .map(|value| value.multiply_ratio(10_000 - u128::from(slippage_bps), 10_000u128))
+            }
+        };
+        let min_out = match min_out {
+            Some(min_out) if !min_out.is_zero() => min_out,
+            _ => {
+                sub_msgs.push(SubMsg::new(msg));
+                continue;
+            }
+        };
+        
+        let reply_id = PAYOUT_SWAP_REPLY_SEQ.may_load(storage)?.unwrap_or(PAYOUT_SWAP_REPLY_ID_START);
+        PAYOUT_SWAP_REPLY_SEQ.save(storage, &(reply_id + 1))?;
+        PENDING_PAYOUT_SWAPS.save(storage, reply_id, &PendingPayoutSwap {
+            task_id: task.id,
+            recipient: recipient.clone(),
+            amount: payout.clone(),
+        })?;
+        let swap_msg = WasmMsg::Execute {
+            contract_addr: router.to_string(),
+            msg: to_json_binary(&DexRouterExecuteMsg::Swap {
+                ask_denom: ask_denom.clone(),
+                min_out,
+                to: recipient.to_string(),
+            })?,
+            funds: vec![payout],
+        };
+        sub_msgs.push(SubMsg::reply_always(swap_msg, reply_id));
+    }
+    Ok((sub_msgs, events))
+}
+
+// Helper function to value `offer` in `ask_denom` at fresh oracle prices of both denoms in the
+// reference currency; None when the oracle is unset, fails or is stale
+fn oracle_swap_value(storage: &dyn Storage, querier: &QuerierWrapper, env: &Env, offer: &Coin, ask_denom: &str) -> Option<Uint128> {
+    let oracle = PRICE_ORACLE.may_load(storage).ok()??;
+    let price = |denom: &str| -> Option<Decimal> {
+        let price: OraclePriceResponse = querier
+            .query_wasm_smart(
+                oracle.clone(),
+                &OracleQueryMsg::Price { currency: PAYOUT_SWAP_REFERENCE_CURRENCY.to_string(), denom: denom.to_string() },
+            )
+            .ok()?;
+        let fresh = env.block.time.seconds().saturating_sub(price.publish_time) <= MAX_PRICE_AGE_SECS;
+        Some(price.price).filter(|price| fresh && !price.is_zero())
+    };
+    // Prices are base units per currency unit, so the value scales by ask over offer
+    let offer_price = price(&offer.denom)?;
+    let ask_price = price(ask_denom)?;
+    offer.amount.checked_multiply_ratio(ask_price.atomics(), offer_price.atomics()).ok()
+}
+
+// Helper function to pay a task's escrow out in `shares` that add up to `amount`, like
+// `pay_out_task_escrow`. The recipients' part of any accrued yield is divided pro rata, and so is
+// any loss when the adapter returns less than the principal; the loss is recorded and reported
+// in a `yield_shortfall` event rather than blocking the payout.
+fn split_task_escrow(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    env: &Env,
+    task_id: u64,
+    amount: &Coin,
+    shares: &[(Addr, Uint128)],
+) -> Result<(Vec<CosmosMsg>, Vec<cosmwasm_std::Event>), ContractError> {
+    let send = |to_address: &Addr, amount: Coin| CosmosMsg::Bank(BankMsg::Send {
+        to_address: to_address.to_string(),
+        amount: vec![amount],
+    });
+    // Shares are scaled to what is actually payable; the last one takes the rounding remainder
+    let send_shares = |denom: &str, payable: Uint128| {
+        let mut remaining = payable;
+        let mut msgs = vec![];
+        for (i, (recipient, share)) in shares.iter().enumerate() {
+            let payout = if i + 1 == shares.len() {
+                remaining
+            } else {
+                payable.multiply_ratio(*share, amount.amount)
+            };
+            remaining -= payout;
+            if !payout.is_zero() {
+                msgs.push(send(recipient, Coin { denom: denom.to_string(), amount: payout }));
+            }
+        }
+        msgs
+    };
+    
+    let position = match YIELD_POSITIONS.may_load(storage, task_id)? {
+        Some(position) => position,
+        None => return Ok((send_shares(&amount.denom, amount.amount), vec![])),
+    };
+    YIELD_POSITIONS.remove(storage, task_id);
+    
+    // Withdraw pays out the queried value within the same transaction; a position reported in
+    // another denom is treated as lost
+    let value = querier.query_wasm_smart::<AdapterPositionResponse>(
+        position.adapter.clone(),
+        &YieldAdapterQueryMsg::Position {
+            depositor: env.contract.address.to_string(),
+            position_id: task_id,
+        },
+    )?.value;
+    let value = if value.denom == position.principal.denom { value.amount } else { Uint128::zero() };
+    let accrued = value.saturating_sub(position.principal.amount);
+    let loss = position.principal.amount.saturating_sub(value);
+    let user_share = accrued.multiply_ratio(position.user_share_bps, 10_000u128);
+    let treasury_share = accrued - user_share;
+    
+    let mut msgs = vec![CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: position.adapter.to_string(),
+        msg: to_json_binary(&YieldAdapterExecuteMsg::Withdraw { position_id: task_id })?,
+        funds: vec![],
+    })];
+    // Escrow added after the deposit (e.g. an accepted counter-offer) is held by the contract itself
+    let payable = amount.amount.max(position.principal.amount) + user_share - loss;
+    msgs.extend(send_shares(&position.principal.denom, payable));
+    if !treasury_share.is_zero() {
+        msgs.push(send(&position.treasury, Coin {
+            denom: position.principal.denom.clone(),
+            amount: treasury_share,
+        }));
+    }
+    
+    let mut events = vec![];
+    if !loss.is_zero() {
+        ESCROW_LOSSES.update(storage, position.principal.denom.clone(), |total| -> StdResult<_> {
+            Ok(total.unwrap_or_default().checked_add(loss)?)
+        })?;
+        events.push(
+            cosmwasm_std::Event::new("yield_shortfall")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("principal", position.principal.to_string())
+                .add_attribute("loss", Coin { denom: position.principal.denom, amount: loss }.to_string())
+        );
+    }
+    Ok((msgs, events))
+}
+
+// Helper function to take a record's registered completion callback as a submessage
+fn take_callback(
+    storage: &mut dyn Storage,
+    kind: RecordKind,
+    id: u64,
+    status: String,
+    amount: &Coin,
+) -> StdResult<Option<SubMsg>> {
+    let callbacks = match kind {
+        RecordKind::Payment => &PAYMENT_CALLBACKS,
+        RecordKind::Task => &TASK_CALLBACKS,
+    };
+    
+    let callback = match callbacks.may_load(storage, id)? {
+        Some(callback) => callback,
+        None => return Ok(None),
+    };
+    callbacks.remove(storage, id);
+    
+    let payload = CallbackPayload {
+        kind,
+        id,
+        status,
+        amount: amount.clone(),
+        msg: callback.msg,
+    };
+    let wasm_msg = WasmMsg::Execute {
+        contract_addr: callback.contract_addr.to_string(),
+        msg: to_json_binary(&CallbackExecuteMsg::ProofpayCallback(payload))?,
+        funds: vec![],
+    };
+    Ok(Some(SubMsg::reply_on_error(wasm_msg, CALLBACK_REPLY_ID)))
+}
+
+// USER MANAGEMENT FUNCTIONS
+
+pub fn execute_register_user(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    username: String,
+    display_name: String,
+) -> Result<Response, ContractError> {
+    // Validate username format
+    validate_username(&username)?;
+    
+    // Normalize username for case-insensitive checking
+    let normalized_username = normalize_username(&username);
+    
+    // Check if username is already taken (case-insensitive) or retired by a deactivation;
+    // a registration past its grace period is released here if the reaper has not run yet
+    let now = env.block.time.seconds();
+    let mut released = None;
+    if let Some(existing) = USERS_BY_USERNAME.may_load(deps.storage, normalized_username.clone())? {
+        if !username_reclaimable(deps.storage, &existing, now)? {
+            return Err(ContractError::UsernameAlreadyTaken {});
+        }
+        released = Some(release_username(deps.storage, &existing)?);
+    } else if RETIRED_USERNAMES.has(deps.storage, normalized_username.clone()) {
+        return Err(ContractError::UsernameAlreadyTaken {});
+    }
+    
+    // Check if wallet is already registered
+    if USERS_BY_WALLET.may_load(deps.storage, info.sender.clone())?.is_some() {
+        return Err(ContractError::WalletAlreadyRegistered {});
+    }
+    
+    let user = User {
+        wallet_address: info.sender.clone(),
+        username: normalized_username.clone(),
+        display_name,
+        profile_picture: None,
+        require_friendship_for_requests: false,
+        default_visibility: Visibility::Public,
+        pubkey: None,
+        tokenized: false,
+        badge: None,
+        bio: None,
+        links: vec![],
+        country: None,
+        timezone: None,
+        preferred_denom: None,
+        payout_slippage_bps: None,
+        expires_at: USERNAME_EXPIRY.may_load(deps.storage)?.map(|expiry| now + expiry.term_secs),
+        created_at: now,
+        updated_at: now,
+    };
+    
+    // Save user data using normalized username
+    USERS_BY_USERNAME.save(deps.storage, normalized_username.clone(), &user)?;
+    USERS_BY_WALLET.save(deps.storage, info.sender.clone(), &normalized_username)?;
+    let user_count = USER_COUNT.may_load(deps.storage)?.unwrap_or(0);
+    USER_COUNT.save(deps.storage, &(user_count + 1))?;
+    if let Some(expires_at) = user.expires_at {
+        USERNAME_EXPIRIES.save(deps.storage, (expires_at, normalized_username.clone()), &true)?;
+    }
+    let requests_bound = bind_wallet_payment_requests(deps.storage, &env, &info.sender, &normalized_username)?;
+    
+    let mut response = Response::new()
+        .add_attribute("action", "register_user")
+        .add_attribute("username", &normalized_username)
+        .add_attribute("wallet", info.sender.as_str())
+        .add_event(
+            cosmwasm_std::Event::new("username_registered")
+                .add_attribute("wallet", info.sender.as_str())
+                .add_attribute("username", &normalized_username)
+        );
+    if let Some((refund, event)) = released {
+        response = response.add_messages(refund).add_event(event);
+    }
+    if requests_bound > 0 {
+        response = response.add_attribute("payment_requests_bound", requests_bound.to_string());
+    }
+    
+    // Charge the tier for the username's length; deposits stay with the contract, fees go to the treasury
+    if let Some((tier, treasury)) = registration_fee_tier(deps.storage, &normalized_username)? {
+        if let Some(refund) = validate_funds(deps.storage, &info, &tier.amount)? {
+            response = response.add_message(refund);
+        }
+        if tier.refundable {
+            add_escrow_total(deps.storage, &tier.amount)?;
+            REGISTRATION_DEPOSITS.save(deps.storage, normalized_username.clone(), &tier.amount)?;
+        } else {
+            response = response.add_message(BankMsg::Send {
+                to_address: treasury.to_string(),
+                amount: vec![tier.amount.clone()],
+            });
+        }
+        response = response
+            .add_attribute("registration_fee", tier.amount.to_string())
+            .add_attribute("refundable", tier.refundable.to_string());
+    }
+    
+    Ok(response)
+}
+
+// Helper function to find the registration fee tier for a username, with the treasury receiving fees
+fn registration_fee_tier(storage: &dyn Storage, username: &str) -> StdResult<Option<(RegistrationFeeTier, Addr)>> {
+    let Some(fees) = REGISTRATION_FEES.may_load(storage)? else {
+        return Ok(None);
+    };
+    let length = username.chars().count() as u32;
+    Ok(fees.tiers.into_iter()
+        .find(|tier| length <= tier.max_length)
+        .map(|tier| (tier, fees.treasury)))
+}
+
+// Profile detail limits
+const MAX_BIO_LENGTH: usize = 280;
+const MAX_PROFILE_LINKS: usize = 5;
+const MAX_PROFILE_LINK_LENGTH: usize = 200;
+const MAX_TIMEZONE_LENGTH: usize = 64;
+
+// Helper function to validate optional profile details; empty strings are allowed and clear the field
+fn validate_profile_details(
+    bio: &Option<String>,
+    links: &Option<Vec<String>>,
+    country: &Option<String>,
+    timezone: &Option<String>,
+) -> Result<(), ContractError> {
+    if bio.as_ref().is_some_and(|bio| bio.chars().count() > MAX_BIO_LENGTH) {
+        return Err(ContractError::InvalidBio {});
+    }
+    if let Some(links) = links {
+        let invalid_link = |link: &String| {
+            link.len() > MAX_PROFILE_LINK_LENGTH ||
+                !link.starts_with("https://") ||
+                link.len() == "https://".len() ||
+                link.chars().any(|c| c.is_whitespace() || c.is_control())
+        };
+        if links.len() > MAX_PROFILE_LINKS || links.iter().any(invalid_link) {
+            return Err(ContractError::InvalidProfileLinks {});
+        }
+    }
+    if let Some(country) = country {
+        if !country.is_empty() && (country.len() != 2 || !country.chars().all(|c| c.is_ascii_uppercase())) {
+            return Err(ContractError::InvalidCountryCode {});
+        }
+    }
+    if let Some(timezone) = timezone {
+        // IANA names such as "America/Argentina/Buenos_Aires" or "Etc/GMT+5"
+        let valid = timezone.len() <= MAX_TIMEZONE_LENGTH &&
+            timezone.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-' | '+'));
+        if !valid {
+            return Err(ContractError::InvalidTimezone {});
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_update_user_profile(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    display_name: Option<String>,
+    profile_picture: Option<String>,
+    require_friendship_for_requests: Option<bool>,
+    default_visibility: Option<Visibility>,
+    bio: Option<String>,
+    links: Option<Vec<String>>,
+    country: Option<String>,
+    timezone: Option<String>,
+    preferred_denom: Option<String>,
+    payout_slippage_bps: Option<u16>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    validate_profile_details(&bio, &links, &country, &timezone)?;
+    if let Some(denom) = preferred_denom.as_deref().filter(|denom| !denom.is_empty()) {
+        ensure_denom_accepted(deps.storage, denom)?;
+    }
+    if payout_slippage_bps.is_some_and(|bps| bps > MAX_PAYOUT_SLIPPAGE_BPS) {
+        return Err(ContractError::InvalidPayoutSlippage {});
+    }
+    
+    USERS_BY_USERNAME.update(deps.storage, username.clone(), |user| -> Result<_, ContractError> {
+        let mut user = user.ok_or(ContractError::UserNotFound {})?;
+        
+        if let Some(new_display_name) = display_name {
+            user.display_name = new_display_name;
+        }
+        
+        if let Some(new_profile_picture) = profile_picture {
+            user.profile_picture = Some(new_profile_picture);
+        }
+        
+        if let Some(require_friendship) = require_friendship_for_requests {
+            user.require_friendship_for_requests = require_friendship;
+        }
+        
+        if let Some(visibility) = default_visibility {
+            user.default_visibility = visibility;
+        }
+        
+        // Empty strings clear the optional details
+        if let Some(bio) = bio {
+            user.bio = Some(bio).filter(|bio| !bio.is_empty());
+        }
+        if let Some(links) = links {
+            user.links = links;
+        }
+        if let Some(country) = country {
+            user.country = Some(country).filter(|country| !country.is_empty());
+        }
+        if let Some(timezone) = timezone {
+            user.timezone = Some(timezone).filter(|timezone| !timezone.is_empty());
+        }
+        if let Some(preferred_denom) = preferred_denom {
+            user.preferred_denom = Some(preferred_denom).filter(|denom| !denom.is_empty());
+        }
+        if let Some(bps) = payout_slippage_bps {
+            user.payout_slippage_bps = Some(bps);
+        }
+        
+        user.updated_at = env.block.time.seconds();
+        
+        Ok(user)
+    })?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "update_user_profile")
+        .add_attribute("username", username))
+}
+
+// FRIENDS SYSTEM FUNCTIONS
+
+pub fn execute_register_pubkey(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pubkey: Binary,
+    proof_signature: Binary,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    
+    // 33-byte compressed or 65-byte uncompressed secp256k1 keys
+    if pubkey.len() != 33 && pubkey.len() != 65 {
+        return Err(ContractError::InvalidPubkey {});
+    }
+    
+    // The key must sign a challenge naming this wallet, so it cannot be copied from another user
+    let challenge = pubkey_challenge(&env.block.chain_id, &env.contract.address, &info.sender);
+    let hash = adr36_sign_hash(info.sender.as_str(), &challenge);
+    let verified = deps.api.secp256k1_verify(&hash, &proof_signature, &pubkey)
+        .map_err(|_| ContractError::InvalidSignature {})?;
+    if !verified {
+        return Err(ContractError::InvalidSignature {});
+    }
+    
+    USERS_BY_USERNAME.update(deps.storage, username.clone(), |user| -> Result<_, ContractError> {
+        let mut user = user.ok_or(ContractError::UserNotFound {})?;
+        user.pubkey = Some(pubkey);
+        user.updated_at = env.block.time.seconds();
+        Ok(user)
+    })?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "register_pubkey")
+        .add_attribute("username", username))
+}
+
+pub fn execute_remove_pubkey(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    
+    USERS_BY_USERNAME.update(deps.storage, username.clone(), |user| -> Result<_, ContractError> {
+        let mut user = user.ok_or(ContractError::UserNotFound {})?;
+        if user.pubkey.take().is_none() {
+            return Err(ContractError::SigningKeyNotSet {});
+        }
+        user.updated_at = env.block.time.seconds();
+        Ok(user)
+    })?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "remove_pubkey")
+        .add_attribute("username", username))
+}
+
+pub fn execute_deactivate_user(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let user = USERS_BY_USERNAME.load(deps.storage, username.clone())?;
+    if user_removal_blocked(deps.storage, &user)? {
+        return Err(ContractError::DeactivationBlocked {});
+    }
+    
+    // The username is retired rather than freed, so its payment and task history never passes to a new owner
+    let (friendships_removed, refund) = remove_user_records(deps.storage, &user)?;
+    RETIRED_USERNAMES.save(deps.storage, username.clone(), &env.block.time.seconds())?;
+    
+    let mut response = Response::new()
+        .add_attribute("action", "deactivate_user")
+        .add_attribute("username", &username)
+        .add_attribute("wallet", info.sender.as_str())
+        .add_attribute("friendships_removed", friendships_removed.to_string());
+    if let Some(refund) = refund {
+        response = response.add_message(refund);
+    }
+    Ok(response.add_event(
+        cosmwasm_std::Event::new("username_deactivated")
+            .add_attribute("wallet", info.sender.as_str())
+            .add_attribute("username", username)
+    ))
+}
+
+// Helper function to check whether a user holds escrowed funds, a tokenized handle, an unsettled
+// task as payer or worker, or an open payment that must be settled before the account can be removed
+fn user_removal_blocked(storage: &dyn Storage, user: &User) -> StdResult<bool> {
+    let has_escrow = USER_ESCROWED
+        .prefix(user.username.clone())
+        .keys(storage, None, None, Order::Ascending)
+        .next()
+        .is_some();
+    let open_statuses = [
+        TaskStatus::Escrowed,
+        TaskStatus::ProofSubmitted,
+        TaskStatus::PendingRelease,
+        TaskStatus::ApprovedPendingFunding,
+        TaskStatus::Disputed,
+        TaskStatus::Blocked,
+    ];
+    let has_open_task = open_statuses.iter().any(|status| {
+        USER_TASKS_BY_STATUS
+            .prefix((user.username.clone(), status.key().to_string()))
+            .keys(storage, None, None, Order::Ascending)
+            .next()
+            .is_some()
+    });
+    let has_open_payment = OPEN_PAYMENTS
+        .sub_prefix(user.username.clone())
+        .keys(storage, None, None, Order::Ascending)
+        .next()
+        .is_some();
+    Ok(has_escrow
+        || has_open_task
+        || has_open_payment
+        || user.tokenized
+        || USERNAME_TRANSFERS.has(storage, user.username.clone()))
+}
+
+// Helper function to remove a user's account records: friendships in both directions,
+// outgoing friend requests, username maps and expiry entry. Returns the friendships removed
+// and the refund of any registration deposit to the user's wallet.
+fn remove_user_records(storage: &mut dyn Storage, user: &User) -> Result<(usize, Option<BankMsg>), ContractError> {
+    let username = user.username.clone();
+    let friends: Vec<String> = FRIENDSHIPS
+        .prefix(username.clone())
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for friend in &friends {
+        FRIENDSHIPS.remove(storage, (username.clone(), friend.clone()));
+        FRIENDSHIPS.remove(storage, (friend.clone(), username.clone()));
+        // Accepted requests from friends are kept under the sender
+        FRIEND_REQUESTS.remove(storage, (friend.clone(), username.clone()));
+    }
+    let requested: Vec<String> = FRIEND_REQUESTS
+        .prefix(username.clone())
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for to_username in requested {
+        set_friend_request_pending(storage, &username, &to_username, false)?;
+        FRIEND_REQUESTS.remove(storage, (username.clone(), to_username));
+    }
+    // Requests still waiting on the user, so their senders' sent counts are released too
+    let senders: Vec<String> = PENDING_FRIEND_REQUESTS
+        .prefix(username.clone())
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for from_username in senders {
+        set_friend_request_pending(storage, &from_username, &username, false)?;
+        FRIEND_REQUESTS.remove(storage, (from_username, username.clone()));
+    }
+    
+    USERS_BY_USERNAME.remove(storage, username.clone());
+    let user_count = USER_COUNT.may_load(storage)?.unwrap_or(0);
+    USER_COUNT.save(storage, &user_count.saturating_sub(1))?;
+    USERS_BY_WALLET.remove(storage, user.wallet_address.clone());
+    if let Some(expires_at) = user.expires_at {
+        USERNAME_EXPIRIES.remove(storage, (expires_at, username.clone()));
+    }
+    
+    let refund = match REGISTRATION_DEPOSITS.may_load(storage, username.clone())? {
+        Some(deposit) => {
+            REGISTRATION_DEPOSITS.remove(storage, username);
+            release_escrow_total(storage, &deposit)?;
+            Some(BankMsg::Send {
+                to_address: user.wallet_address.to_string(),
+                amount: vec![deposit],
+            })
+        }
+        None => None,
+    };
+    Ok((friends.len(), refund))
+}
+
+pub fn execute_set_registration_fees(
+    deps: DepsMut,
+    info: MessageInfo,
+    config: Option<RegistrationFeesMsg>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
+    
+    let config = match config {
+        Some(config) => config,
+        None => {
+            REGISTRATION_FEES.remove(deps.storage);
+            return Ok(Response::new()
+                .add_attribute("action", "set_registration_fees")
+                .add_attribute("enabled", "false"));
+        }
+    };
+    let ascending = config.tiers.windows(2).all(|pair| pair[0].max_length < pair[1].max_length);
+    if !ascending || config.tiers.iter().any(|tier| tier.amount.amount.is_zero()) {
+        return Err(ContractError::InvalidRegistrationFees {});
+    }
+    
+    let fees = RegistrationFees {
+        tiers: config.tiers,
+        treasury: validate_address(deps.api, &config.treasury)?,
+    };
+    REGISTRATION_FEES.save(deps.storage, &fees)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "set_registration_fees")
+        .add_attribute("enabled", "true")
+        .add_attribute("tiers", fees.tiers.len().to_string())
+        .add_attribute("treasury", fees.treasury))
+}
+
+pub fn execute_set_username_expiry(
+    deps: DepsMut,
+    info: MessageInfo,
+    config: Option<UsernameExpiry>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
+    
+    let config = match config {
+        Some(config) => config,
+        None => {
+            USERNAME_EXPIRY.remove(deps.storage);
+            return Ok(Response::new()
+                .add_attribute("action", "set_username_expiry")
+                .add_attribute("enabled", "false"));
+        }
+    };
+    if config.term_secs == 0 {
+        return Err(ContractError::InvalidUsernameExpiry {});
+    }
+    USERNAME_EXPIRY.save(deps.storage, &config)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "set_username_expiry")
+        .add_attribute("enabled", "true")
+        .add_attribute("term_secs", config.term_secs.to_string())
+        .add_attribute("grace_secs", config.grace_secs.to_string()))
+}
+
+// Helper function to reject senders whose username registration has expired
+fn ensure_username_current(deps: Deps, env: &Env, sender: &Addr) -> Result<(), ContractError> {
+    if !USERNAME_EXPIRY.exists(deps.storage) {
+        return Ok(());
+    }
+    let Some(username) = USERS_BY_WALLET.may_load(deps.storage, sender.clone())? else {
+        return Ok(());
+    };
+    let user = USERS_BY_USERNAME.load(deps.storage, username)?;
+    if user.expires_at.is_some_and(|expires_at| env.block.time.seconds() >= expires_at) {
+        return Err(ContractError::UsernameExpired {});
+    }
+    Ok(())
+}
+
+// Helper function to tell whether a registration's grace period has ended, releasing the username
+fn username_grace_ended(storage: &dyn Storage, user: &User, now: u64) -> StdResult<bool> {
+    let Some(expiry) = USERNAME_EXPIRY.may_load(storage)? else {
+        return Ok(false);
+    };
+    Ok(user.expires_at.is_some_and(|expires_at| now >= expires_at.saturating_add(expiry.grace_secs)))
+}
+
+// Helper function to tell whether another wallet may register a user's username now
+fn username_reclaimable(storage: &dyn Storage, user: &User, now: u64) -> StdResult<bool> {
+    Ok(username_grace_ended(storage, user, now)? && !user_removal_blocked(storage, user)?)
+}
+
+// Helper function to release an expired username, returning the deposit refund and its event
+fn release_username(storage: &mut dyn Storage, user: &User) -> Result<(Vec<BankMsg>, cosmwasm_std::Event), ContractError> {
+    let (_, refund) = remove_user_records(storage, user)?;
+    let event = cosmwasm_std::Event::new("username_released")
+        .add_attribute("username", &user.username)
+        .add_attribute("wallet", user.wallet_address.as_str());
+    Ok((refund.into_iter().collect(), event))
+}
+
+pub fn execute_renew_username(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut user = USERS_BY_USERNAME.load(deps.storage, username.clone())?;
+    let now = env.block.time.seconds();
+    let (Some(expiry), Some(expires_at)) = (USERNAME_EXPIRY.may_load(deps.storage)?, user.expires_at) else {
+        return Err(ContractError::UsernameDoesNotExpire {});
+    };
+    if username_grace_ended(deps.storage, &user, now)? {
+        return Err(ContractError::UsernameExpired {});
+    }
+    
+    // Fee tiers are charged again on renewal; a deposit tier is already covered by the held deposit
+    let mut response = Response::new();
+    match registration_fee_tier(deps.storage, &username)? {
+        Some((tier, treasury)) if !tier.refundable => {
+            if let Some(refund) = validate_funds(deps.storage, &info, &tier.amount)? {
+                response = response.add_message(refund);
+            }
+            response = response
+                .add_message(BankMsg::Send {
+                    to_address: treasury.to_string(),
+                    amount: vec![tier.amount.clone()],
+                })
+                .add_attribute("renewal_fee", tier.amount.to_string());
+        }
+        _ => ensure_no_funds(&info)?,
+    }
+    
+    let renewed_until = expires_at.max(now) + expiry.term_secs;
+    USERNAME_EXPIRIES.remove(deps.storage, (expires_at, username.clone()));
+    USERNAME_EXPIRIES.save(deps.storage, (renewed_until, username.clone()), &true)?;
+    user.expires_at = Some(renewed_until);
+    user.updated_at = now;
+    USERS_BY_USERNAME.save(deps.storage, username.clone(), &user)?;
+    
+    Ok(response
+        .add_attribute("action", "renew_username")
+        .add_attribute("username", username)
+        .add_attribute("expires_at", renewed_until.to_string()))
+}
+
+// Default and maximum number of expiry entries scanned per reaper call
+const DEFAULT_REAP_LIMIT: u32 = 30;
+const MAX_REAP_LIMIT: u32 = 100;
+
+// Releases usernames whose grace period has ended, oldest expiry first. Anyone can call it.
+// Usernames holding escrowed funds, unsettled tasks or payments, or a tokenized handle are dropped from the scan;
+// RegisterUser still releases them once they are settled. Expired idempotency keys are deleted in the same pass.
+pub fn execute_reap_expired_usernames(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let limit = limit.unwrap_or(DEFAULT_REAP_LIMIT).min(MAX_REAP_LIMIT) as usize;
+    let now = env.block.time.seconds();
+    let keys_removed = reap_idempotency_keys(deps.storage, &env, limit)?;
+    let mut response = Response::new()
+        .add_attribute("action", "reap_expired_usernames")
+        .add_attribute("idempotency_keys_removed", keys_removed.to_string());
+    let Some(expiry) = USERNAME_EXPIRY.may_load(deps.storage)? else {
+        return Ok(response.add_attribute("released", "0"));
+    };
+    
+    let cutoff = now.saturating_sub(expiry.grace_secs);
+    let entries: Vec<(u64, String)> = USERNAME_EXPIRIES
+        .keys(deps.storage, None, Some(Bound::exclusive((cutoff + 1, String::new()))), Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+    let mut released = 0u32;
+    for (expires_at, username) in entries {
+        USERNAME_EXPIRIES.remove(deps.storage, (expires_at, username.clone()));
+        let Some(user) = USERS_BY_USERNAME.may_load(deps.storage, username)? else {
+            continue;
+        };
+        if user.expires_at != Some(expires_at) || user_removal_blocked(deps.storage, &user)? {
+            continue;
+        }
+        let (refund, event) = release_username(deps.storage, &user)?;
+        response = response.add_messages(refund).add_event(event);
+        released += 1;
+    }
+    Ok(response.add_attribute("released", released.to_string()))
+}
+
+// Maximum length of a friend request message
+const MAX_FRIEND_REQUEST_MESSAGE_LENGTH: usize = 140;
+
+// Helper function to trim a friend request message, dropping it when blank
+fn validate_friend_request_message(message: Option<String>) -> Result<Option<String>, ContractError> {
+    let message = message.map(|message| message.trim().to_string()).filter(|message| !message.is_empty());
+    if message.as_ref().is_some_and(|message| message.chars().count() > MAX_FRIEND_REQUEST_MESSAGE_LENGTH) {
+        return Err(ContractError::InvalidFriendRequestMessage {});
+    }
+    Ok(message)
+}
+
+pub fn execute_send_friend_request(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to_username: String,
+    message: Option<String>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    let normalized_to_username = normalize_username(&to_username);
+    let message = validate_friend_request_message(message)?;
+    
+    // Check if trying to add self
+    if from_username == normalized_to_username {
+        return Err(ContractError::CannotAddSelf {});
+    }
+    
+    // Check if target user exists
+    if USERS_BY_USERNAME.may_load(deps.storage, normalized_to_username.clone())?.is_none() {
+        return Err(ContractError::UserNotFound {});
+    }
+    
+    // Check if already friends
+    let friendship_key1 = (from_username.clone(), normalized_to_username.clone());
+    let friendship_key2 = (normalized_to_username.clone(), from_username.clone());
+    
+    if FRIENDSHIPS.may_load(deps.storage, friendship_key1)?.is_some() ||
+       FRIENDSHIPS.may_load(deps.storage, friendship_key2)?.is_some() {
+        return Err(ContractError::AlreadyFriends {});
+    }
+    
+    // Requesting someone who already requested you accepts their request instead
+    let reverse_pending = FRIEND_REQUESTS.may_load(deps.storage, (normalized_to_username.clone(), from_username.clone()))?
+        .is_some_and(|request| matches!(request.status, FriendRequestStatus::Pending));
+    if reverse_pending {
+        let response = execute_accept_friend_request(deps, env, info, normalized_to_username)?;
+        return Ok(response.add_attribute("auto_accepted", "true"));
+    }
+    
+    // Only a pending request blocks a new one; a declined one waits out the cooldown
+    let request_key = (from_username.clone(), normalized_to_username.clone());
+    let previous = FRIEND_REQUESTS.may_load(deps.storage, request_key.clone())?;
+    let mut declines = FRIEND_REQUEST_DECLINES.may_load(deps.storage, request_key.clone())?;
+    match previous {
+        Some(request) if matches!(request.status, FriendRequestStatus::Pending) => {
+            return Err(ContractError::FriendRequestAlreadyExists {});
+        }
+        // Requests declined before declines were tracked separately
+        Some(request) if matches!(request.status, FriendRequestStatus::Declined) && declines.is_none() => {
+            declines = Some(FriendRequestDeclines { count: 1, last_declined_at: request.updated_at });
+        }
+        _ => {}
+    }
+    if let Some(declines) = &declines {
+        let policy = CONFIG.may_load(deps.storage)?.unwrap_or_default().friend_requests;
+        if declines.count >= policy.max_declines {
+            return Err(ContractError::FriendRequestBlocked {});
+        }
+        if env.block.time.seconds() < declines.last_declined_at.saturating_add(policy.cooldown_secs) {
+            return Err(ContractError::FriendRequestCooldown {});
+        }
+        FRIEND_REQUEST_DECLINES.save(deps.storage, request_key.clone(), declines)?;
+    }
+    ensure_open_item_limit(
+        deps.storage,
+        &SENT_FRIEND_REQUEST_COUNTS,
+        &from_username,
+        |limits| limits.friend_requests,
+        ContractError::TooManyOpenFriendRequests {},
+    )?;
+    
+    let friend_request = FriendRequest {
+        from_username: from_username.clone(),
+        to_username: normalized_to_username.clone(),
+        status: FriendRequestStatus::Pending,
+        message,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+    };
+    
+    FRIEND_REQUESTS.save(deps.storage, request_key, &friend_request)?;
+    set_friend_request_pending(deps.storage, &from_username, &normalized_to_username, true)?;
+    notify(deps.storage, &env, &normalized_to_username, NotificationKind::FriendRequestReceived, &from_username, None)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "send_friend_request")
+        .add_attribute("from_username", from_username)
+        .add_attribute("to_username", normalized_to_username))
+}
+
+pub fn execute_accept_friend_request(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    from_username: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let to_username = get_username_from_wallet(&deps, &info.sender)?;
+    
+    let request_key = (from_username.clone(), to_username.clone());
+    let _friend_request = FRIEND_REQUESTS.load(deps.storage, request_key.clone())
+        .map_err(|_| ContractError::FriendRequestNotFound {})?;
+    
+    // Update friend request status
+    FRIEND_REQUESTS.update(deps.storage, request_key.clone(), |req| -> Result<_, ContractError> {
+        let mut req = req.ok_or(ContractError::FriendRequestNotFound {})?;
+        req.status = FriendRequestStatus::Accepted;
+        req.updated_at = env.block.time.seconds();
+        Ok(req)
+    })?;
     
     // Create friendship (store both directions for easier lookup)
     let friendship = Friendship {
@@ -298,1162 +2016,6251 @@ pub fn execute_accept_friend_request(
         created_at: env.block.time.seconds(),
     };
     
-    FRIENDSHIPS.save(deps.storage, (from_username.clone(), to_username.clone()), &friendship)?;
-    FRIENDSHIPS.save(deps.storage, (to_username.clone(), from_username.clone()), &friendship)?;
+    FRIENDSHIPS.save(deps.storage, (from_username.clone(), to_username.clone()), &friendship)?;
+    FRIENDSHIPS.save(deps.storage, (to_username.clone(), from_username.clone()), &friendship)?;
+    set_friend_request_pending(deps.storage, &from_username, &to_username, false)?;
+    
+    // A mirrored request the accepter sent earlier is settled by this friendship
+    let mirrored_key = (to_username.clone(), from_username.clone());
+    let mirrored_pending = FRIEND_REQUESTS.may_load(deps.storage, mirrored_key.clone())?
+        .is_some_and(|request| matches!(request.status, FriendRequestStatus::Pending));
+    if mirrored_pending {
+        FRIEND_REQUESTS.remove(deps.storage, mirrored_key);
+        set_friend_request_pending(deps.storage, &to_username, &from_username, false)?;
+    }
+    
+    Ok(Response::new()
+        .add_attribute("action", "accept_friend_request")
+        .add_attribute("from", from_username)
+        .add_attribute("to", to_username))
+}
+
+pub fn execute_decline_friend_request(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    from_username: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let to_username = get_username_from_wallet(&deps, &info.sender)?;
+    
+    let request_key = (from_username.clone(), to_username.clone());
+    
+    // Declined requests are deleted; only the decline count is kept
+    let pending = FRIEND_REQUESTS.may_load(deps.storage, request_key.clone())?
+        .is_some_and(|request| matches!(request.status, FriendRequestStatus::Pending));
+    if !pending {
+        return Err(ContractError::FriendRequestNotFound {});
+    }
+    FRIEND_REQUESTS.remove(deps.storage, request_key.clone());
+    set_friend_request_pending(deps.storage, &from_username, &to_username, false)?;
+    let previous = FRIEND_REQUEST_DECLINES.may_load(deps.storage, request_key.clone())?;
+    FRIEND_REQUEST_DECLINES.save(deps.storage, request_key, &FriendRequestDeclines {
+        count: previous.map_or(0, |declines| declines.count) + 1,
+        last_declined_at: env.block.time.seconds(),
+    })?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "decline_friend_request")
+        .add_attribute("from", from_username)
+        .add_attribute("to", to_username))
+}
+
+// Helper function to add or remove a request in the recipient's pending index, keeping the
+// recipient's and sender's pending counts in step; marking a request that is already in that state does nothing
+fn set_friend_request_pending(storage: &mut dyn Storage, from_username: &str, to_username: &str, pending: bool) -> StdResult<()> {
+    let key = (to_username.to_string(), from_username.to_string());
+    if PENDING_FRIEND_REQUESTS.has(storage, key.clone()) == pending {
+        return Ok(());
+    }
+    if pending {
+        PENDING_FRIEND_REQUESTS.save(storage, key, &true)?;
+    } else {
+        PENDING_FRIEND_REQUESTS.remove(storage, key);
+    }
+    adjust_open_count(storage, &PENDING_FRIEND_REQUEST_COUNTS, to_username.to_string(), pending)?;
+    adjust_open_count(storage, &SENT_FRIEND_REQUEST_COUNTS, from_username.to_string(), pending)
+}
+
+pub fn execute_remove_friend(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    friend_username: String,
+    leave_shared_groups: bool,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    
+    // Check if they are friends
+    let friendship_key1 = (username.clone(), friend_username.clone());
+    let friendship_key2 = (friend_username.clone(), username.clone());
+    
+    if FRIENDSHIPS.may_load(deps.storage, friendship_key1.clone())?.is_none() {
+        return Err(ContractError::NotFriends {});
+    }
+    
+    // Remove friendship (both directions)
+    FRIENDSHIPS.remove(deps.storage, friendship_key1);
+    FRIENDSHIPS.remove(deps.storage, friendship_key2);
+    
+    let mut response = Response::new()
+        .add_attribute("action", "remove_friend")
+        .add_attribute("user", username.clone())
+        .add_attribute("removed_friend", friend_username.clone());
+    
+    // Cancel open payment requests either way, with or without proof; requests hold no funds
+    // until approval. The pair's open payment index covers every one of them.
+    let payment_ids: Vec<u64> = OPEN_PAYMENTS
+        .prefix((username.clone(), friend_username.clone()))
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for payment_id in payment_ids {
+        let mut payment = PAYMENTS.load(deps.storage, payment_id)?;
+        if !payment_request_open(&payment) {
+            continue;
+        }
+        payment.status = PaymentStatus::Cancelled;
+        payment.updated_at = env.block.time.seconds();
+        payment.updated_at_height = env.block.height;
+        save_payment(deps.storage, payment_id, &payment)?;
+        let receipt_hash = issue_receipt(deps.storage, &payment)?;
+        let callback = take_callback(deps.storage, RecordKind::Payment, payment_id, format!("{:?}", PaymentStatus::Cancelled), &payment.amount)?;
+        response = response.add_submessages(callback).add_event(
+            cosmwasm_std::Event::new("payment_request_cancelled")
+                .add_attribute("payment_id", payment_id.to_string())
+                .add_attribute("reason", "unfriended")
+                .add_attribute("receipt_hash", receipt_hash)
+        );
+    }
+    
+    // Each owner drops the other from their groups, unless that would lose unsettled balances
+    if leave_shared_groups {
+        for (owner, member) in [(&username, &friend_username), (&friend_username, &username)] {
+            let group_ids: Vec<u64> = USER_GROUPS
+                .prefix(member.clone())
+                .keys(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<_>>()?;
+            for group_id in group_ids {
+                let mut group = GROUPS.load(deps.storage, group_id)?;
+                if group.owner != *owner {
+                    continue;
+                }
+                if has_unsettled_balance(deps.storage, group_id, member)? {
+                    response = response.add_event(
+                        cosmwasm_std::Event::new("group_member_kept")
+                            .add_attribute("group_id", group_id.to_string())
+                            .add_attribute("username", member.clone())
+                            .add_attribute("reason", "unsettled_balance")
+                    );
+                    continue;
+                }
+                group.members.retain(|existing| existing != member);
+                group.updated_at = env.block.time.seconds();
+                GROUPS.save(deps.storage, group_id, &group)?;
+                USER_GROUPS.remove(deps.storage, (member.clone(), group_id));
+                response = response.add_event(
+                    cosmwasm_std::Event::new("group_member_removed")
+                        .add_attribute("group_id", group_id.to_string())
+                        .add_attribute("username", member.clone())
+                        .add_attribute("reason", "unfriended")
+                );
+            }
+        }
+    }
+    
+    Ok(response)
+}
+
+// FRIEND GROUP FUNCTIONS
+
+// Maximum number of members in a group, including the owner
+const MAX_GROUP_MEMBERS: usize = 50;
+
+pub fn execute_create_group(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let owner = get_username_from_wallet(&deps, &info.sender)?;
+    
+    let name = name.trim().to_string();
+    if name.is_empty() || name.len() > MAX_LABEL_LENGTH {
+        return Err(ContractError::InvalidGroupName {});
+    }
+    if GROUP_NAMES.has(deps.storage, (owner.clone(), name.clone())) {
+        return Err(ContractError::GroupNameTaken {});
+    }
+    
+    let group_id = GROUP_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    GROUP_SEQ.save(deps.storage, &group_id)?;
+    
+    let group = FriendGroup {
+        id: group_id,
+        owner: owner.clone(),
+        name: name.clone(),
+        members: vec![owner.clone()],
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+    };
+    GROUPS.save(deps.storage, group_id, &group)?;
+    GROUP_NAMES.save(deps.storage, (owner.clone(), name.clone()), &group_id)?;
+    USER_GROUPS.save(deps.storage, (owner.clone(), group_id), &true)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "create_group")
+        .add_attribute("group_id", group_id.to_string())
+        .add_attribute("owner", owner)
+        .add_attribute("name", name))
+}
+
+pub fn execute_add_to_group(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    group_id: u64,
+    username: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let sender = get_username_from_wallet(&deps, &info.sender)?;
+    let username = normalize_username(&username);
+    
+    let mut group = GROUPS.load(deps.storage, group_id)
+        .map_err(|_| ContractError::GroupNotFound {})?;
+    if group.owner != sender {
+        return Err(ContractError::OnlyGroupOwner {});
+    }
+    if group.members.contains(&username) {
+        return Err(ContractError::AlreadyGroupMember {});
+    }
+    if group.members.len() >= MAX_GROUP_MEMBERS {
+        return Err(ContractError::GroupFull {});
+    }
+    
+    // Groups organize the owner's contacts, so only friends can be added
+    if !FRIENDSHIPS.has(deps.storage, (sender.clone(), username.clone())) {
+        return Err(ContractError::NotFriends {});
+    }
+    
+    group.members.push(username.clone());
+    group.updated_at = env.block.time.seconds();
+    GROUPS.save(deps.storage, group_id, &group)?;
+    USER_GROUPS.save(deps.storage, (username.clone(), group_id), &true)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "add_to_group")
+        .add_attribute("group_id", group_id.to_string())
+        .add_attribute("username", username))
+}
+
+pub fn execute_remove_from_group(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    group_id: u64,
+    username: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let sender = get_username_from_wallet(&deps, &info.sender)?;
+    let username = normalize_username(&username);
+    
+    let mut group = GROUPS.load(deps.storage, group_id)
+        .map_err(|_| ContractError::GroupNotFound {})?;
+    // The owner manages membership; members may also leave on their own
+    if group.owner != sender && username != sender {
+        return Err(ContractError::OnlyGroupOwner {});
+    }
+    if username == group.owner {
+        return Err(ContractError::CannotRemoveGroupOwner {});
+    }
+    if !group.members.contains(&username) {
+        return Err(ContractError::NotGroupMember {});
+    }
+    // Leaving would drop the member's debts or credits from the ledger
+    if has_unsettled_balance(deps.storage, group_id, &username)? {
+        return Err(ContractError::GroupBalanceOutstanding {});
+    }
+    
+    group.members.retain(|member| member != &username);
+    group.updated_at = env.block.time.seconds();
+    GROUPS.save(deps.storage, group_id, &group)?;
+    USER_GROUPS.remove(deps.storage, (username.clone(), group_id));
+    
+    Ok(Response::new()
+        .add_attribute("action", "remove_from_group")
+        .add_attribute("group_id", group_id.to_string())
+        .add_attribute("username", username)
+        .add_attribute("removed_by", sender))
+}
+
+pub fn execute_record_group_expense(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    group_id: u64,
+    amount: Coin,
+    description: String,
+    split_among: Vec<String>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let payer = get_username_from_wallet(&deps, &info.sender)?;
+    validate_text_length(deps.storage, TextKind::Description, &description)?;
+    let group = GROUPS.load(deps.storage, group_id)
+        .map_err(|_| ContractError::GroupNotFound {})?;
+    if !group.members.contains(&payer) {
+        return Err(ContractError::NotGroupMember {});
+    }
+    if amount.amount.is_zero() {
+        return Err(ContractError::InvalidPaymentAmount {});
+    }
+    
+    let participants: Vec<String> = if split_among.is_empty() {
+        group.members.clone()
+    } else {
+        split_among.iter().map(|username| normalize_username(username)).collect()
+    };
+    for (i, participant) in participants.iter().enumerate() {
+        if !group.members.contains(participant) || participants[..i].contains(participant) {
+            return Err(ContractError::InvalidExpenseSplit {});
+        }
+    }
+    
+    // Equal shares; the remainder goes one unit each to the first participants
+    let count = Uint128::from(participants.len() as u128);
+    let share = amount.amount / count;
+    let remainder = (amount.amount - share * count).u128() as usize;
+    for (i, participant) in participants.iter().enumerate() {
+        let owed = if i < remainder { share + Uint128::one() } else { share };
+        update_group_balance(deps.storage, group_id, participant, &amount.denom, Uint128::zero(), owed)?;
+    }
+    update_group_balance(deps.storage, group_id, &payer, &amount.denom, amount.amount, Uint128::zero())?;
+    
+    let expense_id = GROUP_EXPENSE_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    GROUP_EXPENSE_SEQ.save(deps.storage, &expense_id)?;
+    let expense = GroupExpense {
+        id: expense_id,
+        group_id,
+        payer: payer.clone(),
+        amount,
+        description,
+        participants,
+        created_at: env.block.time.seconds(),
+    };
+    GROUP_EXPENSES.save(deps.storage, (group_id, expense_id), &expense)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "record_group_expense")
+        .add_attribute("group_id", group_id.to_string())
+        .add_attribute("expense_id", expense_id.to_string())
+        .add_attribute("payer", payer)
+        .add_attribute("amount", expense.amount.to_string()))
+}
+
+pub fn execute_settle_up(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    group_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let sender = get_username_from_wallet(&deps, &info.sender)?;
+    let group = GROUPS.load(deps.storage, group_id)
+        .map_err(|_| ContractError::GroupNotFound {})?;
+    if !group.members.contains(&sender) {
+        return Err(ContractError::NotGroupMember {});
+    }
+    
+    let mut balances: Vec<GroupBalance> = GROUP_BALANCES
+        .sub_prefix(group_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, balance)| balance))
+        .collect::<StdResult<_>>()?;
+    // Requests from an earlier settle up still waiting on payment count as paid, so their
+    // amounts are not requested twice
+    let open_settlements: Vec<u64> = GROUP_SETTLEMENTS
+        .prefix(group_id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for payment_id in open_settlements {
+        let payment = PAYMENTS.load(deps.storage, payment_id)?;
+        for (username, paid, owed) in [
+            (&payment.to_username, payment.amount.amount, Uint128::zero()),
+            (&payment.from_username, Uint128::zero(), payment.amount.amount),
+        ] {
+            match balances.iter_mut().find(|balance| balance.username == *username && balance.denom == payment.amount.denom) {
+                Some(balance) => {
+                    balance.paid += paid;
+                    balance.owed += owed;
+                }
+                None => balances.push(GroupBalance {
+                    username: username.clone(),
+                    denom: payment.amount.denom.clone(),
+                    paid,
+                    owed,
+                }),
+            }
+        }
+    }
+    
+    // Net creditors and debtors per denom as (amount, username), largest first
+    type NetPositions = Vec<(Uint128, String)>;
+    let mut nets: std::collections::BTreeMap<String, (NetPositions, NetPositions)> =
+        std::collections::BTreeMap::new();
+    for balance in &balances {
+        let (creditors, debtors) = nets.entry(balance.denom.clone()).or_default();
+        if balance.paid > balance.owed {
+            creditors.push((balance.paid - balance.owed, balance.username.clone()));
+        } else if balance.owed > balance.paid {
+            debtors.push((balance.owed - balance.paid, balance.username.clone()));
+        }
+    }
+    
+    // Greedily match the largest debtor with the largest creditor, which
+    // needs at most one request fewer than the number of members involved
+    let mut transfers: Vec<(String, String, Coin)> = vec![]; // (creditor, debtor, amount)
+    for (denom, (mut creditors, mut debtors)) in nets {
+        creditors.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        debtors.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        let (mut c, mut d) = (0, 0);
+        while c < creditors.len() && d < debtors.len() {
+            let amount = creditors[c].0.min(debtors[d].0);
+            transfers.push((creditors[c].1.clone(), debtors[d].1.clone(), Coin { denom: denom.clone(), amount }));
+            creditors[c].0 -= amount;
+            debtors[d].0 -= amount;
+            if creditors[c].0.is_zero() {
+                c += 1;
+            }
+            if debtors[d].0.is_zero() {
+                d += 1;
+            }
+        }
+    }
+    if transfers.is_empty() {
+        return Err(ContractError::NothingToSettle {});
+    }
+    
+    // Balances stay on the ledger until each request is paid; a rejected or cancelled request
+    // leaves its debt there for the next settle up
+    ensure_payment_type_enabled(deps.storage, &PaymentType::PaymentRequest)?;
+    let mut state = STATE.load(deps.storage)?;
+    let mut payment_ids = vec![];
+    for (creditor, debtor, amount) in transfers {
+        ensure_open_item_limit(
+            deps.storage,
+            &OPEN_PAYMENT_REQUEST_COUNTS,
+            &creditor,
+            |limits| limits.payment_requests,
+            ContractError::TooManyOpenPaymentRequests {},
+        )?;
+        let debtor_user = USERS_BY_USERNAME.load(deps.storage, debtor.clone())?;
+        ensure_request_allowed(deps.storage, &creditor, &debtor_user)?;
+        let payment_id = state.next_payment_id;
+        state.next_payment_id += 1;
+        let payment = Payment {
+            id: payment_id,
+            from_username: creditor.clone(),
+            to_username: debtor.clone(),
+            to_wallet: None,
+            amount,
+            description: format!("Settle up: {}", group.name),
+            payment_type: PaymentType::PaymentRequest,
+            proof_type: ProofType::None,
+            proof_data: None,
+            status: PaymentStatus::Pending,
+            category: None,
+            tags: vec![],
+            redacted: false,
+            visibility: resolve_visibility(deps.storage, &creditor, None)?,
+            price_quote: None,
+            external_ref: None,
+            reminder_count: 0,
+            last_reminded_at: None,
+            created_at: env.block.time.seconds(),
+            updated_at: env.block.time.seconds(),
+            created_at_height: env.block.height,
+            updated_at_height: env.block.height,
+            release_delay_secs: None,
+            release_at: None,
+        };
+        save_payment(deps.storage, payment_id, &payment)?;
+        GROUP_SETTLEMENTS.save(deps.storage, (group_id, payment_id), &true)?;
+        SETTLEMENT_GROUPS.save(deps.storage, payment_id, &group_id)?;
+        index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &creditor, payment_id)?;
+        index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &debtor, payment_id)?;
+        notify(deps.storage, &env, &debtor, NotificationKind::PaymentRequestReceived, &creditor, Some(payment_id))?;
+        payment_ids.push(payment_id.to_string());
+    }
+    STATE.save(deps.storage, &state)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "settle_up")
+        .add_attribute("group_id", group_id.to_string())
+        .add_attribute("settled_by", sender)
+        .add_attribute("payment_ids", payment_ids.join(",")))
+}
+
+// Helper function to add to a member's running totals in a group ledger
+fn update_group_balance(
+    storage: &mut dyn Storage,
+    group_id: u64,
+    username: &str,
+    denom: &str,
+    paid: Uint128,
+    owed: Uint128,
+) -> StdResult<()> {
+    let key = (group_id, username.to_string(), denom.to_string());
+    let mut balance = GROUP_BALANCES.may_load(storage, key.clone())?.unwrap_or(GroupBalance {
+        username: username.to_string(),
+        denom: denom.to_string(),
+        paid: Uint128::zero(),
+        owed: Uint128::zero(),
+    });
+    balance.paid += paid;
+    balance.owed += owed;
+    GROUP_BALANCES.save(storage, key, &balance)
+}
+
+// Helper function to check whether a member still owes or is owed anything in a group
+fn has_unsettled_balance(storage: &dyn Storage, group_id: u64, username: &str) -> StdResult<bool> {
+    for item in GROUP_BALANCES
+        .prefix((group_id, username.to_string()))
+        .range(storage, None, None, Order::Ascending)
+    {
+        let (_, balance) = item?;
+        if balance.paid != balance.owed {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// PAYMENT SYSTEM FUNCTIONS
+
+// Helper function to add a record to a user's payment or task index, counting it only once
+fn index_user_record(
+    storage: &mut dyn Storage,
+    index: &Map<(String, u64), bool>,
+    counts: &Map<String, u64>,
+    username: &str,
+    id: u64,
+) -> StdResult<()> {
+    if index.has(storage, (username.to_string(), id)) {
+        return Ok(());
+    }
+    index.save(storage, (username.to_string(), id), &true)?;
+    let count = counts.may_load(storage, username.to_string())?.unwrap_or(0);
+    counts.save(storage, username.to_string(), &(count + 1))
+}
+
+// Helper function to remove a record from a user's payment or task index and its count
+fn unindex_user_record(
+    storage: &mut dyn Storage,
+    index: &Map<(String, u64), bool>,
+    counts: &Map<String, u64>,
+    username: &str,
+    id: u64,
+) -> StdResult<()> {
+    if !index.has(storage, (username.to_string(), id)) {
+        return Ok(());
+    }
+    index.remove(storage, (username.to_string(), id));
+    let count = counts.may_load(storage, username.to_string())?.unwrap_or(0);
+    counts.save(storage, username.to_string(), &count.saturating_sub(1))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_send_direct_payment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to_username: String,
+    amount: cosmwasm_std::Coin,
+    description: String,
+    proof_type: ProofType,
+    category: Option<String>,
+    tags: Vec<String>,
+    visibility: Option<Visibility>,
+    external_ref: Option<String>,
+    idempotency_key: Option<String>,
+    release_delay_secs: Option<u64>,
+) -> Result<Response, ContractError> {
+    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    validate_text_length(deps.storage, TextKind::Description, &description)?;
+    if let Some(payment_id) = replayed_record(deps.storage, &env, &info.sender, &idempotency_key, RecordKind::Payment)? {
+        return Ok(idempotent_replay(&info, "send_direct_payment", "payment_id", payment_id));
+    }
+    ensure_payment_type_enabled(deps.storage, &PaymentType::DirectPayment)?;
+    ensure_proof_type_enabled(deps.storage, &proof_type)?;
+    // Only payments waiting on proof hold their funds until approval
+    let holds_escrow = !matches!(proof_type, ProofType::None);
+    if release_delay_secs.is_some_and(|delay| delay == 0 || delay > MAX_RELEASE_DELAY_SECS || !holds_escrow) {
+        return Err(ContractError::InvalidReleaseDelay {});
+    }
+    validate_labels(&category, &tags)?;
+    let visibility = resolve_visibility(deps.storage, &from_username, visibility)?;
+    
+    // Validate payment
+    if from_username == to_username {
+        return Err(ContractError::CannotPaySelf {});
+    }
+    
+    // Check if recipient exists
+    let recipient = USERS_BY_USERNAME.load(deps.storage, to_username.clone())
+        .map_err(|_| ContractError::UserNotFound {})?;
+    
+    // Validate payment amount
+    if amount.amount.is_zero() {
+        return Err(ContractError::InvalidPaymentAmount {});
+    }
+    
+    // Check if sufficient funds were sent; any surplus goes back to the sender
+    let refund = validate_funds(deps.storage, &info, &amount)?;
+    
+    let mut state = STATE.load(deps.storage)?;
+    let payment_id = state.next_payment_id;
+    state.next_payment_id += 1;
+    STATE.save(deps.storage, &state)?;
+    
+    let payment = Payment {
+        id: payment_id,
+        from_username: from_username.clone(),
+        to_username: to_username.clone(),
+        to_wallet: None,
+        amount,
+        description,
+        payment_type: PaymentType::DirectPayment,
+        proof_type: proof_type.clone(),
+        proof_data: None,
+        status: if matches!(proof_type, ProofType::None) { 
+            PaymentStatus::Completed 
+        } else { 
+            PaymentStatus::Pending 
+        },
+        category,
+        tags,
+        redacted: false,
+        visibility,
+        price_quote: None,
+        external_ref,
+        reminder_count: 0,
+        last_reminded_at: None,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+        created_at_height: env.block.height,
+        updated_at_height: env.block.height,
+        release_delay_secs,
+        release_at: None,
+    };
+    
+    claim_external_ref(deps.storage, &PAYMENT_EXTERNAL_REFS, &from_username, &payment.external_ref, payment_id)?;
+    save_idempotency_key(deps.storage, &env, &info.sender, idempotency_key, RecordKind::Payment, payment_id)?;
+    save_payment(deps.storage, payment_id, &payment)?;
+    index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &from_username, payment_id)?;
+    index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &to_username, payment_id)?;
+    
+    if payment_holds_escrow(&payment) {
+        add_escrow(deps.storage, &from_username, &payment.amount)?;
+    }
+    
+    let mut response = Response::new()
+        .add_messages(refund)
+        .add_attribute("action", "send_direct_payment")
+        .add_attribute("from", from_username)
+        .add_attribute("to", to_username.clone())
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("amount", payment.amount.to_string());
+    
+    // If no proof required, send payment immediately
+    if matches!(proof_type, ProofType::None) {
+        let receipt_hash = issue_receipt(deps.storage, &payment)?;
+        record_counterparties(deps.storage, &env, &payment.from_username, &payment.to_username)?;
+        let payment_msg = CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.wallet_address.to_string(),
+            amount: vec![payment.amount],
+        });
+        response = response
+            .add_message(payment_msg)
+            .add_attribute("receipt_hash", receipt_hash);
+    }
+    
+    Ok(response)
+}
+
+// Payer of a payment request: a registered user, or a wallet that has not registered yet
+pub enum RequestPayer {
+    Username(String),
+    Wallet(Addr),
+}
+
+// Maximum open payment requests one user can hold against a wallet until it registers
+const MAX_WALLET_PAYMENT_REQUESTS: u64 = 20;
+
+// Helper function to address a payment request to a wallet, by its username once it has one
+fn request_payer_for_wallet(deps: Deps, wallet: &str) -> Result<RequestPayer, ContractError> {
+    let wallet = validate_address(deps.api, wallet)?;
+    Ok(match USERS_BY_WALLET.may_load(deps.storage, wallet.clone())? {
+        Some(username) => RequestPayer::Username(username),
+        None => RequestPayer::Wallet(wallet),
+    })
+}
+
+// Helper function to bind payment requests made to a wallet to the username it registers
+fn bind_wallet_payment_requests(storage: &mut dyn Storage, env: &Env, wallet: &Addr, username: &str) -> StdResult<usize> {
+    let payment_ids: Vec<u64> = WALLET_PAYMENT_REQUESTS
+        .prefix(wallet.clone())
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    let mut bound = 0;
+    for payment_id in payment_ids {
+        WALLET_PAYMENT_REQUESTS.remove(storage, (wallet.clone(), payment_id));
+        let Some(mut payment) = PAYMENTS.may_load(storage, payment_id)? else {
+            continue;
+        };
+        payment.to_username = username.to_string();
+        payment.to_wallet = None;
+        save_payment(storage, payment_id, &payment)?;
+        index_user_record(storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, username, payment_id)?;
+        if matches!(payment.status, PaymentStatus::Pending) {
+            notify(storage, env, username, NotificationKind::PaymentRequestReceived, &payment.from_username, Some(payment_id))?;
+        }
+        bound += 1;
+    }
+    Ok(bound)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create_payment_request(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payer: RequestPayer,
+    amount: cosmwasm_std::Coin,
+    description: String,
+    proof_type: ProofType,
+    category: Option<String>,
+    tags: Vec<String>,
+    visibility: Option<Visibility>,
+    price_quote: Option<PriceQuoteMsg>,
+    external_ref: Option<String>,
+    idempotency_key: Option<String>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    validate_text_length(deps.storage, TextKind::Description, &description)?;
+    if let Some(payment_id) = replayed_record(deps.storage, &env, &info.sender, &idempotency_key, RecordKind::Payment)? {
+        return Ok(idempotent_replay(&info, "create_payment_request", "payment_id", payment_id));
+    }
+    ensure_payment_type_enabled(deps.storage, &PaymentType::PaymentRequest)?;
+    ensure_proof_type_enabled(deps.storage, &proof_type)?;
+    ensure_open_item_limit(
+        deps.storage,
+        &OPEN_PAYMENT_REQUEST_COUNTS,
+        &from_username,
+        |limits| limits.payment_requests,
+        ContractError::TooManyOpenPaymentRequests {},
+    )?;
+    validate_labels(&category, &tags)?;
+    let visibility = resolve_visibility(deps.storage, &from_username, visibility)?;
+    
+    // Requests are funded at approval; reject unaccepted denoms up front
+    ensure_denom_accepted(deps.storage, &amount.denom)?;
+    let price_quote = validate_price_quote(deps.storage, price_quote)?;
+    
+    let (to_username, to_wallet) = match payer {
+        RequestPayer::Username(to_username) => {
+            if from_username == to_username {
+                return Err(ContractError::CannotPaySelf {});
+            }
+            // Check if recipient exists
+            let recipient = USERS_BY_USERNAME.load(deps.storage, to_username.clone())
+                .map_err(|_| ContractError::UserNotFound {})?;
+            ensure_request_allowed(deps.storage, &from_username, &recipient)?;
+            (to_username, None)
+        }
+        RequestPayer::Wallet(to_wallet) => {
+            let waiting = WALLET_PAYMENT_REQUEST_COUNTS
+                .may_load(deps.storage, (from_username.clone(), to_wallet.clone()))?
+                .unwrap_or(0);
+            if waiting >= MAX_WALLET_PAYMENT_REQUESTS {
+                return Err(ContractError::TooManyWalletPaymentRequests {});
+            }
+            (String::new(), Some(to_wallet))
+        }
+    };
+    
+    let mut state = STATE.load(deps.storage)?;
+    let payment_id = state.next_payment_id;
+    state.next_payment_id += 1;
+    STATE.save(deps.storage, &state)?;
+    
+    let payment = Payment {
+        id: payment_id,
+        from_username: from_username.clone(),
+        to_username: to_username.clone(),
+        to_wallet: to_wallet.clone(),
+        amount,
+        description,
+        payment_type: PaymentType::PaymentRequest,
+        proof_type,
+        proof_data: None,
+        status: PaymentStatus::Pending,
+        category,
+        tags,
+        redacted: false,
+        visibility,
+        price_quote,
+        external_ref,
+        reminder_count: 0,
+        last_reminded_at: None,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+        created_at_height: env.block.height,
+        updated_at_height: env.block.height,
+        release_delay_secs: None,
+        release_at: None,
+    };
+    
+    claim_external_ref(deps.storage, &PAYMENT_EXTERNAL_REFS, &from_username, &payment.external_ref, payment_id)?;
+    save_idempotency_key(deps.storage, &env, &info.sender, idempotency_key, RecordKind::Payment, payment_id)?;
+    save_payment(deps.storage, payment_id, &payment)?;
+    index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &from_username, payment_id)?;
+    let to = match &to_wallet {
+        Some(to_wallet) => {
+            WALLET_PAYMENT_REQUESTS.save(deps.storage, (to_wallet.clone(), payment_id), &true)?;
+            to_wallet.to_string()
+        }
+        None => {
+            index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &to_username, payment_id)?;
+            notify(deps.storage, &env, &to_username, NotificationKind::PaymentRequestReceived, &from_username, Some(payment_id))?;
+            to_username
+        }
+    };
+    
+    Ok(Response::new()
+        .add_attribute("action", "create_payment_request")
+        .add_attribute("from", from_username)
+        .add_attribute("to", to)
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("amount", payment.amount.to_string()))
+}
+
+pub fn execute_create_help_request(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to_username: String,
+    amount: cosmwasm_std::Coin,
+    description: String,
+    proof_type: ProofType,
+) -> Result<Response, ContractError> {
+    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_payment_type_enabled(deps.storage, &PaymentType::PaymentRequest)?;
+    ensure_proof_type_enabled(deps.storage, &proof_type)?;
+    ensure_open_item_limit(
+        deps.storage,
+        &OPEN_PAYMENT_REQUEST_COUNTS,
+        &from_username,
+        |limits| limits.payment_requests,
+        ContractError::TooManyOpenPaymentRequests {},
+    )?;
+    
+    // Validate
+    if from_username == to_username {
+        return Err(ContractError::CannotPaySelf {});
+    }
+    
+    // Check if recipient exists
+    if USERS_BY_USERNAME.may_load(deps.storage, to_username.clone())?.is_none() {
+        return Err(ContractError::UserNotFound {});
+    }
+    
+    // Check if sufficient funds were sent for escrow
+    let refund = validate_funds(deps.storage, &info, &amount)?;
+    
+    let mut state = STATE.load(deps.storage)?;
+    let payment_id = state.next_payment_id;
+    state.next_payment_id += 1;
+    STATE.save(deps.storage, &state)?;
+    
+    let payment = Payment {
+        id: payment_id,
+        from_username: from_username.clone(),
+        to_username: to_username.clone(),
+        to_wallet: None,
+        amount,
+        description,
+        payment_type: PaymentType::PaymentRequest, // Changed from HelpRequest to PaymentRequest
+        proof_type,
+        proof_data: None,
+        status: PaymentStatus::Pending,
+        category: None,
+        tags: vec![],
+        redacted: false,
+        visibility: resolve_visibility(deps.storage, &from_username, None)?,
+        price_quote: None,
+        external_ref: None,
+        reminder_count: 0,
+        last_reminded_at: None,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+        created_at_height: env.block.height,
+        updated_at_height: env.block.height,
+        release_delay_secs: None,
+        release_at: None,
+    };
+    
+    save_payment(deps.storage, payment_id, &payment)?;
+    index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &from_username, payment_id)?;
+    index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &to_username, payment_id)?;
+    
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_attribute("action", "create_help_request")
+        .add_attribute("from", from_username)
+        .add_attribute("to", to_username)
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("amount", payment.amount.to_string()))
+}
+
+pub fn execute_submit_proof(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payment_id: u64,
+    proof_data: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    validate_text_length(deps.storage, TextKind::Evidence, &proof_data)?;
+    
+    let payment = update_payment(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
+        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
+        
+        // Check authorization - only the recipient can submit proof
+        if payment.to_username != username {
+            return Err(ContractError::PaymentNotAuthorized {});
+        }
+        
+        // Check if proof is required
+        if matches!(payment.proof_type, ProofType::None) {
+            return Err(ContractError::NoProofRequired {});
+        }
+        
+        // Check payment status
+        if !matches!(payment.status, PaymentStatus::Pending) {
+            return Err(ContractError::PaymentAlreadyCompleted {});
+        }
+        
+        payment.proof_data = Some(proof_data);
+        payment.status = PaymentStatus::ProofSubmitted;
+        payment.updated_at = env.block.time.seconds();
+        payment.updated_at_height = env.block.height;
+        
+        Ok(payment)
+    })?;
+    ensure_proof_type_enabled(deps.storage, &payment.proof_type)?;
+    notify(deps.storage, &env, &payment.from_username, NotificationKind::PaymentProofSubmitted, &username, Some(payment_id))?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "submit_proof")
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("submitter", username))
+}
+
+pub fn execute_approve_payment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payment_id: u64,
+) -> Result<Response, ContractError> {
+    // Wallets without a username can approve payment requests addressed to them
+    let username = match get_username_from_wallet(&deps, &info.sender) {
+        Ok(username) => username,
+        Err(err) => {
+            let to_wallet = PAYMENTS.may_load(deps.storage, payment_id)?.and_then(|payment| payment.to_wallet);
+            if to_wallet.as_ref() != Some(&info.sender) {
+                return Err(err);
+            }
+            String::new()
+        }
+    };
+    
+    // Check and set the status in a single update before any funds move,
+    // so a payment can only ever be approved once
+    let mut payment = update_payment(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
+        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
+        
+        // Check authorization based on payment type
+        let authorized = match (&payment.payment_type, &payment.to_wallet) {
+            (PaymentType::DirectPayment, _) => payment.from_username == username,
+            (PaymentType::PaymentRequest, Some(to_wallet)) => *to_wallet == info.sender,
+            (PaymentType::PaymentRequest, None) => payment.to_username == username, // PaymentRequest: receiver approves
+        };
+        
+        if !authorized {
+            return Err(ContractError::PaymentNotAuthorized {});
+        }
+        
+        // Only open payments can be approved
+        match payment.status {
+            PaymentStatus::Completed => return Err(ContractError::PaymentAlreadyCompleted {}),
+            PaymentStatus::Cancelled => return Err(ContractError::PaymentAlreadyCancelled {}),
+            PaymentStatus::Rejected => return Err(ContractError::PaymentAlreadyRejected {}),
+            PaymentStatus::PendingRelease | PaymentStatus::Disputed => return Err(ContractError::PaymentAlreadyApproved {}),
+            PaymentStatus::Pending | PaymentStatus::ProofSubmitted => {}
+        }
+        
+        // Check if proof is required and submitted
+        if !matches!(payment.proof_type, ProofType::None) && 
+           !matches!(payment.status, PaymentStatus::ProofSubmitted) {
+            return Err(ContractError::ProofRequired {});
+        }
+        
+        // A release delay holds the escrow after approval so the sender can still dispute
+        match (&payment.payment_type, payment.release_delay_secs) {
+            (PaymentType::DirectPayment, Some(delay)) => {
+                payment.status = PaymentStatus::PendingRelease;
+                payment.release_at = Some(env.block.time.seconds() + delay);
+            }
+            _ => payment.status = PaymentStatus::Completed,
+        }
+        payment.updated_at = env.block.time.seconds();
+        payment.updated_at_height = env.block.height;
+        
+        Ok(payment)
+    })?;
+    
+    let approver = if username.is_empty() { info.sender.to_string() } else { username };
+    let mut response = Response::new()
+        .add_attribute("action", "approve_payment")
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("approver", approver);
+    
+    // Handle payment based on type
+    match payment.payment_type {
+        PaymentType::DirectPayment => {
+            // Direct payment funds already held in contract, send to recipient
+            ensure_no_funds(&info)?;
+            if let Some(release_at) = payment.release_at {
+                return Ok(response.add_event(
+                    cosmwasm_std::Event::new("payment_pending_release")
+                        .add_attribute("payment_id", payment_id.to_string())
+                        .add_attribute("release_at", release_at.to_string())
+                ));
+            }
+            release_escrow(deps.storage, &payment.from_username, &payment.amount)?;
+            let recipient = USERS_BY_USERNAME.load(deps.storage, payment.to_username.clone())?;
+            let payment_msg = CosmosMsg::Bank(BankMsg::Send {
+                to_address: recipient.wallet_address.to_string(),
+                amount: vec![payment.amount.clone()],
+            });
+            response = response.add_message(payment_msg);
+        },
+        PaymentType::PaymentRequest => {
+            // Price-quoted requests settle at the oracle price at approval time
+            if let Some(quote) = payment.price_quote.as_mut() {
+                payment.amount.amount = resolve_price_quote(deps.as_ref(), &env, quote, &payment.amount.denom)?;
+                save_payment(deps.storage, payment_id, &payment)?;
+                response = response.add_attribute("resolved_amount", payment.amount.to_string());
+            }
+            
+            // Payment request: approver (to_username) should send funds to requester (from_username)
+            // Check if sufficient funds were sent by approver; any surplus goes back to them
+            let refund = validate_funds(deps.storage, &info, &payment.amount)?;
+            
+            let requester = USERS_BY_USERNAME.load(deps.storage, payment.from_username.clone())?;
+            let payment_msg = CosmosMsg::Bank(BankMsg::Send {
+                to_address: requester.wallet_address.to_string(),
+                amount: vec![payment.amount.clone()],
+            });
+            response = response.add_message(payment_msg).add_messages(refund);
+        }
+    }
+    
+    let receipt_hash = issue_receipt(deps.storage, &payment)?;
+    response = response.add_attribute("receipt_hash", receipt_hash);
+    if payment.to_wallet.is_none() {
+        record_counterparties(deps.storage, &env, &payment.from_username, &payment.to_username)?;
+    }
+    
+    let callback = take_callback(deps.storage, RecordKind::Payment, payment_id, format!("{:?}", PaymentStatus::Completed), &payment.amount)?;
+    response = response.add_submessages(callback);
+    
+    Ok(response)
+}
+
+pub fn execute_reject_payment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payment_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let payment = PAYMENTS.load(deps.storage, payment_id)
+        .map_err(|_| ContractError::PaymentNotFound {})?;
+    
+    // Wallets without a username can reject payment requests addressed to them
+    let username = match get_username_from_wallet(&deps, &info.sender) {
+        Ok(username) => username,
+        Err(err) if payment.to_wallet.as_ref() != Some(&info.sender) => return Err(err),
+        Err(_) => String::new(),
+    };
+    
+    // Check authorization based on payment type
+    let authorized = match (&payment.payment_type, &payment.to_wallet) {
+        (PaymentType::DirectPayment, _) => payment.from_username == username,
+        (PaymentType::PaymentRequest, Some(to_wallet)) => *to_wallet == info.sender,
+        (PaymentType::PaymentRequest, None) => payment.to_username == username, // PaymentRequest: receiver approves
+    };
+    
+    if !authorized {
+        return Err(ContractError::PaymentNotAuthorized {});
+    }
+    
+    // Update payment status
+    let payment = update_payment(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
+        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
+        
+        if matches!(payment.status, PaymentStatus::Completed | PaymentStatus::Cancelled) {
+            return Err(ContractError::PaymentAlreadyCompleted {});
+        }
+        
+        // An approved payment can only be disputed during its release delay
+        if matches!(payment.status, PaymentStatus::PendingRelease | PaymentStatus::Disputed) {
+            return Err(ContractError::PaymentAlreadyApproved {});
+        }
+        
+        // A rejected payment has already been refunded
+        if matches!(payment.status, PaymentStatus::Rejected) {
+            return Err(ContractError::PaymentAlreadyRejected {});
+        }
+        
+        payment.status = PaymentStatus::Rejected;
+        payment.updated_at = env.block.time.seconds();
+        payment.updated_at_height = env.block.height;
+        
+        Ok(payment)
+    })?;
+    
+    let rejector = if username.is_empty() { info.sender.to_string() } else { username };
+    let mut response = Response::new()
+        .add_attribute("action", "reject_payment")
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("rejector", rejector);
+    
+    // Return escrowed funds to the sender
+    if payment_holds_escrow(&payment) {
+        release_escrow(deps.storage, &payment.from_username, &payment.amount)?;
+        let sender = USERS_BY_USERNAME.load(deps.storage, payment.from_username.clone())?;
+        let refund_msg = CosmosMsg::Bank(BankMsg::Send {
+            to_address: sender.wallet_address.to_string(),
+            amount: vec![payment.amount.clone()],
+        });
+        response = response.add_message(refund_msg);
+    }
+    
+    let receipt_hash = issue_receipt(deps.storage, &payment)?;
+    response = response.add_attribute("receipt_hash", receipt_hash);
+    
+    let callback = take_callback(deps.storage, RecordKind::Payment, payment_id, format!("{:?}", PaymentStatus::Rejected), &payment.amount)?;
+    response = response.add_submessages(callback);
+    
+    Ok(response)
+}
+
+pub fn execute_cancel_payment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payment_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    
+    let payment = PAYMENTS.load(deps.storage, payment_id)
+        .map_err(|_| ContractError::PaymentNotFound {})?;
+    
+    // Only sender can cancel
+    if payment.from_username != username {
+        return Err(ContractError::OnlySenderCanCancel {});
+    }
+    
+    // Update payment status
+    let payment = update_payment(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
+        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
+        
+        if matches!(payment.status, PaymentStatus::Completed) {
+            return Err(ContractError::PaymentAlreadyCompleted {});
+        }
+        
+        if matches!(payment.status, PaymentStatus::Cancelled) {
+            return Err(ContractError::PaymentAlreadyCancelled {});
+        }
+        
+        if matches!(payment.status, PaymentStatus::Rejected) {
+            return Err(ContractError::PaymentAlreadyCompleted {});
+        }
+        
+        // An approved payment can only be disputed during its release delay
+        if matches!(payment.status, PaymentStatus::PendingRelease | PaymentStatus::Disputed) {
+            return Err(ContractError::PaymentAlreadyApproved {});
+        }
+        
+        payment.status = PaymentStatus::Cancelled;
+        payment.updated_at = env.block.time.seconds();
+        payment.updated_at_height = env.block.height;
+        
+        Ok(payment)
+    })?;
+    
+    let sender = USERS_BY_USERNAME.load(deps.storage, payment.from_username.clone())?;
+    
+    let mut response = Response::new()
+        .add_attribute("action", "cancel_payment")
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("canceller", username);
+    
+    // Refund to sender (payment requests hold no funds until approval)
+    if payment_holds_escrow(&payment) {
+        release_escrow(deps.storage, &payment.from_username, &payment.amount)?;
+        let refund_msg = CosmosMsg::Bank(BankMsg::Send {
+            to_address: sender.wallet_address.to_string(),
+            amount: vec![payment.amount.clone()],
+        });
+        response = response.add_message(refund_msg);
+    }
+    
+    let receipt_hash = issue_receipt(deps.storage, &payment)?;
+    response = response.add_attribute("receipt_hash", receipt_hash);
+    
+    let callback = take_callback(deps.storage, RecordKind::Payment, payment_id, format!("{:?}", PaymentStatus::Cancelled), &payment.amount)?;
+    response = response.add_submessages(callback);
+    
+    Ok(response)
+}
+
+pub fn execute_dispute_payment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payment_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    
+    update_payment(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
+        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
+        
+        // Only the sender can dispute
+        if payment.from_username != username {
+            return Err(ContractError::PaymentNotAuthorized {});
+        }
+        
+        // Only approved payments held by a release delay can be disputed, until the delay passes
+        let Some(release_at) = payment.release_at.filter(|_| matches!(payment.status, PaymentStatus::PendingRelease)) else {
+            return Err(ContractError::PaymentNotPendingRelease {});
+        };
+        if env.block.time.seconds() > release_at {
+            return Err(ContractError::DisputeWindowClosed {});
+        }
+        
+        payment.status = PaymentStatus::Disputed;
+        payment.updated_at = env.block.time.seconds();
+        payment.updated_at_height = env.block.height;
+        
+        Ok(payment)
+    })?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "dispute_payment")
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("disputer", username)
+        .add_event(
+            cosmwasm_std::Event::new("payment_disputed")
+                .add_attribute("payment_id", payment_id.to_string())
+        ))
+}
+
+pub fn execute_release_payment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payment_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    
+    // Anyone can release an approved payment once its release delay has passed
+    let payment = update_payment(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
+        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
+        
+        let Some(release_at) = payment.release_at.filter(|_| matches!(payment.status, PaymentStatus::PendingRelease)) else {
+            return Err(ContractError::PaymentNotPendingRelease {});
+        };
+        if env.block.time.seconds() <= release_at {
+            return Err(ContractError::DisputeWindowNotElapsed {});
+        }
+        
+        payment.status = PaymentStatus::Completed;
+        payment.updated_at = env.block.time.seconds();
+        payment.updated_at_height = env.block.height;
+        
+        Ok(payment)
+    })?;
+    
+    release_escrow(deps.storage, &payment.from_username, &payment.amount)?;
+    let recipient = USERS_BY_USERNAME.load(deps.storage, payment.to_username.clone())?;
+    let payment_msg = CosmosMsg::Bank(BankMsg::Send {
+        to_address: recipient.wallet_address.to_string(),
+        amount: vec![payment.amount.clone()],
+    });
+    
+    let receipt_hash = issue_receipt(deps.storage, &payment)?;
+    record_counterparties(deps.storage, &env, &payment.from_username, &payment.to_username)?;
+    let callback = take_callback(deps.storage, RecordKind::Payment, payment_id, format!("{:?}", PaymentStatus::Completed), &payment.amount)?;
+    
+    Ok(Response::new()
+        .add_message(payment_msg)
+        .add_submessages(callback)
+        .add_attribute("action", "release_payment")
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("receipt_hash", receipt_hash))
+}
+
+// Minimum time between reminders for the same payment request, and the most reminders allowed
+const REMINDER_COOLDOWN_SECS: u64 = 86400;
+const MAX_REMINDERS: u32 = 5;
+
+pub fn execute_remind_payment_request(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payment_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let now = env.block.time.seconds();
+    
+    let payment = update_payment(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
+        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
+        
+        // Only the requester can nudge the payer
+        if payment.from_username != username {
+            return Err(ContractError::NotAuthorized {});
+        }
+        if !matches!(payment.payment_type, PaymentType::PaymentRequest)
+            || !matches!(payment.status, PaymentStatus::Pending)
+        {
+            return Err(ContractError::ReminderNotAllowed {});
+        }
+        if payment.reminder_count >= MAX_REMINDERS {
+            return Err(ContractError::ReminderLimitReached {});
+        }
+        if payment.last_reminded_at.is_some_and(|at| now < at + REMINDER_COOLDOWN_SECS) {
+            return Err(ContractError::ReminderCooldown {});
+        }
+        
+        payment.reminder_count += 1;
+        payment.last_reminded_at = Some(now);
+        Ok(payment)
+    })?;
+    
+    // Wallets without a username have no notification inbox
+    let to = match payment.to_wallet {
+        Some(to_wallet) => to_wallet.to_string(),
+        None => {
+            notify(deps.storage, &env, &payment.to_username, NotificationKind::PaymentRequestReminder, &username, Some(payment_id))?;
+            payment.to_username
+        }
+    };
+    
+    Ok(Response::new()
+        .add_attribute("action", "remind_payment_request")
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("from", username)
+        .add_attribute("to", to)
+        .add_attribute("reminder_count", payment.reminder_count.to_string()))
+}
+
+// CAMPAIGN FUNCTIONS
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create_campaign(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    title: String,
+    goal: Coin,
+    deadline: u64,
+    beneficiary_username: String,
+    all_or_nothing: bool,
+) -> Result<Response, ContractError> {
+    let creator = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err(ContractError::InvalidCampaignTitle {});
+    }
+    if goal.amount.is_zero() {
+        return Err(ContractError::InvalidPaymentAmount {});
+    }
+    ensure_denom_accepted(deps.storage, &goal.denom)?;
+    if deadline <= env.block.time.seconds() {
+        return Err(ContractError::InvalidCampaignDeadline {});
+    }
+    let beneficiary = normalize_username(&beneficiary_username);
+    if !USERS_BY_USERNAME.has(deps.storage, beneficiary.clone()) {
+        return Err(ContractError::UserNotFound {});
+    }
+    
+    let campaign_id = CAMPAIGN_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    CAMPAIGN_SEQ.save(deps.storage, &campaign_id)?;
+    let campaign = Campaign {
+        id: campaign_id,
+        creator: creator.clone(),
+        beneficiary: beneficiary.clone(),
+        title,
+        goal,
+        raised: Uint128::zero(),
+        deadline,
+        all_or_nothing,
+        status: CampaignStatus::Active,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+    };
+    CAMPAIGNS.save(deps.storage, campaign_id, &campaign)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "create_campaign")
+        .add_attribute("campaign_id", campaign_id.to_string())
+        .add_attribute("creator", creator)
+        .add_attribute("beneficiary", beneficiary)
+        .add_attribute("goal", campaign.goal.to_string()))
+}
+
+pub fn execute_donate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    campaign_id: u64,
+) -> Result<Response, ContractError> {
+    let donor = get_username_from_wallet(&deps, &info.sender)?;
+    let mut campaign = CAMPAIGNS.load(deps.storage, campaign_id)
+        .map_err(|_| ContractError::CampaignNotFound {})?;
+    if !matches!(campaign.status, CampaignStatus::Active) || env.block.time.seconds() > campaign.deadline {
+        return Err(ContractError::CampaignClosed {});
+    }
+    
+    // Donations are whatever is attached in the goal's denom
+    if info.funds.iter().any(|coin| coin.denom != campaign.goal.denom && !coin.amount.is_zero()) {
+        return Err(ContractError::UnexpectedFunds {});
+    }
+    let amount: Uint128 = info.funds.iter().map(|coin| coin.amount).sum();
+    if amount.is_zero() {
+        return Err(ContractError::InvalidPaymentAmount {});
+    }
+    ensure_denom_accepted(deps.storage, &campaign.goal.denom)?;
+    let donation = Coin { denom: campaign.goal.denom.clone(), amount };
+    
+    add_escrow_total(deps.storage, &donation)?;
+    CAMPAIGN_DONATIONS.update(deps.storage, (campaign_id, donor.clone()), |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount)?)
+    })?;
+    campaign.raised = campaign.raised.checked_add(amount).map_err(StdError::from)?;
+    campaign.updated_at = env.block.time.seconds();
+    CAMPAIGNS.save(deps.storage, campaign_id, &campaign)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "donate")
+        .add_attribute("campaign_id", campaign_id.to_string())
+        .add_attribute("donor", donor)
+        .add_attribute("amount", donation.to_string())
+        .add_attribute("raised", campaign.raised.to_string()))
+}
+
+// Helper function to check whether an all-or-nothing campaign missed its goal
+fn campaign_failed(campaign: &Campaign, now: u64) -> bool {
+    campaign.all_or_nothing && now > campaign.deadline && campaign.raised < campaign.goal.amount
+}
+
+pub fn execute_withdraw_campaign(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    campaign_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut campaign = CAMPAIGNS.load(deps.storage, campaign_id)
+        .map_err(|_| ContractError::CampaignNotFound {})?;
+    if campaign.beneficiary != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !matches!(campaign.status, CampaignStatus::Active) {
+        return Err(ContractError::CampaignClosed {});
+    }
+    
+    // Withdrawable once the goal is met, or after the deadline unless the campaign failed
+    let now = env.block.time.seconds();
+    let goal_met = campaign.raised >= campaign.goal.amount;
+    if campaign.raised.is_zero() || !(goal_met || now > campaign.deadline) || campaign_failed(&campaign, now) {
+        return Err(ContractError::CampaignNotWithdrawable {});
+    }
+    
+    let payout = Coin { denom: campaign.goal.denom.clone(), amount: campaign.raised };
+    release_escrow_total(deps.storage, &payout)?;
+    campaign.status = CampaignStatus::Withdrawn;
+    campaign.updated_at = now;
+    CAMPAIGNS.save(deps.storage, campaign_id, &campaign)?;
+    
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![payout.clone()],
+        })
+        .add_attribute("action", "withdraw_campaign")
+        .add_attribute("campaign_id", campaign_id.to_string())
+        .add_attribute("beneficiary", username)
+        .add_attribute("amount", payout.to_string()))
+}
+
+pub fn execute_claim_campaign_refund(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    campaign_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let donor = get_username_from_wallet(&deps, &info.sender)?;
+    let mut campaign = CAMPAIGNS.load(deps.storage, campaign_id)
+        .map_err(|_| ContractError::CampaignNotFound {})?;
+    
+    // Donors claim their own refunds, so a failed campaign never loops over donors
+    let now = env.block.time.seconds();
+    let donated = CAMPAIGN_DONATIONS.may_load(deps.storage, (campaign_id, donor.clone()))?.unwrap_or_default();
+    if !campaign_failed(&campaign, now) || donated.is_zero() {
+        return Err(ContractError::NoCampaignRefund {});
+    }
+    
+    let refund = Coin { denom: campaign.goal.denom.clone(), amount: donated };
+    release_escrow_total(deps.storage, &refund)?;
+    CAMPAIGN_DONATIONS.remove(deps.storage, (campaign_id, donor.clone()));
+    campaign.raised -= donated;
+    campaign.updated_at = now;
+    CAMPAIGNS.save(deps.storage, campaign_id, &campaign)?;
+    
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![refund.clone()],
+        })
+        .add_attribute("action", "claim_campaign_refund")
+        .add_attribute("campaign_id", campaign_id.to_string())
+        .add_attribute("donor", donor)
+        .add_attribute("amount", refund.to_string()))
+}
+
+// SUBSCRIPTION FUNCTIONS
+
+// Upper bound on the periods prepaid by one subscription
+const MAX_SUBSCRIPTION_PERIODS: u32 = 120;
+
+pub fn execute_create_subscription(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to_username: String,
+    amount: Coin,
+    period: u64,
+    periods: u32,
+) -> Result<Response, ContractError> {
+    let payer = get_username_from_wallet(&deps, &info.sender)?;
+    let recipient = normalize_username(&to_username);
+    if payer == recipient {
+        return Err(ContractError::CannotPaySelf {});
+    }
+    if !USERS_BY_USERNAME.has(deps.storage, recipient.clone()) {
+        return Err(ContractError::UserNotFound {});
+    }
+    if amount.amount.is_zero() || period == 0 || periods == 0 || periods > MAX_SUBSCRIPTION_PERIODS {
+        return Err(ContractError::InvalidSubscription {});
+    }
+    
+    // Every period is prepaid; any surplus goes back to the payer
+    let total = Coin {
+        denom: amount.denom.clone(),
+        amount: amount.amount.checked_mul(Uint128::from(periods)).map_err(StdError::from)?,
+    };
+    let refund = validate_funds(deps.storage, &info, &total)?;
+    
+    let subscription_id = SUBSCRIPTION_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    SUBSCRIPTION_SEQ.save(deps.storage, &subscription_id)?;
+    let subscription = Subscription {
+        id: subscription_id,
+        payer: payer.clone(),
+        recipient: recipient.clone(),
+        amount,
+        period,
+        periods_funded: periods,
+        periods_claimed: 0,
+        start_ts: env.block.time.seconds(),
+        status: SubscriptionStatus::Active,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+    };
+    SUBSCRIPTIONS.save(deps.storage, subscription_id, &subscription)?;
+    USER_SUBSCRIPTIONS.save(deps.storage, (payer.clone(), subscription_id), &true)?;
+    USER_SUBSCRIPTIONS.save(deps.storage, (recipient.clone(), subscription_id), &true)?;
+    add_escrow(deps.storage, &payer, &total)?;
+    
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_attribute("action", "create_subscription")
+        .add_attribute("subscription_id", subscription_id.to_string())
+        .add_attribute("payer", payer)
+        .add_attribute("recipient", recipient)
+        .add_attribute("amount", subscription.amount.to_string())
+        .add_attribute("period", period.to_string())
+        .add_attribute("periods", periods.to_string()))
+}
+
+// Helper function to count the funded periods that have matured by `now`
+fn matured_periods(subscription: &Subscription, now: u64) -> u32 {
+    let elapsed = now.saturating_sub(subscription.start_ts) / subscription.period;
+    elapsed.min(subscription.periods_funded as u64) as u32
+}
+
+// Helper function to count matured periods the recipient has not claimed yet
+fn claimable_periods(subscription: &Subscription, now: u64) -> u32 {
+    match subscription.status {
+        SubscriptionStatus::Active => matured_periods(subscription, now) - subscription.periods_claimed,
+        _ => 0,
+    }
+}
+
+// Helper function to price a number of subscription periods
+fn subscription_periods_amount(subscription: &Subscription, periods: u32) -> Result<Coin, ContractError> {
+    Ok(Coin {
+        denom: subscription.amount.denom.clone(),
+        amount: subscription.amount.amount.checked_mul(Uint128::from(periods)).map_err(StdError::from)?,
+    })
+}
+
+pub fn execute_claim_subscription(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    subscription_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let mut subscription = SUBSCRIPTIONS.load(deps.storage, subscription_id)
+        .map_err(|_| ContractError::SubscriptionNotFound {})?;
+    if subscription.recipient != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !matches!(subscription.status, SubscriptionStatus::Active) {
+        return Err(ContractError::SubscriptionNotActive {});
+    }
+    
+    let now = env.block.time.seconds();
+    let periods = claimable_periods(&subscription, now);
+    if periods == 0 {
+        return Err(ContractError::NothingToClaim {});
+    }
+    let payout = subscription_periods_amount(&subscription, periods)?;
+    release_escrow(deps.storage, &subscription.payer, &payout)?;
+    subscription.periods_claimed += periods;
+    if subscription.periods_claimed == subscription.periods_funded {
+        subscription.status = SubscriptionStatus::Completed;
+    }
+    subscription.updated_at = now;
+    SUBSCRIPTIONS.save(deps.storage, subscription_id, &subscription)?;
+    
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![payout.clone()],
+        })
+        .add_attribute("action", "claim_subscription")
+        .add_attribute("subscription_id", subscription_id.to_string())
+        .add_attribute("recipient", username)
+        .add_attribute("periods", periods.to_string())
+        .add_attribute("amount", payout.to_string()))
+}
+
+pub fn execute_cancel_subscription(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    subscription_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let mut subscription = SUBSCRIPTIONS.load(deps.storage, subscription_id)
+        .map_err(|_| ContractError::SubscriptionNotFound {})?;
+    if subscription.payer != username && subscription.recipient != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !matches!(subscription.status, SubscriptionStatus::Active) {
+        return Err(ContractError::SubscriptionNotActive {});
+    }
+    
+    // Matured periods still belong to the recipient; only unmatured ones are refunded
+    let now = env.block.time.seconds();
+    let owed_periods = claimable_periods(&subscription, now);
+    let refund_periods = subscription.periods_funded - subscription.periods_claimed - owed_periods;
+    let owed = subscription_periods_amount(&subscription, owed_periods)?;
+    let refund = subscription_periods_amount(&subscription, refund_periods)?;
+    
+    let mut messages = vec![];
+    if !owed.amount.is_zero() {
+        let recipient = USERS_BY_USERNAME.load(deps.storage, subscription.recipient.clone())?;
+        release_escrow(deps.storage, &subscription.payer, &owed)?;
+        messages.push(BankMsg::Send {
+            to_address: recipient.wallet_address.to_string(),
+            amount: vec![owed.clone()],
+        });
+    }
+    if !refund.amount.is_zero() {
+        let payer = USERS_BY_USERNAME.load(deps.storage, subscription.payer.clone())?;
+        release_escrow(deps.storage, &subscription.payer, &refund)?;
+        messages.push(BankMsg::Send {
+            to_address: payer.wallet_address.to_string(),
+            amount: vec![refund.clone()],
+        });
+    }
+    subscription.periods_claimed += owed_periods;
+    subscription.status = SubscriptionStatus::Cancelled;
+    subscription.updated_at = now;
+    SUBSCRIPTIONS.save(deps.storage, subscription_id, &subscription)?;
+    
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "cancel_subscription")
+        .add_attribute("subscription_id", subscription_id.to_string())
+        .add_attribute("cancelled_by", username)
+        .add_attribute("paid_out", owed.to_string())
+        .add_attribute("refunded", refund.to_string()))
+}
+
+// INVOICE FUNCTIONS
+
+// Upper bound on the line items of one invoice
+const MAX_INVOICE_LINE_ITEMS: usize = 50;
+
+pub fn execute_create_invoice(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to_username: String,
+    line_items: Vec<InvoiceLineItem>,
+    due_ts: u64,
+) -> Result<Response, ContractError> {
+    let issuer = get_username_from_wallet(&deps, &info.sender)?;
+    for item in &line_items {
+        validate_text_length(deps.storage, TextKind::Description, &item.description)?;
+    }
+    ensure_no_funds(&info)?;
+    let payer_username = normalize_username(&to_username);
+    if issuer == payer_username {
+        return Err(ContractError::CannotPaySelf {});
+    }
+    let payer = USERS_BY_USERNAME.load(deps.storage, payer_username.clone())
+        .map_err(|_| ContractError::UserNotFound {})?;
+    ensure_request_allowed(deps.storage, &issuer, &payer)?;
+    if due_ts <= env.block.time.seconds() {
+        return Err(ContractError::InvalidInvoiceDueDate {});
+    }
+    
+    // Line items share one denom and add up to a single payable total
+    let denom = match line_items.first() {
+        Some(item) if line_items.len() <= MAX_INVOICE_LINE_ITEMS => item.amount.denom.clone(),
+        _ => return Err(ContractError::InvalidInvoiceLineItems {}),
+    };
+    let mut total = Uint128::zero();
+    let mut items = Vec::with_capacity(line_items.len());
+    for item in line_items {
+        let description = item.description.trim().to_string();
+        if description.is_empty() || item.amount.amount.is_zero() || item.amount.denom != denom {
+            return Err(ContractError::InvalidInvoiceLineItems {});
+        }
+        total = total.checked_add(item.amount.amount).map_err(StdError::from)?;
+        items.push(InvoiceLineItem { description, amount: item.amount });
+    }
+    ensure_denom_accepted(deps.storage, &denom)?;
+    
+    let invoice_id = INVOICE_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    INVOICE_SEQ.save(deps.storage, &invoice_id)?;
+    let invoice = Invoice {
+        id: invoice_id,
+        issuer: issuer.clone(),
+        payer: payer_username.clone(),
+        line_items: items,
+        total: Coin { denom, amount: total },
+        paid: Uint128::zero(),
+        due_ts,
+        status: InvoiceStatus::Open,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+    };
+    INVOICES.save(deps.storage, invoice_id, &invoice)?;
+    USER_INVOICES.save(deps.storage, (issuer.clone(), invoice_id), &true)?;
+    USER_INVOICES.save(deps.storage, (payer_username.clone(), invoice_id), &true)?;
+    notify(deps.storage, &env, &payer_username, NotificationKind::InvoiceReceived, &issuer, Some(invoice_id))?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "create_invoice")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("issuer", issuer)
+        .add_attribute("payer", payer_username)
+        .add_attribute("total", invoice.total.to_string()))
+}
+
+pub fn execute_pay_invoice(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut invoice = INVOICES.load(deps.storage, invoice_id)
+        .map_err(|_| ContractError::InvoiceNotFound {})?;
+    if invoice.payer != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !matches!(invoice.status, InvoiceStatus::Open) {
+        return Err(ContractError::InvoiceNotOpen {});
+    }
+    
+    // Any amount up to the remaining balance is accepted; the surplus goes back to the payer
+    if info.funds.iter().any(|coin| coin.denom != invoice.total.denom && !coin.amount.is_zero()) {
+        return Err(ContractError::UnexpectedFunds {});
+    }
+    let sent: Uint128 = info.funds.iter().map(|coin| coin.amount).sum();
+    if sent.is_zero() {
+        return Err(ContractError::InvalidPaymentAmount {});
+    }
+    ensure_denom_accepted(deps.storage, &invoice.total.denom)?;
+    let applied = sent.min(invoice.total.amount - invoice.paid);
+    let surplus = sent - applied;
+    
+    invoice.paid += applied;
+    if invoice.paid == invoice.total.amount {
+        invoice.status = InvoiceStatus::Paid;
+    }
+    invoice.updated_at = env.block.time.seconds();
+    INVOICES.save(deps.storage, invoice_id, &invoice)?;
+    
+    let issuer = USERS_BY_USERNAME.load(deps.storage, invoice.issuer.clone())?;
+    let payment = Coin { denom: invoice.total.denom.clone(), amount: applied };
+    let mut messages = vec![BankMsg::Send {
+        to_address: issuer.wallet_address.to_string(),
+        amount: vec![payment.clone()],
+    }];
+    if !surplus.is_zero() {
+        messages.push(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![Coin { denom: invoice.total.denom.clone(), amount: surplus }],
+        });
+    }
+    
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "pay_invoice")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("payer", username)
+        .add_attribute("amount", payment.to_string())
+        .add_attribute("remaining", (invoice.total.amount - invoice.paid).to_string()))
+}
+
+pub fn execute_cancel_invoice(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let mut invoice = INVOICES.load(deps.storage, invoice_id)
+        .map_err(|_| ContractError::InvoiceNotFound {})?;
+    if invoice.issuer != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !matches!(invoice.status, InvoiceStatus::Open) {
+        return Err(ContractError::InvoiceNotOpen {});
+    }
+    
+    invoice.status = InvoiceStatus::Cancelled;
+    invoice.updated_at = env.block.time.seconds();
+    INVOICES.save(deps.storage, invoice_id, &invoice)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "cancel_invoice")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("issuer", username))
+}
+
+// PAYMENT LINK FUNCTIONS
+
+// Number of hex characters of the link hash used as its code
+const PAYMENT_LINK_CODE_LENGTH: usize = 16;
+
+pub fn execute_create_payment_link(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Option<Coin>,
+    description: String,
+    max_uses: Option<u32>,
+    expires_at: Option<u64>,
+) -> Result<Response, ContractError> {
+    let merchant = get_username_from_wallet(&deps, &info.sender)?;
+    validate_text_length(deps.storage, TextKind::Description, &description)?;
+    ensure_no_funds(&info)?;
+    if let Some(amount) = &amount {
+        if amount.amount.is_zero() {
+            return Err(ContractError::InvalidPaymentLink {});
+        }
+        ensure_denom_accepted(deps.storage, &amount.denom)?;
+    }
+    if max_uses == Some(0) || expires_at.is_some_and(|expires_at| expires_at <= env.block.time.seconds()) {
+        return Err(ContractError::InvalidPaymentLink {});
+    }
+    
+    // Codes are derived from a sequence so they never repeat, and hashed so they don't reveal the merchant
+    let link_seq = PAYMENT_LINK_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    PAYMENT_LINK_SEQ.save(deps.storage, &link_seq)?;
+    let code = hash_data(&format!("{}:{}:{}", env.contract.address, merchant, link_seq))[..PAYMENT_LINK_CODE_LENGTH].to_string();
+    if PAYMENT_LINKS.has(deps.storage, code.clone()) {
+        return Err(StdError::generic_err("payment link code collision").into());
+    }
+    
+    let link = PaymentLink {
+        code: code.clone(),
+        merchant: merchant.clone(),
+        amount,
+        description,
+        max_uses,
+        uses: 0,
+        expires_at,
+        active: true,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+    };
+    PAYMENT_LINKS.save(deps.storage, code.clone(), &link)?;
+    USER_PAYMENT_LINKS.save(deps.storage, (merchant.clone(), code.clone()), &true)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "create_payment_link")
+        .add_attribute("code", code)
+        .add_attribute("merchant", merchant))
+}
+
+// Helper function to check a link can still be paid through
+fn payment_link_usable(link: &PaymentLink, now: u64) -> bool {
+    link.active
+        && link.expires_at.is_none_or(|expires_at| now <= expires_at)
+        && link.max_uses.is_none_or(|max_uses| link.uses < max_uses)
+}
+
+pub fn execute_pay_link(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    code: String,
+) -> Result<Response, ContractError> {
+    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut link = PAYMENT_LINKS.load(deps.storage, code.clone())
+        .map_err(|_| ContractError::PaymentLinkNotFound {})?;
+    if !payment_link_usable(&link, env.block.time.seconds()) {
+        return Err(ContractError::PaymentLinkInactive {});
+    }
+    if link.merchant == from_username {
+        return Err(ContractError::CannotPaySelf {});
+    }
+    
+    // Fixed links take their amount and refund any surplus; open links take the single attached coin
+    let (amount, refund) = match &link.amount {
+        Some(amount) => (amount.clone(), validate_funds(deps.storage, &info, amount)?),
+        None => {
+            let mut sent = info.funds.iter().filter(|coin| !coin.amount.is_zero());
+            let amount = match (sent.next(), sent.next()) {
+                (Some(coin), None) => coin.clone(),
+                (None, _) => return Err(ContractError::InvalidPaymentAmount {}),
+                (Some(_), Some(_)) => return Err(ContractError::UnexpectedFunds {}),
+            };
+            ensure_denom_accepted(deps.storage, &amount.denom)?;
+            (amount, None)
+        }
+    };
+    
+    link.uses += 1;
+    link.updated_at = env.block.time.seconds();
+    PAYMENT_LINKS.save(deps.storage, code.clone(), &link)?;
+    
+    let merchant = USERS_BY_USERNAME.load(deps.storage, link.merchant.clone())?;
+    let mut state = STATE.load(deps.storage)?;
+    let payment_id = state.next_payment_id;
+    state.next_payment_id += 1;
+    STATE.save(deps.storage, &state)?;
+    
+    let payment = Payment {
+        id: payment_id,
+        from_username: from_username.clone(),
+        to_username: link.merchant.clone(),
+        to_wallet: None,
+        amount,
+        description: link.description.clone(),
+        payment_type: PaymentType::DirectPayment,
+        proof_type: ProofType::None,
+        proof_data: None,
+        status: PaymentStatus::Completed,
+        category: None,
+        tags: vec![],
+        redacted: false,
+        visibility: resolve_visibility(deps.storage, &from_username, None)?,
+        price_quote: None,
+        external_ref: None,
+        reminder_count: 0,
+        last_reminded_at: None,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+        created_at_height: env.block.height,
+        updated_at_height: env.block.height,
+        release_delay_secs: None,
+        release_at: None,
+    };
+    save_payment(deps.storage, payment_id, &payment)?;
+    index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &from_username, payment_id)?;
+    index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &link.merchant, payment_id)?;
+    let receipt_hash = issue_receipt(deps.storage, &payment)?;
+    record_counterparties(deps.storage, &env, &payment.from_username, &payment.to_username)?;
+    
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_message(BankMsg::Send {
+            to_address: merchant.wallet_address.to_string(),
+            amount: vec![payment.amount.clone()],
+        })
+        .add_attribute("action", "pay_link")
+        .add_attribute("code", code)
+        .add_attribute("from", from_username)
+        .add_attribute("to", link.merchant)
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("amount", payment.amount.to_string())
+        .add_attribute("receipt_hash", receipt_hash))
+}
+
+pub fn execute_disable_payment_link(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    code: String,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let mut link = PAYMENT_LINKS.load(deps.storage, code.clone())
+        .map_err(|_| ContractError::PaymentLinkNotFound {})?;
+    if link.merchant != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !link.active {
+        return Err(ContractError::PaymentLinkInactive {});
+    }
+    
+    link.active = false;
+    link.updated_at = env.block.time.seconds();
+    PAYMENT_LINKS.save(deps.storage, code.clone(), &link)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "disable_payment_link")
+        .add_attribute("code", code)
+        .add_attribute("merchant", username))
+}
+
+// SCHEDULED PAYMENT FUNCTIONS
+
+pub fn execute_schedule_payment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to_username: String,
+    amount: Coin,
+    description: String,
+    execute_at: u64,
+) -> Result<Response, ContractError> {
+    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    validate_text_length(deps.storage, TextKind::Description, &description)?;
+    let to_username = normalize_username(&to_username);
+    if from_username == to_username {
+        return Err(ContractError::CannotPaySelf {});
+    }
+    if !USERS_BY_USERNAME.has(deps.storage, to_username.clone()) {
+        return Err(ContractError::UserNotFound {});
+    }
+    if amount.amount.is_zero() || execute_at <= env.block.time.seconds() {
+        return Err(ContractError::InvalidScheduledPayment {});
+    }
+    let refund = validate_funds(deps.storage, &info, &amount)?;
+    
+    let scheduled_id = SCHEDULED_PAYMENT_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    SCHEDULED_PAYMENT_SEQ.save(deps.storage, &scheduled_id)?;
+    let scheduled = ScheduledPayment {
+        id: scheduled_id,
+        from_username: from_username.clone(),
+        to_username: to_username.clone(),
+        amount,
+        description,
+        execute_at,
+        status: ScheduledPaymentStatus::Pending,
+        payment_id: None,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+    };
+    SCHEDULED_PAYMENTS.save(deps.storage, scheduled_id, &scheduled)?;
+    USER_SCHEDULED_PAYMENTS.save(deps.storage, (from_username.clone(), scheduled_id), &true)?;
+    USER_SCHEDULED_PAYMENTS.save(deps.storage, (to_username.clone(), scheduled_id), &true)?;
+    PENDING_SCHEDULED_PAYMENTS.save(deps.storage, (execute_at, scheduled_id), &true)?;
+    add_escrow(deps.storage, &from_username, &scheduled.amount)?;
+    
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_attribute("action", "schedule_payment")
+        .add_attribute("scheduled_id", scheduled_id.to_string())
+        .add_attribute("from", from_username)
+        .add_attribute("to", to_username)
+        .add_attribute("amount", scheduled.amount.to_string())
+        .add_attribute("execute_at", execute_at.to_string()))
+}
+
+pub fn execute_execute_scheduled_payment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    scheduled_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let mut scheduled = SCHEDULED_PAYMENTS.load(deps.storage, scheduled_id)
+        .map_err(|_| ContractError::ScheduledPaymentNotFound {})?;
+    if !matches!(scheduled.status, ScheduledPaymentStatus::Pending) {
+        return Err(ContractError::ScheduledPaymentNotPending {});
+    }
+    if env.block.time.seconds() < scheduled.execute_at {
+        return Err(ContractError::ScheduledPaymentNotDue {});
+    }
+    
+    // Executing turns the schedule into an ordinary completed direct payment
+    let mut state = STATE.load(deps.storage)?;
+    let payment_id = state.next_payment_id;
+    state.next_payment_id += 1;
+    STATE.save(deps.storage, &state)?;
+    
+    let payment = Payment {
+        id: payment_id,
+        from_username: scheduled.from_username.clone(),
+        to_username: scheduled.to_username.clone(),
+        to_wallet: None,
+        amount: scheduled.amount.clone(),
+        description: scheduled.description.clone(),
+        payment_type: PaymentType::DirectPayment,
+        proof_type: ProofType::None,
+        proof_data: None,
+        status: PaymentStatus::Completed,
+        category: None,
+        tags: vec![],
+        redacted: false,
+        visibility: resolve_visibility(deps.storage, &scheduled.from_username, None)?,
+        price_quote: None,
+        external_ref: None,
+        reminder_count: 0,
+        last_reminded_at: None,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+        created_at_height: env.block.height,
+        updated_at_height: env.block.height,
+        release_delay_secs: None,
+        release_at: None,
+    };
+    save_payment(deps.storage, payment_id, &payment)?;
+    index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &payment.from_username, payment_id)?;
+    index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &payment.to_username, payment_id)?;
+    let receipt_hash = issue_receipt(deps.storage, &payment)?;
+    record_counterparties(deps.storage, &env, &payment.from_username, &payment.to_username)?;
+    release_escrow(deps.storage, &scheduled.from_username, &scheduled.amount)?;
+    
+    PENDING_SCHEDULED_PAYMENTS.remove(deps.storage, (scheduled.execute_at, scheduled_id));
+    scheduled.status = ScheduledPaymentStatus::Executed;
+    scheduled.payment_id = Some(payment_id);
+    scheduled.updated_at = env.block.time.seconds();
+    SCHEDULED_PAYMENTS.save(deps.storage, scheduled_id, &scheduled)?;
+    
+    let recipient = USERS_BY_USERNAME.load(deps.storage, scheduled.to_username.clone())?;
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: recipient.wallet_address.to_string(),
+            amount: vec![scheduled.amount.clone()],
+        })
+        .add_attribute("action", "execute_scheduled_payment")
+        .add_attribute("scheduled_id", scheduled_id.to_string())
+        .add_attribute("payment_id", payment_id.to_string())
+        .add_attribute("executed_by", info.sender)
+        .add_attribute("amount", scheduled.amount.to_string())
+        .add_attribute("receipt_hash", receipt_hash))
+}
+
+pub fn execute_cancel_scheduled_payment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    scheduled_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let mut scheduled = SCHEDULED_PAYMENTS.load(deps.storage, scheduled_id)
+        .map_err(|_| ContractError::ScheduledPaymentNotFound {})?;
+    if scheduled.from_username != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !matches!(scheduled.status, ScheduledPaymentStatus::Pending) {
+        return Err(ContractError::ScheduledPaymentNotPending {});
+    }
+    
+    release_escrow(deps.storage, &username, &scheduled.amount)?;
+    PENDING_SCHEDULED_PAYMENTS.remove(deps.storage, (scheduled.execute_at, scheduled_id));
+    scheduled.status = ScheduledPaymentStatus::Cancelled;
+    scheduled.updated_at = env.block.time.seconds();
+    SCHEDULED_PAYMENTS.save(deps.storage, scheduled_id, &scheduled)?;
+    
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![scheduled.amount.clone()],
+        })
+        .add_attribute("action", "cancel_scheduled_payment")
+        .add_attribute("scheduled_id", scheduled_id.to_string())
+        .add_attribute("from", username)
+        .add_attribute("refunded", scheduled.amount.to_string()))
+}
+
+// GIFT CODE FUNCTIONS
+
+pub fn execute_create_gift_code(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Coin,
+    code_hash: String,
+    expires_at: u64,
+) -> Result<Response, ContractError> {
+    let creator = get_username_from_wallet(&deps, &info.sender)?;
+    let code_hash = code_hash.to_lowercase();
+    let valid_hash = code_hash.len() == 64 && code_hash.chars().all(|c| c.is_ascii_hexdigit());
+    if amount.amount.is_zero() || !valid_hash || expires_at <= env.block.time.seconds() {
+        return Err(ContractError::InvalidGiftCode {});
+    }
+    if ACTIVE_GIFT_CODE_HASHES.has(deps.storage, code_hash.clone()) {
+        return Err(ContractError::GiftCodeHashInUse {});
+    }
+    let refund = validate_funds(deps.storage, &info, &amount)?;
+    
+    let gift_id = GIFT_CODE_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    GIFT_CODE_SEQ.save(deps.storage, &gift_id)?;
+    let gift_code = GiftCode {
+        id: gift_id,
+        creator: creator.clone(),
+        amount,
+        code_hash: code_hash.clone(),
+        expires_at,
+        status: GiftCodeStatus::Active,
+        claimed_by: None,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+    };
+    GIFT_CODES.save(deps.storage, gift_id, &gift_code)?;
+    ACTIVE_GIFT_CODE_HASHES.save(deps.storage, code_hash, &gift_id)?;
+    USER_GIFT_CODES.save(deps.storage, (creator.clone(), gift_id), &true)?;
+    add_escrow(deps.storage, &creator, &gift_code.amount)?;
+    
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_attribute("action", "create_gift_code")
+        .add_attribute("gift_id", gift_id.to_string())
+        .add_attribute("creator", creator)
+        .add_attribute("amount", gift_code.amount.to_string())
+        .add_attribute("expires_at", expires_at.to_string()))
+}
+
+// The claim reveals the code on-chain, so only the first claim with it can succeed
+pub fn execute_claim_gift_code(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    code: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let gift_id = ACTIVE_GIFT_CODE_HASHES.may_load(deps.storage, hash_data(&code))?
+        .ok_or(ContractError::GiftCodeNotFound {})?;
+    let mut gift_code = GIFT_CODES.load(deps.storage, gift_id)?;
+    if env.block.time.seconds() >= gift_code.expires_at {
+        return Err(ContractError::GiftCodeExpired {});
+    }
+    
+    release_escrow(deps.storage, &gift_code.creator, &gift_code.amount)?;
+    ACTIVE_GIFT_CODE_HASHES.remove(deps.storage, gift_code.code_hash.clone());
+    gift_code.status = GiftCodeStatus::Claimed;
+    gift_code.claimed_by = Some(info.sender.clone());
+    gift_code.updated_at = env.block.time.seconds();
+    GIFT_CODES.save(deps.storage, gift_id, &gift_code)?;
+    
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![gift_code.amount.clone()],
+        })
+        .add_attribute("action", "claim_gift_code")
+        .add_attribute("gift_id", gift_id.to_string())
+        .add_attribute("claimed_by", info.sender.as_str())
+        .add_attribute("amount", gift_code.amount.to_string()))
+}
+
+pub fn execute_refund_gift_code(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    gift_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let mut gift_code = GIFT_CODES.load(deps.storage, gift_id)
+        .map_err(|_| ContractError::GiftCodeNotFound {})?;
+    if gift_code.creator != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !matches!(gift_code.status, GiftCodeStatus::Active) {
+        return Err(ContractError::GiftCodeNotActive {});
+    }
+    if env.block.time.seconds() < gift_code.expires_at {
+        return Err(ContractError::GiftCodeNotExpired {});
+    }
+    
+    release_escrow(deps.storage, &username, &gift_code.amount)?;
+    ACTIVE_GIFT_CODE_HASHES.remove(deps.storage, gift_code.code_hash.clone());
+    gift_code.status = GiftCodeStatus::Refunded;
+    gift_code.updated_at = env.block.time.seconds();
+    GIFT_CODES.save(deps.storage, gift_id, &gift_code)?;
+    
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![gift_code.amount.clone()],
+        })
+        .add_attribute("action", "refund_gift_code")
+        .add_attribute("gift_id", gift_id.to_string())
+        .add_attribute("creator", username)
+        .add_attribute("refunded", gift_code.amount.to_string()))
+}
+
+// HTLC FUNCTIONS
+
+pub fn execute_create_htlc(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to_username: String,
+    amount: Coin,
+    hashlock: String,
+    timelock: u64,
+) -> Result<Response, ContractError> {
+    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    let to_username = normalize_username(&to_username);
+    if from_username == to_username {
+        return Err(ContractError::CannotPaySelf {});
+    }
+    if !USERS_BY_USERNAME.has(deps.storage, to_username.clone()) {
+        return Err(ContractError::UserNotFound {});
+    }
+    let hashlock = hashlock.to_lowercase();
+    let valid_hash = hashlock.len() == 64 && hashlock.chars().all(|c| c.is_ascii_hexdigit());
+    if amount.amount.is_zero() || !valid_hash || timelock <= env.block.time.seconds() {
+        return Err(ContractError::InvalidHtlc {});
+    }
+    let refund = validate_funds(deps.storage, &info, &amount)?;
+    
+    let htlc_id = HTLC_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    HTLC_SEQ.save(deps.storage, &htlc_id)?;
+    let htlc = Htlc {
+        id: htlc_id,
+        from_username: from_username.clone(),
+        to_username: to_username.clone(),
+        amount,
+        hashlock: hashlock.clone(),
+        timelock,
+        status: HtlcStatus::Locked,
+        preimage: None,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+    };
+    HTLCS.save(deps.storage, htlc_id, &htlc)?;
+    USER_HTLCS.save(deps.storage, (from_username.clone(), htlc_id), &true)?;
+    USER_HTLCS.save(deps.storage, (to_username.clone(), htlc_id), &true)?;
+    add_escrow(deps.storage, &from_username, &htlc.amount)?;
+    
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_attribute("action", "create_htlc")
+        .add_attribute("htlc_id", htlc_id.to_string())
+        .add_attribute("from", from_username)
+        .add_attribute("to", to_username)
+        .add_attribute("amount", htlc.amount.to_string())
+        .add_attribute("hashlock", hashlock)
+        .add_attribute("timelock", timelock.to_string()))
+}
+
+// The preimage is published in the claim so the sender can claim the other leg of the swap
+pub fn execute_claim_htlc(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    htlc_id: u64,
+    preimage: String,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let mut htlc = HTLCS.load(deps.storage, htlc_id)
+        .map_err(|_| ContractError::HtlcNotFound {})?;
+    if htlc.to_username != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !matches!(htlc.status, HtlcStatus::Locked) {
+        return Err(ContractError::HtlcNotLocked {});
+    }
+    if env.block.time.seconds() >= htlc.timelock {
+        return Err(ContractError::HtlcExpired {});
+    }
+    let preimage = preimage.to_lowercase();
+    if hash_preimage(&preimage).as_deref() != Some(htlc.hashlock.as_str()) {
+        return Err(ContractError::InvalidPreimage {});
+    }
+    
+    release_escrow(deps.storage, &htlc.from_username, &htlc.amount)?;
+    htlc.status = HtlcStatus::Claimed;
+    htlc.preimage = Some(preimage.clone());
+    htlc.updated_at = env.block.time.seconds();
+    HTLCS.save(deps.storage, htlc_id, &htlc)?;
+    
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![htlc.amount.clone()],
+        })
+        .add_attribute("action", "claim_htlc")
+        .add_attribute("htlc_id", htlc_id.to_string())
+        .add_attribute("claimer", username)
+        .add_attribute("preimage", preimage)
+        .add_attribute("amount", htlc.amount.to_string()))
+}
+
+pub fn execute_refund_htlc(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    htlc_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let mut htlc = HTLCS.load(deps.storage, htlc_id)
+        .map_err(|_| ContractError::HtlcNotFound {})?;
+    if htlc.from_username != username {
+        return Err(ContractError::NotAuthorized {});
+    }
+    if !matches!(htlc.status, HtlcStatus::Locked) {
+        return Err(ContractError::HtlcNotLocked {});
+    }
+    if env.block.time.seconds() < htlc.timelock {
+        return Err(ContractError::HtlcNotExpired {});
+    }
+    
+    release_escrow(deps.storage, &username, &htlc.amount)?;
+    htlc.status = HtlcStatus::Refunded;
+    htlc.updated_at = env.block.time.seconds();
+    HTLCS.save(deps.storage, htlc_id, &htlc)?;
+    
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![htlc.amount.clone()],
+        })
+        .add_attribute("action", "refund_htlc")
+        .add_attribute("htlc_id", htlc_id.to_string())
+        .add_attribute("from", username)
+        .add_attribute("refunded", htlc.amount.to_string()))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        // User Management
+        QueryMsg::GetUserByUsername { username } => query_user_by_username(deps, env, username),
+        QueryMsg::GetUserByWallet { wallet_address } => query_user_by_wallet(deps, wallet_address),
+        QueryMsg::IsUsernameAvailable { username } => query_username_available(deps, env, username),
+        QueryMsg::SearchUsers { query, start_after } => query_search_users(deps, query, start_after),
+        
+        // New username-specific queries
+        QueryMsg::GetUsernameByWallet { wallet_address } => query_username_by_wallet(deps, wallet_address),
+        QueryMsg::GetWalletByUsername { username } => query_wallet_by_username(deps, env, username),
+        QueryMsg::HasUsername { wallet_address } => query_has_username(deps, wallet_address),
+        
+        // Friends System
+        QueryMsg::GetUserFriends { username } => query_user_friends(deps, username),
+        QueryMsg::GetPendingRequests { username, start_after } => query_pending_requests(deps, username, start_after),
+        QueryMsg::AreFriends { username1, username2 } => query_are_friends(deps, username1, username2),
+        QueryMsg::GetSuggestedFriends { username, limit } => query_suggested_friends(deps, username, limit),
+        
+        // Friend Groups
+        QueryMsg::GetGroup { group_id } => query_group(deps, group_id),
+        QueryMsg::GetUserGroups { username } => query_user_groups(deps, username),
+        QueryMsg::GetGroupLedger { group_id } => query_group_ledger(deps, group_id),
+        QueryMsg::GetGroupExpenses { group_id, start_after, limit } => {
+            query_group_expenses(deps, group_id, start_after, limit)
+        }
+        
+        // Payment System
+        QueryMsg::GetPaymentById { payment_id, viewer } => query_payment_by_id(deps, payment_id, viewer),
+        QueryMsg::GetPaymentsByIds { ids, viewer } => query_payments_by_ids(deps, ids, viewer),
+        QueryMsg::GetPaymentByExternalRef { creator, external_ref, viewer } => {
+            query_payment_by_external_ref(deps, creator, external_ref, viewer)
+        }
+        QueryMsg::GetReceipt { payment_id, viewer } => query_receipt(deps, payment_id, viewer),
+        QueryMsg::GetPaymentHistory { username, viewer, start_after } => {
+            query_payment_history(deps, username, viewer, start_after)
+        }
+        QueryMsg::GetPendingPayments { username, viewer, start_after } => {
+            query_pending_payments(deps, username, viewer, start_after)
+        }
+        QueryMsg::GetWalletPaymentRequests { wallet_address, start_after } => {
+            query_wallet_payment_requests(deps, wallet_address, start_after)
+        }
+        QueryMsg::GetPaymentHistoryFiltered { username, viewer, category, status, from_ts, to_ts, start_after } => {
+            query_payment_history_filtered(deps, username, viewer, category, status, from_ts, to_ts, start_after)
+        }
+        QueryMsg::GetActivityFeed { username, before, limit } => query_activity_feed(deps, username, before, limit),
+        
+        // Task System
+        QueryMsg::GetTaskById { task_id } => query_task_by_id(deps, task_id),
+        QueryMsg::GetTasksByIds { ids } => query_tasks_by_ids(deps, ids),
+        QueryMsg::GetTaskHistory { username, start_after } => query_task_history(deps, username, start_after),
+        QueryMsg::GetPendingTasks { username, start_after } => query_pending_tasks(deps, username, start_after),
+        QueryMsg::GetTasksByStatus { status, start_after, limit } => {
+            query_tasks_by_status(deps, status, start_after, limit)
+        }
+        QueryMsg::GetUserTasksByStatus { username, status, start_after, limit } => {
+            query_user_tasks_by_status(deps, username, status, start_after, limit)
+        }
+        QueryMsg::GetDisputeStats {} => query_dispute_stats(deps),
+        QueryMsg::GetReputation { username } => query_reputation(deps, username),
+        QueryMsg::GetTaskBatch { batch_id } => query_task_batch(deps, batch_id),
+        
+        // Campaigns
+        QueryMsg::GetCampaign { campaign_id } => query_campaign(deps, campaign_id),
+        QueryMsg::GetCampaignDonation { campaign_id, username } => {
+            query_campaign_donation(deps, campaign_id, username)
+        }
+        
+        // Subscriptions
+        QueryMsg::GetSubscription { subscription_id } => query_subscription(deps, env, subscription_id),
+        QueryMsg::GetUserSubscriptions { username } => query_user_subscriptions(deps, username),
+        
+        // Invoices
+        QueryMsg::GetInvoice { invoice_id } => query_invoice(deps, env, invoice_id),
+        QueryMsg::GetUserInvoices { username } => query_user_invoices(deps, username),
+        QueryMsg::GetPaymentLink { code } => query_payment_link(deps, env, code),
+        QueryMsg::GetUserPaymentLinks { username } => query_user_payment_links(deps, username),
+        QueryMsg::GetScheduledPayment { scheduled_id } => query_scheduled_payment(deps, env, scheduled_id),
+        QueryMsg::GetUserScheduledPayments { username } => query_user_scheduled_payments(deps, username),
+        QueryMsg::GetDueScheduledPayments {} => query_due_scheduled_payments(deps, env),
+        
+        // Gift codes
+        QueryMsg::GetGiftCode { gift_id } => query_gift_code(deps, gift_id),
+        QueryMsg::GetUserGiftCodes { username } => query_user_gift_codes(deps, username),
+        
+        // Hash-timelocked payments
+        QueryMsg::GetHtlc { htlc_id } => query_htlc(deps, htlc_id),
+        QueryMsg::GetUserHtlcs { username } => query_user_htlcs(deps, username),
+        
+        // Admin
+        QueryMsg::GetOwnership {} => query_ownership(deps),
+        QueryMsg::GetRoles {} => query_roles(deps),
+        QueryMsg::GetConfig {} => query_config(deps),
+        QueryMsg::GetAcceptedDenoms {} => query_accepted_denoms(deps),
+        QueryMsg::GetPriceOracle {} => query_price_oracle(deps),
+        QueryMsg::GetFeatureFlags {} => query_feature_flags(deps),
+        QueryMsg::GetPolicyContract {} => query_policy_contract(deps),
+        QueryMsg::GetDexRouter {} => query_dex_router(deps),
+        QueryMsg::GetContractInfo {} => query_contract_info(deps),
+        QueryMsg::GetErrorCodes {} => query_error_codes(),
+        
+        // Registration Fees
+        QueryMsg::GetRegistrationFees {} => query_registration_fees(deps),
+        QueryMsg::GetRegistrationFee { username } => query_registration_fee(deps, username),
+        QueryMsg::GetUsernameExpiry {} => query_username_expiry(deps),
+        
+        // Yield Routing
+        QueryMsg::GetInsurancePool {} => query_insurance_pool(deps),
+        QueryMsg::GetArbiters {} => query_arbiters(deps),
+        QueryMsg::GetSigner { username } => query_signer(deps, username),
+        QueryMsg::GetRelayers {} => query_relayers(deps),
+        QueryMsg::GetUsernameCollection {} => query_username_collection(deps),
+        QueryMsg::GetUsernameTransfer { username } => query_username_transfer(deps, username),
+        QueryMsg::GetYieldConfig {} => query_yield_config(deps),
+        QueryMsg::GetYieldPosition { task_id } => query_yield_position(deps, task_id),
+        
+        // Escrow Accounting
+        QueryMsg::GetEscrowLiabilities {} => query_escrow_liabilities(deps),
+        QueryMsg::GetUserEscrowed { username } => query_user_escrowed(deps, username),
+        
+        // Maintenance
+        QueryMsg::GetIndexReport {} => query_index_report(deps),
+        QueryMsg::GetArchiveRoot {} => query_archive_root(deps),
+        #[cfg(any(test, feature = "invariants"))]
+        QueryMsg::VerifyInvariants { limit } => query_verify_invariants(deps, limit),
+        
+        // Analytics
+        QueryMsg::ExportEvents { since_seq, limit } => query_export_events(deps, since_seq, limit),
+        
+        // Notifications
+        QueryMsg::GetNotifications { username, start_after, limit } => {
+            query_notifications(deps, username, start_after, limit)
+        }
+        
+        // Account Snapshot
+        QueryMsg::GetAccountSnapshot { username, viewer, limit, payments_before, tasks_before } => {
+            query_account_snapshot(deps, username, viewer, limit, payments_before, tasks_before)
+        }
+        
+        // Open Item Limits
+        QueryMsg::GetOpenItems { username } => query_open_items(deps, username),
+        
+        // Action Items
+        QueryMsg::GetActionItems { username } => query_action_items(deps, username),
+        QueryMsg::GetWalletActionItems { wallet_address } => query_wallet_action_items(deps, wallet_address),
+    }
+}
+
+// Maximum number of records returned by a single list query
+const MAX_QUERY_RESULTS: usize = 100;
+// Maximum number of storage entries a single list query may scan
+const MAX_QUERY_SCAN: usize = 1000;
+
+// A bounded slice of query results along with why collection stopped
+struct BoundedPage<T> {
+    items: Vec<T>,
+    truncated: bool,             // more matching records exist beyond MAX_QUERY_RESULTS
+    scan_budget_exhausted: bool, // scanning stopped at MAX_QUERY_SCAN with entries left unchecked
+}
+
+// Helper function to collect list query results without unbounded iteration
+fn collect_bounded<I, E, T, F>(entries: I, mut select: F) -> StdResult<BoundedPage<T>>
+where
+    I: Iterator<Item = StdResult<E>>,
+    F: FnMut(E) -> StdResult<Option<T>>,
+{
+    let mut items = Vec::new();
+    
+    for (scanned, entry) in entries.enumerate() {
+        if scanned == MAX_QUERY_SCAN {
+            return Ok(BoundedPage { items, truncated: false, scan_budget_exhausted: true });
+        }
+        
+        if let Some(item) = select(entry?)? {
+            if items.len() == MAX_QUERY_RESULTS {
+                return Ok(BoundedPage { items, truncated: true, scan_budget_exhausted: false });
+            }
+            items.push(item);
+        }
+    }
+    
+    Ok(BoundedPage { items, truncated: false, scan_budget_exhausted: false })
+}
+
+// Helper function to collect a bounded page over keyed entries, also returning the key to pass as
+// `start_after` for the next page when collection stopped early
+fn collect_page<I, K, V, T, F>(entries: I, mut select: F) -> StdResult<(BoundedPage<T>, Option<K>)>
+where
+    I: Iterator<Item = StdResult<(K, V)>>,
+    K: Clone,
+    F: FnMut((K, V)) -> StdResult<Option<T>>,
+{
+    let mut items = Vec::new();
+    let mut last_key = None;
+    
+    for (scanned, entry) in entries.enumerate() {
+        if scanned == MAX_QUERY_SCAN {
+            return Ok((BoundedPage { items, truncated: false, scan_budget_exhausted: true }, last_key));
+        }
+        
+        let (key, value) = entry?;
+        if let Some(item) = select((key.clone(), value))? {
+            if items.len() == MAX_QUERY_RESULTS {
+                return Ok((BoundedPage { items, truncated: true, scan_budget_exhausted: false }, last_key));
+            }
+            items.push(item);
+        }
+        last_key = Some(key);
+    }
+    
+    Ok((BoundedPage { items, truncated: false, scan_budget_exhausted: false }, None))
+}
+
+// USER MANAGEMENT QUERIES
+
+fn query_user_by_username(deps: Deps, env: Env, username: String) -> StdResult<Binary> {
+    let user = load_unreleased_user(deps, &env, &username)?;
+    to_json_binary(&UserResponse { user })
+}
+
+// Helper function to look up a user by username, treating a username released by expiry as not found
+// even before the reaper removes it
+fn load_unreleased_user(deps: Deps, env: &Env, username: &str) -> StdResult<User> {
+    let user = USERS_BY_USERNAME.load(deps.storage, normalize_username(username))?;
+    if username_grace_ended(deps.storage, &user, env.block.time.seconds())? {
+        return Err(StdError::not_found("user"));
+    }
+    Ok(user)
+}
+
+fn query_user_by_wallet(deps: Deps, wallet_address: String) -> StdResult<Binary> {
+    let wallet_addr = validate_query_address(deps.api, &wallet_address)?;
+    let username = USERS_BY_WALLET.load(deps.storage, wallet_addr)?;
+    let user = USERS_BY_USERNAME.load(deps.storage, username)?;
+    to_json_binary(&UserResponse { user })
+}
+
+fn query_username_available(deps: Deps, env: Env, username: String) -> StdResult<Binary> {
+    // Validate username format first
+    if validate_username(&username).is_err() {
+        // If username format is invalid, consider it not available
+        return to_json_binary(&UsernameAvailableResponse { available: false });
+    }
+    
+    let normalized_username = normalize_username(&username);
+    let available = match USERS_BY_USERNAME.may_load(deps.storage, normalized_username.clone())? {
+        Some(user) => username_reclaimable(deps.storage, &user, env.block.time.seconds())?,
+        None => !RETIRED_USERNAMES.has(deps.storage, normalized_username),
+    };
+    to_json_binary(&UsernameAvailableResponse { available })
+}
+
+// New username-specific query functions
+fn query_username_by_wallet(deps: Deps, wallet_address: String) -> StdResult<Binary> {
+    let wallet_addr = validate_query_address(deps.api, &wallet_address)?;
+    let username = USERS_BY_WALLET.load(deps.storage, wallet_addr)?;
+    to_json_binary(&UsernameResponse { username })
+}
+
+fn query_wallet_by_username(deps: Deps, env: Env, username: String) -> StdResult<Binary> {
+    let user = load_unreleased_user(deps, &env, &username)?;
+    to_json_binary(&WalletResponse { wallet_address: user.wallet_address.to_string() })
+}
+
+fn query_has_username(deps: Deps, wallet_address: String) -> StdResult<Binary> {
+    let wallet_addr = validate_query_address(deps.api, &wallet_address)?;
+    let has_username = USERS_BY_WALLET.may_load(deps.storage, wallet_addr)?.is_some();
+    to_json_binary(&HasUsernameResponse { has_username })
+}
+
+fn query_search_users(deps: Deps, query: String, start_after: Option<String>) -> StdResult<Binary> {
+    let query_lower = query.to_lowercase();
+    let (page, next_start_after) = collect_page(
+        USERS_BY_USERNAME.range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending),
+        |(_, user)| {
+            let matches = user.username.to_lowercase().contains(&query_lower) ||
+                user.display_name.to_lowercase().contains(&query_lower);
+            Ok(if matches { Some(user) } else { None })
+        },
+    )?;
+    // Every user matches an empty query, so the registered user count is its total
+    let total = if query.is_empty() { Some(USER_COUNT.may_load(deps.storage)?.unwrap_or(0)) } else { None };
+    to_json_binary(&UsersResponse {
+        users: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+        total,
+        next_start_after,
+    })
+}
+
+// FRIENDS SYSTEM QUERIES
+
+fn query_user_friends(deps: Deps, username: String) -> StdResult<Binary> {
+    to_json_binary(&user_friends(deps, username)?)
+}
+
+fn user_friends(deps: Deps, username: String) -> StdResult<FriendsResponse> {
+    let page = collect_bounded(
+        FRIENDSHIPS.prefix(username).range(deps.storage, None, None, Order::Ascending),
+        |(friend_username, _)| Ok(Some(friend_username)),
+    )?;
+    Ok(FriendsResponse {
+        friends: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+fn query_pending_requests(deps: Deps, username: String, start_after: Option<String>) -> StdResult<Binary> {
+    to_json_binary(&pending_friend_requests(deps, username, start_after)?)
+}
+
+fn pending_friend_requests(deps: Deps, username: String, start_after: Option<String>) -> StdResult<FriendRequestsResponse> {
+    // Get requests sent TO this user
+    let (page, next_start_after) = collect_page(
+        PENDING_FRIEND_REQUESTS
+            .prefix(username.clone())
+            .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending),
+        |(from_username, _)| FRIEND_REQUESTS.may_load(deps.storage, (from_username, username.clone())),
+    )?;
+    Ok(FriendRequestsResponse {
+        requests: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+        total: Some(PENDING_FRIEND_REQUEST_COUNTS.may_load(deps.storage, username)?.unwrap_or(0)),
+        next_start_after,
+    })
+}
+
+// Default and maximum number of suggested friends returned
+const DEFAULT_SUGGESTION_LIMIT: u32 = 10;
+const MAX_SUGGESTION_LIMIT: u32 = 50;
+
+fn query_suggested_friends(deps: Deps, username: String, limit: Option<u32>) -> StdResult<Binary> {
+    let username = normalize_username(&username);
+    let limit = limit.unwrap_or(DEFAULT_SUGGESTION_LIMIT).min(MAX_SUGGESTION_LIMIT) as usize;
+    
+    // Counterparties the user has completed payments or tasks with but is not friends with
+    let page = collect_bounded(
+        COUNTERPARTIES.prefix(username.clone()).range(deps.storage, None, None, Order::Ascending),
+        |(counterparty, last_transacted_at)| {
+            let friends = FRIENDSHIPS.has(deps.storage, (username.clone(), counterparty.clone()));
+            Ok((!friends).then_some(SuggestedFriend { username: counterparty, last_transacted_at }))
+        },
+    )?;
+    let mut suggestions = page.items;
+    suggestions.sort_by(|a, b| b.last_transacted_at.cmp(&a.last_transacted_at).then_with(|| a.username.cmp(&b.username)));
+    suggestions.truncate(limit);
+    to_json_binary(&SuggestedFriendsResponse { suggestions })
+}
+
+fn query_are_friends(deps: Deps, username1: String, username2: String) -> StdResult<Binary> {
+    let are_friends = FRIENDSHIPS
+        .may_load(deps.storage, (username1, username2))?
+        .is_some();
+    to_json_binary(&AreFriendsResponse { are_friends })
+}
+
+// FRIEND GROUP QUERIES
+
+fn query_group(deps: Deps, group_id: u64) -> StdResult<Binary> {
+    let group = GROUPS.load(deps.storage, group_id)?;
+    to_json_binary(&GroupResponse { group })
+}
+
+fn query_user_groups(deps: Deps, username: String) -> StdResult<Binary> {
+    // Groups the user owns or belongs to
+    let page = collect_bounded(
+        USER_GROUPS.prefix(normalize_username(&username)).range(deps.storage, None, None, Order::Ascending),
+        |(group_id, _)| GROUPS.may_load(deps.storage, group_id),
+    )?;
+    to_json_binary(&GroupsResponse {
+        groups: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+fn query_group_ledger(deps: Deps, group_id: u64) -> StdResult<Binary> {
+    // Bounded by the group's member count times the denoms it has used
+    let mut balances: Vec<GroupBalance> = GROUP_BALANCES
+        .sub_prefix(group_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, balance)| balance))
+        .collect::<StdResult<_>>()?;
+    // Composite keys order usernames by length first
+    balances.sort_by(|a, b| (&a.username, &a.denom).cmp(&(&b.username, &b.denom)));
+    to_json_binary(&GroupLedgerResponse { balances })
+}
+
+// Default and maximum number of group expenses returned per page
+const DEFAULT_GROUP_EXPENSE_LIMIT: u32 = 30;
+const MAX_GROUP_EXPENSE_LIMIT: u32 = 100;
+
+fn query_group_expenses(deps: Deps, group_id: u64, start_after: Option<u64>, limit: Option<u32>) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_GROUP_EXPENSE_LIMIT).min(MAX_GROUP_EXPENSE_LIMIT) as usize;
+    let expenses: Vec<GroupExpense> = GROUP_EXPENSES
+        .prefix(group_id)
+        .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, expense)| expense))
+        .collect::<StdResult<_>>()?;
+    to_json_binary(&GroupExpensesResponse { expenses })
+}
+
+// PAYMENT SYSTEM QUERIES
+
+fn query_payment_by_id(deps: Deps, payment_id: u64, viewer: Option<String>) -> StdResult<Binary> {
+    let payment = PAYMENTS.load(deps.storage, payment_id)?;
+    // Hidden payments look the same as missing ones
+    if !payment_visible_to(deps.storage, &payment, viewer.as_deref())? {
+        return Err(StdError::not_found("Payment"));
+    }
+    to_json_binary(&PaymentResponse { payment })
+}
+
+// Maximum number of ids a single by-ids query may look up
+const MAX_QUERY_IDS: usize = 100;
+
+// Helper function to reject by-ids queries over the lookup cap
+fn ensure_query_ids_bounded(ids: &[u64]) -> StdResult<()> {
+    if ids.len() > MAX_QUERY_IDS {
+        return Err(StdError::generic_err(format!("At most {} ids can be queried at once", MAX_QUERY_IDS)));
+    }
+    Ok(())
+}
+
+fn query_payments_by_ids(deps: Deps, ids: Vec<u64>, viewer: Option<String>) -> StdResult<Binary> {
+    ensure_query_ids_bounded(&ids)?;
+    let mut payments = vec![];
+    let mut missing = vec![];
+    for payment_id in ids {
+        match load_visible_payment(deps, payment_id, viewer.as_deref())? {
+            Some(payment) => payments.push(payment),
+            None => missing.push(payment_id),
+        }
+    }
+    to_json_binary(&PaymentsByIdsResponse { payments, missing })
+}
+
+fn query_payment_by_external_ref(deps: Deps, creator: String, external_ref: String, viewer: Option<String>) -> StdResult<Binary> {
+    let payment_id = PAYMENT_EXTERNAL_REFS.may_load(deps.storage, (creator, external_ref))?
+        .ok_or_else(|| StdError::not_found("Payment"))?;
+    query_payment_by_id(deps, payment_id, viewer)
+}
+
+fn query_receipt(deps: Deps, payment_id: u64, viewer: Option<String>) -> StdResult<Binary> {
+    let hash = RECEIPT_HASHES.load(deps.storage, payment_id)?;
+    let receipt = RECEIPTS.load(deps.storage, payment_id)?;
+    
+    // The hash is public so parties can share a hidden receipt off-chain for verification
+    let visible = parties_visible_to(deps.storage, receipt.visibility, &receipt.from_username, &receipt.to_username, viewer.as_deref())?;
+    to_json_binary(&ReceiptResponse {
+        receipt: visible.then_some(receipt),
+        hash,
+    })
+}
+
+// Helper function to load a payment if it exists and `viewer` may see it
+fn load_visible_payment(deps: Deps, payment_id: u64, viewer: Option<&str>) -> StdResult<Option<Payment>> {
+    match PAYMENTS.may_load(deps.storage, payment_id)? {
+        Some(payment) if payment_visible_to(deps.storage, &payment, viewer)? => Ok(Some(payment)),
+        _ => Ok(None),
+    }
+}
+
+fn query_payment_history(deps: Deps, username: String, viewer: Option<String>, start_after: Option<u64>) -> StdResult<Binary> {
+    // Get all payments for this user
+    let (page, next_start_after) = collect_page(
+        USER_PAYMENTS.prefix(username.clone()).range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending),
+        |(payment_id, _)| load_visible_payment(deps, payment_id, viewer.as_deref()),
+    )?;
+    // Other viewers may not see every payment, so only the user themselves gets a total
+    let total = if viewer.as_deref() == Some(username.as_str()) {
+        Some(USER_PAYMENT_COUNTS.may_load(deps.storage, username)?.unwrap_or(0))
+    } else {
+        None
+    };
+    to_json_binary(&PaymentsResponse {
+        payments: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+        total,
+        next_start_after,
+    })
+}
+
+fn query_wallet_payment_requests(deps: Deps, wallet_address: String, start_after: Option<u64>) -> StdResult<Binary> {
+    let wallet = validate_query_address(deps.api, &wallet_address)?;
+    let (page, next_start_after) = collect_page(
+        WALLET_PAYMENT_REQUESTS.prefix(wallet).range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending),
+        |(payment_id, _)| load_visible_payment(deps, payment_id, None),
+    )?;
+    to_json_binary(&PaymentsResponse {
+        payments: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+        total: None,
+        next_start_after,
+    })
+}
+
+fn query_pending_payments(deps: Deps, username: String, viewer: Option<String>, start_after: Option<u64>) -> StdResult<Binary> {
+    // Get all payments for this user that are pending
+    let (page, next_start_after) = collect_page(
+        USER_PAYMENTS.prefix(username).range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending),
+        |(payment_id, _)| {
+            let payment = load_visible_payment(deps, payment_id, viewer.as_deref())?;
+            Ok(payment.filter(payment_open))
+        },
+    )?;
+    to_json_binary(&PaymentsResponse {
+        payments: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+        total: None,
+        next_start_after,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn query_payment_history_filtered(
+    deps: Deps,
+    username: String,
+    viewer: Option<String>,
+    category: Option<String>,
+    status: Option<PaymentStatus>,
+    from_ts: Option<u64>,
+    to_ts: Option<u64>,
+    start_after: Option<u64>,
+) -> StdResult<Binary> {
+    // Get this user's payments matching every provided filter
+    let (page, next_start_after) = collect_page(
+        USER_PAYMENTS.prefix(username).range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending),
+        |(payment_id, _)| {
+            let payment = load_visible_payment(deps, payment_id, viewer.as_deref())?;
+            Ok(payment.filter(|p| {
+                category.as_ref().is_none_or(|c| p.category.as_ref() == Some(c)) &&
+                status.as_ref().is_none_or(|s| &p.status == s) &&
+                from_ts.is_none_or(|ts| p.created_at >= ts) &&
+                to_ts.is_none_or(|ts| p.created_at <= ts)
+            }))
+        },
+    )?;
+    to_json_binary(&PaymentsResponse {
+        payments: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+        total: None,
+        next_start_after,
+    })
+}
+
+// Default and maximum number of payments returned per activity feed page
+const DEFAULT_FEED_LIMIT: u32 = 20;
+const MAX_FEED_LIMIT: u32 = 50;
+
+fn query_activity_feed(deps: Deps, username: String, before: Option<u64>, limit: Option<u32>) -> StdResult<Binary> {
+    let username = normalize_username(&username);
+    let limit = limit.unwrap_or(DEFAULT_FEED_LIMIT).min(MAX_FEED_LIMIT) as usize;
+    
+    // Merge each friend's newest payment ids; one extra id tells whether an older page exists.
+    // Paging on ids before the visibility filter keeps pages bounded without skipping payments.
+    let mut ids = std::collections::BTreeSet::new();
+    for friend in user_friends(deps, username.clone())?.friends {
+        for id in USER_PAYMENTS
+            .prefix(friend)
+            .keys(deps.storage, None, before.map(Bound::exclusive), Order::Descending)
+            .take(limit + 1)
+        {
+            ids.insert(id?);
+        }
+    }
+    let ids: Vec<u64> = ids.into_iter().rev().take(limit + 1).collect();
+    let next_before = recent_page_cursor(&ids, limit);
+    
+    let payments = ids.into_iter()
+        .take(limit)
+        .filter_map(|id| load_visible_payment(deps, id, Some(&username)).transpose())
+        .collect::<StdResult<_>>()?;
+    to_json_binary(&ActivityFeedResponse { payments, next_before })
+}
+
+// TASK SYSTEM FUNCTIONS
+
+use crate::state::{Task, TaskStatus, TASKS, USER_TASKS};
+use crate::helpers::{adr36_sign_hash, hash_data, hash_preimage, pubkey_challenge, verify_zktls};
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create_task(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to_username: String,
+    amount: cosmwasm_std::Coin,
+    description: String,
+    proof_type: ProofType,
+    deadline_ts: u64,
+    review_window_secs: Option<u64>,
+    endpoint: String,
+    category: Option<String>,
+    tags: Vec<String>,
+    auto_approve_after_secs: Option<u64>,
+    escrow_upfront: bool,
+    co_approvers: Vec<String>,
+    approvals_required: Option<u32>,
+    price_quote: Option<PriceQuoteMsg>,
+    external_ref: Option<String>,
+    idempotency_key: Option<String>,
+    arbiter: Option<String>,
+    depends_on: Option<u64>,
+    payout_swap: Option<PayoutSwap>,
+    release_delay_secs: Option<u64>,
+) -> Result<Response, ContractError> {
+    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    validate_text_length(deps.storage, TextKind::Description, &description)?;
+    validate_text_length(deps.storage, TextKind::Endpoint, &endpoint)?;
+    if let Some(task_id) = replayed_record(deps.storage, &env, &info.sender, &idempotency_key, RecordKind::Task)? {
+        return Ok(idempotent_replay(&info, "create_task", "task_id", task_id));
+    }
+    ensure_proof_type_enabled(deps.storage, &proof_type)?;
+    ensure_open_item_limit(
+        deps.storage,
+        &OPEN_TASK_COUNTS,
+        &from_username,
+        |limits| limits.tasks,
+        ContractError::TooManyOpenTasks {},
+    )?;
+    validate_labels(&category, &tags)?;
+    
+    // Auto-approval and optional upfront escrow only apply to payer-approved soft tasks
+    if (auto_approve_after_secs.is_some() || escrow_upfront) && !matches!(proof_type, ProofType::Soft) {
+        return Err(ContractError::InvalidProofType {});
+    }
+    // A quoted price is resolved when funds move, so only tasks funded at approval can carry one
+    if price_quote.is_some() && (!matches!(proof_type, ProofType::Soft) || escrow_upfront) {
+        return Err(ContractError::InvalidPriceQuote {});
+    }
+    let price_quote = validate_price_quote(deps.storage, price_quote)?;
+    
+    // Validate task creation
+    if from_username == to_username {
+        return Err(ContractError::CannotCreateTaskWithSelf {});
+    }
+    
+    // Check if worker exists
+    let worker = USERS_BY_USERNAME.load(deps.storage, to_username.clone())
+        .map_err(|_| ContractError::UserNotFound {})?;
+    ensure_request_allowed(deps.storage, &from_username, &worker)?;
+    
+    // Validate deadline
+    if deadline_ts <= env.block.time.seconds() {
+        return Err(ContractError::InvalidTaskDeadline {});
+    }
+    
+    // Validate payment amount
+    if amount.amount.is_zero() {
+        return Err(ContractError::InvalidPaymentAmount {});
+    }
+    ensure_denom_accepted(deps.storage, &amount.denom)?;
+    
+    // Only hybrid tasks can be disputed, and neither party can arbitrate their own task
+    let agreed_arbiter = arbiter.map(|arbiter| validate_address(deps.api, &arbiter)).transpose()?;
+    if let Some(arbiter) = &agreed_arbiter {
+        if !matches!(proof_type, ProofType::Hybrid) || *arbiter == info.sender || *arbiter == worker.wallet_address {
+            return Err(ContractError::InvalidArbiter {});
+        }
+    }
+    
+    // Soft tasks not funded upfront take their escrow at approval
+    let holds_escrow = !matches!(proof_type, ProofType::Soft) || escrow_upfront;
+    
+    // A dependent task is escrowed now but blocked until the payer's task it depends on is released
+    let mut blocked = false;
+    if let Some(dependency_id) = depends_on {
+        let dependency = TASKS.load(deps.storage, dependency_id)
+            .map_err(|_| ContractError::TaskNotFound {})?;
+        if dependency.payer != from_username || matches!(dependency.status, TaskStatus::Refunded) || !holds_escrow {
+            return Err(ContractError::InvalidTaskDependency {});
+        }
+        blocked = !matches!(dependency.status, TaskStatus::Released);
+        let dependents = TASK_DEPENDENTS.prefix(dependency_id).keys(deps.storage, None, None, Order::Ascending).count();
+        if blocked && dependents >= MAX_TASK_DEPENDENTS {
+            return Err(ContractError::TooManyTaskDependents {});
+        }
+    }
+    
+    if payout_swap.as_ref().is_some_and(|swap| swap.ask_denom.is_empty() || swap.min_out.is_zero()) {
+        return Err(ContractError::InvalidPayoutSwap {});
+    }
+    
+    // A release delay holds escrow after proof or approval, so tasks funded at approval cannot take one
+    if release_delay_secs.is_some_and(|delay| delay == 0 || delay > MAX_RELEASE_DELAY_SECS || !holds_escrow) {
+        return Err(ContractError::InvalidReleaseDelay {});
+    }
+    
+    let review_window_secs = resolve_review_window(deps.storage, &proof_type, review_window_secs)?;
+    let approvals_required = validate_co_approvers(deps.storage, &from_username, &to_username, &amount, &co_approvers, approvals_required)?;
+    
+    // For non-soft and upfront-funded soft tasks, require escrow funds; other soft tasks take none
+    let refund = if holds_escrow {
+        validate_funds(deps.storage, &info, &amount)?
+    } else {
+        ensure_no_funds(&info)?;
+        None
+    };
+    
+    let mut state = STATE.load(deps.storage)?;
+    let task_id = state.next_task_id;
+    state.next_task_id += 1;
+    STATE.save(deps.storage, &state)?;
+    
+    let task = Task {
+        id: task_id,
+        payer: from_username.clone(),
+        worker: to_username.clone(),
+        amount,
+        proof_type: proof_type.clone(),
+        status: if blocked {
+            TaskStatus::Blocked
+        } else {
+            task_start_status(&proof_type)
+        },
+        deadline_ts,
+        review_window_secs,
+        endpoint,
+        evidence_hash: None,
+        evidence_submitted_at: None,
+        auto_approve_after_secs,
+        escrow_upfront,
+        co_approvers,
+        approvals_required,
+        approvals: vec![],
+        cancel_requested_by: None,
+        accepted_at: None,
+        counter_offer: None,
+        price_quote,
+        external_ref,
+        pending_amendment: None,
+        amendments: vec![],
+        insurance: None,
+        dispute_award_bps: None,
+        dispute_reason: None,
+        dispute_outcome: None,
+        arbiter: None,
+        agreed_arbiter,
+        arbiter_accepted_at: None,
+        depends_on,
+        zk_proof_hash: None,
+        verified_at: None,
+        verifier_id: None,
+        description,
+        category,
+        tags,
+        redacted: false,
+        created_at: env.block.time.seconds(),
+        updated_at: env.block.time.seconds(),
+        created_at_height: env.block.height,
+        updated_at_height: env.block.height,
+        batch_id: None,
+        payout_swap,
+        release_delay_secs,
+    };
+    
+    claim_external_ref(deps.storage, &TASK_EXTERNAL_REFS, &from_username, &task.external_ref, task_id)?;
+    save_idempotency_key(deps.storage, &env, &info.sender, idempotency_key, RecordKind::Task, task_id)?;
+    save_task(deps.storage, task_id, &task)?;
+    index_user_record(deps.storage, &USER_TASKS, &USER_TASK_COUNTS, &from_username, task_id)?;
+    index_user_record(deps.storage, &USER_TASKS, &USER_TASK_COUNTS, &to_username, task_id)?;
+    
+    let mut yield_deposit = None;
+    if holds_escrow {
+        add_escrow(deps.storage, &from_username, &task.amount)?;
+        yield_deposit = deposit_task_yield(deps.storage, task_id, &task.amount)?;
+    }
+    
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_messages(yield_deposit)
+        .add_attribute("action", "create_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("payer", from_username)
+        .add_attribute("worker", to_username)
+        .add_attribute("amount", task.amount.to_string())
+        .add_event(
+            cosmwasm_std::Event::new("task_created")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("payer", task.payer.clone())
+                .add_attribute("worker", task.worker.clone())
+                .add_attribute("proof_type", format!("{:?}", task.proof_type))
+                .add_attribute("deadline", task.deadline_ts.to_string())
+        ))
+}
+
+pub fn execute_submit_soft_evidence(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+    evidence_hash: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    validate_text_length(deps.storage, TextKind::Evidence, &evidence_hash)?;
+    
+    let task = update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+        
+        // Check authorization - only worker can submit evidence
+        if task.worker != username {
+            return Err(ContractError::TaskNotAuthorized {});
+        }
+        
+        // Check task type
+        if !matches!(task.proof_type, ProofType::Soft) {
+            return Err(ContractError::InvalidProofType {});
+        }
+        
+        // Check task status
+        if !matches!(task.status, TaskStatus::ProofSubmitted) {
+            return Err(ContractError::TaskAlreadyCompleted {});
+        }
+        
+        // Check deadline
+        if env.block.time.seconds() > task.deadline_ts {
+            return Err(ContractError::TaskExpired {});
+        }
+        
+        task.evidence_hash = Some(evidence_hash.clone());
+        task.evidence_submitted_at = Some(env.block.time.seconds());
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        
+        Ok(task)
+    })?;
+    ensure_proof_type_enabled(deps.storage, &task.proof_type)?;
+    notify(deps.storage, &env, &task.payer, NotificationKind::TaskProofSubmitted, &username, Some(task_id))?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "submit_soft_evidence")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("submitter", username)
+        .add_event(
+            cosmwasm_std::Event::new("proof_submitted")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("proof_type", "soft")
+                .add_attribute("evidence_hash", evidence_hash)
+        ))
+}
+
+pub fn execute_submit_zktls_proof(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+    proof_blob_or_ref: String,
+    zk_proof_hash: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    validate_text_length(deps.storage, TextKind::Evidence, &proof_blob_or_ref)?;
+    validate_text_length(deps.storage, TextKind::Evidence, &zk_proof_hash)?;
+    
+    let task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    
+    // Check authorization - only worker can submit proof
+    if task.worker != username {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
+    
+    // Check task type
+    if !matches!(task.proof_type, ProofType::ZkTLS | ProofType::Hybrid) {
+        return Err(ContractError::InvalidProofType {});
+    }
+    ensure_proof_type_enabled(deps.storage, &task.proof_type)?;
+    
+    // Check task status
+    if !matches!(task.status, TaskStatus::Escrowed) {
+        return Err(ContractError::TaskAlreadyCompleted {});
+    }
+    
+    // Check deadline
+    if env.block.time.seconds() > task.deadline_ts {
+        return Err(ContractError::TaskExpired {});
+    }
+    
+    // Work only counts once the agreed arbiter is on board
+    if task.agreed_arbiter.is_some() && task.arbiter_accepted_at.is_none() {
+        return Err(ContractError::ArbitrationNotAccepted {});
+    }
+    
+    // Verify zkTLS proof
+    let verification_result = verify_zktls(&proof_blob_or_ref, &task.endpoint)?;
+    if !verification_result {
+        return Err(ContractError::ZkTlsVerificationFailed {});
+    }
+    let trusted_worker = releases_instantly(deps.storage, &task)?;
+    
+    // Update task based on proof type
+    update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+        
+        task.zk_proof_hash = Some(zk_proof_hash.clone());
+        task.verified_at = Some(env.block.time.seconds());
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        
+        match task.proof_type {
+            ProofType::ZkTLS if co_approvals_missing(&task) > 0 || task.release_delay_secs.is_some() => {
+                // Hold until co-approved and past any release delay, then release via ReleaseIfWindowElapsed
+                task.status = TaskStatus::PendingRelease;
+            },
+            ProofType::ZkTLS => {
+                // Instant release for zkTLS mode
+                task.status = TaskStatus::Released;
+            },
+            ProofType::Hybrid if trusted_worker => {
+                // Trusted workers skip the review window on small tasks
+                task.status = TaskStatus::Released;
+            },
+            ProofType::Hybrid => {
+                // Move to pending release for hybrid mode
+                task.status = TaskStatus::PendingRelease;
+            },
+            _ => return Err(ContractError::InvalidProofType {}),
+        }
+        
+        Ok(task)
+    })?;
+    
+    let updated_task = TASKS.load(deps.storage, task_id)?;
+    notify(deps.storage, &env, &updated_task.payer, NotificationKind::TaskProofSubmitted, &username, Some(task_id))?;
+    let mut response = Response::new()
+        .add_attribute("action", "submit_zktls_proof")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("submitter", username)
+        .add_event(
+            cosmwasm_std::Event::new("proof_submitted")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("proof_type", format!("{:?}", updated_task.proof_type))
+                .add_attribute("zk_proof_hash", zk_proof_hash)
+        );
+    
+    // For zkTLS mode and trusted hybrid workers, immediately release payment
+    if matches!(updated_task.status, TaskStatus::Released) {
+        release_escrow(deps.storage, &updated_task.payer, &updated_task.amount)?;
+        record_task_paid_in_full(deps.storage, &updated_task.worker)?;
+        let worker = USERS_BY_USERNAME.load(deps.storage, updated_task.worker.clone())?;
+        let (payment_msgs, payout_events) = pay_worker_task_escrow(deps.storage, &deps.querier, &env, &updated_task, &worker)?;
+        response = response.add_submessages(payment_msgs)
+            .add_events(payout_events)
+            .add_event(
+                cosmwasm_std::Event::new("task_released")
+                    .add_attribute("task_id", task_id.to_string())
+                    .add_attribute("release_type", if trusted_worker { "trusted_worker" } else { "instant" })
+            );
+        record_counterparties(deps.storage, &env, &updated_task.payer, &updated_task.worker)?;
+        let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", TaskStatus::Released), &updated_task.amount)?;
+        response = response.add_submessages(callback);
+    } else {
+        // For hybrid mode and held zkTLS proofs, emit pending release event
+        let mut event = cosmwasm_std::Event::new("task_pending_release")
+            .add_attribute("task_id", task_id.to_string())
+            .add_attribute("review_window", updated_task.review_window_secs.unwrap_or(0).to_string());
+        if let Some(delay) = updated_task.release_delay_secs {
+            event = event.add_attribute("release_delay", delay.to_string());
+        }
+        response = response.add_event(event);
+    }
+    
+    Ok(response)
+}
+
+pub fn execute_approve_task(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    
+    let mut task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    
+    // Only payer can approve tasks
+    if task.payer != username {
+        return Err(ContractError::OnlyPayerCanApproveSoft {});
+    }
+    
+    // Check if task is in correct state for approval (auto-approved tasks still need funding)
+    if !matches!(task.status, TaskStatus::ProofSubmitted | TaskStatus::ApprovedPendingFunding) {
+        return Err(ContractError::TaskAlreadyCompleted {});
+    }
+    
+    // Only soft tasks can be manually approved
+    if !matches!(task.proof_type, ProofType::Soft) {
+        return Err(ContractError::InvalidProofType {});
+    }
+    
+    ensure_co_approved(&task)?;
+    
+    // Price-quoted tasks are funded at the oracle price at approval time
+    if let Some(quote) = task.price_quote.as_mut() {
+        task.amount.amount = resolve_price_quote(deps.as_ref(), &env, quote, &task.amount.denom)?;
+    }
+    
+    // A release delay holds the approved escrow, disputable by the payer, until ReleaseIfWindowElapsed
+    if let Some(delay) = task.release_delay_secs {
+        ensure_no_funds(&info)?;
+        task.status = TaskStatus::PendingRelease;
+        task.verified_at = Some(env.block.time.seconds());
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        save_task(deps.storage, task_id, &task)?;
+        return Ok(Response::new()
+            .add_attribute("action", "approve_task")
+            .add_attribute("task_id", task_id.to_string())
+            .add_attribute("approver", username)
+            .add_event(
+                cosmwasm_std::Event::new("task_pending_release")
+                    .add_attribute("task_id", task_id.to_string())
+                    .add_attribute("release_delay", delay.to_string())
+            ));
+    }
+    
+    // Update task status
+    task.status = TaskStatus::Released;
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
+    
+    let refund = if task_holds_escrow(&task) {
+        // Upfront-funded soft tasks release from escrow
+        ensure_no_funds(&info)?;
+        release_escrow(deps.storage, &task.payer, &task.amount)?;
+        None
+    } else {
+        // For other soft tasks, payer sends funds when approving
+        validate_funds(deps.storage, &info, &task.amount)?
+    };
+    
+    let worker = USERS_BY_USERNAME.load(deps.storage, task.worker.clone())?;
+    record_counterparties(deps.storage, &env, &task.payer, &task.worker)?;
+    record_task_paid_in_full(deps.storage, &task.worker)?;
+    
+    let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", TaskStatus::Released), &task.amount)?;
+    
+    let (payment_msgs, payout_events) = pay_worker_task_escrow(deps.storage, &deps.querier, &env, &task, &worker)?;
+    
+    Ok(Response::new()
+        .add_submessages(payment_msgs)
+        .add_events(payout_events)
+        .add_messages(refund)
+        .add_submessages(callback)
+        .add_attribute("action", "approve_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("approver", username)
+        .add_event(
+            cosmwasm_std::Event::new("task_released")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("release_type", "manual_approval")
+        ))
+}
+
+pub fn execute_dispute_task(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+    reason: Option<DisputeReason>,
+    reason_hash: Option<String>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    if let Some(reason_hash) = &reason_hash {
+        validate_text_length(deps.storage, TextKind::Evidence, reason_hash)?;
+    }
+    let reason = reason.unwrap_or(DisputeReason::Other);
+    
+    let mut task = update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+        
+        // Only payer can dispute
+        if task.payer != username {
+            return Err(ContractError::OnlyPayerCanDispute {});
+        }
+        
+        // Can only dispute hybrid tasks and tasks held by a release delay, in pending release state
+        if !(matches!(task.proof_type, ProofType::Hybrid) || task.release_delay_secs.is_some()) ||
+           !matches!(task.status, TaskStatus::PendingRelease) {
+            return Err(ContractError::TaskNotAuthorized {});
+        }
+        
+        // Check if dispute window is still open
+        let window_end = dispute_window_end(&task).ok_or(ContractError::TaskNotAuthorized {})?;
+        if env.block.time.seconds() > window_end {
+            return Err(ContractError::DisputeWindowClosed {});
+        }
+        
+        task.status = TaskStatus::Disputed;
+        task.dispute_reason = Some(reason);
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        
+        Ok(task)
+    })?;
+    
+    notify(deps.storage, &env, &task.worker, NotificationKind::TaskDisputed, &username, Some(task_id))?;
+    increment_dispute_count(deps.storage, &DISPUTE_REASON_COUNTS, reason.key())?;
+    
+    // Tasks with an agreed arbiter skip bonded arbiter selection
+    if task.agreed_arbiter.is_some() {
+        task.arbiter = None;
+    } else if let Some(arbiter) = select_arbiter(deps.storage, &env, &task)? {
+        ARBITERS.update(deps.storage, arbiter.clone(), |bonded| -> Result<_, ContractError> {
+            let mut bonded = bonded.ok_or(ContractError::ArbiterNotBonded {})?;
+            bonded.open_disputes += 1;
+            Ok(bonded)
+        })?;
+        task.arbiter = Some(arbiter);
+        save_task(deps.storage, task_id, &task)?;
+    }
+    
+    // The reason hash and arbiter are optional; empty attribute values are rejected by the chain
+    let mut event = cosmwasm_std::Event::new("task_disputed")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("reason", reason.key());
+    if let Some(reason_hash) = reason_hash {
+        event = event.add_attribute("reason_hash", reason_hash);
+    }
+    if let Some(arbiter) = task.agreed_arbiter.as_ref().or(task.arbiter.as_ref()) {
+        event = event.add_attribute("arbiter", arbiter);
+    }
+    
+    Ok(Response::new()
+        .add_attribute("action", "dispute_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("disputer", username)
+        .add_event(event))
+}
+
+pub fn execute_resolve_dispute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+    worker_bps: u16,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    
+    // An agreed arbiter alone resolves the task. A bonded arbiter resolves their assigned disputes,
+    // though the owner can step in. Otherwise the owner or a dispute resolver resolves it.
+    if let Some(arbiter) = &task.agreed_arbiter {
+        if *arbiter != info.sender {
+            return Err(ContractError::NotTaskArbiter {});
+        }
+    } else if let Some(arbiter) = &task.arbiter {
+        if *arbiter != info.sender && info.sender != STATE.load(deps.storage)?.owner {
+            return Err(ContractError::NotTaskArbiter {});
+        }
+    } else if !has_role(deps.storage, &info.sender, AdminRole::DisputeResolver)? {
+        return Err(ContractError::OnlyOwnerCanResolveDispute {});
+    }
+    if worker_bps > 10_000 {
+        return Err(ContractError::InvalidWorkerShare {});
+    }
+    
+    // Check if task is in dispute
+    if !matches!(task.status, TaskStatus::Disputed) {
+        return Err(ContractError::TaskNotInDispute {});
+    }
+    
+    // Any award to the worker releases the task; the award itself is kept on the task.
+    // Ruling at least partly against an insured worker opens a claim on the pool.
+    let status = if worker_bps == 0 { TaskStatus::Refunded } else { TaskStatus::Released };
+    let outcome = match worker_bps {
+        0 => DisputeOutcome::PayerRefunded,
+        10_000 => DisputeOutcome::WorkerAwarded,
+        _ => DisputeOutcome::Split,
+    };
+    let task = update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+        task.status = status.clone();
+        task.dispute_award_bps = Some(worker_bps);
+        task.dispute_outcome = Some(outcome);
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        if let Some(insurance) = task.insurance.as_mut().filter(|_| worker_bps < 10_000) {
+            insurance.claim_open = true;
+        }
+        Ok(task)
+    })?;
+    if let Some(insurance) = task.insurance.as_ref().filter(|insurance| insurance.claim_open) {
+        update_insurance_total(deps.storage, &INSURANCE_OPEN_CLAIMS, &insurance.max_payout, true)?;
+    }
+    increment_dispute_count(deps.storage, &DISPUTE_OUTCOME_COUNTS, outcome.key())?;
+    release_arbiter(deps.storage, &task)?;
+    
+    // Any ruling against the worker restarts their reputation
+    if worker_bps == 10_000 {
+        record_task_paid_in_full(deps.storage, &task.worker)?;
+    } else {
+        WORKER_REPUTATION.remove(deps.storage, task.worker.clone());
+    }
+    
+    release_escrow(deps.storage, &task.payer, &task.amount)?;
+    
+    // Both parties are paid their share in this transaction
+    let worker_amount = task.amount.amount.multiply_ratio(worker_bps, 10_000u128);
+    let payer_amount = task.amount.amount - worker_amount;
+    let worker = USERS_BY_USERNAME.load(deps.storage, task.worker.clone())?;
+    let payer = USERS_BY_USERNAME.load(deps.storage, task.payer.clone())?;
+    let shares = [(worker.wallet_address, worker_amount), (payer.wallet_address, payer_amount)];
+    let (payout_msgs, payout_events) = split_task_escrow(deps.storage, &deps.querier, &env, task_id, &task.amount, &shares)?;
+    
+    let mut response = Response::new()
+        .add_messages(payout_msgs)
+        .add_events(payout_events)
+        .add_attribute("action", "resolve_dispute")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("worker_bps", worker_bps.to_string())
+        .add_attribute("worker_amount", worker_amount.to_string())
+        .add_attribute("payer_amount", payer_amount.to_string());
+    if !worker_amount.is_zero() {
+        response = response.add_event(
+            cosmwasm_std::Event::new("task_released")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("release_type", "dispute_resolved")
+        );
+        record_counterparties(deps.storage, &env, &task.payer, &task.worker)?;
+    }
+    if !payer_amount.is_zero() {
+        response = response.add_event(
+            cosmwasm_std::Event::new("task_refunded")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("refund_reason", "dispute_resolved")
+        );
+    }
+    
+    let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", status), &task.amount)?;
+    response = response.add_submessages(callback);
+    
+    Ok(response)
+}
+
+pub fn execute_refund_if_expired(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    
+    // Check if task has expired
+    if env.block.time.seconds() <= task.deadline_ts {
+        return Err(ContractError::TaskNotExpired {});
+    }
+    
+    // Can only refund tasks still waiting on the worker; a verified proof
+    // pending release is settled by its dispute window, not the deadline
+    if !matches!(task.status, TaskStatus::Escrowed | TaskStatus::ProofSubmitted) {
+        return Err(ContractError::TaskAlreadyCompleted {});
+    }
+    
+    // Update task status
+    update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+        task.status = TaskStatus::Refunded;
+        task.counter_offer = None;
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        Ok(task)
+    })?;
+    
+    let payer = USERS_BY_USERNAME.load(deps.storage, task.payer.clone())?;
+    
+    // Refund to payer (only for escrowed tasks)
+    let mut response = Response::new()
+        .add_attribute("action", "refund_expired_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_event(
+            cosmwasm_std::Event::new("task_refunded")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("refund_reason", "expired")
+        );
+    
+    // Only refund escrowed funds (soft tasks hold escrow only when funded upfront)
+    if task_holds_escrow(&task) {
+        release_escrow(deps.storage, &task.payer, &task.amount)?;
+        let (refund_msgs, refund_events) = pay_out_task_escrow(deps.storage, &deps.querier, &env, task_id, &task.amount, &payer.wallet_address)?;
+        response = response.add_messages(refund_msgs).add_events(refund_events);
+    }
+    
+    let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", TaskStatus::Refunded), &task.amount)?;
+    response = response.add_submessages(callback);
+    
+    Ok(response)
+}
+
+pub fn execute_auto_approve_if_silent(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    
+    // Only soft tasks awaiting payer approval can be auto-approved
+    if !matches!(task.proof_type, ProofType::Soft) {
+        return Err(ContractError::InvalidProofType {});
+    }
+    if !matches!(task.status, TaskStatus::ProofSubmitted) {
+        return Err(ContractError::TaskAlreadyCompleted {});
+    }
+    
+    // Check if the payer's silence window has elapsed since evidence was submitted
+    if let (Some(submitted_at), Some(window)) = (task.evidence_submitted_at, task.auto_approve_after_secs) {
+        if env.block.time.seconds() <= submitted_at + window {
+            return Err(ContractError::DisputeWindowNotElapsed {});
+        }
+    } else {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
+    
+    // Funded tasks release straight away, or after their release delay; unfunded ones still need the payer to fund them
+    let funded = task_holds_escrow(&task);
+    if funded {
+        ensure_co_approved(&task)?;
+    }
+    let delayed = funded && task.release_delay_secs.is_some();
+    let status = match (funded, delayed) {
+        (true, true) => TaskStatus::PendingRelease,
+        (true, false) => TaskStatus::Released,
+        (false, _) => TaskStatus::ApprovedPendingFunding,
+    };
+    update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+        task.status = status.clone();
+        if delayed {
+            task.verified_at = Some(env.block.time.seconds());
+        }
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        Ok(task)
+    })?;
+    
+    let mut response = Response::new()
+        .add_attribute("action", "auto_approve_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_event(
+            cosmwasm_std::Event::new("task_auto_approved")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("payer", task.payer.clone())
+                .add_attribute("worker", task.worker.clone())
+                .add_attribute("amount", task.amount.to_string())
+                .add_attribute("funding_required", (!funded).to_string())
+        );
+    
+    if delayed {
+        response = response.add_event(
+            cosmwasm_std::Event::new("task_pending_release")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("release_delay", task.release_delay_secs.unwrap_or(0).to_string())
+        );
+    } else if funded {
+        release_escrow(deps.storage, &task.payer, &task.amount)?;
+        let worker = USERS_BY_USERNAME.load(deps.storage, task.worker.clone())?;
+        let (payment_msgs, payout_events) = pay_worker_task_escrow(deps.storage, &deps.querier, &env, &task, &worker)?;
+        response = response.add_submessages(payment_msgs)
+            .add_events(payout_events)
+            .add_event(
+                cosmwasm_std::Event::new("task_released")
+                    .add_attribute("task_id", task_id.to_string())
+                    .add_attribute("release_type", "auto_approval")
+            );
+        record_counterparties(deps.storage, &env, &task.payer, &task.worker)?;
+        record_task_paid_in_full(deps.storage, &task.worker)?;
+        let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", status), &task.amount)?;
+        response = response.add_submessages(callback);
+    }
+    
+    Ok(response)
+}
+
+// Maximum number of co-approvers on a task
+const MAX_CO_APPROVERS: usize = 10;
+
+// Helper function to validate a task's co-approvers, returning how many approvals release needs
+fn validate_co_approvers(
+    storage: &dyn Storage,
+    payer: &str,
+    worker: &str,
+    amount: &Coin,
+    co_approvers: &[String],
+    approvals_required: Option<u32>,
+) -> Result<u32, ContractError> {
+    let config = CONFIG.may_load(storage)?.unwrap_or_default();
+    let high_value = config.co_approval_thresholds.iter()
+        .any(|threshold| threshold.denom == amount.denom && amount.amount > threshold.amount);
+    if high_value && co_approvers.is_empty() {
+        return Err(ContractError::CoApproversRequired {});
+    }
+    
+    if co_approvers.len() > MAX_CO_APPROVERS {
+        return Err(ContractError::InvalidCoApprovers {});
+    }
+    for (i, co_approver) in co_approvers.iter().enumerate() {
+        if co_approver == payer || co_approver == worker || co_approvers[..i].contains(co_approver) {
+            return Err(ContractError::InvalidCoApprovers {});
+        }
+        if !USERS_BY_USERNAME.has(storage, co_approver.clone()) {
+            return Err(ContractError::UserNotFound {});
+        }
+    }
+    
+    let approvals_required = approvals_required.unwrap_or(co_approvers.len() as u32);
+    if co_approvers.is_empty() && approvals_required == 0 {
+        return Ok(0);
+    }
+    if approvals_required == 0 || approvals_required as usize > co_approvers.len() {
+        return Err(ContractError::InvalidApprovalThreshold {});
+    }
+    Ok(approvals_required)
+}
+
+// Helper function to count the co-approvals a task still needs
+fn co_approvals_missing(task: &Task) -> u32 {
+    task.approvals_required.saturating_sub(task.approvals.len() as u32)
+}
+
+// Helper function to ensure a task has collected its co-approvals before paying the worker
+fn ensure_co_approved(task: &Task) -> Result<(), ContractError> {
+    if co_approvals_missing(task) > 0 {
+        return Err(ContractError::CoApprovalsPending {});
+    }
+    Ok(())
+}
+
+pub fn execute_co_approve_task(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    
+    let task = update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+        if !task.co_approvers.contains(&username) {
+            return Err(ContractError::NotCoApprover {});
+        }
+        if matches!(task.status, TaskStatus::Released | TaskStatus::Refunded) {
+            return Err(ContractError::TaskAlreadyCompleted {});
+        }
+        if task.approvals.contains(&username) {
+            return Err(ContractError::AlreadyCoApproved {});
+        }
+        task.approvals.push(username.clone());
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        Ok(task)
+    })?;
     
     Ok(Response::new()
-        .add_attribute("action", "accept_friend_request")
-        .add_attribute("from", from_username)
-        .add_attribute("to", to_username))
+        .add_attribute("action", "co_approve_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("approver", username.clone())
+        .add_event(
+            cosmwasm_std::Event::new("task_co_approved")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("approver", username)
+                .add_attribute("approvals", task.approvals.len().to_string())
+                .add_attribute("approvals_required", task.approvals_required.to_string())
+        ))
 }
 
-pub fn execute_decline_friend_request(
+// Helper function to apply the configured default and bounds to a hybrid task's review window
+fn resolve_review_window(
+    storage: &dyn Storage,
+    proof_type: &ProofType,
+    review_window_secs: Option<u64>,
+) -> Result<Option<u64>, ContractError> {
+    // Only hybrid tasks have a dispute window
+    if !matches!(proof_type, ProofType::Hybrid) {
+        return Ok(review_window_secs);
+    }
+    let bounds = CONFIG.may_load(storage)?.unwrap_or_default().review_window;
+    let review_window_secs = review_window_secs.or(bounds.default_secs);
+    
+    let window = review_window_secs.unwrap_or(0);
+    if bounds.min_secs.is_some_and(|min| window < min) || bounds.max_secs.is_some_and(|max| window > max) {
+        return Err(ContractError::InvalidReviewWindow {});
+    }
+    Ok(review_window_secs)
+}
+
+// Maximum release delay a payer can put on a task
+const MAX_RELEASE_DELAY_SECS: u64 = 30 * 24 * 60 * 60;
+
+// Helper function to get the last second a verified task pending release can be disputed
+fn dispute_window_end(task: &Task) -> Option<u64> {
+    // Only hybrid tasks have a review window; zkTLS tasks held for co-approval and hybrid
+    // tasks created without a window can be released as soon as they are verified.
+    // A release delay runs on after the review window, or from approval for soft tasks.
+    let window = match task.proof_type {
+        ProofType::Hybrid => task.review_window_secs.unwrap_or(0),
+        _ => 0,
+    };
+    let delay = task.release_delay_secs.unwrap_or(0);
+    task.verified_at.map(|verified_at| verified_at + window + delay)
+}
+
+pub fn execute_release_if_window_elapsed(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    from_username: String,
+    task_id: u64,
 ) -> Result<Response, ContractError> {
-    let to_username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
     
-    let request_key = (from_username.clone(), to_username.clone());
+    // Check if task is in pending release state
+    if !matches!(task.status, TaskStatus::PendingRelease) {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
     
-    FRIEND_REQUESTS.update(deps.storage, request_key, |req| -> Result<_, ContractError> {
-        let mut req = req.ok_or(ContractError::FriendRequestNotFound {})?;
-        req.status = FriendRequestStatus::Declined;
-        req.updated_at = env.block.time.seconds();
-        Ok(req)
+    // Check if dispute window has elapsed
+    let window_end = dispute_window_end(&task).ok_or(ContractError::TaskNotAuthorized {})?;
+    if env.block.time.seconds() <= window_end {
+        return Err(ContractError::DisputeWindowNotElapsed {});
+    }
+    ensure_co_approved(&task)?;
+    
+    // Update task status
+    update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+        task.status = TaskStatus::Released;
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        Ok(task)
     })?;
     
+    release_escrow(deps.storage, &task.payer, &task.amount)?;
+    let worker = USERS_BY_USERNAME.load(deps.storage, task.worker.clone())?;
+    record_counterparties(deps.storage, &env, &task.payer, &task.worker)?;
+    record_task_paid_in_full(deps.storage, &task.worker)?;
+    let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", TaskStatus::Released), &task.amount)?;
+    
+    // Release payment to worker
+    let (payment_msgs, payout_events) = pay_worker_task_escrow(deps.storage, &deps.querier, &env, &task, &worker)?;
+    
     Ok(Response::new()
-        .add_attribute("action", "decline_friend_request")
-        .add_attribute("from", from_username)
-        .add_attribute("to", to_username))
+        .add_submessages(payment_msgs)
+        .add_events(payout_events)
+        .add_submessages(callback)
+        .add_attribute("action", "release_after_window")
+        .add_attribute("task_id", task_id.to_string())
+        .add_event(
+            cosmwasm_std::Event::new("task_released")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("release_type", "window_elapsed")
+        ))
 }
 
-pub fn execute_remove_friend(
+// Helper function to check whether the worker has yet to submit proof or evidence
+fn task_not_started(task: &Task) -> bool {
+    // Soft tasks start out ready for approval, so "not started" means no evidence yet;
+    // blocked tasks have not started either, so their payer can take the escrow back if the dependency fails
+    matches!(task.status, TaskStatus::Escrowed | TaskStatus::Blocked)
+        || (matches!(task.proof_type, ProofType::Soft)
+            && matches!(task.status, TaskStatus::ProofSubmitted)
+            && task.evidence_hash.is_none())
+}
+
+// Helper function to load a task the calling worker can still accept, decline or counter
+fn load_unaccepted_task(deps: &DepsMut, info: &MessageInfo, task_id: u64) -> Result<(String, Task), ContractError> {
+    let username = get_username_from_wallet(deps, &info.sender)?;
+    let task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    if task.worker != username {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
+    if matches!(task.status, TaskStatus::Released | TaskStatus::Refunded) {
+        return Err(ContractError::TaskAlreadyCompleted {});
+    }
+    if task.accepted_at.is_some() || !task_not_started(&task) {
+        return Err(ContractError::TaskAlreadyStarted {});
+    }
+    Ok((username, task))
+}
+
+pub fn execute_accept_task(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    friend_username: String,
+    task_id: u64,
 ) -> Result<Response, ContractError> {
-    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let (username, mut task) = load_unaccepted_task(&deps, &info, task_id)?;
     
-    // Check if they are friends
-    let friendship_key1 = (username.clone(), friend_username.clone());
-    let friendship_key2 = (friend_username.clone(), username.clone());
+    task.accepted_at = Some(env.block.time.seconds());
+    task.counter_offer = None;
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
     
-    if FRIENDSHIPS.may_load(deps.storage, friendship_key1.clone())?.is_none() {
-        return Err(ContractError::NotFriends {});
+    Ok(Response::new()
+        .add_attribute("action", "accept_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("worker", username))
+}
+
+pub fn execute_decline_task(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let (username, mut task) = load_unaccepted_task(&deps, &info, task_id)?;
+    
+    task.status = TaskStatus::Refunded;
+    task.counter_offer = None;
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
+    
+    let mut response = Response::new()
+        .add_attribute("action", "decline_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("worker", username)
+        .add_event(
+            cosmwasm_std::Event::new("task_refunded")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("refund_reason", "declined")
+        );
+    
+    // Only refund escrowed funds (soft tasks hold escrow only when funded upfront)
+    if task_holds_escrow(&task) {
+        release_escrow(deps.storage, &task.payer, &task.amount)?;
+        let payer = USERS_BY_USERNAME.load(deps.storage, task.payer.clone())?;
+        let (refund_msgs, refund_events) = pay_out_task_escrow(deps.storage, &deps.querier, &env, task_id, &task.amount, &payer.wallet_address)?;
+        response = response.add_messages(refund_msgs).add_events(refund_events);
     }
     
-    // Remove friendship (both directions)
-    FRIENDSHIPS.remove(deps.storage, friendship_key1);
-    FRIENDSHIPS.remove(deps.storage, friendship_key2);
+    let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", TaskStatus::Refunded), &task.amount)?;
+    response = response.add_submessages(callback);
+    
+    Ok(response)
+}
+
+pub fn execute_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+    amount: Coin,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let (username, mut task) = load_unaccepted_task(&deps, &info, task_id)?;
+    // Quoted tasks are priced by the oracle, not by the indicative amount
+    if task.price_quote.is_some() || amount.denom != task.amount.denom || amount.amount <= task.amount.amount {
+        return Err(ContractError::InvalidCounterOffer {});
+    }
+    
+    task.counter_offer = Some(amount.clone());
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
     
     Ok(Response::new()
-        .add_attribute("action", "remove_friend")
-        .add_attribute("user", username)
-        .add_attribute("removed_friend", friend_username))
+        .add_attribute("action", "counter_offer")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("worker", username)
+        .add_attribute("amount", amount.to_string()))
 }
 
-// PAYMENT SYSTEM FUNCTIONS
+pub fn execute_accept_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    if task.payer != username {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
+    // A counter-offer only stands while the worker has yet to start
+    if matches!(task.status, TaskStatus::Released | TaskStatus::Refunded) {
+        return Err(ContractError::TaskAlreadyCompleted {});
+    }
+    if !task_not_started(&task) {
+        return Err(ContractError::TaskAlreadyStarted {});
+    }
+    let offer = task.counter_offer.take().ok_or(ContractError::NoCounterOffer {})?;
+    
+    // Escrowed tasks are topped up by the difference; others are funded at approval
+    let top_up = Coin { denom: offer.denom.clone(), amount: offer.amount - task.amount.amount };
+    let refund = if task_holds_escrow(&task) {
+        let refund = validate_funds(deps.storage, &info, &top_up)?;
+        add_escrow(deps.storage, &task.payer, &top_up)?;
+        refund
+    } else {
+        ensure_no_funds(&info)?;
+        None
+    };
+    
+    task.amount = offer;
+    task.accepted_at = Some(env.block.time.seconds());
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
+    
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_attribute("action", "accept_counter_offer")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("payer", username)
+        .add_attribute("amount", task.amount.to_string()))
+}
 
-pub fn execute_send_direct_payment(
+// Maximum number of accepted amendments kept on a task
+const MAX_TASK_AMENDMENTS: usize = 20;
+
+// Helper function to check whether a task is still in progress and open to amendments
+fn task_amendable(task: &Task) -> bool {
+    matches!(task.status, TaskStatus::Escrowed | TaskStatus::ProofSubmitted)
+}
+
+pub fn execute_amend_task(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    to_username: String,
-    amount: cosmwasm_std::Coin,
-    description: String,
-    proof_type: ProofType,
+    task_id: u64,
+    additional_amount: Option<Coin>,
+    new_description_hash: Option<String>,
+    new_deadline: Option<u64>,
 ) -> Result<Response, ContractError> {
-    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    if task.payer != username {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
+    if !task_amendable(&task) {
+        return Err(ContractError::TaskAlreadyCompleted {});
+    }
+    if task.pending_amendment.is_some() {
+        return Err(ContractError::AmendmentPending {});
+    }
+    if task.amendments.len() >= MAX_TASK_AMENDMENTS {
+        return Err(ContractError::AmendmentLimitReached {});
+    }
     
-    // Validate payment
-    if from_username == to_username {
-        return Err(ContractError::CannotPaySelf {});
+    let additional_amount = additional_amount.filter(|amount| !amount.amount.is_zero());
+    let new_description_hash = new_description_hash.filter(|hash| !hash.is_empty());
+    if additional_amount.as_ref().is_some_and(|amount| amount.denom != task.amount.denom || task.price_quote.is_some())
+        || new_deadline.is_some_and(|deadline| deadline <= env.block.time.seconds())
+        || (additional_amount.is_none() && new_description_hash.is_none() && new_deadline.is_none())
+    {
+        return Err(ContractError::InvalidAmendment {});
     }
     
-    // Check if recipient exists
-    let recipient = USERS_BY_USERNAME.load(deps.storage, to_username.clone())
-        .map_err(|_| ContractError::UserNotFound {})?;
+    // Escrowed tasks hold the top-up from the proposal until it is accepted or withdrawn
+    let refund = match &additional_amount {
+        Some(amount) if task_holds_escrow(&task) => {
+            let refund = validate_funds(deps.storage, &info, amount)?;
+            add_escrow(deps.storage, &task.payer, amount)?;
+            refund
+        }
+        _ => {
+            ensure_no_funds(&info)?;
+            None
+        }
+    };
     
-    // Validate payment amount
-    if amount.amount.is_zero() {
-        return Err(ContractError::InvalidPaymentAmount {});
+    task.pending_amendment = Some(TaskAmendment {
+        additional_amount,
+        description_hash: new_description_hash,
+        deadline_ts: new_deadline,
+        proposed_at: env.block.time.seconds(),
+        accepted_at: None,
+    });
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
+    
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_attribute("action", "amend_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("payer", username))
+}
+
+pub fn execute_accept_amendment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    if task.worker != username {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
+    if !task_amendable(&task) {
+        return Err(ContractError::TaskAlreadyCompleted {});
+    }
+    let mut amendment = task.pending_amendment.take().ok_or(ContractError::NoAmendmentPending {})?;
+    if amendment.deadline_ts.is_some_and(|deadline| deadline <= env.block.time.seconds()) {
+        return Err(ContractError::InvalidAmendment {});
+    }
+    
+    if let Some(additional) = &amendment.additional_amount {
+        task.amount.amount = task.amount.amount.checked_add(additional.amount).map_err(StdError::from)?;
+    }
+    if let Some(deadline) = amendment.deadline_ts {
+        task.deadline_ts = deadline;
+    }
+    amendment.accepted_at = Some(env.block.time.seconds());
+    task.amendments.push(amendment);
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "accept_amendment")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("worker", username)
+        .add_attribute("amount", task.amount.to_string())
+        .add_attribute("deadline_ts", task.deadline_ts.to_string()))
+}
+
+// Also works once the task has settled, so an unaccepted top-up is never stranded
+pub fn execute_withdraw_amendment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    if task.payer != username {
+        return Err(ContractError::TaskNotAuthorized {});
     }
+    let amendment = task.pending_amendment.take().ok_or(ContractError::NoAmendmentPending {})?;
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
     
-    // Check if sufficient funds were sent
-    let sent_amount = info.funds.iter()
-        .find(|coin| coin.denom == amount.denom)
-        .map(|coin| coin.amount)
-        .unwrap_or_default();
+    let mut response = Response::new()
+        .add_attribute("action", "withdraw_amendment")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("payer", username);
     
-    if sent_amount < amount.amount {
-        return Err(ContractError::InsufficientFunds {});
+    if let Some(additional) = amendment.additional_amount.filter(|_| task_holds_escrow(&task)) {
+        release_escrow(deps.storage, &task.payer, &additional)?;
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![additional],
+        });
     }
+    Ok(response)
+}
+
+// The payer may cancel alone until the worker has started; afterwards both parties must agree
+pub fn execute_cancel_task(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
     
-    let mut state = STATE.load(deps.storage)?;
-    let payment_id = state.next_payment_id;
-    state.next_payment_id += 1;
-    STATE.save(deps.storage, &state)?;
+    if task.payer != username && task.worker != username {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
+    if matches!(task.status, TaskStatus::Released | TaskStatus::Refunded) {
+        return Err(ContractError::TaskAlreadyCompleted {});
+    }
     
-    let payment = Payment {
-        id: payment_id,
-        from_username: from_username.clone(),
-        to_username: to_username.clone(),
-        amount,
-        description,
-        payment_type: PaymentType::DirectPayment,
-        proof_type: proof_type.clone(),
-        proof_data: None,
-        status: if matches!(proof_type, ProofType::None) { 
-            PaymentStatus::Completed 
-        } else { 
-            PaymentStatus::Pending 
-        },
-        created_at: env.block.time.seconds(),
-        updated_at: env.block.time.seconds(),
+    let agreed = match &task.cancel_requested_by {
+        Some(requester) if requester != &username => true,
+        Some(_) => return Err(ContractError::CancelAlreadyRequested {}),
+        None => task.payer == username && task_not_started(&task),
     };
     
-    PAYMENTS.save(deps.storage, payment_id, &payment)?;
-    USER_PAYMENTS.save(deps.storage, (from_username.clone(), payment_id), &true)?;
-    USER_PAYMENTS.save(deps.storage, (to_username.clone(), payment_id), &true)?;
+    if !agreed {
+        task.cancel_requested_by = Some(username.clone());
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        save_task(deps.storage, task_id, &task)?;
+        return Ok(Response::new()
+            .add_attribute("action", "request_task_cancel")
+            .add_attribute("task_id", task_id.to_string())
+            .add_attribute("requested_by", username));
+    }
+    
+    let mutual = task.cancel_requested_by.is_some();
+    task.status = TaskStatus::Refunded;
+    task.cancel_requested_by = None;
+    task.counter_offer = None;
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
     
     let mut response = Response::new()
-        .add_attribute("action", "send_direct_payment")
-        .add_attribute("from", from_username)
-        .add_attribute("to", to_username.clone())
-        .add_attribute("payment_id", payment_id.to_string())
-        .add_attribute("amount", payment.amount.to_string());
+        .add_attribute("action", "cancel_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_event(
+            cosmwasm_std::Event::new("task_cancelled")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("cancelled_by", username)
+                .add_attribute("mutual", mutual.to_string())
+        );
     
-    // If no proof required, send payment immediately
-    if matches!(proof_type, ProofType::None) {
-        let payment_msg = CosmosMsg::Bank(BankMsg::Send {
-            to_address: recipient.wallet_address.to_string(),
-            amount: vec![payment.amount],
-        });
-        response = response.add_message(payment_msg);
+    // Only refund escrowed funds (soft tasks hold escrow only when funded upfront)
+    if task_holds_escrow(&task) {
+        release_escrow(deps.storage, &task.payer, &task.amount)?;
+        let payer = USERS_BY_USERNAME.load(deps.storage, task.payer.clone())?;
+        let (refund_msgs, refund_events) = pay_out_task_escrow(deps.storage, &deps.querier, &env, task_id, &task.amount, &payer.wallet_address)?;
+        response = response.add_messages(refund_msgs).add_events(refund_events);
     }
     
+    let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", TaskStatus::Refunded), &task.amount)?;
+    response = response.add_submessages(callback);
+    
     Ok(response)
 }
 
-pub fn execute_create_payment_request(
-    deps: DepsMut,
+// TASK BATCH FUNCTIONS
+
+// Maximum number of workers in one task batch
+const MAX_TASK_BATCH_WORKERS: usize = 20;
+
+pub fn execute_create_task_batch(
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    to_username: String,
-    amount: cosmwasm_std::Coin,
-    description: String,
-    proof_type: ProofType,
+    template: TaskTemplate,
+    workers: Vec<String>,
 ) -> Result<Response, ContractError> {
-    let from_username = get_username_from_wallet(&deps, &info.sender)?;
-    
-    // Validate
-    if from_username == to_username {
-        return Err(ContractError::CannotPaySelf {});
+    let payer = get_username_from_wallet(&deps, &info.sender)?;
+    let workers: Vec<String> = workers.iter().map(|worker| normalize_username(worker)).collect();
+    let mut distinct = workers.clone();
+    distinct.sort();
+    distinct.dedup();
+    let holds_escrow = !matches!(template.proof_type, ProofType::Soft) || template.escrow_upfront;
+    if workers.is_empty() || workers.len() > MAX_TASK_BATCH_WORKERS || distinct.len() != workers.len() || !holds_escrow {
+        return Err(ContractError::InvalidTaskBatch {});
     }
-    
-    // Check if recipient exists
-    if USERS_BY_USERNAME.may_load(deps.storage, to_username.clone())?.is_none() {
-        return Err(ContractError::UserNotFound {});
+    let total = Coin {
+        denom: template.amount.denom.clone(),
+        amount: template.amount.amount.checked_mul(Uint128::from(workers.len() as u128)).map_err(StdError::from)?,
+    };
+    let refund = validate_funds(deps.storage, &info, &total)?;
+    
+    let batch_id = TASK_BATCH_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    TASK_BATCH_SEQ.save(deps.storage, &batch_id)?;
+    
+    let mut response = Response::new().add_messages(refund);
+    let mut task_ids = vec![];
+    for worker in workers {
+        // Each task is created as if the payer had attached only its share of the funds
+        let task_info = MessageInfo {
+            sender: info.sender.clone(),
+            funds: vec![template.amount.clone()],
+        };
+        let created = execute_create_task(
+            deps.branch(),
+            env.clone(),
+            task_info,
+            worker,
+            template.amount.clone(),
+            template.description.clone(),
+            template.proof_type.clone(),
+            template.deadline_ts,
+            template.review_window_secs,
+            template.endpoint.clone(),
+            template.category.clone(),
+            template.tags.clone(),
+            None,
+            template.escrow_upfront,
+            vec![],
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )?;
+        let task_id = STATE.load(deps.storage)?.next_task_id - 1;
+        update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+            let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+            task.batch_id = Some(batch_id);
+            Ok(task)
+        })?;
+        task_ids.push(task_id);
+        response = response.add_submessages(created.messages).add_events(created.events);
     }
     
-    let mut state = STATE.load(deps.storage)?;
-    let payment_id = state.next_payment_id;
-    state.next_payment_id += 1;
-    STATE.save(deps.storage, &state)?;
-    
-    let payment = Payment {
-        id: payment_id,
-        from_username: from_username.clone(),
-        to_username: to_username.clone(),
-        amount,
-        description,
-        payment_type: PaymentType::PaymentRequest,
-        proof_type,
-        proof_data: None,
-        status: PaymentStatus::Pending,
+    let tasks = task_ids.len();
+    TASK_BATCHES.save(deps.storage, batch_id, &TaskBatch {
+        id: batch_id,
+        payer: payer.clone(),
+        task_ids,
         created_at: env.block.time.seconds(),
-        updated_at: env.block.time.seconds(),
-    };
-    
-    PAYMENTS.save(deps.storage, payment_id, &payment)?;
-    USER_PAYMENTS.save(deps.storage, (from_username.clone(), payment_id), &true)?;
-    USER_PAYMENTS.save(deps.storage, (to_username.clone(), payment_id), &true)?;
+    })?;
     
-    Ok(Response::new()
-        .add_attribute("action", "create_payment_request")
-        .add_attribute("from", from_username)
-        .add_attribute("to", to_username)
-        .add_attribute("payment_id", payment_id.to_string())
-        .add_attribute("amount", payment.amount.to_string()))
+    Ok(response
+        .add_attribute("action", "create_task_batch")
+        .add_attribute("batch_id", batch_id.to_string())
+        .add_attribute("payer", payer)
+        .add_attribute("tasks", tasks.to_string())
+        .add_attribute("amount", total.to_string()))
 }
 
-pub fn execute_create_help_request(
-    deps: DepsMut,
+pub fn execute_cancel_task_batch(
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    to_username: String,
-    amount: cosmwasm_std::Coin,
-    description: String,
-    proof_type: ProofType,
+    batch_id: u64,
 ) -> Result<Response, ContractError> {
-    let from_username = get_username_from_wallet(&deps, &info.sender)?;
-    
-    // Validate
-    if from_username == to_username {
-        return Err(ContractError::CannotPaySelf {});
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let batch = TASK_BATCHES.load(deps.storage, batch_id)
+        .map_err(|_| ContractError::TaskBatchNotFound {})?;
+    if batch.payer != username {
+        return Err(ContractError::TaskNotAuthorized {});
     }
     
-    // Check if recipient exists
-    if USERS_BY_USERNAME.may_load(deps.storage, to_username.clone())?.is_none() {
-        return Err(ContractError::UserNotFound {});
+    // Only tasks no worker has claimed are cancelled; accepted or started ones keep running,
+    // and tasks archived since the batch was created are reported as not found
+    let mut response = Response::new();
+    let mut cancelled = 0;
+    let mut not_found = vec![];
+    for task_id in &batch.task_ids {
+        let Some(task) = TASKS.may_load(deps.storage, *task_id)? else {
+            not_found.push(task_id.to_string());
+            continue;
+        };
+        if task.accepted_at.is_some() || task.cancel_requested_by.is_some() || !task_not_started(&task) {
+            continue;
+        }
+        let cancel = execute_cancel_task(deps.branch(), env.clone(), info.clone(), *task_id)?;
+        response = response.add_submessages(cancel.messages).add_events(cancel.events);
+        cancelled += 1;
     }
     
-    // Check if sufficient funds were sent for escrow
-    let sent_amount = info.funds.iter()
-        .find(|coin| coin.denom == amount.denom)
-        .map(|coin| coin.amount)
-        .unwrap_or_default();
+    Ok(response
+        .add_attribute("action", "cancel_task_batch")
+        .add_attribute("batch_id", batch_id.to_string())
+        .add_attribute("cancelled", cancelled.to_string())
+        .add_attribute("kept", (batch.task_ids.len() - cancelled - not_found.len()).to_string())
+        .add_attribute("not_found", if not_found.is_empty() { "none".to_string() } else { not_found.join(",") }))
+}
+
+// NOTIFICATION FUNCTIONS
+
+// Maximum number of acknowledged notifications deleted per AckNotifications call
+const MAX_ACK_PRUNE: usize = 100;
+
+pub fn execute_ack_notifications(
+    deps: DepsMut,
+    info: MessageInfo,
+    up_to_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = get_username_from_wallet(&deps, &info.sender)?;
     
-    if sent_amount < amount.amount {
-        return Err(ContractError::InsufficientFunds {});
+    // The watermark hides everything up to the id at once; storage is pruned in bounded batches
+    let acked_up_to = NOTIFICATIONS_ACKED.may_load(deps.storage, username.clone())?.unwrap_or_default().max(up_to_id);
+    NOTIFICATIONS_ACKED.save(deps.storage, username.clone(), &acked_up_to)?;
+    
+    let pruned: Vec<u64> = NOTIFICATIONS
+        .prefix(username.clone())
+        .keys(deps.storage, None, Some(Bound::inclusive(acked_up_to)), Order::Ascending)
+        .take(MAX_ACK_PRUNE)
+        .collect::<StdResult<_>>()?;
+    for id in &pruned {
+        NOTIFICATIONS.remove(deps.storage, (username.clone(), *id));
     }
     
-    let mut state = STATE.load(deps.storage)?;
-    let payment_id = state.next_payment_id;
-    state.next_payment_id += 1;
-    STATE.save(deps.storage, &state)?;
-    
-    let payment = Payment {
-        id: payment_id,
-        from_username: from_username.clone(),
-        to_username: to_username.clone(),
-        amount,
-        description,
-        payment_type: PaymentType::PaymentRequest, // Changed from HelpRequest to PaymentRequest
-        proof_type,
-        proof_data: None,
-        status: PaymentStatus::Pending,
-        created_at: env.block.time.seconds(),
-        updated_at: env.block.time.seconds(),
-    };
-    
-    PAYMENTS.save(deps.storage, payment_id, &payment)?;
-    USER_PAYMENTS.save(deps.storage, (from_username.clone(), payment_id), &true)?;
-    USER_PAYMENTS.save(deps.storage, (to_username.clone(), payment_id), &true)?;
-    
     Ok(Response::new()
-        .add_attribute("action", "create_help_request")
-        .add_attribute("from", from_username)
-        .add_attribute("to", to_username)
-        .add_attribute("payment_id", payment_id.to_string())
-        .add_attribute("amount", payment.amount.to_string()))
+        .add_attribute("action", "ack_notifications")
+        .add_attribute("username", username)
+        .add_attribute("acked_up_to", acked_up_to.to_string())
+        .add_attribute("pruned", pruned.len().to_string()))
 }
 
-pub fn execute_submit_proof(
+// PRIVACY FUNCTIONS
+
+pub fn execute_redact_record_text(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    payment_id: u64,
-    proof_data: String,
+    kind: RecordKind,
+    id: u64,
 ) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
     let username = get_username_from_wallet(&deps, &info.sender)?;
     
-    PAYMENTS.update(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
-        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
-        
-        // Check authorization - only the recipient can submit proof
-        if payment.to_username != username {
-            return Err(ContractError::PaymentNotAuthorized {});
-        }
-        
-        // Check if proof is required
-        if matches!(payment.proof_type, ProofType::None) {
-            return Err(ContractError::NoProofRequired {});
+    // Either party may redact free text once the record is final; amounts and statuses are kept
+    match kind {
+        RecordKind::Payment => {
+            update_payment(deps.storage, id, |payment| -> Result<_, ContractError> {
+                let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
+                if payment.from_username != username && payment.to_username != username {
+                    return Err(ContractError::PaymentNotAuthorized {});
+                }
+                if !matches!(payment.status, PaymentStatus::Completed | PaymentStatus::Rejected | PaymentStatus::Cancelled) {
+                    return Err(ContractError::RecordNotFinal {});
+                }
+                if payment.redacted {
+                    return Err(ContractError::AlreadyRedacted {});
+                }
+                payment.description = hash_data(&payment.description);
+                payment.redacted = true;
+                payment.updated_at = env.block.time.seconds();
+                payment.updated_at_height = env.block.height;
+                Ok(payment)
+            })?;
         }
-        
-        // Check payment status
-        if !matches!(payment.status, PaymentStatus::Pending) {
-            return Err(ContractError::PaymentAlreadyCompleted {});
+        RecordKind::Task => {
+            update_task(deps.storage, id, |task| -> Result<_, ContractError> {
+                let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+                if task.payer != username && task.worker != username {
+                    return Err(ContractError::TaskNotAuthorized {});
+                }
+                if !matches!(task.status, TaskStatus::Released | TaskStatus::Refunded) {
+                    return Err(ContractError::RecordNotFinal {});
+                }
+                if task.redacted {
+                    return Err(ContractError::AlreadyRedacted {});
+                }
+                task.description = hash_data(&task.description);
+                task.redacted = true;
+                task.updated_at = env.block.time.seconds();
+                task.updated_at_height = env.block.height;
+                Ok(task)
+            })?;
         }
-        
-        payment.proof_data = Some(proof_data);
-        payment.status = PaymentStatus::ProofSubmitted;
-        payment.updated_at = env.block.time.seconds();
-        
-        Ok(payment)
-    })?;
+    }
     
     Ok(Response::new()
-        .add_attribute("action", "submit_proof")
-        .add_attribute("payment_id", payment_id.to_string())
-        .add_attribute("submitter", username))
+        .add_attribute("action", "redact_record_text")
+        .add_attribute("kind", format!("{:?}", kind))
+        .add_attribute("id", id.to_string())
+        .add_attribute("redactor", username))
 }
 
-pub fn execute_approve_payment(
+// COMPLETION CALLBACK FUNCTIONS
+
+pub fn execute_register_callback(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    payment_id: u64,
+    kind: RecordKind,
+    id: u64,
+    contract_addr: String,
+    msg: Option<Binary>,
 ) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
     let username = get_username_from_wallet(&deps, &info.sender)?;
-    
-    let payment = PAYMENTS.load(deps.storage, payment_id)
-        .map_err(|_| ContractError::PaymentNotFound {})?;
-    
-    // Check authorization based on payment type
-    let authorized = match payment.payment_type {
-        PaymentType::DirectPayment => payment.from_username == username,
-        PaymentType::PaymentRequest => payment.to_username == username, // PaymentRequest: receiver approves
-    };
-    
-    if !authorized {
-        return Err(ContractError::PaymentNotAuthorized {});
+    let contract_addr = validate_address(deps.api, &contract_addr)?;
+    
+    // Only the creator of an open record can register a callback on it
+    match kind {
+        RecordKind::Payment => {
+            let payment = PAYMENTS.load(deps.storage, id)
+                .map_err(|_| ContractError::PaymentNotFound {})?;
+            if payment.from_username != username {
+                return Err(ContractError::PaymentNotAuthorized {});
+            }
+            if !payment_open(&payment) {
+                return Err(ContractError::PaymentAlreadyCompleted {});
+            }
+        }
+        RecordKind::Task => {
+            let task = TASKS.load(deps.storage, id)
+                .map_err(|_| ContractError::TaskNotFound {})?;
+            if task.payer != username {
+                return Err(ContractError::TaskNotAuthorized {});
+            }
+            if matches!(task.status, TaskStatus::Released | TaskStatus::Refunded) {
+                return Err(ContractError::TaskAlreadyCompleted {});
+            }
+        }
     }
     
-    // Check if proof is required and submitted
-    if !matches!(payment.proof_type, ProofType::None) && 
-       !matches!(payment.status, PaymentStatus::ProofSubmitted) {
-        return Err(ContractError::ProofRequired {});
+    let callback = Callback { contract_addr: contract_addr.clone(), msg };
+    match kind {
+        RecordKind::Payment => PAYMENT_CALLBACKS.save(deps.storage, id, &callback)?,
+        RecordKind::Task => TASK_CALLBACKS.save(deps.storage, id, &callback)?,
     }
     
-    // Update payment status
-    PAYMENTS.update(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
-        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
-        
-        if matches!(payment.status, PaymentStatus::Completed) {
-            return Err(ContractError::PaymentAlreadyCompleted {});
-        }
-        
-        payment.status = PaymentStatus::Completed;
-        payment.updated_at = env.block.time.seconds();
-        
-        Ok(payment)
-    })?;
-    
-    let mut response = Response::new()
-        .add_attribute("action", "approve_payment")
-        .add_attribute("payment_id", payment_id.to_string())
-        .add_attribute("approver", username);
-    
-    // Handle payment based on type
-    match payment.payment_type {
-        PaymentType::DirectPayment => {
-            // Direct payment funds already held in contract, send to recipient
-            let recipient = USERS_BY_USERNAME.load(deps.storage, payment.to_username.clone())?;
-            let payment_msg = CosmosMsg::Bank(BankMsg::Send {
-                to_address: recipient.wallet_address.to_string(),
-                amount: vec![payment.amount],
-            });
-            response = response.add_message(payment_msg);
-        },
-        PaymentType::PaymentRequest => {
-            // Payment request: approver (to_username) should send funds to requester (from_username)
-            // Check if sufficient funds were sent by approver
-            let sent_amount = info.funds.iter()
-                .find(|coin| coin.denom == payment.amount.denom)
-                .map(|coin| coin.amount)
-                .unwrap_or_default();
-            
-            if sent_amount < payment.amount.amount {
-                return Err(ContractError::InsufficientFunds {});
-            }
-            
-            let requester = USERS_BY_USERNAME.load(deps.storage, payment.from_username.clone())?;
-            let payment_msg = CosmosMsg::Bank(BankMsg::Send {
-                to_address: requester.wallet_address.to_string(),
-                amount: vec![payment.amount],
-            });
-            response = response.add_message(payment_msg);
-        }
+    Ok(Response::new()
+        .add_attribute("action", "register_callback")
+        .add_attribute("kind", format!("{:?}", kind))
+        .add_attribute("id", id.to_string())
+        .add_attribute("contract_addr", contract_addr))
+}
+
+// ADMIN FUNCTIONS
+
+// Helper function to ensure the sender is the contract owner
+fn ensure_owner(storage: &dyn Storage, sender: &Addr) -> Result<(), ContractError> {
+    let state = STATE.load(storage)?;
+    if *sender != state.owner {
+        return Err(ContractError::NotAuthorized {});
+    }
+    Ok(())
+}
+
+// Helper function to check whether an address holds an admin role (the owner holds them all)
+fn has_role(storage: &dyn Storage, address: &Addr, role: AdminRole) -> StdResult<bool> {
+    if *address == STATE.load(storage)?.owner {
+        return Ok(true);
+    }
+    Ok(ADMIN_ROLES.has(storage, (address.clone(), role.key().to_string())))
+}
+
+// Helper function to ensure the sender holds an admin role
+fn ensure_role(storage: &dyn Storage, sender: &Addr, role: AdminRole) -> Result<(), ContractError> {
+    if !has_role(storage, sender, role)? {
+        return Err(ContractError::NotAuthorized {});
     }
+    Ok(())
+}
+
+// Helper function to ensure the sender may resolve disputes and a reason was given
+fn validate_admin_intervention(deps: &DepsMut, sender: &Addr, reason: &str) -> Result<(), ContractError> {
+    ensure_role(deps.storage, sender, AdminRole::DisputeResolver)?;
     
-    Ok(response)
+    if reason.trim().is_empty() {
+        return Err(ContractError::AdminReasonRequired {});
+    }
+    
+    Ok(())
 }
 
-pub fn execute_reject_payment(
+pub fn execute_admin_resolve_payment(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     payment_id: u64,
+    outcome: AdminOutcome,
+    reason: String,
 ) -> Result<Response, ContractError> {
-    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    validate_admin_intervention(&deps, &info.sender, &reason)?;
     
     let payment = PAYMENTS.load(deps.storage, payment_id)
         .map_err(|_| ContractError::PaymentNotFound {})?;
     
-    // Check authorization based on payment type
-    let authorized = match payment.payment_type {
-        PaymentType::DirectPayment => payment.from_username == username,
-        PaymentType::PaymentRequest => payment.to_username == username, // PaymentRequest: receiver approves
-    };
+    // Only open payments can be resolved, including approved payments the sender has disputed
+    if !payment_open(&payment) {
+        return Err(ContractError::PaymentAlreadyCompleted {});
+    }
     
-    if !authorized {
-        return Err(ContractError::PaymentNotAuthorized {});
+    let escrowed = payment_holds_escrow(&payment);
+    
+    if matches!(outcome, AdminOutcome::Release) && !escrowed {
+        return Err(ContractError::NoEscrowToRelease {});
     }
     
-    // Update payment status
-    PAYMENTS.update(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
+    let payment = update_payment(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
         let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
-        
-        if matches!(payment.status, PaymentStatus::Completed | PaymentStatus::Cancelled) {
-            return Err(ContractError::PaymentAlreadyCompleted {});
-        }
-        
-        payment.status = PaymentStatus::Rejected;
+        payment.status = match outcome {
+            AdminOutcome::Release => PaymentStatus::Completed,
+            AdminOutcome::Refund => PaymentStatus::Cancelled,
+        };
         payment.updated_at = env.block.time.seconds();
-        
+        payment.updated_at_height = env.block.height;
         Ok(payment)
     })?;
     
-    Ok(Response::new()
-        .add_attribute("action", "reject_payment")
+    let outcome_label = match outcome {
+        AdminOutcome::Release => "release",
+        AdminOutcome::Refund => "refund",
+    };
+    
+    let mut response = Response::new()
+        .add_attribute("action", "admin_resolve_payment")
         .add_attribute("payment_id", payment_id.to_string())
-        .add_attribute("rejector", username))
+        .add_attribute("outcome", outcome_label)
+        .add_attribute("reason", reason.clone())
+        .add_event(
+            cosmwasm_std::Event::new("admin_intervention")
+                .add_attribute("admin", info.sender.as_str())
+                .add_attribute("target", "payment")
+                .add_attribute("id", payment_id.to_string())
+                .add_attribute("outcome", outcome_label)
+                .add_attribute("reason", reason)
+        );
+    
+    if escrowed {
+        release_escrow(deps.storage, &payment.from_username, &payment.amount)?;
+        let beneficiary = match outcome {
+            AdminOutcome::Release => payment.to_username.clone(),
+            AdminOutcome::Refund => payment.from_username.clone(),
+        };
+        let beneficiary = USERS_BY_USERNAME.load(deps.storage, beneficiary)?;
+        let payment_msg = CosmosMsg::Bank(BankMsg::Send {
+            to_address: beneficiary.wallet_address.to_string(),
+            amount: vec![payment.amount.clone()],
+        });
+        response = response.add_message(payment_msg);
+    }
+    
+    let status = match outcome {
+        AdminOutcome::Release => PaymentStatus::Completed,
+        AdminOutcome::Refund => PaymentStatus::Cancelled,
+    };
+    let receipt_hash = issue_receipt(deps.storage, &payment)?;
+    response = response.add_attribute("receipt_hash", receipt_hash);
+    if matches!(status, PaymentStatus::Completed) {
+        record_counterparties(deps.storage, &env, &payment.from_username, &payment.to_username)?;
+    }
+    
+    let callback = take_callback(deps.storage, RecordKind::Payment, payment_id, format!("{:?}", status), &payment.amount)?;
+    response = response.add_submessages(callback);
+    
+    Ok(response)
 }
 
-pub fn execute_cancel_payment(
+pub fn execute_admin_cancel_task(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    payment_id: u64,
+    task_id: u64,
+    reason: String,
 ) -> Result<Response, ContractError> {
-    let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    validate_admin_intervention(&deps, &info.sender, &reason)?;
     
-    let payment = PAYMENTS.load(deps.storage, payment_id)
-        .map_err(|_| ContractError::PaymentNotFound {})?;
+    let task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
     
-    // Only sender can cancel
-    if payment.from_username != username {
-        return Err(ContractError::OnlySenderCanCancel {});
+    // Released and refunded tasks are final
+    if matches!(task.status, TaskStatus::Released | TaskStatus::Refunded) {
+        return Err(ContractError::TaskAlreadyCompleted {});
     }
     
-    // Update payment status
-    PAYMENTS.update(deps.storage, payment_id, |payment| -> Result<_, ContractError> {
-        let mut payment = payment.ok_or(ContractError::PaymentNotFound {})?;
-        
-        if matches!(payment.status, PaymentStatus::Completed) {
-            return Err(ContractError::PaymentAlreadyCompleted {});
-        }
-        
-        if matches!(payment.status, PaymentStatus::Cancelled) {
-            return Err(ContractError::PaymentAlreadyCancelled {});
+    // Cancelling a disputed task settles the dispute
+    let disputed = matches!(task.status, TaskStatus::Disputed);
+    update_task(deps.storage, task_id, |task| -> Result<_, ContractError> {
+        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
+        task.status = TaskStatus::Refunded;
+        task.counter_offer = None;
+        if disputed {
+            task.dispute_outcome = Some(DisputeOutcome::AdminCancelled);
         }
-        
-        payment.status = PaymentStatus::Cancelled;
-        payment.updated_at = env.block.time.seconds();
-        
-        Ok(payment)
+        task.updated_at = env.block.time.seconds();
+        task.updated_at_height = env.block.height;
+        Ok(task)
     })?;
+    if disputed {
+        increment_dispute_count(deps.storage, &DISPUTE_OUTCOME_COUNTS, DisputeOutcome::AdminCancelled.key())?;
+        release_arbiter(deps.storage, &task)?;
+    }
     
-    let sender = USERS_BY_USERNAME.load(deps.storage, payment.from_username.clone())?;
-    
-    // Refund to sender (for HelpRequest type)
     let mut response = Response::new()
-        .add_attribute("action", "cancel_payment")
-        .add_attribute("payment_id", payment_id.to_string())
-        .add_attribute("canceller", username);
+        .add_attribute("action", "admin_cancel_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("reason", reason.clone())
+        .add_event(
+            cosmwasm_std::Event::new("admin_intervention")
+                .add_attribute("admin", info.sender.as_str())
+                .add_attribute("target", "task")
+                .add_attribute("id", task_id.to_string())
+                .add_attribute("outcome", "refund")
+                .add_attribute("reason", reason)
+        )
+        .add_event(
+            cosmwasm_std::Event::new("task_refunded")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("refund_reason", "admin_cancelled")
+        );
     
-    if matches!(payment.payment_type, PaymentType::PaymentRequest) {
-        let refund_msg = CosmosMsg::Bank(BankMsg::Send {
-            to_address: sender.wallet_address.to_string(),
-            amount: vec![payment.amount],
-        });
-        response = response.add_message(refund_msg);
+    // Only refund escrowed funds (soft tasks hold escrow only when funded upfront)
+    if task_holds_escrow(&task) {
+        release_escrow(deps.storage, &task.payer, &task.amount)?;
+        let payer = USERS_BY_USERNAME.load(deps.storage, task.payer.clone())?;
+        let (refund_msgs, refund_events) = pay_out_task_escrow(deps.storage, &deps.querier, &env, task_id, &task.amount, &payer.wallet_address)?;
+        response = response.add_messages(refund_msgs).add_events(refund_events);
     }
     
+    let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", TaskStatus::Refunded), &task.amount)?;
+    response = response.add_submessages(callback);
+    
     Ok(response)
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        // User Management
-        QueryMsg::GetUserByUsername { username } => query_user_by_username(deps, username),
-        QueryMsg::GetUserByWallet { wallet_address } => query_user_by_wallet(deps, wallet_address),
-        QueryMsg::IsUsernameAvailable { username } => query_username_available(deps, username),
-        QueryMsg::SearchUsers { query } => query_search_users(deps, query),
-        
-        // New username-specific queries
-        QueryMsg::GetUsernameByWallet { wallet_address } => query_username_by_wallet(deps, wallet_address),
-        QueryMsg::GetWalletByUsername { username } => query_wallet_by_username(deps, username),
-        QueryMsg::HasUsername { wallet_address } => query_has_username(deps, wallet_address),
-        
-        // Friends System
-        QueryMsg::GetUserFriends { username } => query_user_friends(deps, username),
-        QueryMsg::GetPendingRequests { username } => query_pending_requests(deps, username),
-        QueryMsg::AreFriends { username1, username2 } => query_are_friends(deps, username1, username2),
-        
-        // Payment System
-        QueryMsg::GetPaymentById { payment_id } => query_payment_by_id(deps, payment_id),
-        QueryMsg::GetPaymentHistory { username } => query_payment_history(deps, username),
-        QueryMsg::GetPendingPayments { username } => query_pending_payments(deps, username),
-        
-        // Task System
-        QueryMsg::GetTaskById { task_id } => query_task_by_id(deps, task_id),
-        QueryMsg::GetTaskHistory { username } => query_task_history(deps, username),
-        QueryMsg::GetPendingTasks { username } => query_pending_tasks(deps, username),
+// Ownership is handed over in two steps so a mistyped address can never lock out the admin
+pub fn execute_transfer_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+    new_owner: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_owner(deps.storage, &info.sender)?;
+    let new_owner = validate_address(deps.api, &new_owner)?;
+    
+    let mut state = STATE.load(deps.storage)?;
+    if new_owner == state.owner {
+        return Err(ContractError::AlreadyOwner {});
     }
+    state.pending_owner = Some(new_owner.clone());
+    STATE.save(deps.storage, &state)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "transfer_ownership")
+        .add_attribute("owner", state.owner)
+        .add_attribute("pending_owner", new_owner))
 }
 
-// USER MANAGEMENT QUERIES
-
-fn query_user_by_username(deps: Deps, username: String) -> StdResult<Binary> {
-    let normalized_username = normalize_username(&username);
-    let user = USERS_BY_USERNAME.load(deps.storage, normalized_username)?;
-    to_json_binary(&UserResponse { user })
-}
-
-fn query_user_by_wallet(deps: Deps, wallet_address: String) -> StdResult<Binary> {
-    let wallet_addr = deps.api.addr_validate(&wallet_address)?;
-    let username = USERS_BY_WALLET.load(deps.storage, wallet_addr)?;
-    let user = USERS_BY_USERNAME.load(deps.storage, username)?;
-    to_json_binary(&UserResponse { user })
-}
-
-fn query_username_available(deps: Deps, username: String) -> StdResult<Binary> {
-    // Validate username format first
-    if let Err(_) = validate_username(&username) {
-        // If username format is invalid, consider it not available
-        return to_json_binary(&UsernameAvailableResponse { available: false });
+pub fn execute_accept_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let mut state = STATE.load(deps.storage)?;
+    match &state.pending_owner {
+        None => return Err(ContractError::NoPendingOwnershipTransfer {}),
+        Some(pending_owner) if *pending_owner != info.sender => {
+            return Err(ContractError::NotAuthorized {});
+        }
+        Some(_) => {}
     }
     
-    let normalized_username = normalize_username(&username);
-    let available = USERS_BY_USERNAME.may_load(deps.storage, normalized_username)?.is_none();
-    to_json_binary(&UsernameAvailableResponse { available })
-}
-
-// New username-specific query functions
-fn query_username_by_wallet(deps: Deps, wallet_address: String) -> StdResult<Binary> {
-    let wallet_addr = deps.api.addr_validate(&wallet_address)?;
-    let username = USERS_BY_WALLET.load(deps.storage, wallet_addr)?;
-    to_json_binary(&UsernameResponse { username })
+    let previous_owner = state.owner;
+    state.owner = info.sender.clone();
+    state.pending_owner = None;
+    STATE.save(deps.storage, &state)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "accept_ownership")
+        .add_attribute("previous_owner", previous_owner)
+        .add_attribute("owner", info.sender))
 }
 
-fn query_wallet_by_username(deps: Deps, username: String) -> StdResult<Binary> {
-    let normalized_username = normalize_username(&username);
-    let user = USERS_BY_USERNAME.load(deps.storage, normalized_username)?;
-    to_json_binary(&WalletResponse { wallet_address: user.wallet_address.to_string() })
+pub fn execute_cancel_ownership_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_owner(deps.storage, &info.sender)?;
+    
+    let mut state = STATE.load(deps.storage)?;
+    if state.pending_owner.take().is_none() {
+        return Err(ContractError::NoPendingOwnershipTransfer {});
+    }
+    STATE.save(deps.storage, &state)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "cancel_ownership_transfer"))
 }
 
-fn query_has_username(deps: Deps, wallet_address: String) -> StdResult<Binary> {
-    let wallet_addr = deps.api.addr_validate(&wallet_address)?;
-    let has_username = USERS_BY_WALLET.may_load(deps.storage, wallet_addr)?.is_some();
-    to_json_binary(&HasUsernameResponse { has_username })
+pub fn execute_grant_role(
+    deps: DepsMut,
+    info: MessageInfo,
+    role: AdminRole,
+    address: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_owner(deps.storage, &info.sender)?;
+    let address = validate_address(deps.api, &address)?;
+    
+    let key = (address.clone(), role.key().to_string());
+    if ADMIN_ROLES.has(deps.storage, key.clone()) {
+        return Err(ContractError::RoleAlreadyGranted {});
+    }
+    ADMIN_ROLES.save(deps.storage, key, &role)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "grant_role")
+        .add_attribute("role", role.key())
+        .add_attribute("address", address))
 }
 
-fn query_search_users(deps: Deps, query: String) -> StdResult<Binary> {
-    let query_lower = query.to_lowercase();
-    let users: StdResult<Vec<User>> = USERS_BY_USERNAME
-        .range(deps.storage, None, None, Order::Ascending)
-        .map(|item| item.map(|(_, user)| user))
-        .filter(|user| {
-            user.as_ref()
-                .map(|u| {
-                    u.username.to_lowercase().contains(&query_lower) ||
-                    u.display_name.to_lowercase().contains(&query_lower)
-                })
-                .unwrap_or(false)
-        })
-        .collect();
-    to_json_binary(&UsersResponse { users: users? })
+pub fn execute_revoke_role(
+    deps: DepsMut,
+    info: MessageInfo,
+    role: AdminRole,
+    address: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_owner(deps.storage, &info.sender)?;
+    let address = validate_address(deps.api, &address)?;
+    
+    let key = (address.clone(), role.key().to_string());
+    if !ADMIN_ROLES.has(deps.storage, key.clone()) {
+        return Err(ContractError::RoleNotGranted {});
+    }
+    ADMIN_ROLES.remove(deps.storage, key);
+    
+    Ok(Response::new()
+        .add_attribute("action", "revoke_role")
+        .add_attribute("role", role.key())
+        .add_attribute("address", address))
 }
 
-// FRIENDS SYSTEM QUERIES
-
-fn query_user_friends(deps: Deps, username: String) -> StdResult<Binary> {
-    let friends: StdResult<Vec<String>> = FRIENDSHIPS
-        .prefix(username)
-        .range(deps.storage, None, None, Order::Ascending)
-        .map(|item| item.map(|(friend_username, _)| friend_username))
-        .collect();
-    to_json_binary(&FriendsResponse { friends: friends? })
+pub fn execute_set_user_badge(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    username: String,
+    badge: Option<Badge>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::Attestor)?;
+    
+    let username = normalize_username(&username);
+    let mut user = USERS_BY_USERNAME.load(deps.storage, username.clone())
+        .map_err(|_| ContractError::UserNotFound {})?;
+    user.badge = badge;
+    user.updated_at = env.block.time.seconds();
+    USERS_BY_USERNAME.save(deps.storage, username.clone(), &user)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "set_user_badge")
+        .add_attribute("username", username)
+        .add_attribute("badge", badge.map_or("none".to_string(), |badge| format!("{:?}", badge)))
+        .add_attribute("attestor", info.sender))
 }
 
-fn query_pending_requests(deps: Deps, username: String) -> StdResult<Binary> {
-    let mut requests = Vec::new();
+#[allow(clippy::too_many_arguments)]
+pub fn execute_update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    co_approval_thresholds: Option<Vec<Coin>>,
+    review_window: Option<ReviewWindowBounds>,
+    archive_retention_secs: Option<u64>,
+    accepted_denoms: Option<AcceptedDenoms>,
+    instant_release: Option<InstantReleaseConfig>,
+    friend_requests: Option<FriendRequestPolicy>,
+    text_limits: Option<TextLimits>,
+    open_item_limits: Option<OpenItemLimits>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
     
-    // Get requests sent TO this user
-    for item in FRIEND_REQUESTS.range(deps.storage, None, None, Order::Ascending) {
-        let ((_from, to), request) = item?;
-        if to == username && matches!(request.status, FriendRequestStatus::Pending) {
-            requests.push(request);
+    let mut config = CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    let mut response = Response::new().add_attribute("action", "update_config");
+    
+    if let Some(thresholds) = co_approval_thresholds {
+        let summary = if thresholds.is_empty() {
+            "none".to_string()
+        } else {
+            thresholds.iter().map(|threshold| threshold.to_string()).collect::<Vec<_>>().join(",")
+        };
+        response = response.add_attribute("co_approval_thresholds", summary);
+        config.co_approval_thresholds = thresholds;
+    }
+    
+    if let Some(bounds) = review_window {
+        let min = bounds.min_secs.unwrap_or(0);
+        let max = bounds.max_secs.unwrap_or(u64::MAX);
+        if min > max || bounds.default_secs.is_some_and(|default| default < min || default > max) {
+            return Err(ContractError::InvalidReviewWindow {});
+        }
+        let describe = |secs: Option<u64>| secs.map_or("none".to_string(), |secs| secs.to_string());
+        response = response.add_attribute(
+            "review_window",
+            format!(
+                "default={},min={},max={}",
+                describe(bounds.default_secs),
+                describe(bounds.min_secs),
+                describe(bounds.max_secs)
+            ),
+        );
+        config.review_window = bounds;
+    }
+    
+    if let Some(retention) = archive_retention_secs {
+        response = response.add_attribute("archive_retention_secs", retention.to_string());
+        config.archive_retention_secs = Some(retention);
+    }
+    
+    if let Some(accepted) = accepted_denoms {
+        let summary = match &accepted {
+            AcceptedDenoms::AllowAll => "all".to_string(),
+            AcceptedDenoms::Only(denoms) if denoms.is_empty() => "none".to_string(),
+            AcceptedDenoms::Only(denoms) => denoms.join(","),
+        };
+        response = response.add_attribute("accepted_denoms", summary);
+        config.accepted_denoms = accepted;
+    }
+    
+    if let Some(instant) = instant_release {
+        let summary = if instant.max_amounts.is_empty() {
+            "disabled".to_string()
+        } else {
+            let amounts = instant.max_amounts.iter().map(|amount| amount.to_string()).collect::<Vec<_>>().join(",");
+            format!("min_reputation={},max_amounts={}", instant.min_reputation, amounts)
+        };
+        response = response.add_attribute("instant_release", summary);
+        config.instant_release = instant;
+    }
+    
+    if let Some(policy) = friend_requests {
+        response = response.add_attribute(
+            "friend_requests",
+            format!("cooldown_secs={},max_declines={}", policy.cooldown_secs, policy.max_declines),
+        );
+        config.friend_requests = policy;
+    }
+    
+    if let Some(limits) = text_limits {
+        if limits.description == 0 || limits.evidence == 0 || limits.endpoint == 0 {
+            return Err(ContractError::InvalidTextLimits {});
+        }
+        response = response.add_attribute(
+            "text_limits",
+            format!("description={},evidence={},endpoint={}", limits.description, limits.evidence, limits.endpoint),
+        );
+        config.text_limits = limits;
+    }
+    
+    if let Some(limits) = open_item_limits {
+        if limits.friend_requests == 0 || limits.payment_requests == 0 || limits.tasks == 0 {
+            return Err(ContractError::InvalidOpenItemLimits {});
         }
+        response = response.add_attribute(
+            "open_item_limits",
+            format!("friend_requests={},payment_requests={},tasks={}", limits.friend_requests, limits.payment_requests, limits.tasks),
+        );
+        config.open_item_limits = limits;
     }
     
-    to_json_binary(&FriendRequestsResponse { requests })
+    CONFIG.save(deps.storage, &config)?;
+    Ok(response)
 }
 
-fn query_are_friends(deps: Deps, username1: String, username2: String) -> StdResult<Binary> {
-    let are_friends = FRIENDSHIPS
-        .may_load(deps.storage, (username1, username2))?
-        .is_some();
-    to_json_binary(&AreFriendsResponse { are_friends })
+// Yield routing stays off until a config manager sets a config; clearing it stops new deposits
+pub fn execute_set_yield_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    config: Option<YieldConfigMsg>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
+    
+    let config = match config {
+        Some(config) => config,
+        None => {
+            YIELD_CONFIG.remove(deps.storage);
+            return Ok(Response::new()
+                .add_attribute("action", "set_yield_config")
+                .add_attribute("enabled", "false"));
+        }
+    };
+    if config.user_share_bps > 10_000 {
+        return Err(ContractError::InvalidYieldShare {});
+    }
+    
+    let config = YieldConfig {
+        adapter: validate_address(deps.api, &config.adapter)?,
+        denom: config.denom,
+        threshold: config.threshold,
+        treasury: validate_address(deps.api, &config.treasury)?,
+        user_share_bps: config.user_share_bps,
+    };
+    YIELD_CONFIG.save(deps.storage, &config)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "set_yield_config")
+        .add_attribute("enabled", "true")
+        .add_attribute("adapter", config.adapter)
+        .add_attribute("denom", config.denom)
+        .add_attribute("threshold", config.threshold)
+        .add_attribute("user_share_bps", config.user_share_bps.to_string()))
 }
 
-// PAYMENT SYSTEM QUERIES
+// INSURANCE POOL FUNCTIONS
 
-fn query_payment_by_id(deps: Deps, payment_id: u64) -> StdResult<Binary> {
-    let payment = PAYMENTS.load(deps.storage, payment_id)?;
-    to_json_binary(&PaymentResponse { payment })
+pub fn execute_set_insurance_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    config: Option<InsuranceConfig>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
+    
+    let config = match config {
+        Some(config) => config,
+        None => {
+            INSURANCE_CONFIG.remove(deps.storage);
+            return Ok(Response::new()
+                .add_attribute("action", "set_insurance_config")
+                .add_attribute("enabled", "false"));
+        }
+    };
+    if config.premium_bps > 10_000 || config.max_payout_bps > 10_000 {
+        return Err(ContractError::InvalidInsuranceConfig {});
+    }
+    INSURANCE_CONFIG.save(deps.storage, &config)?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "set_insurance_config")
+        .add_attribute("enabled", "true")
+        .add_attribute("premium_bps", config.premium_bps.to_string())
+        .add_attribute("max_payout_bps", config.max_payout_bps.to_string()))
 }
 
-fn query_payment_history(deps: Deps, username: String) -> StdResult<Binary> {
-    let mut payments = Vec::new();
-    
-    // Get all payments for this user
-    for item in USER_PAYMENTS.prefix(username).range(deps.storage, None, None, Order::Ascending) {
-        let (payment_id, _) = item?;
-        if let Ok(payment) = PAYMENTS.load(deps.storage, payment_id) {
-            payments.push(payment);
-        }
+pub fn execute_fund_insurance_pool(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let funds: Vec<Coin> = info.funds.into_iter().filter(|coin| !coin.amount.is_zero()).collect();
+    if funds.is_empty() {
+        return Err(ContractError::InvalidPaymentAmount {});
+    }
+    for coin in &funds {
+        ensure_denom_accepted(deps.storage, &coin.denom)?;
+        update_insurance_total(deps.storage, &INSURANCE_POOL, coin, true)?;
+        add_escrow_total(deps.storage, coin)?;
     }
     
-    to_json_binary(&PaymentsResponse { payments })
+    let funded: Vec<String> = funds.iter().map(|coin| coin.to_string()).collect();
+    Ok(Response::new()
+        .add_attribute("action", "fund_insurance_pool")
+        .add_attribute("funder", info.sender)
+        .add_attribute("amount", funded.join(",")))
 }
 
-fn query_pending_payments(deps: Deps, username: String) -> StdResult<Binary> {
-    let mut payments = Vec::new();
+pub fn execute_insure_task(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    task_id: u64,
+) -> Result<Response, ContractError> {
+    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let config = INSURANCE_CONFIG.may_load(deps.storage)?
+        .ok_or(ContractError::InsuranceDisabled {})?;
     
-    // Get all payments for this user that are pending
-    for item in USER_PAYMENTS.prefix(username).range(deps.storage, None, None, Order::Ascending) {
-        let (payment_id, _) = item?;
-        if let Ok(payment) = PAYMENTS.load(deps.storage, payment_id) {
-            if matches!(payment.status, PaymentStatus::Pending | PaymentStatus::ProofSubmitted) {
-                payments.push(payment);
-            }
-        }
+    let mut task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    if task.payer != username && task.worker != username {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
+    if task.insurance.is_some() {
+        return Err(ContractError::TaskAlreadyInsured {});
     }
+    // Cover is bought on escrowed tasks before any work has been submitted
+    if !task_holds_escrow(&task) || !task_not_started(&task) {
+        return Err(ContractError::TaskNotInsurable {});
+    }
+    
+    let premium = Coin {
+        denom: task.amount.denom.clone(),
+        amount: task.amount.amount.multiply_ratio(config.premium_bps, 10_000u128),
+    };
+    let refund = if premium.amount.is_zero() {
+        ensure_no_funds(&info)?;
+        None
+    } else {
+        let refund = validate_funds(deps.storage, &info, &premium)?;
+        update_insurance_total(deps.storage, &INSURANCE_POOL, &premium, true)?;
+        add_escrow_total(deps.storage, &premium)?;
+        refund
+    };
+    
+    let max_payout = Coin {
+        denom: task.amount.denom.clone(),
+        amount: task.amount.amount.multiply_ratio(config.max_payout_bps, 10_000u128),
+    };
+    task.insurance = Some(TaskInsurance {
+        premium: premium.clone(),
+        max_payout: max_payout.clone(),
+        claim_open: false,
+        payout: None,
+    });
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
     
-    to_json_binary(&PaymentsResponse { payments })
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_attribute("action", "insure_task")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("insured_by", username)
+        .add_attribute("premium", premium.to_string())
+        .add_attribute("max_payout", max_payout.to_string()))
 }
 
-// TASK SYSTEM FUNCTIONS
-
-use crate::state::{Task, TaskStatus, TASKS, USER_TASKS};
-use crate::helpers::verify_zktls;
-
-pub fn execute_create_task(
+pub fn execute_pay_insurance_claim(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    to_username: String,
-    amount: cosmwasm_std::Coin,
-    description: String,
-    proof_type: ProofType,
-    deadline_ts: u64,
-    review_window_secs: Option<u64>,
-    endpoint: String,
+    task_id: u64,
+    amount: Uint128,
 ) -> Result<Response, ContractError> {
-    let from_username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::DisputeResolver)?;
     
-    // Validate task creation
-    if from_username == to_username {
-        return Err(ContractError::CannotCreateTaskWithSelf {});
+    let mut task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    let insurance = task.insurance.as_mut()
+        .filter(|insurance| insurance.claim_open)
+        .ok_or(ContractError::NoInsuranceClaim {})?;
+    if amount > insurance.max_payout.amount {
+        return Err(ContractError::InvalidInsurancePayout {});
     }
-    
-    // Check if worker exists
-    if USERS_BY_USERNAME.may_load(deps.storage, to_username.clone())?.is_none() {
-        return Err(ContractError::UserNotFound {});
+    let payout = Coin {
+        denom: insurance.max_payout.denom.clone(),
+        amount,
+    };
+    let pool = INSURANCE_POOL.may_load(deps.storage, payout.denom.clone())?.unwrap_or_default();
+    if pool < amount {
+        return Err(ContractError::InsufficientInsurancePool {});
     }
     
-    // Validate deadline
-    if deadline_ts <= env.block.time.seconds() {
-        return Err(ContractError::InvalidTaskDeadline {});
+    update_insurance_total(deps.storage, &INSURANCE_OPEN_CLAIMS, &insurance.max_payout, false)?;
+    insurance.claim_open = false;
+    let mut response = Response::new()
+        .add_attribute("action", "pay_insurance_claim")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("amount", payout.to_string());
+    // The arbiter may also close a claim without compensation
+    if !amount.is_zero() {
+        update_insurance_total(deps.storage, &INSURANCE_POOL, &payout, false)?;
+        release_escrow_total(deps.storage, &payout)?;
+        let worker = USERS_BY_USERNAME.load(deps.storage, task.worker.clone())?;
+        response = response.add_message(BankMsg::Send {
+            to_address: worker.wallet_address.to_string(),
+            amount: vec![payout.clone()],
+        });
+        insurance.payout = Some(payout);
     }
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
     
-    // Validate payment amount
+    Ok(response)
+}
+
+// Helper function to add to or subtract from a per-denom insurance total
+fn update_insurance_total(
+    storage: &mut dyn Storage,
+    totals: &Map<String, Uint128>,
+    amount: &Coin,
+    add: bool,
+) -> StdResult<()> {
     if amount.amount.is_zero() {
-        return Err(ContractError::InvalidPaymentAmount {});
+        return Ok(());
     }
-    
-    // For non-soft tasks, require escrow funds
-    if !matches!(proof_type, ProofType::Soft) {
-        let sent_amount = info.funds.iter()
-            .find(|coin| coin.denom == amount.denom)
-            .map(|coin| coin.amount)
-            .unwrap_or_default();
-        
-        if sent_amount < amount.amount {
-            return Err(ContractError::InsufficientFunds {});
+    let total = totals.may_load(storage, amount.denom.clone())?.unwrap_or_default();
+    let total = if add { total + amount.amount } else { total.checked_sub(amount.amount)? };
+    if total.is_zero() {
+        totals.remove(storage, amount.denom.clone());
+    } else {
+        totals.save(storage, amount.denom.clone(), &total)?;
+    }
+    Ok(())
+}
+
+// ARBITER FUNCTIONS
+
+// Upper bound on bonded arbiters, which are all scanned when a dispute is assigned
+const MAX_ARBITERS: usize = 50;
+
+pub fn execute_set_arbiter_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    config: Option<ArbiterConfig>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
+    
+    let config = match config {
+        Some(config) => config,
+        None => {
+            ARBITER_CONFIG.remove(deps.storage);
+            return Ok(Response::new()
+                .add_attribute("action", "set_arbiter_config")
+                .add_attribute("enabled", "false"));
         }
+    };
+    if config.bond_denom.is_empty() || config.min_bond.is_zero() {
+        return Err(ContractError::InvalidArbiterConfig {});
     }
+    ensure_denom_accepted(deps.storage, &config.bond_denom)?;
+    ARBITER_CONFIG.save(deps.storage, &config)?;
     
-    let mut state = STATE.load(deps.storage)?;
-    let task_id = state.next_task_id;
-    state.next_task_id += 1;
-    STATE.save(deps.storage, &state)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_arbiter_config")
+        .add_attribute("enabled", "true")
+        .add_attribute("bond_denom", config.bond_denom)
+        .add_attribute("min_bond", config.min_bond.to_string()))
+}
+
+pub fn execute_bond_arbiter(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, AdminRole::DisputeResolver)?;
+    let config = ARBITER_CONFIG.may_load(deps.storage)?
+        .ok_or(ContractError::ArbitersDisabled {})?;
     
-    let task = Task {
-        id: task_id,
-        payer: from_username.clone(),
-        worker: to_username.clone(),
-        amount,
-        proof_type: proof_type.clone(),
-        status: if matches!(proof_type, ProofType::Soft) {
-            TaskStatus::ProofSubmitted // Soft tasks don't escrow, so they start ready for approval
-        } else {
-            TaskStatus::Escrowed
+    if info.funds.iter().any(|coin| coin.denom != config.bond_denom && !coin.amount.is_zero()) {
+        return Err(ContractError::UnexpectedFunds {});
+    }
+    let amount: Uint128 = info.funds.iter().map(|coin| coin.amount).sum();
+    if amount.is_zero() {
+        return Err(ContractError::InvalidPaymentAmount {});
+    }
+    
+    let mut arbiter = match ARBITERS.may_load(deps.storage, info.sender.clone())? {
+        Some(arbiter) => arbiter,
+        None if ARBITERS.keys(deps.storage, None, None, Order::Ascending).count() >= MAX_ARBITERS => {
+            return Err(ContractError::TooManyArbiters {});
+        }
+        None => Arbiter {
+            bond: Coin { denom: config.bond_denom.clone(), amount: Uint128::zero() },
+            open_disputes: 0,
         },
-        deadline_ts,
-        review_window_secs,
-        endpoint,
-        evidence_hash: None,
-        zk_proof_hash: None,
-        verified_at: None,
-        verifier_id: None,
-        description,
-        created_at: env.block.time.seconds(),
-        updated_at: env.block.time.seconds(),
     };
+    // A bond left over from an earlier bond denom has to be withdrawn first
+    if arbiter.bond.denom != config.bond_denom {
+        return Err(ContractError::UnexpectedFunds {});
+    }
+    arbiter.bond.amount = arbiter.bond.amount.checked_add(amount).map_err(StdError::from)?;
+    ARBITERS.save(deps.storage, info.sender.clone(), &arbiter)?;
+    add_escrow_total(deps.storage, &Coin { denom: config.bond_denom, amount })?;
+    
+    Ok(Response::new()
+        .add_attribute("action", "bond_arbiter")
+        .add_attribute("arbiter", info.sender)
+        .add_attribute("bond", arbiter.bond.to_string()))
+}
+
+pub fn execute_unbond_arbiter(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let arbiter = ARBITERS.may_load(deps.storage, info.sender.clone())?
+        .ok_or(ContractError::ArbiterNotBonded {})?;
+    if arbiter.open_disputes > 0 {
+        return Err(ContractError::ArbiterHasOpenDisputes {});
+    }
     
-    TASKS.save(deps.storage, task_id, &task)?;
-    USER_TASKS.save(deps.storage, (from_username.clone(), task_id), &true)?;
-    USER_TASKS.save(deps.storage, (to_username.clone(), task_id), &true)?;
+    ARBITERS.remove(deps.storage, info.sender.clone());
+    release_escrow_total(deps.storage, &arbiter.bond)?;
     
     Ok(Response::new()
-        .add_attribute("action", "create_task")
-        .add_attribute("task_id", task_id.to_string())
-        .add_attribute("payer", from_username)
-        .add_attribute("worker", to_username)
-        .add_attribute("amount", task.amount.to_string())
-        .add_event(
-            cosmwasm_std::Event::new("task_created")
-                .add_attribute("task_id", task_id.to_string())
-                .add_attribute("payer", task.payer.clone())
-                .add_attribute("worker", task.worker.clone())
-                .add_attribute("proof_type", format!("{:?}", task.proof_type))
-                .add_attribute("deadline", task.deadline_ts.to_string())
-        ))
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![arbiter.bond.clone()],
+        })
+        .add_attribute("action", "unbond_arbiter")
+        .add_attribute("arbiter", info.sender)
+        .add_attribute("bond", arbiter.bond.to_string()))
 }
 
-pub fn execute_submit_soft_evidence(
+// Helper function to load a task the calling agreed arbiter can still accept or decline
+fn load_unaccepted_arbitration(deps: &DepsMut, info: &MessageInfo, task_id: u64) -> Result<Task, ContractError> {
+    ensure_no_funds(info)?;
+    let task = TASKS.load(deps.storage, task_id)
+        .map_err(|_| ContractError::TaskNotFound {})?;
+    if task.agreed_arbiter.as_ref() != Some(&info.sender) {
+        return Err(ContractError::NotTaskArbiter {});
+    }
+    if task.arbiter_accepted_at.is_some() || !matches!(task.status, TaskStatus::Escrowed) {
+        return Err(ContractError::TaskNotAuthorized {});
+    }
+    Ok(task)
+}
+
+pub fn execute_accept_arbitration(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     task_id: u64,
-    evidence_hash: String,
 ) -> Result<Response, ContractError> {
-    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut task = load_unaccepted_arbitration(&deps, &info, task_id)?;
     
-    TASKS.update(deps.storage, task_id, |task| -> Result<_, ContractError> {
-        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
-        
-        // Check authorization - only worker can submit evidence
-        if task.worker != username {
-            return Err(ContractError::TaskNotAuthorized {});
-        }
-        
-        // Check task type
-        if !matches!(task.proof_type, ProofType::Soft) {
-            return Err(ContractError::InvalidProofType {});
-        }
-        
-        // Check task status
-        if !matches!(task.status, TaskStatus::ProofSubmitted) {
-            return Err(ContractError::TaskAlreadyCompleted {});
-        }
-        
-        // Check deadline
-        if env.block.time.seconds() > task.deadline_ts {
-            return Err(ContractError::TaskExpired {});
-        }
-        
-        task.evidence_hash = Some(evidence_hash.clone());
-        task.updated_at = env.block.time.seconds();
-        
-        Ok(task)
-    })?;
+    task.arbiter_accepted_at = Some(env.block.time.seconds());
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
     
     Ok(Response::new()
-        .add_attribute("action", "submit_soft_evidence")
+        .add_attribute("action", "accept_arbitration")
         .add_attribute("task_id", task_id.to_string())
-        .add_attribute("submitter", username)
-        .add_event(
-            cosmwasm_std::Event::new("proof_submitted")
-                .add_attribute("task_id", task_id.to_string())
-                .add_attribute("proof_type", "soft")
-                .add_attribute("evidence_hash", evidence_hash)
-        ))
+        .add_attribute("arbiter", info.sender))
 }
 
-pub fn execute_submit_zktls_proof(
+pub fn execute_decline_arbitration(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     task_id: u64,
-    proof_blob_or_ref: String,
-    zk_proof_hash: String,
 ) -> Result<Response, ContractError> {
-    let username = get_username_from_wallet(&deps, &info.sender)?;
+    let mut task = load_unaccepted_arbitration(&deps, &info, task_id)?;
     
-    let task = TASKS.load(deps.storage, task_id)
-        .map_err(|_| ContractError::TaskNotFound {})?;
+    // The escrow only locks once the arbiter accepts, so a declined task goes straight back to the payer
+    task.status = TaskStatus::Refunded;
+    task.cancel_requested_by = None;
+    task.counter_offer = None;
+    task.updated_at = env.block.time.seconds();
+    task.updated_at_height = env.block.height;
+    save_task(deps.storage, task_id, &task)?;
     
-    // Check authorization - only worker can submit proof
-    if task.worker != username {
-        return Err(ContractError::TaskNotAuthorized {});
-    }
+    release_escrow(deps.storage, &task.payer, &task.amount)?;
+    let payer = USERS_BY_USERNAME.load(deps.storage, task.payer.clone())?;
+    let (refund_msgs, refund_events) = pay_out_task_escrow(deps.storage, &deps.querier, &env, task_id, &task.amount, &payer.wallet_address)?;
+    let callback = take_callback(deps.storage, RecordKind::Task, task_id, format!("{:?}", TaskStatus::Refunded), &task.amount)?;
     
-    // Check task type
-    if !matches!(task.proof_type, ProofType::ZkTLS | ProofType::Hybrid) {
-        return Err(ContractError::InvalidProofType {});
-    }
+    Ok(Response::new()
+        .add_messages(refund_msgs)
+        .add_events(refund_events)
+        .add_submessages(callback)
+        .add_attribute("action", "decline_arbitration")
+        .add_attribute("task_id", task_id.to_string())
+        .add_attribute("arbiter", info.sender)
+        .add_event(
+            cosmwasm_std::Event::new("task_refunded")
+                .add_attribute("task_id", task_id.to_string())
+                .add_attribute("refund_reason", "arbiter_declined")
+        ))
+}
+
+// Helper function to check whether a bonded arbiter can be assigned new disputes
+fn arbiter_eligible(storage: &dyn Storage, config: &ArbiterConfig, address: &Addr, arbiter: &Arbiter) -> StdResult<bool> {
+    Ok(arbiter.bond.denom == config.bond_denom
+        && arbiter.bond.amount >= config.min_bond
+        && has_role(storage, address, AdminRole::DisputeResolver)?)
+}
+
+// Helper function to pick the arbiter for a newly disputed task, weighted by bond.
+// The seed mixes the block with the task id so neither party can choose the arbiter; CosmWasm
+// does not expose the block hash, so chain id, height and time stand in for it.
+fn select_arbiter(storage: &dyn Storage, env: &Env, task: &Task) -> StdResult<Option<Addr>> {
+    let config = match ARBITER_CONFIG.may_load(storage)? {
+        Some(config) => config,
+        None => return Ok(None),
+    };
     
-    // Check task status
-    if !matches!(task.status, TaskStatus::Escrowed) {
-        return Err(ContractError::TaskAlreadyCompleted {});
+    // The payer and worker cannot arbitrate their own task
+    let parties = [&task.payer, &task.worker]
+        .into_iter()
+        .map(|username| Ok(USERS_BY_USERNAME.load(storage, username.clone())?.wallet_address))
+        .collect::<StdResult<Vec<Addr>>>()?;
+    let mut candidates = vec![];
+    let mut total_weight = Uint128::zero();
+    for entry in ARBITERS.range(storage, None, None, Order::Ascending) {
+        let (address, arbiter) = entry?;
+        if !parties.contains(&address) && arbiter_eligible(storage, &config, &address, &arbiter)? {
+            total_weight += arbiter.bond.amount;
+            candidates.push((address, total_weight));
+        }
     }
-    
-    // Check deadline
-    if env.block.time.seconds() > task.deadline_ts {
-        return Err(ContractError::TaskExpired {});
+    if candidates.is_empty() {
+        return Ok(None);
     }
     
-    // Verify zkTLS proof
-    let verification_result = verify_zktls(&proof_blob_or_ref, &task.endpoint)?;
-    if !verification_result {
-        return Err(ContractError::ZkTlsVerificationFailed {});
+    let seed = hash_data(&format!("{}:{}:{}:{}", env.block.chain_id, env.block.height, env.block.time.nanos(), task.id));
+    let seed = u128::from_str_radix(&seed[..32], 16).map_err(|err| StdError::generic_err(err.to_string()))?;
+    let pick = Uint128::new(seed % total_weight.u128());
+    Ok(candidates.into_iter()
+        .find(|(_, cumulative_weight)| pick < *cumulative_weight)
+        .map(|(address, _)| address))
+}
+
+// Helper function to free the assigned arbiter once a task's dispute is settled
+fn release_arbiter(storage: &mut dyn Storage, task: &Task) -> StdResult<()> {
+    let address = match &task.arbiter {
+        Some(address) => address.clone(),
+        None => return Ok(()),
+    };
+    if let Some(mut arbiter) = ARBITERS.may_load(storage, address.clone())? {
+        arbiter.open_disputes = arbiter.open_disputes.saturating_sub(1);
+        ARBITERS.save(storage, address, &arbiter)?;
     }
+    Ok(())
+}
+
+// RELAYED EXECUTION FUNCTIONS
+
+pub fn execute_set_relayer(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    authorized: bool,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
     
-    // Update task based on proof type
-    TASKS.update(deps.storage, task_id, |task| -> Result<_, ContractError> {
-        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
-        
-        task.zk_proof_hash = Some(zk_proof_hash.clone());
-        task.verified_at = Some(env.block.time.seconds());
-        task.updated_at = env.block.time.seconds();
-        
-        match task.proof_type {
-            ProofType::ZkTLS => {
-                // Instant release for zkTLS mode
-                task.status = TaskStatus::Released;
-            },
-            ProofType::Hybrid => {
-                // Move to pending release for hybrid mode
-                task.status = TaskStatus::PendingRelease;
-            },
-            _ => return Err(ContractError::InvalidProofType {}),
-        }
-        
-        Ok(task)
-    })?;
-    
-    let updated_task = TASKS.load(deps.storage, task_id)?;
-    let mut response = Response::new()
-        .add_attribute("action", "submit_zktls_proof")
-        .add_attribute("task_id", task_id.to_string())
-        .add_attribute("submitter", username)
-        .add_event(
-            cosmwasm_std::Event::new("proof_submitted")
-                .add_attribute("task_id", task_id.to_string())
-                .add_attribute("proof_type", format!("{:?}", updated_task.proof_type))
-                .add_attribute("zk_proof_hash", zk_proof_hash)
-        );
-    
-    // For zkTLS mode, immediately release payment
-    if matches!(updated_task.proof_type, ProofType::ZkTLS) {
-        let worker = USERS_BY_USERNAME.load(deps.storage, updated_task.worker.clone())?;
-        let payment_msg = CosmosMsg::Bank(BankMsg::Send {
-            to_address: worker.wallet_address.to_string(),
-            amount: vec![updated_task.amount],
-        });
-        response = response.add_message(payment_msg)
-            .add_event(
-                cosmwasm_std::Event::new("task_released")
-                    .add_attribute("task_id", task_id.to_string())
-                    .add_attribute("release_type", "instant")
-            );
+    let relayer = validate_address(deps.api, &address)?;
+    if authorized {
+        RELAYERS.save(deps.storage, relayer.clone(), &true)?;
     } else {
-        // For hybrid mode, emit pending release event
-        response = response.add_event(
-            cosmwasm_std::Event::new("task_pending_release")
-                .add_attribute("task_id", task_id.to_string())
-                .add_attribute("review_window", updated_task.review_window_secs.unwrap_or(0).to_string())
-        );
+        RELAYERS.remove(deps.storage, relayer.clone());
     }
     
-    Ok(response)
+    Ok(Response::new()
+        .add_attribute("action", "set_relayer")
+        .add_attribute("relayer", relayer)
+        .add_attribute("authorized", authorized.to_string()))
 }
 
-pub fn execute_approve_task(
+pub fn execute_signed(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    task_id: u64,
+    payload: Binary,
+    signature: Binary,
 ) -> Result<Response, ContractError> {
-    let username = get_username_from_wallet(&deps, &info.sender)?;
-    
-    let task = TASKS.load(deps.storage, task_id)
-        .map_err(|_| ContractError::TaskNotFound {})?;
-    
-    // Only payer can approve tasks
-    if task.payer != username {
-        return Err(ContractError::OnlyPayerCanApproveSoft {});
+    if !RELAYERS.has(deps.storage, info.sender.clone()) {
+        return Err(ContractError::RelayerNotAuthorized {});
     }
+    ensure_no_funds(&info)?;
     
-    // Check if task is in correct state for approval
-    if !matches!(task.status, TaskStatus::ProofSubmitted) {
-        return Err(ContractError::TaskAlreadyCompleted {});
+    // Bind the payload to this chain and contract so it cannot be replayed elsewhere
+    let signed: SignedPayload = from_json(&payload)
+        .map_err(|_| ContractError::InvalidSignedPayload {})?;
+    if signed.chain_id != env.block.chain_id || signed.contract != env.contract.address.as_str() {
+        return Err(ContractError::InvalidSignedPayload {});
     }
     
-    // Only soft tasks can be manually approved
-    if !matches!(task.proof_type, ProofType::Soft) {
-        return Err(ContractError::InvalidProofType {});
+    let user = USERS_BY_USERNAME.load(deps.storage, signed.signer.clone())
+        .map_err(|_| ContractError::UserNotFound {})?;
+    let pubkey = user.pubkey.ok_or(ContractError::SigningKeyNotSet {})?;
+    let nonce = SIGNER_NONCES.may_load(deps.storage, signed.signer.clone())?.unwrap_or_default();
+    if signed.nonce != nonce {
+        return Err(ContractError::InvalidNonce {});
     }
     
-    // Update task status
-    TASKS.update(deps.storage, task_id, |task| -> Result<_, ContractError> {
-        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
-        task.status = TaskStatus::Released;
-        task.updated_at = env.block.time.seconds();
-        Ok(task)
-    })?;
-    
-    // For soft tasks, payer sends funds when approving
-    let sent_amount = info.funds.iter()
-        .find(|coin| coin.denom == task.amount.denom)
-        .map(|coin| coin.amount)
-        .unwrap_or_default();
-    
-    if sent_amount < task.amount.amount {
-        return Err(ContractError::InsufficientFunds {});
+    let hash = adr36_sign_hash(user.wallet_address.as_str(), &payload);
+    let verified = deps.api.secp256k1_verify(&hash, &signature, &pubkey)
+        .map_err(|_| ContractError::InvalidSignature {})?;
+    if !verified {
+        return Err(ContractError::InvalidSignature {});
     }
+    SIGNER_NONCES.save(deps.storage, signed.signer.clone(), &(nonce + 1))?;
     
-    let worker = USERS_BY_USERNAME.load(deps.storage, task.worker.clone())?;
+    // Run the action as if the signer had sent it, without funds
+    let signer_info = MessageInfo {
+        sender: user.wallet_address,
+        funds: vec![],
+    };
+    let action: ExecuteMsg = signed.action.into();
+    if !matches!(action, ExecuteMsg::RenewUsername {} | ExecuteMsg::DeactivateUser {}) {
+        ensure_username_current(deps.as_ref(), &env, &signer_info.sender)?;
+    }
+    let response = dispatch_execute(deps, env, signer_info, action)?;
+    Ok(response
+        .add_attribute("relayer", info.sender)
+        .add_attribute("signer", signed.signer)
+        .add_attribute("nonce", nonce.to_string()))
+}
+
+// USERNAME NFT FUNCTIONS
+
+// Time a username sent to the contract by a new holder waits before it is re-pointed
+const USERNAME_TRANSFER_DELAY_SECS: u64 = 3 * 86400;
+
+pub fn execute_set_username_collection(
+    deps: DepsMut,
+    info: MessageInfo,
+    collection: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
     
-    let payment_msg = CosmosMsg::Bank(BankMsg::Send {
-        to_address: worker.wallet_address.to_string(),
-        amount: vec![task.amount],
-    });
+    // Minted handles live in this collection, so it cannot be swapped out later
+    if USERNAME_COLLECTION.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::UsernameCollectionAlreadySet {});
+    }
+    let collection = validate_address(deps.api, &collection)?;
+    USERNAME_COLLECTION.save(deps.storage, &collection)?;
     
     Ok(Response::new()
-        .add_message(payment_msg)
-        .add_attribute("action", "approve_task")
-        .add_attribute("task_id", task_id.to_string())
-        .add_attribute("approver", username)
-        .add_event(
-            cosmwasm_std::Event::new("task_released")
-                .add_attribute("task_id", task_id.to_string())
-                .add_attribute("release_type", "manual_approval")
-        ))
+        .add_attribute("action", "set_username_collection")
+        .add_attribute("collection", collection))
 }
 
-pub fn execute_dispute_task(
+pub fn execute_tokenize_username(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    task_id: u64,
-    reason_hash: Option<String>,
 ) -> Result<Response, ContractError> {
     let username = get_username_from_wallet(&deps, &info.sender)?;
+    ensure_no_funds(&info)?;
+    let collection = USERNAME_COLLECTION.may_load(deps.storage)?
+        .ok_or(ContractError::UsernameCollectionNotConfigured {})?;
     
-    TASKS.update(deps.storage, task_id, |task| -> Result<_, ContractError> {
-        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
-        
-        // Only payer can dispute
-        if task.payer != username {
-            return Err(ContractError::OnlyPayerCanDispute {});
-        }
-        
-        // Can only dispute hybrid tasks in pending release state
-        if !matches!(task.proof_type, ProofType::Hybrid) ||
-           !matches!(task.status, TaskStatus::PendingRelease) {
-            return Err(ContractError::TaskNotAuthorized {});
-        }
-        
-        // Check if dispute window is still open
-        if let (Some(verified_at), Some(review_window)) = (task.verified_at, task.review_window_secs) {
-            if env.block.time.seconds() > verified_at + review_window {
-                return Err(ContractError::DisputeWindowNotElapsed {});
-            }
-        }
-        
-        task.status = TaskStatus::Disputed;
-        task.updated_at = env.block.time.seconds();
-        
-        Ok(task)
-    })?;
+    let mut user = USERS_BY_USERNAME.load(deps.storage, username.clone())?;
+    if user.tokenized {
+        return Err(ContractError::UsernameAlreadyTokenized {});
+    }
+    user.tokenized = true;
+    user.updated_at = env.block.time.seconds();
+    USERS_BY_USERNAME.save(deps.storage, username.clone(), &user)?;
+    
+    let mint = WasmMsg::Execute {
+        contract_addr: collection.to_string(),
+        msg: to_json_binary(&Cw721ExecuteMsg::Mint {
+            token_id: username.clone(),
+            owner: info.sender.to_string(),
+            token_uri: None,
+            extension: Empty {},
+        })?,
+        funds: vec![],
+    };
     
     Ok(Response::new()
-        .add_attribute("action", "dispute_task")
-        .add_attribute("task_id", task_id.to_string())
-        .add_attribute("disputer", username)
-        .add_event(
-            cosmwasm_std::Event::new("task_disputed")
-                .add_attribute("task_id", task_id.to_string())
-                .add_attribute("reason_hash", reason_hash.unwrap_or_default())
-        ))
+        .add_message(mint)
+        .add_attribute("action", "tokenize_username")
+        .add_attribute("username", username)
+        .add_attribute("owner", info.sender))
 }
 
-pub fn execute_resolve_dispute(
+pub fn execute_receive_username_nft(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    task_id: u64,
-    decision: bool,
+    receive: Cw721ReceiveMsg,
 ) -> Result<Response, ContractError> {
-    let state = STATE.load(deps.storage)?;
-    
-    // Only contract owner can resolve disputes
-    if info.sender != state.owner {
-        return Err(ContractError::OnlyOwnerCanResolveDispute {});
+    ensure_no_funds(&info)?;
+    // Only tokens of the username collection can claim a handle
+    let collection = USERNAME_COLLECTION.may_load(deps.storage)?
+        .ok_or(ContractError::UsernameCollectionNotConfigured {})?;
+    if info.sender != collection {
+        return Err(ContractError::NotAuthorized {});
+    }
+    let user = USERS_BY_USERNAME.load(deps.storage, receive.token_id.clone())
+        .map_err(|_| ContractError::UserNotFound {})?;
+    if !user.tokenized {
+        return Err(ContractError::UsernameNotTokenized {});
     }
     
-    let task = TASKS.load(deps.storage, task_id)
-        .map_err(|_| ContractError::TaskNotFound {})?;
+    // Wallets hold at most one username, including the current holder re-sending its own
+    let to_wallet = validate_address(deps.api, &receive.sender)?;
+    if USERS_BY_WALLET.has(deps.storage, to_wallet.clone()) {
+        return Err(ContractError::WalletAlreadyRegistered {});
+    }
+    let transfer = UsernameTransfer {
+        to_wallet,
+        effective_at: env.block.time.seconds() + USERNAME_TRANSFER_DELAY_SECS,
+    };
+    USERNAME_TRANSFERS.save(deps.storage, receive.token_id.clone(), &transfer)?;
     
-    // Check if task is in dispute
-    if !matches!(task.status, TaskStatus::Disputed) {
-        return Err(ContractError::TaskNotInDispute {});
+    Ok(Response::new()
+        .add_attribute("action", "receive_username_nft")
+        .add_attribute("username", receive.token_id)
+        .add_attribute("from_wallet", user.wallet_address)
+        .add_attribute("to_wallet", transfer.to_wallet)
+        .add_attribute("effective_at", transfer.effective_at.to_string()))
+}
+
+pub fn execute_finalize_username_transfer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    username: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = normalize_username(&username);
+    let transfer = USERNAME_TRANSFERS.may_load(deps.storage, username.clone())?
+        .ok_or(ContractError::UsernameTransferNotFound {})?;
+    if env.block.time.seconds() < transfer.effective_at {
+        return Err(ContractError::UsernameTransferNotReady {});
+    }
+    // The new wallet may have registered another username during the delay
+    if USERS_BY_WALLET.has(deps.storage, transfer.to_wallet.clone()) {
+        return Err(ContractError::WalletAlreadyRegistered {});
     }
     
-    // Update task status
-    TASKS.update(deps.storage, task_id, |task| -> Result<_, ContractError> {
-        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
-        task.status = if decision { TaskStatus::Released } else { TaskStatus::Refunded };
-        task.updated_at = env.block.time.seconds();
-        Ok(task)
-    })?;
+    // Re-point the handle; the old wallet's signing key does not carry over
+    let mut user = USERS_BY_USERNAME.load(deps.storage, username.clone())?;
+    let from_wallet = user.wallet_address.clone();
+    USERS_BY_WALLET.remove(deps.storage, from_wallet.clone());
+    USERS_BY_WALLET.save(deps.storage, transfer.to_wallet.clone(), &username)?;
+    user.wallet_address = transfer.to_wallet.clone();
+    user.pubkey = None;
+    user.updated_at = env.block.time.seconds();
+    USERS_BY_USERNAME.save(deps.storage, username.clone(), &user)?;
+    USERNAME_TRANSFERS.remove(deps.storage, username.clone());
+    
+    let return_nft = username_nft_transfer(deps.storage, &username, &transfer.to_wallet)?;
+    Ok(Response::new()
+        .add_message(return_nft)
+        .add_attribute("action", "finalize_username_transfer")
+        .add_attribute("username", &username)
+        .add_attribute("from_wallet", from_wallet.as_str())
+        .add_attribute("to_wallet", transfer.to_wallet.as_str())
+        .add_event(
+            cosmwasm_std::Event::new("username_transferred")
+                .add_attribute("username", username)
+                .add_attribute("from_wallet", from_wallet)
+                .add_attribute("to_wallet", transfer.to_wallet)
+        ))
+}
+
+pub fn execute_cancel_username_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+    username: String,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    let username = normalize_username(&username);
+    let transfer = USERNAME_TRANSFERS.may_load(deps.storage, username.clone())?
+        .ok_or(ContractError::UsernameTransferNotFound {})?;
+    if transfer.to_wallet != info.sender {
+        return Err(ContractError::NotAuthorized {});
+    }
+    USERNAME_TRANSFERS.remove(deps.storage, username.clone());
     
-    let mut response = Response::new()
-        .add_attribute("action", "resolve_dispute")
-        .add_attribute("task_id", task_id.to_string())
-        .add_attribute("decision", decision.to_string());
+    // The claimant gets the NFT back and the handle stays where it was
+    let return_nft = username_nft_transfer(deps.storage, &username, &info.sender)?;
+    Ok(Response::new()
+        .add_message(return_nft)
+        .add_attribute("action", "cancel_username_transfer")
+        .add_attribute("username", username)
+        .add_attribute("claimant", info.sender))
+}
+
+// Helper function to send a username NFT held by the contract to `recipient`
+fn username_nft_transfer(storage: &dyn Storage, username: &str, recipient: &Addr) -> StdResult<WasmMsg> {
+    let collection = USERNAME_COLLECTION.load(storage)?;
+    Ok(WasmMsg::Execute {
+        contract_addr: collection.to_string(),
+        msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+            recipient: recipient.to_string(),
+            token_id: username.to_string(),
+        })?,
+        funds: vec![],
+    })
+}
+
+// PRICE ORACLE FUNCTIONS
+
+// Oracle prices older than this are rejected when resolving a quote
+const MAX_PRICE_AGE_SECS: u64 = 300;
+
+pub fn execute_set_price_oracle(
+    deps: DepsMut,
+    info: MessageInfo,
+    oracle: Option<String>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
+    
+    let oracle = match oracle {
+        Some(oracle) => validate_address(deps.api, &oracle)?,
+        None => {
+            PRICE_ORACLE.remove(deps.storage);
+            return Ok(Response::new()
+                .add_attribute("action", "set_price_oracle")
+                .add_attribute("oracle", "none"));
+        }
+    };
+    PRICE_ORACLE.save(deps.storage, &oracle)?;
     
-    if decision {
-        // Release to worker
-        let worker = USERS_BY_USERNAME.load(deps.storage, task.worker.clone())?;
-        let payment_msg = CosmosMsg::Bank(BankMsg::Send {
-            to_address: worker.wallet_address.to_string(),
-            amount: vec![task.amount],
-        });
-        response = response.add_message(payment_msg)
-            .add_event(
-                cosmwasm_std::Event::new("task_released")
-                    .add_attribute("task_id", task_id.to_string())
-                    .add_attribute("release_type", "dispute_resolved")
-            );
+    Ok(Response::new()
+        .add_attribute("action", "set_price_oracle")
+        .add_attribute("oracle", oracle))
+}
+
+pub fn execute_set_feature_flags(
+    deps: DepsMut,
+    info: MessageInfo,
+    disabled_payment_types: Vec<PaymentType>,
+    disabled_proof_types: Vec<ProofType>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
+    
+    let flags = FeatureFlags { disabled_payment_types, disabled_proof_types };
+    let attribute = |types: Vec<String>| if types.is_empty() { "none".to_string() } else { types.join(",") };
+    let payment_types = attribute(flags.disabled_payment_types.iter().map(|t| format!("{:?}", t)).collect());
+    let proof_types = attribute(flags.disabled_proof_types.iter().map(|t| format!("{:?}", t)).collect());
+    if flags == FeatureFlags::default() {
+        FEATURE_FLAGS.remove(deps.storage);
     } else {
-        // Refund to payer
-        let payer = USERS_BY_USERNAME.load(deps.storage, task.payer.clone())?;
-        let refund_msg = CosmosMsg::Bank(BankMsg::Send {
-            to_address: payer.wallet_address.to_string(),
-            amount: vec![task.amount],
-        });
-        response = response.add_message(refund_msg)
-            .add_event(
-                cosmwasm_std::Event::new("task_refunded")
-                    .add_attribute("task_id", task_id.to_string())
-                    .add_attribute("refund_reason", "dispute_resolved")
-            );
+        FEATURE_FLAGS.save(deps.storage, &flags)?;
     }
     
-    Ok(response)
+    Ok(Response::new()
+        .add_attribute("action", "set_feature_flags")
+        .add_attribute("disabled_payment_types", payment_types)
+        .add_attribute("disabled_proof_types", proof_types))
 }
 
-pub fn execute_refund_if_expired(
+pub fn execute_set_policy_contract(
     deps: DepsMut,
-    env: Env,
-    _info: MessageInfo,
-    task_id: u64,
+    info: MessageInfo,
+    policy: Option<String>,
 ) -> Result<Response, ContractError> {
-    let task = TASKS.load(deps.storage, task_id)
-        .map_err(|_| ContractError::TaskNotFound {})?;
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
+    
+    let policy = match policy {
+        Some(policy) => validate_address(deps.api, &policy)?,
+        None => {
+            POLICY_CONTRACT.remove(deps.storage);
+            return Ok(Response::new()
+                .add_attribute("action", "set_policy_contract")
+                .add_attribute("policy", "none"));
+        }
+    };
+    POLICY_CONTRACT.save(deps.storage, &policy)?;
     
-    // Check if task has expired
-    if env.block.time.seconds() <= task.deadline_ts {
-        return Err(ContractError::TaskNotAuthorized {});
-    }
+    Ok(Response::new()
+        .add_attribute("action", "set_policy_contract")
+        .add_attribute("policy", policy))
+}
+
+pub fn execute_set_dex_router(
+    deps: DepsMut,
+    info: MessageInfo,
+    router: Option<String>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_role(deps.storage, &info.sender, AdminRole::ConfigManager)?;
+    
+    let router = match router {
+        Some(router) => validate_address(deps.api, &router)?,
+        None => {
+            DEX_ROUTER.remove(deps.storage);
+            return Ok(Response::new()
+                .add_attribute("action", "set_dex_router")
+                .add_attribute("router", "none"));
+        }
+    };
+    DEX_ROUTER.save(deps.storage, &router)?;
     
-    // Can only refund tasks that are still escrowed or pending
-    if !matches!(task.status, TaskStatus::Escrowed | TaskStatus::ProofSubmitted | TaskStatus::PendingRelease) {
-        return Err(ContractError::TaskAlreadyCompleted {});
+    Ok(Response::new()
+        .add_attribute("action", "set_dex_router")
+        .add_attribute("router", router))
+}
+
+// Helper function to ask the policy contract, if one is set, to approve an execute that takes
+// funds or pays them out; executes that move no funds are not checked
+fn ensure_policy_allows(deps: Deps, sender: &Addr, funds: &[Coin], response: &Response) -> Result<(), ContractError> {
+    let Some(policy) = POLICY_CONTRACT.may_load(deps.storage)? else {
+        return Ok(());
+    };
+    // Payout swaps count as transfers to the recipient the router pays
+    let router = DEX_ROUTER.may_load(deps.storage)?;
+    let transfers: Vec<PolicyTransfer> = response.messages.iter()
+        .filter_map(|sub_msg| match &sub_msg.msg {
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => Some(PolicyTransfer {
+                recipient: to_address.clone(),
+                amount: amount.clone(),
+            }),
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, funds })
+                if router.as_ref().is_some_and(|router| router.as_str() == contract_addr) =>
+            {
+                match from_json(msg).ok()? {
+                    DexRouterExecuteMsg::Swap { to, .. } => Some(PolicyTransfer { recipient: to, amount: funds.clone() }),
+                }
+            }
+            _ => None,
+        })
+        .collect();
+    if funds.is_empty() && transfers.is_empty() {
+        return Ok(());
     }
     
-    // Update task status
-    TASKS.update(deps.storage, task_id, |task| -> Result<_, ContractError> {
-        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
-        task.status = TaskStatus::Refunded;
-        task.updated_at = env.block.time.seconds();
-        Ok(task)
-    })?;
+    let action = response.attributes.iter()
+        .find(|attr| attr.key == "action")
+        .map(|attr| attr.value.clone())
+        .unwrap_or_default();
+    let check: PolicyCheckResponse = deps.querier.query_wasm_smart(
+        policy,
+        &PolicyQueryMsg::CheckTransfer { sender: sender.to_string(), action, funds: funds.to_vec(), transfers },
+    )?;
+    if !check.allowed {
+        return Err(ContractError::PolicyDenied {});
+    }
+    Ok(())
+}
+
+// Helper function to reject payment types switched off with SetFeatureFlags
+fn ensure_payment_type_enabled(storage: &dyn Storage, payment_type: &PaymentType) -> Result<(), ContractError> {
+    let flags = FEATURE_FLAGS.may_load(storage)?.unwrap_or_default();
+    if flags.disabled_payment_types.contains(payment_type) {
+        return Err(ContractError::PaymentTypeDisabled {});
+    }
+    Ok(())
+}
+
+// Helper function to reject proof types switched off with SetFeatureFlags
+fn ensure_proof_type_enabled(storage: &dyn Storage, proof_type: &ProofType) -> Result<(), ContractError> {
+    let flags = FEATURE_FLAGS.may_load(storage)?.unwrap_or_default();
+    if flags.disabled_proof_types.contains(proof_type) {
+        return Err(ContractError::ProofTypeDisabled {});
+    }
+    Ok(())
+}
+
+// Helper function to validate a price quote at creation; quotes need a configured oracle
+fn validate_price_quote(storage: &dyn Storage, quote: Option<PriceQuoteMsg>) -> Result<Option<PriceQuote>, ContractError> {
+    let quote = match quote {
+        Some(quote) => quote,
+        None => return Ok(None),
+    };
+    if quote.currency.trim().is_empty() || quote.currency.len() > MAX_LABEL_LENGTH || quote.amount.is_zero() {
+        return Err(ContractError::InvalidPriceQuote {});
+    }
+    if PRICE_ORACLE.may_load(storage)?.is_none() {
+        return Err(ContractError::PriceOracleNotConfigured {});
+    }
+    Ok(Some(PriceQuote {
+        currency: quote.currency,
+        amount: quote.amount,
+        resolved_price: None,
+        resolved_at: None,
+    }))
+}
+
+// Helper function to convert a quote into an amount of `denom` at the current oracle price
+fn resolve_price_quote(deps: Deps, env: &Env, quote: &mut PriceQuote, denom: &str) -> Result<Uint128, ContractError> {
+    let oracle = PRICE_ORACLE.may_load(deps.storage)?
+        .ok_or(ContractError::PriceOracleNotConfigured {})?;
+    let price: OraclePriceResponse = deps.querier.query_wasm_smart(
+        oracle,
+        &OracleQueryMsg::Price { currency: quote.currency.clone(), denom: denom.to_string() },
+    )?;
+    if env.block.time.seconds().saturating_sub(price.publish_time) > MAX_PRICE_AGE_SECS {
+        return Err(ContractError::StalePrice {});
+    }
     
-    let payer = USERS_BY_USERNAME.load(deps.storage, task.payer.clone())?;
+    let amount = quote.amount.checked_mul(price.price)
+        .map_err(|_| ContractError::InvalidPriceQuote {})?
+        .to_uint_floor();
+    if amount.is_zero() {
+        return Err(ContractError::InvalidPaymentAmount {});
+    }
+    quote.resolved_price = Some(price.price);
+    quote.resolved_at = Some(env.block.time.seconds());
+    Ok(amount)
+}
+
+// Default and maximum number of primary records processed per RebuildIndex call
+const DEFAULT_REBUILD_LIMIT: u32 = 100;
+const MAX_REBUILD_LIMIT: u32 = 500;
+
+pub fn execute_rebuild_index(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    index: IndexKind,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    ensure_no_funds(&info)?;
+    ensure_owner(deps.storage, &info.sender)?;
+    
+    let limit = limit.unwrap_or(DEFAULT_REBUILD_LIMIT).min(MAX_REBUILD_LIMIT) as usize;
+    let mut processed = 0;
+    let mut last_key = None;
+    
+    match index {
+        IndexKind::UsersByWallet => {
+            let start = start_after.map(Bound::exclusive);
+            let users: Vec<(String, User)> = USERS_BY_USERNAME
+                .range(deps.storage, start, None, Order::Ascending)
+                .take(limit)
+                .collect::<StdResult<_>>()?;
+            for (username, user) in users {
+                USERS_BY_WALLET.save(deps.storage, user.wallet_address, &username)?;
+                processed += 1;
+                last_key = Some(username);
+            }
+        }
+        IndexKind::UserPayments => {
+            let start = parse_id_cursor(start_after)?.map(Bound::exclusive);
+            let payments: Vec<(u64, Payment)> = PAYMENTS
+                .range(deps.storage, start, None, Order::Ascending)
+                .take(limit)
+                .collect::<StdResult<_>>()?;
+            for (payment_id, payment) in payments {
+                index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &payment.from_username, payment_id)?;
+                match payment.to_wallet {
+                    Some(to_wallet) => WALLET_PAYMENT_REQUESTS.save(deps.storage, (to_wallet, payment_id), &true)?,
+                    None => index_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &payment.to_username, payment_id)?,
+                }
+                processed += 1;
+                last_key = Some(payment_id.to_string());
+            }
+        }
+        IndexKind::UserTasks => {
+            let start = parse_id_cursor(start_after)?.map(Bound::exclusive);
+            let tasks: Vec<(u64, Task)> = TASKS
+                .range(deps.storage, start, None, Order::Ascending)
+                .take(limit)
+                .collect::<StdResult<_>>()?;
+            for (task_id, task) in tasks {
+                index_user_record(deps.storage, &USER_TASKS, &USER_TASK_COUNTS, &task.payer, task_id)?;
+                index_user_record(deps.storage, &USER_TASKS, &USER_TASK_COUNTS, &task.worker, task_id)?;
+                processed += 1;
+                last_key = Some(task_id.to_string());
+            }
+        }
+        IndexKind::TasksByStatus => {
+            let start = parse_id_cursor(start_after)?.map(Bound::exclusive);
+            let tasks: Vec<(u64, Task)> = TASKS
+                .range(deps.storage, start, None, Order::Ascending)
+                .take(limit)
+                .collect::<StdResult<_>>()?;
+            for (task_id, task) in tasks {
+                TASKS_BY_STATUS.save(deps.storage, (task.status.key().to_string(), task_id), &true)?;
+                processed += 1;
+                last_key = Some(task_id.to_string());
+            }
+        }
+        IndexKind::UserTasksByStatus => {
+            let start = parse_id_cursor(start_after)?.map(Bound::exclusive);
+            let tasks: Vec<(u64, Task)> = TASKS
+                .range(deps.storage, start, None, Order::Ascending)
+                .take(limit)
+                .collect::<StdResult<_>>()?;
+            for (task_id, task) in tasks {
+                let status = task.status.key().to_string();
+                USER_TASKS_BY_STATUS.save(deps.storage, (task.payer, status.clone(), task_id), &true)?;
+                USER_TASKS_BY_STATUS.save(deps.storage, (task.worker, status, task_id), &true)?;
+                processed += 1;
+                last_key = Some(task_id.to_string());
+            }
+        }
+    }
     
-    // Refund to payer (only for escrowed tasks)
-    let mut response = Response::new()
-        .add_attribute("action", "refund_expired_task")
-        .add_attribute("task_id", task_id.to_string())
-        .add_event(
-            cosmwasm_std::Event::new("task_refunded")
-                .add_attribute("task_id", task_id.to_string())
-                .add_attribute("refund_reason", "expired")
-        );
+    // A short batch means the end of the primary map was reached
+    let done = processed < limit;
     
-    // Only refund escrowed funds (soft tasks don't hold escrow)
-    if !matches!(task.proof_type, ProofType::Soft) {
-        let refund_msg = CosmosMsg::Bank(BankMsg::Send {
-            to_address: payer.wallet_address.to_string(),
-            amount: vec![task.amount],
-        });
-        response = response.add_message(refund_msg);
+    let mut response = Response::new()
+        .add_attribute("action", "rebuild_index")
+        .add_attribute("index", format!("{:?}", index))
+        .add_attribute("processed", processed.to_string())
+        .add_attribute("done", done.to_string());
+    // An empty batch has no cursor to report
+    if let Some(last_key) = last_key {
+        response = response.add_attribute("last_key", last_key);
     }
     
     Ok(response)
 }
 
-pub fn execute_release_if_window_elapsed(
+// Default retention before settled records can be archived, and per-call scan sizes
+const DEFAULT_ARCHIVE_RETENTION_SECS: u64 = 90 * 86400;
+const DEFAULT_ARCHIVE_LIMIT: u32 = 100;
+const MAX_ARCHIVE_LIMIT: u32 = 500;
+
+// Settled payments and tasks are removed from storage and folded into a hash chain:
+// root = sha256(previous root + "payment:" or "task:" + record JSON), payments before tasks.
+// The scan cursors wrap around, so records skipped as unsettled are revisited on a later pass.
+pub fn execute_archive_records(
     deps: DepsMut,
     env: Env,
-    _info: MessageInfo,
-    task_id: u64,
+    info: MessageInfo,
+    up_to_ts: u64,
+    limit: Option<u32>,
 ) -> Result<Response, ContractError> {
-    let task = TASKS.load(deps.storage, task_id)
-        .map_err(|_| ContractError::TaskNotFound {})?;
-    
-    // Check if task is in pending release state
-    if !matches!(task.status, TaskStatus::PendingRelease) {
-        return Err(ContractError::TaskNotAuthorized {});
+    ensure_no_funds(&info)?;
+    ensure_owner(deps.storage, &info.sender)?;
+    
+    let limit = limit.unwrap_or(DEFAULT_ARCHIVE_LIMIT).min(MAX_ARCHIVE_LIMIT) as usize;
+    let retention = CONFIG.may_load(deps.storage)?.unwrap_or_default()
+        .archive_retention_secs
+        .unwrap_or(DEFAULT_ARCHIVE_RETENTION_SECS);
+    let cutoff = up_to_ts.min(env.block.time.seconds().saturating_sub(retention));
+    let mut archive = ARCHIVE.may_load(deps.storage)?.unwrap_or_default();
+    
+    let payments: Vec<(u64, Payment)> = PAYMENTS
+        .range(deps.storage, Some(Bound::exclusive(archive.payments_cursor)), None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+    archive.payments_cursor = if payments.len() < limit { 0 } else { payments[payments.len() - 1].0 };
+    let mut payment_ids = vec![];
+    for (payment_id, payment) in payments {
+        let settled = matches!(payment.status, PaymentStatus::Completed | PaymentStatus::Rejected | PaymentStatus::Cancelled);
+        if !settled || payment.updated_at > cutoff {
+            continue;
+        }
+        archive.root = hash_data(&format!("{}payment:{}", archive.root, to_json_string(&payment)?));
+        PAYMENTS.remove(deps.storage, payment_id);
+        if let Some(external_ref) = payment.external_ref {
+            PAYMENT_EXTERNAL_REFS.remove(deps.storage, (payment.from_username.clone(), external_ref));
+        }
+        unindex_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &payment.from_username, payment_id)?;
+        unindex_user_record(deps.storage, &USER_PAYMENTS, &USER_PAYMENT_COUNTS, &payment.to_username, payment_id)?;
+        if let Some(to_wallet) = payment.to_wallet {
+            WALLET_PAYMENT_REQUESTS.remove(deps.storage, (to_wallet, payment_id));
+        }
+        payment_ids.push(payment_id.to_string());
     }
     
-    // Check if dispute window has elapsed
-    if let (Some(verified_at), Some(review_window)) = (task.verified_at, task.review_window_secs) {
-        if env.block.time.seconds() <= verified_at + review_window {
-            return Err(ContractError::DisputeWindowNotElapsed {});
+    let tasks: Vec<(u64, Task)> = TASKS
+        .range(deps.storage, Some(Bound::exclusive(archive.tasks_cursor)), None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+    archive.tasks_cursor = if tasks.len() < limit { 0 } else { tasks[tasks.len() - 1].0 };
+    let mut task_ids = vec![];
+    for (task_id, task) in tasks {
+        // A pending amendment still holds the payer's top-up until withdrawn
+        let settled = matches!(task.status, TaskStatus::Released | TaskStatus::Refunded)
+            && task.pending_amendment.is_none()
+            && !task.insurance.as_ref().is_some_and(|insurance| insurance.claim_open);
+        if !settled || task.updated_at > cutoff {
+            continue;
         }
-    } else {
-        return Err(ContractError::TaskNotAuthorized {});
+        archive.root = hash_data(&format!("{}task:{}", archive.root, to_json_string(&task)?));
+        TASKS.remove(deps.storage, task_id);
+        remove_task_status_index(deps.storage, task_id, &task);
+        if let Some(external_ref) = task.external_ref {
+            TASK_EXTERNAL_REFS.remove(deps.storage, (task.payer.clone(), external_ref));
+        }
+        unindex_user_record(deps.storage, &USER_TASKS, &USER_TASK_COUNTS, &task.payer, task_id)?;
+        unindex_user_record(deps.storage, &USER_TASKS, &USER_TASK_COUNTS, &task.worker, task_id)?;
+        task_ids.push(task_id.to_string());
     }
     
-    // Update task status
-    TASKS.update(deps.storage, task_id, |task| -> Result<_, ContractError> {
-        let mut task = task.ok_or(ContractError::TaskNotFound {})?;
-        task.status = TaskStatus::Released;
-        task.updated_at = env.block.time.seconds();
-        Ok(task)
-    })?;
-    
-    let worker = USERS_BY_USERNAME.load(deps.storage, task.worker.clone())?;
-    
-    // Release payment to worker
-    let payment_msg = CosmosMsg::Bank(BankMsg::Send {
-        to_address: worker.wallet_address.to_string(),
-        amount: vec![task.amount],
-    });
+    let archived = payment_ids.len() + task_ids.len();
+    if archived > 0 {
+        archive.archived_records += archived as u64;
+        archive.last_archived_at = Some(env.block.time.seconds());
+    }
+    ARCHIVE.save(deps.storage, &archive)?;
     
+    let describe = |ids: Vec<String>| if ids.is_empty() { "none".to_string() } else { ids.join(",") };
     Ok(Response::new()
-        .add_message(payment_msg)
-        .add_attribute("action", "release_after_window")
-        .add_attribute("task_id", task_id.to_string())
-        .add_event(
-            cosmwasm_std::Event::new("task_released")
-                .add_attribute("task_id", task_id.to_string())
-                .add_attribute("release_type", "window_elapsed")
-        ))
+        .add_attribute("action", "archive_records")
+        .add_attribute("payment_ids", describe(payment_ids))
+        .add_attribute("task_ids", describe(task_ids))
+        .add_attribute("archived", archived.to_string())
+        .add_attribute("root", if archive.root.is_empty() { "none".to_string() } else { archive.root }))
+}
+
+// Helper function to parse a RebuildIndex cursor for id-keyed primary maps
+fn parse_id_cursor(start_after: Option<String>) -> Result<Option<u64>, ContractError> {
+    start_after
+        .map(|cursor| cursor.parse::<u64>().map_err(|_| ContractError::InvalidIndexCursor {}))
+        .transpose()
 }
 
 // TASK SYSTEM QUERIES
@@ -1463,32 +8270,896 @@ fn query_task_by_id(deps: Deps, task_id: u64) -> StdResult<Binary> {
     to_json_binary(&crate::msg::TaskResponse { task })
 }
 
-fn query_task_history(deps: Deps, username: String) -> StdResult<Binary> {
-    let mut tasks = Vec::new();
-    
+fn query_tasks_by_ids(deps: Deps, ids: Vec<u64>) -> StdResult<Binary> {
+    ensure_query_ids_bounded(&ids)?;
+    let mut tasks = vec![];
+    let mut missing = vec![];
+    for task_id in ids {
+        match TASKS.may_load(deps.storage, task_id)? {
+            Some(task) => tasks.push(task),
+            None => missing.push(task_id),
+        }
+    }
+    to_json_binary(&crate::msg::TasksByIdsResponse { tasks, missing })
+}
+
+fn query_task_history(deps: Deps, username: String, start_after: Option<u64>) -> StdResult<Binary> {
     // Get all tasks for this user
-    for item in USER_TASKS.prefix(username).range(deps.storage, None, None, Order::Ascending) {
-        let (task_id, _) = item?;
-        if let Ok(task) = TASKS.load(deps.storage, task_id) {
-            tasks.push(task);
+    let (page, next_start_after) = collect_page(
+        USER_TASKS.prefix(username.clone()).range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending),
+        |(task_id, _)| TASKS.may_load(deps.storage, task_id),
+    )?;
+    to_json_binary(&crate::msg::TasksResponse {
+        tasks: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+        total: Some(USER_TASK_COUNTS.may_load(deps.storage, username)?.unwrap_or(0)),
+        next_start_after,
+    })
+}
+
+fn query_pending_tasks(deps: Deps, username: String, start_after: Option<u64>) -> StdResult<Binary> {
+    // Get all tasks for this user that are pending
+    let (page, next_start_after) = collect_page(
+        USER_TASKS.prefix(username).range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending),
+        |(task_id, _)| {
+            let task = TASKS.may_load(deps.storage, task_id)?;
+            Ok(task.filter(|t| matches!(t.status, TaskStatus::Escrowed | TaskStatus::ProofSubmitted | TaskStatus::PendingRelease | TaskStatus::ApprovedPendingFunding | TaskStatus::Blocked)))
+        },
+    )?;
+    to_json_binary(&crate::msg::TasksResponse {
+        tasks: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+        total: None,
+        next_start_after,
+    })
+}
+
+// Default and maximum number of tasks returned per status page
+const DEFAULT_TASK_STATUS_LIMIT: u32 = 30;
+const MAX_TASK_STATUS_LIMIT: u32 = 100;
+
+fn query_tasks_by_status(deps: Deps, status: TaskStatus, start_after: Option<u64>, limit: Option<u32>) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_TASK_STATUS_LIMIT).min(MAX_TASK_STATUS_LIMIT) as usize;
+    let tasks: Vec<Task> = TASKS_BY_STATUS
+        .prefix(status.key().to_string())
+        .keys(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .map(|task_id| TASKS.load(deps.storage, task_id?))
+        .collect::<StdResult<_>>()?;
+    to_json_binary(&TasksByStatusResponse { tasks })
+}
+
+fn query_user_tasks_by_status(
+    deps: Deps,
+    username: String,
+    status: TaskStatus,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_TASK_STATUS_LIMIT).min(MAX_TASK_STATUS_LIMIT) as usize;
+    let tasks: Vec<Task> = USER_TASKS_BY_STATUS
+        .prefix((username, status.key().to_string()))
+        .keys(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .map(|task_id| TASKS.load(deps.storage, task_id?))
+        .collect::<StdResult<_>>()?;
+    to_json_binary(&TasksByStatusResponse { tasks })
+}
+
+fn query_dispute_stats(deps: Deps) -> StdResult<Binary> {
+    let reasons = DisputeReason::ALL.iter()
+        .map(|reason| Ok(DisputeReasonCount {
+            reason: *reason,
+            count: DISPUTE_REASON_COUNTS.may_load(deps.storage, reason.key().to_string())?.unwrap_or_default(),
+        }))
+        .collect::<StdResult<_>>()?;
+    let outcomes = DisputeOutcome::ALL.iter()
+        .map(|outcome| Ok(DisputeOutcomeCount {
+            outcome: *outcome,
+            count: DISPUTE_OUTCOME_COUNTS.may_load(deps.storage, outcome.key().to_string())?.unwrap_or_default(),
+        }))
+        .collect::<StdResult<_>>()?;
+    to_json_binary(&DisputeStatsResponse { reasons, outcomes })
+}
+
+fn query_reputation(deps: Deps, username: String) -> StdResult<Binary> {
+    let reputation = WORKER_REPUTATION.may_load(deps.storage, username.clone())?.unwrap_or_default();
+    to_json_binary(&ReputationResponse { username, reputation })
+}
+
+fn query_task_batch(deps: Deps, batch_id: u64) -> StdResult<Binary> {
+    let batch = TASK_BATCHES.load(deps.storage, batch_id)?;
+    let mut tasks = vec![];
+    let mut missing = vec![];
+    for task_id in &batch.task_ids {
+        match TASKS.may_load(deps.storage, *task_id)? {
+            Some(task) => tasks.push(task),
+            None => missing.push(*task_id),
         }
     }
-    
-    to_json_binary(&crate::msg::TasksResponse { tasks })
+    to_json_binary(&TaskBatchResponse { batch, tasks, missing })
+}
+
+// CAMPAIGN QUERIES
+
+fn query_campaign(deps: Deps, campaign_id: u64) -> StdResult<Binary> {
+    let campaign = CAMPAIGNS.load(deps.storage, campaign_id)?;
+    to_json_binary(&CampaignResponse { campaign })
+}
+
+fn query_campaign_donation(deps: Deps, campaign_id: u64, username: String) -> StdResult<Binary> {
+    let campaign = CAMPAIGNS.load(deps.storage, campaign_id)?;
+    let amount = CAMPAIGN_DONATIONS
+        .may_load(deps.storage, (campaign_id, normalize_username(&username)))?
+        .unwrap_or_default();
+    to_json_binary(&CampaignDonationResponse {
+        amount: Coin { denom: campaign.goal.denom, amount },
+    })
+}
+
+// SUBSCRIPTION QUERIES
+
+fn query_subscription(deps: Deps, env: Env, subscription_id: u64) -> StdResult<Binary> {
+    let subscription = SUBSCRIPTIONS.load(deps.storage, subscription_id)?;
+    let claimable_periods = claimable_periods(&subscription, env.block.time.seconds());
+    to_json_binary(&SubscriptionResponse { subscription, claimable_periods })
+}
+
+fn query_user_subscriptions(deps: Deps, username: String) -> StdResult<Binary> {
+    // Subscriptions the user pays or receives
+    let page = collect_bounded(
+        USER_SUBSCRIPTIONS.prefix(normalize_username(&username)).range(deps.storage, None, None, Order::Ascending),
+        |(subscription_id, _)| SUBSCRIPTIONS.may_load(deps.storage, subscription_id),
+    )?;
+    to_json_binary(&SubscriptionsResponse {
+        subscriptions: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+// INVOICE QUERIES
+
+fn query_invoice(deps: Deps, env: Env, invoice_id: u64) -> StdResult<Binary> {
+    let invoice = INVOICES.load(deps.storage, invoice_id)?;
+    
+    // Payments fill line items in order
+    let mut unapplied = invoice.paid;
+    let line_items = invoice.line_items.iter()
+        .map(|item| {
+            let paid = unapplied.min(item.amount.amount);
+            unapplied -= paid;
+            InvoiceLineItemStatus {
+                description: item.description.clone(),
+                amount: item.amount.clone(),
+                paid,
+                fully_paid: paid == item.amount.amount,
+            }
+        })
+        .collect();
+    let remaining = Coin {
+        denom: invoice.total.denom.clone(),
+        amount: invoice.total.amount - invoice.paid,
+    };
+    let overdue = matches!(invoice.status, InvoiceStatus::Open) && env.block.time.seconds() > invoice.due_ts;
+    to_json_binary(&InvoiceResponse { invoice, line_items, remaining, overdue })
+}
+
+fn query_user_invoices(deps: Deps, username: String) -> StdResult<Binary> {
+    // Invoices the user issued or was billed
+    let page = collect_bounded(
+        USER_INVOICES.prefix(normalize_username(&username)).range(deps.storage, None, None, Order::Ascending),
+        |(invoice_id, _)| INVOICES.may_load(deps.storage, invoice_id),
+    )?;
+    to_json_binary(&InvoicesResponse {
+        invoices: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+// PAYMENT LINK QUERIES
+
+fn query_payment_link(deps: Deps, env: Env, code: String) -> StdResult<Binary> {
+    let link = PAYMENT_LINKS.load(deps.storage, code)?;
+    let usable = payment_link_usable(&link, env.block.time.seconds());
+    to_json_binary(&PaymentLinkResponse { link, usable })
+}
+
+fn query_user_payment_links(deps: Deps, username: String) -> StdResult<Binary> {
+    // Links the user created as a merchant
+    let page = collect_bounded(
+        USER_PAYMENT_LINKS.prefix(normalize_username(&username)).range(deps.storage, None, None, Order::Ascending),
+        |(code, _)| PAYMENT_LINKS.may_load(deps.storage, code),
+    )?;
+    to_json_binary(&PaymentLinksResponse {
+        links: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+// SCHEDULED PAYMENT QUERIES
+
+fn query_scheduled_payment(deps: Deps, env: Env, scheduled_id: u64) -> StdResult<Binary> {
+    let scheduled_payment = SCHEDULED_PAYMENTS.load(deps.storage, scheduled_id)?;
+    let executable = matches!(scheduled_payment.status, ScheduledPaymentStatus::Pending)
+        && env.block.time.seconds() >= scheduled_payment.execute_at;
+    to_json_binary(&ScheduledPaymentResponse { scheduled_payment, executable })
+}
+
+fn query_user_scheduled_payments(deps: Deps, username: String) -> StdResult<Binary> {
+    // Scheduled payments the user sends or receives
+    let page = collect_bounded(
+        USER_SCHEDULED_PAYMENTS.prefix(normalize_username(&username)).range(deps.storage, None, None, Order::Ascending),
+        |(scheduled_id, _)| SCHEDULED_PAYMENTS.may_load(deps.storage, scheduled_id),
+    )?;
+    to_json_binary(&ScheduledPaymentsResponse {
+        scheduled_payments: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+fn query_due_scheduled_payments(deps: Deps, env: Env) -> StdResult<Binary> {
+    // Pending payments whose execution time has passed, oldest first, for keepers to execute
+    let due_before = Bound::exclusive((env.block.time.seconds() + 1, 0));
+    let page = collect_bounded(
+        PENDING_SCHEDULED_PAYMENTS.range(deps.storage, None, Some(due_before), Order::Ascending),
+        |((_, scheduled_id), _)| SCHEDULED_PAYMENTS.may_load(deps.storage, scheduled_id),
+    )?;
+    to_json_binary(&ScheduledPaymentsResponse {
+        scheduled_payments: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+// GIFT CODE QUERIES
+
+fn query_gift_code(deps: Deps, gift_id: u64) -> StdResult<Binary> {
+    let gift_code = GIFT_CODES.load(deps.storage, gift_id)?;
+    to_json_binary(&GiftCodeResponse { gift_code })
+}
+
+fn query_user_gift_codes(deps: Deps, username: String) -> StdResult<Binary> {
+    // Gift codes the user created
+    let page = collect_bounded(
+        USER_GIFT_CODES.prefix(normalize_username(&username)).range(deps.storage, None, None, Order::Ascending),
+        |(gift_id, _)| GIFT_CODES.may_load(deps.storage, gift_id),
+    )?;
+    to_json_binary(&GiftCodesResponse {
+        gift_codes: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+// HTLC QUERIES
+
+fn query_htlc(deps: Deps, htlc_id: u64) -> StdResult<Binary> {
+    let htlc = HTLCS.load(deps.storage, htlc_id)?;
+    to_json_binary(&HtlcResponse { htlc })
+}
+
+fn query_user_htlcs(deps: Deps, username: String) -> StdResult<Binary> {
+    // HTLCs the user sent or can claim
+    let page = collect_bounded(
+        USER_HTLCS.prefix(normalize_username(&username)).range(deps.storage, None, None, Order::Ascending),
+        |(htlc_id, _)| HTLCS.may_load(deps.storage, htlc_id),
+    )?;
+    to_json_binary(&HtlcsResponse {
+        htlcs: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+// ADMIN QUERIES
+
+fn query_ownership(deps: Deps) -> StdResult<Binary> {
+    let state = STATE.load(deps.storage)?;
+    to_json_binary(&OwnershipResponse {
+        owner: state.owner,
+        pending_owner: state.pending_owner,
+    })
+}
+
+fn query_roles(deps: Deps) -> StdResult<Binary> {
+    let state = STATE.load(deps.storage)?;
+    let page = collect_bounded(
+        ADMIN_ROLES.range(deps.storage, None, None, Order::Ascending),
+        |((address, _), role)| Ok(Some(RoleGrant { address, role })),
+    )?;
+    to_json_binary(&RolesResponse {
+        owner: state.owner,
+        grants: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+fn query_config(deps: Deps) -> StdResult<Binary> {
+    let config = CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    to_json_binary(&ConfigResponse { config })
+}
+
+fn query_accepted_denoms(deps: Deps) -> StdResult<Binary> {
+    let config = CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    to_json_binary(&AcceptedDenomsResponse { accepted_denoms: config.accepted_denoms })
+}
+
+// INSURANCE POOL QUERIES
+
+fn query_insurance_pool(deps: Deps) -> StdResult<Binary> {
+    let config = INSURANCE_CONFIG.may_load(deps.storage)?;
+    let balances: Vec<Coin> = INSURANCE_POOL
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<_>>()?;
+    let open_claims: Vec<Coin> = INSURANCE_OPEN_CLAIMS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<_>>()?;
+    let solvent = open_claims.iter().all(|claim| {
+        balances.iter().any(|balance| balance.denom == claim.denom && balance.amount >= claim.amount)
+    });
+    to_json_binary(&InsurancePoolResponse { config, balances, open_claims, solvent })
 }
 
-fn query_pending_tasks(deps: Deps, username: String) -> StdResult<Binary> {
-    let mut tasks = Vec::new();
+// ARBITER QUERIES
+
+fn query_arbiters(deps: Deps) -> StdResult<Binary> {
+    let config = ARBITER_CONFIG.may_load(deps.storage)?;
+    let arbiters = ARBITERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|entry| {
+            let (address, arbiter) = entry?;
+            let eligible = match &config {
+                Some(config) => arbiter_eligible(deps.storage, config, &address, &arbiter)?,
+                None => false,
+            };
+            Ok(ArbiterInfo { address, arbiter, eligible })
+        })
+        .collect::<StdResult<_>>()?;
+    to_json_binary(&ArbitersResponse { config, arbiters })
+}
+
+// RELAYED EXECUTION QUERIES
+
+fn query_signer(deps: Deps, username: String) -> StdResult<Binary> {
+    let pubkey = USERS_BY_USERNAME.may_load(deps.storage, username.clone())?.and_then(|user| user.pubkey);
+    let next_nonce = SIGNER_NONCES.may_load(deps.storage, username)?.unwrap_or_default();
+    to_json_binary(&SignerResponse { pubkey, next_nonce })
+}
+
+fn query_relayers(deps: Deps) -> StdResult<Binary> {
+    let relayers = RELAYERS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(MAX_QUERY_RESULTS)
+        .collect::<StdResult<Vec<_>>>()?;
+    to_json_binary(&RelayersResponse { relayers })
+}
+
+// USERNAME NFT QUERIES
+
+fn query_username_collection(deps: Deps) -> StdResult<Binary> {
+    let collection = USERNAME_COLLECTION.may_load(deps.storage)?;
+    to_json_binary(&UsernameCollectionResponse { collection })
+}
+
+fn query_username_transfer(deps: Deps, username: String) -> StdResult<Binary> {
+    let transfer = USERNAME_TRANSFERS.may_load(deps.storage, normalize_username(&username))?;
+    to_json_binary(&UsernameTransferResponse { transfer })
+}
+
+// PRICE ORACLE QUERIES
+
+fn query_price_oracle(deps: Deps) -> StdResult<Binary> {
+    let oracle = PRICE_ORACLE.may_load(deps.storage)?;
+    to_json_binary(&PriceOracleResponse { oracle })
+}
+
+fn query_feature_flags(deps: Deps) -> StdResult<Binary> {
+    let flags = FEATURE_FLAGS.may_load(deps.storage)?.unwrap_or_default();
+    to_json_binary(&FeatureFlagsResponse { flags })
+}
+
+fn query_policy_contract(deps: Deps) -> StdResult<Binary> {
+    let policy = POLICY_CONTRACT.may_load(deps.storage)?;
+    to_json_binary(&PolicyContractResponse { policy })
+}
+
+fn query_dex_router(deps: Deps) -> StdResult<Binary> {
+    let router = DEX_ROUTER.may_load(deps.storage)?;
+    to_json_binary(&DexRouterResponse { router })
+}
+
+fn query_error_codes() -> StdResult<Binary> {
+    let errors = ContractError::all()
+        .into_iter()
+        .map(|error| ErrorCode {
+            code: error.code(),
+            name: format!("{:?}", error),
+            message: error.to_string(),
+        })
+        .collect();
+    to_json_binary(&ErrorCodesResponse { errors })
+}
+
+fn query_contract_info(deps: Deps) -> StdResult<Binary> {
+    let version = cw2::get_contract_version(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+    let compiled_features = [
+        ("invariants", cfg!(feature = "invariants")),
+        ("proofpay-query", cfg!(feature = "proofpay-query")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(feature, _)| feature.to_string())
+    .collect();
+    to_json_binary(&ContractInfoResponse {
+        contract: version.contract,
+        version: version.version,
+        owner: state.owner,
+        pending_owner: state.pending_owner,
+        compiled_features,
+        feature_flags: FEATURE_FLAGS.may_load(deps.storage)?.unwrap_or_default(),
+        registration_fees: REGISTRATION_FEES.may_load(deps.storage)?,
+        users: USER_COUNT.may_load(deps.storage)?.unwrap_or(0),
+        payments: state.next_payment_id - 1,
+        tasks: state.next_task_id - 1,
+    })
+}
+
+// REGISTRATION FEE QUERIES
+
+fn query_registration_fees(deps: Deps) -> StdResult<Binary> {
+    let config = REGISTRATION_FEES.may_load(deps.storage)?;
+    to_json_binary(&RegistrationFeesResponse { config })
+}
+
+fn query_registration_fee(deps: Deps, username: String) -> StdResult<Binary> {
+    let username = normalize_username(&username);
+    let tier = registration_fee_tier(deps.storage, &username)?.map(|(tier, _)| tier);
+    let deposit_held = REGISTRATION_DEPOSITS.may_load(deps.storage, username.clone())?;
+    to_json_binary(&RegistrationFeeResponse { username, tier, deposit_held })
+}
+
+fn query_username_expiry(deps: Deps) -> StdResult<Binary> {
+    let config = USERNAME_EXPIRY.may_load(deps.storage)?;
+    to_json_binary(&UsernameExpiryResponse { config })
+}
+
+// YIELD ROUTING QUERIES
+
+fn query_yield_config(deps: Deps) -> StdResult<Binary> {
+    let config = YIELD_CONFIG.may_load(deps.storage)?;
+    to_json_binary(&YieldConfigResponse { config })
+}
+
+fn query_yield_position(deps: Deps, task_id: u64) -> StdResult<Binary> {
+    let position = YIELD_POSITIONS.may_load(deps.storage, task_id)?;
+    to_json_binary(&YieldPositionResponse { position })
+}
+
+// ESCROW ACCOUNTING QUERIES
+
+fn query_escrow_liabilities(deps: Deps) -> StdResult<Binary> {
+    let liabilities: StdResult<Vec<Coin>> = ESCROW_TOTALS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect();
+    let losses: StdResult<Vec<Coin>> = ESCROW_LOSSES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect();
+    to_json_binary(&EscrowLiabilitiesResponse { liabilities: liabilities?, losses: losses? })
+}
+
+fn query_user_escrowed(deps: Deps, username: String) -> StdResult<Binary> {
+    let normalized_username = normalize_username(&username);
+    let escrowed = user_escrowed(deps, &normalized_username)?;
+    to_json_binary(&UserEscrowedResponse { username: normalized_username, escrowed })
+}
+
+fn user_escrowed(deps: Deps, username: &str) -> StdResult<Vec<Coin>> {
+    USER_ESCROWED
+        .prefix(username.to_string())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect()
+}
+
+// MAINTENANCE QUERIES
+
+fn query_archive_root(deps: Deps) -> StdResult<Binary> {
+    let archive = ARCHIVE.may_load(deps.storage)?.unwrap_or_default();
+    to_json_binary(&ArchiveRootResponse {
+        root: archive.root,
+        archived_records: archive.archived_records,
+        last_archived_at: archive.last_archived_at,
+    })
+}
+
+fn query_index_report(deps: Deps) -> StdResult<Binary> {
+    let users = USERS_BY_USERNAME.keys(deps.storage, None, None, Order::Ascending).count() as u64;
+    let wallets = USERS_BY_WALLET.keys(deps.storage, None, None, Order::Ascending).count() as u64;
+    let payments = PAYMENTS.keys(deps.storage, None, None, Order::Ascending).count() as u64;
+    let user_payments = USER_PAYMENTS.keys(deps.storage, None, None, Order::Ascending).count() as u64
+        + WALLET_PAYMENT_REQUESTS.keys(deps.storage, None, None, Order::Ascending).count() as u64;
+    let tasks = TASKS.keys(deps.storage, None, None, Order::Ascending).count() as u64;
+    let user_tasks = USER_TASKS.keys(deps.storage, None, None, Order::Ascending).count() as u64;
+    let tasks_by_status = TASKS_BY_STATUS.keys(deps.storage, None, None, Order::Ascending).count() as u64;
+    let user_tasks_by_status = USER_TASKS_BY_STATUS.keys(deps.storage, None, None, Order::Ascending).count() as u64;
+    
+    // Every user has one wallet entry; every payment and task has one entry per party
+    // (the payer of a request awaiting registration is indexed by wallet).
+    // Each task has one status entry, plus one per party in the user status index.
+    let indexes = vec![
+        index_report(IndexKind::UsersByWallet, users, users, wallets),
+        index_report(IndexKind::UserPayments, payments, payments * 2, user_payments),
+        index_report(IndexKind::UserTasks, tasks, tasks * 2, user_tasks),
+        index_report(IndexKind::TasksByStatus, tasks, tasks, tasks_by_status),
+        index_report(IndexKind::UserTasksByStatus, tasks, tasks * 2, user_tasks_by_status),
+    ];
+    to_json_binary(&IndexReportResponse { indexes })
+}
+
+fn index_report(index: IndexKind, primary_count: u64, expected_entries: u64, index_entries: u64) -> IndexReport {
+    IndexReport {
+        index,
+        primary_count,
+        expected_entries,
+        index_entries,
+        in_sync: expected_entries == index_entries,
+    }
+}
+
+// INVARIANT QUERIES
+
+// Default and maximum number of entries scanned per invariant check
+#[cfg(any(test, feature = "invariants"))]
+const DEFAULT_INVARIANT_LIMIT: u32 = 100;
+#[cfg(any(test, feature = "invariants"))]
+const MAX_INVARIANT_LIMIT: u32 = 1000;
+
+#[cfg(any(test, feature = "invariants"))]
+fn query_verify_invariants(deps: Deps, limit: Option<u32>) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_INVARIANT_LIMIT).min(MAX_INVARIANT_LIMIT) as usize;
+    let mut violations = vec![];
+    let mut complete = true;
+    
+    // Escrow totals hold every user's escrow, active campaign donations, arbiter bonds, registration deposits
+    // and the insurance pool
+    let user_escrowed = scan_bounded(USER_ESCROWED.range(deps.storage, None, None, Order::Ascending), limit)?;
+    let campaigns = scan_bounded(CAMPAIGNS.range(deps.storage, None, None, Order::Ascending), limit)?;
+    let arbiters = scan_bounded(ARBITERS.range(deps.storage, None, None, Order::Ascending), limit)?;
+    let deposits = scan_bounded(REGISTRATION_DEPOSITS.range(deps.storage, None, None, Order::Ascending), limit)?;
+    let insurance_pool = scan_bounded(INSURANCE_POOL.range(deps.storage, None, None, Order::Ascending), limit)?;
+    let totals = scan_bounded(ESCROW_TOTALS.range(deps.storage, None, None, Order::Ascending), limit)?;
+    if let (Some(user_escrowed), Some(campaigns), Some(arbiters), Some(deposits), Some(insurance_pool), Some(totals)) =
+        (user_escrowed, campaigns, arbiters, deposits, insurance_pool, totals)
+    {
+        let mut expected: std::collections::BTreeMap<String, Uint128> = std::collections::BTreeMap::new();
+        for ((_, denom), amount) in user_escrowed {
+            *expected.entry(denom).or_default() += amount;
+        }
+        for (_, campaign) in campaigns {
+            if matches!(campaign.status, CampaignStatus::Active) {
+                *expected.entry(campaign.goal.denom).or_default() += campaign.raised;
+            }
+        }
+        for (_, arbiter) in arbiters {
+            *expected.entry(arbiter.bond.denom).or_default() += arbiter.bond.amount;
+        }
+        for (_, deposit) in deposits {
+            *expected.entry(deposit.denom).or_default() += deposit.amount;
+        }
+        for (denom, amount) in insurance_pool {
+            *expected.entry(denom).or_default() += amount;
+        }
+        let recorded: std::collections::BTreeMap<String, Uint128> = totals.into_iter().collect();
+        let denoms: std::collections::BTreeSet<&String> = expected.keys().chain(recorded.keys()).collect();
+        for denom in denoms {
+            let expected = expected.get(denom).copied().unwrap_or_default();
+            let recorded = recorded.get(denom).copied().unwrap_or_default();
+            if expected != recorded {
+                violations.push(InvariantViolation {
+                    invariant: "escrow_totals".to_string(),
+                    key: denom.clone(),
+                    detail: format!("expected {}, recorded {}", expected, recorded),
+                });
+            }
+        }
+    } else {
+        complete = false;
+    }
     
-    // Get all tasks for this user that are pending
-    for item in USER_TASKS.prefix(username).range(deps.storage, None, None, Order::Ascending) {
-        let (task_id, _) = item?;
-        if let Ok(task) = TASKS.load(deps.storage, task_id) {
-            if matches!(task.status, TaskStatus::Escrowed | TaskStatus::ProofSubmitted | TaskStatus::PendingRelease) {
-                tasks.push(task);
+    // Friendships are stored in both directions
+    let friendships = FRIENDSHIPS.keys(deps.storage, None, None, Order::Ascending);
+    match scan_bounded(friendships, limit)? {
+        Some(keys) => {
+            for (user, friend) in keys {
+                if !FRIENDSHIPS.has(deps.storage, (friend.clone(), user.clone())) {
+                    violations.push(InvariantViolation {
+                        invariant: "friendship_symmetry".to_string(),
+                        key: format!("{}:{}", user, friend),
+                        detail: "reverse direction missing".to_string(),
+                    });
+                }
+            }
+        }
+        None => complete = false,
+    }
+    
+    // Every user's wallet maps back to its username
+    match scan_bounded(USERS_BY_USERNAME.range(deps.storage, None, None, Order::Ascending), limit)? {
+        Some(users) => {
+            for (username, user) in users {
+                let mapped = USERS_BY_WALLET.may_load(deps.storage, user.wallet_address.clone())?;
+                if mapped.as_ref() != Some(&username) {
+                    violations.push(InvariantViolation {
+                        invariant: "username_maps".to_string(),
+                        key: username,
+                        detail: format!("wallet {} maps to {}", user.wallet_address, mapped.unwrap_or_else(|| "nothing".to_string())),
+                    });
+                }
             }
         }
+        None => complete = false,
     }
     
-    to_json_binary(&crate::msg::TasksResponse { tasks })
+    to_json_binary(&InvariantsResponse { complete, violations })
+}
+
+// Helper function to collect up to `limit` entries; None when more remain
+#[cfg(any(test, feature = "invariants"))]
+fn scan_bounded<T>(entries: impl Iterator<Item = StdResult<T>>, limit: usize) -> StdResult<Option<Vec<T>>> {
+    let items = entries.take(limit + 1).collect::<StdResult<Vec<T>>>()?;
+    Ok(if items.len() > limit { None } else { Some(items) })
+}
+
+// ANALYTICS QUERIES
+
+// Default and maximum number of activity entries returned per export page
+const DEFAULT_EXPORT_LIMIT: u32 = 100;
+const MAX_EXPORT_LIMIT: u32 = 500;
+
+fn query_export_events(deps: Deps, since_seq: Option<u64>, limit: Option<u32>) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_EXPORT_LIMIT).min(MAX_EXPORT_LIMIT) as usize;
+    let start = since_seq.map(Bound::exclusive);
+    
+    let events: Vec<ActivityEntry> = ACTIVITY_LOG
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, entry)| entry))
+        .collect::<StdResult<_>>()?;
+    let latest_seq = ACTIVITY_SEQ.may_load(deps.storage)?.unwrap_or_default();
+    
+    to_json_binary(&ExportEventsResponse { events, latest_seq })
+}
+
+// NOTIFICATION QUERIES
+
+// Default and maximum number of notifications returned per page
+const DEFAULT_NOTIFICATION_LIMIT: u32 = 30;
+const MAX_NOTIFICATION_LIMIT: u32 = 100;
+
+fn query_notifications(deps: Deps, username: String, start_after: Option<u64>, limit: Option<u32>) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_NOTIFICATION_LIMIT).min(MAX_NOTIFICATION_LIMIT) as usize;
+    let acked_up_to = NOTIFICATIONS_ACKED.may_load(deps.storage, username.clone())?.unwrap_or_default();
+    // Acknowledged notifications not yet pruned are skipped
+    let start = start_after.unwrap_or_default().max(acked_up_to);
+    
+    let notifications: Vec<Notification> = NOTIFICATIONS
+        .prefix(username)
+        .range(deps.storage, Some(Bound::exclusive(start)), None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, notification)| notification))
+        .collect::<StdResult<_>>()?;
+    
+    to_json_binary(&NotificationsResponse { notifications, acked_up_to })
+}
+
+// ACCOUNT SNAPSHOT QUERIES
+
+// Default and maximum number of recent payments and tasks returned per snapshot page
+const DEFAULT_SNAPSHOT_LIMIT: u32 = 10;
+const MAX_SNAPSHOT_LIMIT: u32 = 50;
+
+fn query_account_snapshot(
+    deps: Deps,
+    username: String,
+    viewer: Option<String>,
+    limit: Option<u32>,
+    payments_before: Option<u64>,
+    tasks_before: Option<u64>,
+) -> StdResult<Binary> {
+    let normalized_username = normalize_username(&username);
+    let user = USERS_BY_USERNAME.load(deps.storage, normalized_username.clone())?;
+    let limit = limit.unwrap_or(DEFAULT_SNAPSHOT_LIMIT).min(MAX_SNAPSHOT_LIMIT) as usize;
+    
+    // Newest first; fetch one extra id to know whether an older page exists
+    let payment_ids: Vec<u64> = USER_PAYMENTS
+        .prefix(normalized_username.clone())
+        .keys(deps.storage, None, payments_before.map(Bound::exclusive), Order::Descending)
+        .take(limit + 1)
+        .collect::<StdResult<_>>()?;
+    let next_payments_before = recent_page_cursor(&payment_ids, limit);
+    let recent_payments = payment_ids.into_iter()
+        .take(limit)
+        .filter_map(|id| load_visible_payment(deps, id, viewer.as_deref()).transpose())
+        .collect::<StdResult<_>>()?;
+    
+    let task_ids: Vec<u64> = USER_TASKS
+        .prefix(normalized_username.clone())
+        .keys(deps.storage, None, tasks_before.map(Bound::exclusive), Order::Descending)
+        .take(limit + 1)
+        .collect::<StdResult<_>>()?;
+    let next_tasks_before = recent_page_cursor(&task_ids, limit);
+    let recent_tasks = task_ids.into_iter()
+        .take(limit)
+        .map(|id| TASKS.load(deps.storage, id))
+        .collect::<StdResult<_>>()?;
+    
+    to_json_binary(&AccountSnapshotResponse {
+        user,
+        friends: user_friends(deps, normalized_username.clone())?,
+        pending_friend_requests: pending_friend_requests(deps, normalized_username.clone(), None)?,
+        recent_payments,
+        next_payments_before,
+        recent_tasks,
+        next_tasks_before,
+        escrowed: user_escrowed(deps, &normalized_username)?,
+    })
+}
+
+// OPEN ITEM QUERIES
+
+fn query_open_items(deps: Deps, username: String) -> StdResult<Binary> {
+    let username = normalize_username(&username);
+    let open_count = |counts: &Map<String, u64>| -> StdResult<u64> {
+        Ok(counts.may_load(deps.storage, username.clone())?.unwrap_or(0))
+    };
+    to_json_binary(&OpenItemsResponse {
+        friend_requests: open_count(&SENT_FRIEND_REQUEST_COUNTS)?,
+        payment_requests: open_count(&OPEN_PAYMENT_REQUEST_COUNTS)?,
+        tasks: open_count(&OPEN_TASK_COUNTS)?,
+        limits: CONFIG.may_load(deps.storage)?.unwrap_or_default().open_item_limits,
+    })
+}
+
+// ACTION ITEM QUERIES
+
+fn query_action_items(deps: Deps, username: String) -> StdResult<Binary> {
+    let username = normalize_username(&username);
+    
+    let friend_requests = PENDING_FRIEND_REQUESTS
+        .prefix(username.clone())
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(|from_username| {
+            Ok(Some(ActionItem {
+                kind: ActionItemKind::FriendRequest,
+                id: None,
+                counterparty: from_username?,
+                amount: None,
+                deadline: None,
+            }))
+        });
+    
+    // Only tasks in a status someone can act on are scanned, through the per-user status index
+    let actionable = [
+        TaskStatus::Escrowed,
+        TaskStatus::ProofSubmitted,
+        TaskStatus::PendingRelease,
+        TaskStatus::ApprovedPendingFunding,
+        TaskStatus::Disputed,
+    ];
+    let tasks = actionable
+        .into_iter()
+        .flat_map(|status| {
+            USER_TASKS_BY_STATUS
+                .prefix((username.clone(), status.key().to_string()))
+                .keys(deps.storage, None, None, Order::Ascending)
+        })
+        .map(|task_id| Ok(task_action_item(&TASKS.load(deps.storage, task_id?)?, &username)));
+    
+    // Newest first, so a long settled history does not hide recent requests
+    let payments = USER_PAYMENTS
+        .prefix(username.clone())
+        .keys(deps.storage, None, None, Order::Descending)
+        .map(|payment_id| Ok(payment_action_item(&PAYMENTS.load(deps.storage, payment_id?)?, &username, None)));
+    
+    let page = collect_bounded(friend_requests.chain(tasks).chain(payments), Ok)?;
+    to_json_binary(&ActionItemsResponse {
+        items: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+fn query_wallet_action_items(deps: Deps, wallet_address: String) -> StdResult<Binary> {
+    let wallet = validate_query_address(deps.api, &wallet_address)?;
+    let payments = WALLET_PAYMENT_REQUESTS
+        .prefix(wallet.clone())
+        .keys(deps.storage, None, None, Order::Descending)
+        .map(|payment_id| Ok(payment_action_item(&PAYMENTS.load(deps.storage, payment_id?)?, "", Some(&wallet))));
+    
+    let page = collect_bounded(payments, Ok)?;
+    to_json_binary(&ActionItemsResponse {
+        items: page.items,
+        truncated: page.truncated,
+        scan_budget_exhausted: page.scan_budget_exhausted,
+    })
+}
+
+// Helper function to describe what a task in an actionable status needs from `username`, if anything
+fn task_action_item(task: &Task, username: &str) -> Option<ActionItem> {
+    let is_payer = task.payer == username;
+    // Soft tasks start out in ProofSubmitted, but wait on the worker until evidence arrives
+    let awaiting_evidence = matches!(task.proof_type, ProofType::Soft) && task.evidence_hash.is_none();
+    let (kind, deadline) = match task.status {
+        TaskStatus::Escrowed if !is_payer => (ActionItemKind::ProofDue, Some(task.deadline_ts)),
+        TaskStatus::ProofSubmitted if awaiting_evidence => {
+            if is_payer {
+                return None;
+            }
+            (ActionItemKind::ProofDue, Some(task.deadline_ts))
+        }
+        TaskStatus::ProofSubmitted if is_payer => {
+            let auto_approves_at = task.evidence_submitted_at.zip(task.auto_approve_after_secs).map(|(at, secs)| at + secs);
+            (ActionItemKind::ProofReview, auto_approves_at)
+        }
+        TaskStatus::PendingRelease if is_payer => (ActionItemKind::ProofReview, dispute_window_end(task)),
+        TaskStatus::ApprovedPendingFunding if is_payer => (ActionItemKind::FundingDue, None),
+        TaskStatus::Disputed => (ActionItemKind::Dispute, None),
+        _ => return None,
+    };
+    Some(ActionItem {
+        kind,
+        id: Some(task.id),
+        counterparty: if is_payer { task.worker.clone() } else { task.payer.clone() },
+        amount: Some(task.amount.clone()),
+        deadline,
+    })
+}
+
+// Helper function to describe what an open payment needs from `username`, or from `wallet`
+// for requests to a wallet without a username, if anything
+fn payment_action_item(payment: &Payment, username: &str, wallet: Option<&Addr>) -> Option<ActionItem> {
+    let is_sender = payment.from_username == username;
+    let is_payer = match &payment.to_wallet {
+        Some(to_wallet) => wallet == Some(to_wallet),
+        None => payment.to_username == username,
+    };
+    let proof_required = !matches!(payment.proof_type, ProofType::None);
+    let kind = match (&payment.payment_type, &payment.status) {
+        (PaymentType::PaymentRequest, PaymentStatus::Pending | PaymentStatus::ProofSubmitted) if is_payer => {
+            ActionItemKind::PaymentRequest
+        }
+        (PaymentType::DirectPayment, PaymentStatus::Pending) if !is_sender && proof_required => ActionItemKind::ProofDue,
+        (PaymentType::DirectPayment, PaymentStatus::ProofSubmitted | PaymentStatus::PendingRelease) if is_sender => {
+            ActionItemKind::ProofReview
+        }
+        _ => return None,
+    };
+    Some(ActionItem {
+        kind,
+        id: Some(payment.id),
+        counterparty: if is_sender { payment.to_username.clone() } else { payment.from_username.clone() },
+        amount: Some(payment.amount.clone()),
+        deadline: payment.release_at,
+    })
+}
+
+// Helper function to derive the cursor for the next (older) page of a newest-first id list
+fn recent_page_cursor(ids: &[u64], limit: usize) -> Option<u64> {
+    if ids.len() > limit {
+        ids.get(limit.checked_sub(1)?).copied()
+    } else {
+        None
+    }
 }
@@ -3,128 +3,863 @@ use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
-    #[error("{0}")]
+    #[error("[E0001] {0}")]
     Std(#[from] StdError),
     
     // User Management Errors
-    #[error("Username already taken")]
+    #[error("[E0101] Username already taken")]
     UsernameAlreadyTaken {},
     
-    #[error("User not found")]
+    #[error("[E0102] User not found")]
     UserNotFound {},
     
-    #[error("Invalid username format")]
+    #[error("[E0103] Invalid username format")]
     InvalidUsername {},
     
-    #[error("Wallet already registered")]
+    #[error("[E0104] Bio must be at most 280 characters")]
+    InvalidBio {},
+    
+    #[error("[E0105] Profiles take up to 5 https:// links of at most 200 characters")]
+    InvalidProfileLinks {},
+    
+    #[error("[E0106] Country must be a two-letter ISO 3166-1 code")]
+    InvalidCountryCode {},
+    
+    #[error("[E0107] Invalid timezone name")]
+    InvalidTimezone {},
+    
+    #[error("[E0108] Wallet already registered")]
     WalletAlreadyRegistered {},
     
-    #[error("User not registered")]
+    #[error("[E0109] User not registered")]
     UserNotRegistered {},
     
+    // Registration Fee Errors
+    #[error("[E0201] Registration fee tiers must have non-zero amounts and strictly increasing max lengths")]
+    InvalidRegistrationFees {},
+    
+    #[error("[E0202] Cannot deactivate while funds are escrowed, a task or payment is unsettled, a username transfer is pending or the username is tokenized")]
+    DeactivationBlocked {},
+    
+    // Username Expiry Errors
+    #[error("[E0301] Username registration has expired; renew it to continue")]
+    UsernameExpired {},
+    
+    #[error("[E0302] Username registration does not expire")]
+    UsernameDoesNotExpire {},
+    
+    #[error("[E0303] Username expiry term must be at least 1 second")]
+    InvalidUsernameExpiry {},
+    
+    // Wallet Payment Request Errors
+    #[error("[E0401] You have too many open payment requests awaiting this wallet's registration")]
+    TooManyWalletPaymentRequests {},
+    
+    // Text Length Errors
+    #[error("[E0501] Description exceeds the configured maximum length")]
+    DescriptionTooLong {},
+    
+    #[error("[E0502] Proof or evidence exceeds the configured maximum length")]
+    EvidenceTooLong {},
+    
+    #[error("[E0503] Endpoint exceeds the configured maximum length")]
+    EndpointTooLong {},
+    
+    #[error("[E0504] Text limits must be at least 1")]
+    InvalidTextLimits {},
+    
+    // Address Errors
+    #[error("[E0601] Invalid address")]
+    InvalidAddress {},
+    
+    // Username NFT Errors
+    #[error("[E0701] Username collection is not configured")]
+    UsernameCollectionNotConfigured {},
+    
+    #[error("[E0702] Username collection is already set")]
+    UsernameCollectionAlreadySet {},
+    
+    #[error("[E0703] Username is already tokenized")]
+    UsernameAlreadyTokenized {},
+    
+    #[error("[E0704] Username is not tokenized")]
+    UsernameNotTokenized {},
+    
+    #[error("[E0705] No pending transfer for this username")]
+    UsernameTransferNotFound {},
+    
+    #[error("[E0706] Username transfer delay has not elapsed")]
+    UsernameTransferNotReady {},
+    
     // Friends System Errors
-    #[error("Cannot send friend request to yourself")]
+    #[error("[E0801] Cannot send friend request to yourself")]
     CannotAddSelf {},
     
-    #[error("Friend request already exists")]
+    #[error("[E0802] Friend request already exists")]
     FriendRequestAlreadyExists {},
     
-    #[error("Friend request not found")]
+    #[error("[E0803] Friend request not found")]
     FriendRequestNotFound {},
     
-    #[error("Users are already friends")]
+    #[error("[E0804] Friend request was declined recently; try again later")]
+    FriendRequestCooldown {},
+    
+    #[error("[E0805] Friend request has been declined too many times")]
+    FriendRequestBlocked {},
+    
+    #[error("[E0806] Users are already friends")]
     AlreadyFriends {},
     
-    #[error("Users are not friends")]
+    #[error("[E0807] Users are not friends")]
     NotFriends {},
     
-    #[error("Cannot send friend request to non-friend")]
+    #[error("[E0808] Friend request messages must be at most 140 characters")]
+    InvalidFriendRequestMessage {},
+    
+    #[error("[E0809] Cannot send friend request to non-friend")]
     CannotRequestNonFriend {},
     
+    // Friend Group Errors
+    #[error("[E0901] Group not found")]
+    GroupNotFound {},
+    
+    #[error("[E0902] Group names must be 1-32 characters")]
+    InvalidGroupName {},
+    
+    #[error("[E0903] You already have a group with this name")]
+    GroupNameTaken {},
+    
+    #[error("[E0904] Only the group owner can do this")]
+    OnlyGroupOwner {},
+    
+    #[error("[E0905] User is already in this group")]
+    AlreadyGroupMember {},
+    
+    #[error("[E0906] User is not in this group")]
+    NotGroupMember {},
+    
+    #[error("[E0907] Group is full")]
+    GroupFull {},
+    
+    #[error("[E0908] The group owner cannot be removed")]
+    CannotRemoveGroupOwner {},
+    
+    #[error("[E0909] Expenses must be split among distinct group members")]
+    InvalidExpenseSplit {},
+    
+    #[error("[E0910] Member has an unsettled group balance")]
+    GroupBalanceOutstanding {},
+    
+    #[error("[E0911] Group ledger is already settled")]
+    NothingToSettle {},
+    
+    // Campaign Errors
+    #[error("[E1001] Campaign not found")]
+    CampaignNotFound {},
+    
+    #[error("[E1002] Campaign titles must not be empty")]
+    InvalidCampaignTitle {},
+    
+    #[error("[E1003] Campaign deadline must be in the future")]
+    InvalidCampaignDeadline {},
+    
+    #[error("[E1004] Campaign is not accepting donations")]
+    CampaignClosed {},
+    
+    #[error("[E1005] Campaign funds cannot be withdrawn yet")]
+    CampaignNotWithdrawable {},
+    
+    #[error("[E1006] No refund available for this campaign")]
+    NoCampaignRefund {},
+    
+    // Subscription Errors
+    #[error("[E1101] Subscription not found")]
+    SubscriptionNotFound {},
+    
+    #[error("[E1102] Subscriptions need a non-zero amount and period and a supported number of funded periods")]
+    InvalidSubscription {},
+    
+    #[error("[E1103] Subscription is not active")]
+    SubscriptionNotActive {},
+    
+    #[error("[E1104] No matured subscription periods to claim")]
+    NothingToClaim {},
+    
+    // Invoice Errors
+    #[error("[E1201] Invoice not found")]
+    InvoiceNotFound {},
+    
+    #[error("[E1202] Invoices need between 1 and 50 line items, each with a description and a non-zero amount")]
+    InvalidInvoiceLineItems {},
+    
+    #[error("[E1203] Invoice due date must be in the future")]
+    InvalidInvoiceDueDate {},
+    
+    #[error("[E1204] Invoice is not open")]
+    InvoiceNotOpen {},
+    
+    // Payment Link Errors
+    #[error("[E1301] Payment link not found")]
+    PaymentLinkNotFound {},
+    
+    #[error("[E1302] Payment links need a non-zero amount if fixed, at least one use and a future expiry")]
+    InvalidPaymentLink {},
+    
+    #[error("[E1303] Payment link is disabled, expired or used up")]
+    PaymentLinkInactive {},
+    
+    // Scheduled Payment Errors
+    #[error("[E1401] Scheduled payment not found")]
+    ScheduledPaymentNotFound {},
+    
+    #[error("[E1402] Scheduled payments need a non-zero amount and a future execution time")]
+    InvalidScheduledPayment {},
+    
+    #[error("[E1403] Scheduled payment is no longer pending")]
+    ScheduledPaymentNotPending {},
+    
+    #[error("[E1404] Scheduled payment is not due yet")]
+    ScheduledPaymentNotDue {},
+    
+    // Gift Code Errors
+    #[error("[E1501] Gift code not found")]
+    GiftCodeNotFound {},
+    
+    #[error("[E1502] Gift codes need a non-zero amount, a hex SHA-256 code hash and a future expiry")]
+    InvalidGiftCode {},
+    
+    #[error("[E1503] An active gift code already uses this code hash")]
+    GiftCodeHashInUse {},
+    
+    #[error("[E1504] Gift code is expired")]
+    GiftCodeExpired {},
+    
+    #[error("[E1505] Gift code has not expired yet")]
+    GiftCodeNotExpired {},
+    
+    #[error("[E1506] Gift code is no longer active")]
+    GiftCodeNotActive {},
+    
+    // HTLC Errors
+    #[error("[E1601] HTLC not found")]
+    HtlcNotFound {},
+    
+    #[error("[E1602] HTLCs need a non-zero amount, a hex SHA-256 hashlock and a future timelock")]
+    InvalidHtlc {},
+    
+    #[error("[E1603] Preimage does not match the hashlock")]
+    InvalidPreimage {},
+    
+    #[error("[E1604] HTLC timelock has passed")]
+    HtlcExpired {},
+    
+    #[error("[E1605] HTLC timelock has not passed yet")]
+    HtlcNotExpired {},
+    
+    #[error("[E1606] HTLC is no longer locked")]
+    HtlcNotLocked {},
+    
     // Payment System Errors
-    #[error("Payment not found")]
+    #[error("[E1701] Payment not found")]
     PaymentNotFound {},
     
-    #[error("Not authorized to access this payment")]
+    #[error("[E1702] Not authorized to access this payment")]
     PaymentNotAuthorized {},
     
-    #[error("Payment already completed")]
+    #[error("[E1703] Payment already completed")]
     PaymentAlreadyCompleted {},
     
-    #[error("Payment already cancelled")]
+    #[error("[E1704] Payment already cancelled")]
     PaymentAlreadyCancelled {},
     
-    #[error("Cannot send payment to yourself")]
+    #[error("[E1705] Payment already rejected")]
+    PaymentAlreadyRejected {},
+    
+    #[error("[E1706] Cannot send payment to yourself")]
     CannotPaySelf {},
     
-    #[error("Insufficient funds")]
+    #[error("[E1707] Insufficient funds")]
     InsufficientFunds {},
     
-    #[error("Invalid payment amount")]
+    #[error("[E1708] Unexpected funds attached")]
+    UnexpectedFunds {},
+    
+    #[error("[E1709] Invalid payment amount")]
     InvalidPaymentAmount {},
     
-    #[error("Proof already submitted")]
+    #[error("[E1710] Denom is not accepted")]
+    DenomNotAccepted {},
+    
+    #[error("[E1711] Invalid price quote")]
+    InvalidPriceQuote {},
+    
+    #[error("[E1712] No price oracle configured")]
+    PriceOracleNotConfigured {},
+    
+    #[error("[E1713] Oracle price is stale")]
+    StalePrice {},
+    
+    #[error("[E1714] Invalid external reference")]
+    InvalidExternalRef {},
+    
+    #[error("[E1715] External reference already used")]
+    ExternalRefTaken {},
+    
+    #[error("[E1716] Invalid idempotency key")]
+    InvalidIdempotencyKey {},
+    
+    #[error("[E1717] Idempotency key already used for a different kind of record")]
+    IdempotencyKeyConflict {},
+    
+    #[error("[E1718] Payment already approved")]
+    PaymentAlreadyApproved {},
+    
+    #[error("[E1719] Payment is not waiting for release")]
+    PaymentNotPendingRelease {},
+    
+    // Insurance Pool Errors
+    #[error("[E1801] Invalid insurance config")]
+    InvalidInsuranceConfig {},
+    
+    #[error("[E1802] Insurance is not available")]
+    InsuranceDisabled {},
+    
+    #[error("[E1803] Task is already insured")]
+    TaskAlreadyInsured {},
+    
+    #[error("[E1804] Task cannot be insured")]
+    TaskNotInsurable {},
+    
+    #[error("[E1805] No open insurance claim for this task")]
+    NoInsuranceClaim {},
+    
+    #[error("[E1806] Payout exceeds the claim cap")]
+    InvalidInsurancePayout {},
+    
+    #[error("[E1807] Insurance pool balance too low")]
+    InsufficientInsurancePool {},
+    
+    // Arbiter Errors
+    #[error("[E1901] Arbiter bonding is not enabled")]
+    ArbitersDisabled {},
+    
+    #[error("[E1902] Invalid arbiter config")]
+    InvalidArbiterConfig {},
+    
+    #[error("[E1903] Too many bonded arbiters")]
+    TooManyArbiters {},
+    
+    #[error("[E1904] Not a bonded arbiter")]
+    ArbiterNotBonded {},
+    
+    #[error("[E1905] Arbiter still has open disputes")]
+    ArbiterHasOpenDisputes {},
+    
+    #[error("[E1906] Only the task's arbiter can resolve this dispute")]
+    NotTaskArbiter {},
+    
+    #[error("[E1907] Arbiters can only be named on hybrid tasks and cannot be the payer or worker")]
+    InvalidArbiter {},
+    
+    #[error("[E1908] The task's arbiter has not accepted yet")]
+    ArbitrationNotAccepted {},
+    
+    // Relayed Execution Errors
+    #[error("[E2001] Sender is not an authorized relayer")]
+    RelayerNotAuthorized {},
+    
+    #[error("[E2002] Invalid public key")]
+    InvalidPubkey {},
+    
+    #[error("[E2003] No signing key registered")]
+    SigningKeyNotSet {},
+    
+    #[error("[E2004] Invalid signed payload")]
+    InvalidSignedPayload {},
+    
+    #[error("[E2005] Invalid signature")]
+    InvalidSignature {},
+    
+    #[error("[E2006] Invalid nonce")]
+    InvalidNonce {},
+    
+    #[error("[E2007] Proof already submitted")]
     ProofAlreadySubmitted {},
     
-    #[error("No proof required for this payment")]
+    #[error("[E2008] No proof required for this payment")]
     NoProofRequired {},
     
-    #[error("Proof required before approval")]
+    #[error("[E2009] Proof required before approval")]
     ProofRequired {},
     
-    #[error("Invalid proof type")]
+    #[error("[E2010] Invalid proof type")]
     InvalidProofType {},
     
+    #[error("[E2011] Invalid category")]
+    InvalidCategory {},
+    
+    #[error("[E2012] Invalid tags")]
+    InvalidTags {},
+    
+    #[error("[E2013] Only pending payment requests can be reminded")]
+    ReminderNotAllowed {},
+    
+    #[error("[E2014] A reminder was sent recently")]
+    ReminderCooldown {},
+    
+    #[error("[E2015] Reminder limit reached")]
+    ReminderLimitReached {},
+    
     // Authorization Errors
-    #[error("Not authorized")]
+    #[error("[E2101] Not authorized")]
     NotAuthorized {},
     
-    #[error("Only payment sender can cancel")]
+    #[error("[E2102] Only payment sender can cancel")]
     OnlySenderCanCancel {},
     
-    #[error("Only payment recipient can approve")]
+    #[error("[E2103] Only payment recipient can approve")]
     OnlyRecipientCanApprove {},
     
+    #[error("[E2104] A reason is required for admin interventions")]
+    AdminReasonRequired {},
+    
+    #[error("[E2105] Payment holds no escrow to release")]
+    NoEscrowToRelease {},
+    
+    #[error("[E2106] Invalid index rebuild cursor")]
+    InvalidIndexCursor {},
+    
+    #[error("[E2107] Address is already the owner")]
+    AlreadyOwner {},
+    
+    #[error("[E2108] No ownership transfer pending")]
+    NoPendingOwnershipTransfer {},
+    
+    #[error("[E2109] Address already holds this role")]
+    RoleAlreadyGranted {},
+    
+    #[error("[E2110] Address does not hold this role")]
+    RoleNotGranted {},
+    
+    // Privacy Errors
+    #[error("[E2201] Record must be in a final state")]
+    RecordNotFinal {},
+    
+    #[error("[E2202] Record text already redacted")]
+    AlreadyRedacted {},
+    
+    // Feature Flag Errors
+    #[error("[E2301] This payment type is disabled")]
+    PaymentTypeDisabled {},
+    
+    #[error("[E2302] This proof type is disabled")]
+    ProofTypeDisabled {},
+    
+    // Policy Contract Errors
+    #[error("[E2401] Denied by the policy contract")]
+    PolicyDenied {},
+    
+    // Yield Routing Errors
+    #[error("[E2501] Yield user share must be at most 10000 basis points")]
+    InvalidYieldShare {},
+    
+    // DEX Router Errors
+    #[error("[E2601] Payout swap needs a denom and a non-zero minimum output")]
+    InvalidPayoutSwap {},
+    
+    #[error("[E2602] Payout slippage must be at most 1000 basis points")]
+    InvalidPayoutSlippage {},
+    
     // Task System Errors
-    #[error("Task not found")]
+    #[error("[E2701] Task not found")]
     TaskNotFound {},
     
-    #[error("Not authorized to access this task")]
+    #[error("[E2702] Not authorized to access this task")]
     TaskNotAuthorized {},
     
-    #[error("Task already completed")]
+    #[error("[E2703] Task already completed")]
     TaskAlreadyCompleted {},
     
-    #[error("Task already disputed")]
+    #[error("[E2704] Task already disputed")]
     TaskAlreadyDisputed {},
     
-    #[error("Task deadline expired")]
+    #[error("[E2705] Task deadline expired")]
     TaskExpired {},
     
-    #[error("Task not in dispute")]
+    #[error("[E2706] Task not in dispute")]
     TaskNotInDispute {},
     
-    #[error("Dispute window has not elapsed")]
+    #[error("[E2707] Dispute window has not elapsed")]
     DisputeWindowNotElapsed {},
     
-    #[error("Invalid proof")]
+    #[error("[E2708] Dispute window has closed")]
+    DisputeWindowClosed {},
+    
+    #[error("[E2709] Task deadline has not passed")]
+    TaskNotExpired {},
+    
+    #[error("[E2710] Invalid proof")]
     InvalidProof {},
     
-    #[error("zkTLS verification failed")]
+    #[error("[E2711] zkTLS verification failed")]
     ZkTlsVerificationFailed {},
     
-    #[error("Only payer can approve soft tasks")]
+    #[error("[E2712] Only payer can approve soft tasks")]
     OnlyPayerCanApproveSoft {},
     
-    #[error("Only payer can dispute tasks")]
+    #[error("[E2713] Only payer can dispute tasks")]
     OnlyPayerCanDispute {},
     
-    #[error("Only owner can resolve disputes")]
+    #[error("[E2714] Only the owner or a dispute resolver can resolve disputes")]
     OnlyOwnerCanResolveDispute {},
     
-    #[error("Cannot create task with yourself")]
+    #[error("[E2715] Worker share must be between 0 and 10000 basis points")]
+    InvalidWorkerShare {},
+    
+    #[error("[E2716] Tasks above the co-approval threshold need co-approvers")]
+    CoApproversRequired {},
+    
+    #[error("[E2717] Co-approvers must be up to 10 distinct registered users other than the payer and worker")]
+    InvalidCoApprovers {},
+    
+    #[error("[E2718] Required approvals must be between 1 and the number of co-approvers")]
+    InvalidApprovalThreshold {},
+    
+    #[error("[E2719] Only a designated co-approver can approve this task")]
+    NotCoApprover {},
+    
+    #[error("[E2720] Co-approver has already approved this task")]
+    AlreadyCoApproved {},
+    
+    #[error("[E2721] Task is waiting for co-approvals")]
+    CoApprovalsPending {},
+    
+    #[error("[E2722] Cancellation already requested")]
+    CancelAlreadyRequested {},
+    
+    #[error("[E2723] Task has already been accepted or started")]
+    TaskAlreadyStarted {},
+    
+    #[error("[E2724] Counter-offers must be higher than the task amount, in the same denom")]
+    InvalidCounterOffer {},
+    
+    #[error("[E2725] No counter-offer pending")]
+    NoCounterOffer {},
+    
+    #[error("[E2726] Amendments must change the amount, scope or deadline")]
+    InvalidAmendment {},
+    
+    #[error("[E2727] Tasks can only depend on an unrefunded task of the same payer, and must escrow their funds")]
+    InvalidTaskDependency {},
+    
+    #[error("[E2728] Task already has the maximum number of dependent tasks")]
+    TooManyTaskDependents {},
+    
+    #[error("[E2729] Task batches need 1 to 20 distinct workers and must escrow their funds")]
+    InvalidTaskBatch {},
+    
+    #[error("[E2730] Task batch not found")]
+    TaskBatchNotFound {},
+    
+    #[error("[E2731] An amendment is already pending")]
+    AmendmentPending {},
+    
+    #[error("[E2732] No amendment pending")]
+    NoAmendmentPending {},
+    
+    #[error("[E2733] Task amendment limit reached")]
+    AmendmentLimitReached {},
+    
+    #[error("[E2734] Cannot create task with yourself")]
     CannotCreateTaskWithSelf {},
     
-    #[error("Invalid task deadline")]
+    #[error("[E2735] Invalid task deadline")]
     InvalidTaskDeadline {},
+    
+    #[error("[E2736] Review window is outside the allowed bounds")]
+    InvalidReviewWindow {},
+    
+    #[error("[E2737] Release delay must be between 1 second and 30 days and needs an escrowed task or payment")]
+    InvalidReleaseDelay {},
+    
+    // Open Item Limit Errors
+    #[error("[E2801] Too many pending friend requests sent")]
+    TooManyOpenFriendRequests {},
+    
+    #[error("[E2802] Too many open payment requests")]
+    TooManyOpenPaymentRequests {},
+    
+    #[error("[E2803] Too many open tasks")]
+    TooManyOpenTasks {},
+    
+    #[error("[E2804] Open item limits must be non-zero")]
+    InvalidOpenItemLimits {},
+}
+
+// Stable numeric codes for SDKs, grouped by hundreds like the variants above. Codes are never renumbered
+// or reused; a new error takes the next free code in its group, and its message starts with the code as
+// "[E0000] " so clients can read it from a failed transaction.
+macro_rules! error_codes {
+    ($($variant:ident = $code:literal,)*) => {
+        impl ContractError {
+            /// Stable numeric code of this error; wrapped `StdError`s are code 1
+            pub fn code(&self) -> u32 {
+                match self {
+                    ContractError::Std(_) => 1,
+                    $(ContractError::$variant {} => $code,)*
+                }
+            }
+            
+            /// Every error except wrapped `StdError`s, in code order
+            pub fn all() -> Vec<ContractError> {
+                vec![$(ContractError::$variant {},)*]
+            }
+        }
+    };
+}
+
+error_codes! {
+    // User Management
+    UsernameAlreadyTaken = 101,
+    UserNotFound = 102,
+    InvalidUsername = 103,
+    InvalidBio = 104,
+    InvalidProfileLinks = 105,
+    InvalidCountryCode = 106,
+    InvalidTimezone = 107,
+    WalletAlreadyRegistered = 108,
+    UserNotRegistered = 109,
+    
+    // Registration Fee
+    InvalidRegistrationFees = 201,
+    DeactivationBlocked = 202,
+    
+    // Username Expiry
+    UsernameExpired = 301,
+    UsernameDoesNotExpire = 302,
+    InvalidUsernameExpiry = 303,
+    
+    // Wallet Payment Request
+    TooManyWalletPaymentRequests = 401,
+    
+    // Text Length
+    DescriptionTooLong = 501,
+    EvidenceTooLong = 502,
+    EndpointTooLong = 503,
+    InvalidTextLimits = 504,
+    
+    // Address
+    InvalidAddress = 601,
+    
+    // Username NFT
+    UsernameCollectionNotConfigured = 701,
+    UsernameCollectionAlreadySet = 702,
+    UsernameAlreadyTokenized = 703,
+    UsernameNotTokenized = 704,
+    UsernameTransferNotFound = 705,
+    UsernameTransferNotReady = 706,
+    
+    // Friends System
+    CannotAddSelf = 801,
+    FriendRequestAlreadyExists = 802,
+    FriendRequestNotFound = 803,
+    FriendRequestCooldown = 804,
+    FriendRequestBlocked = 805,
+    AlreadyFriends = 806,
+    NotFriends = 807,
+    InvalidFriendRequestMessage = 808,
+    CannotRequestNonFriend = 809,
+    
+    // Friend Group
+    GroupNotFound = 901,
+    InvalidGroupName = 902,
+    GroupNameTaken = 903,
+    OnlyGroupOwner = 904,
+    AlreadyGroupMember = 905,
+    NotGroupMember = 906,
+    GroupFull = 907,
+    CannotRemoveGroupOwner = 908,
+    InvalidExpenseSplit = 909,
+    GroupBalanceOutstanding = 910,
+    NothingToSettle = 911,
+    
+    // Campaign
+    CampaignNotFound = 1001,
+    InvalidCampaignTitle = 1002,
+    InvalidCampaignDeadline = 1003,
+    CampaignClosed = 1004,
+    CampaignNotWithdrawable = 1005,
+    NoCampaignRefund = 1006,
+    
+    // Subscription
+    SubscriptionNotFound = 1101,
+    InvalidSubscription = 1102,
+    SubscriptionNotActive = 1103,
+    NothingToClaim = 1104,
+    
+    // Invoice
+    InvoiceNotFound = 1201,
+    InvalidInvoiceLineItems = 1202,
+    InvalidInvoiceDueDate = 1203,
+    InvoiceNotOpen = 1204,
+    
+    // Payment Link
+    PaymentLinkNotFound = 1301,
+    InvalidPaymentLink = 1302,
+    PaymentLinkInactive = 1303,
+    
+    // Scheduled Payment
+    ScheduledPaymentNotFound = 1401,
+    InvalidScheduledPayment = 1402,
+    ScheduledPaymentNotPending = 1403,
+    ScheduledPaymentNotDue = 1404,
+    
+    // Gift Code
+    GiftCodeNotFound = 1501,
+    InvalidGiftCode = 1502,
+    GiftCodeHashInUse = 1503,
+    GiftCodeExpired = 1504,
+    GiftCodeNotExpired = 1505,
+    GiftCodeNotActive = 1506,
+    
+    // HTLC
+    HtlcNotFound = 1601,
+    InvalidHtlc = 1602,
+    InvalidPreimage = 1603,
+    HtlcExpired = 1604,
+    HtlcNotExpired = 1605,
+    HtlcNotLocked = 1606,
+    
+    // Payment System
+    PaymentNotFound = 1701,
+    PaymentNotAuthorized = 1702,
+    PaymentAlreadyCompleted = 1703,
+    PaymentAlreadyCancelled = 1704,
+    PaymentAlreadyRejected = 1705,
+    CannotPaySelf = 1706,
+    InsufficientFunds = 1707,
+    UnexpectedFunds = 1708,
+    InvalidPaymentAmount = 1709,
+    DenomNotAccepted = 1710,
+    InvalidPriceQuote = 1711,
+    PriceOracleNotConfigured = 1712,
+    StalePrice = 1713,
+    InvalidExternalRef = 1714,
+    ExternalRefTaken = 1715,
+    InvalidIdempotencyKey = 1716,
+    IdempotencyKeyConflict = 1717,
+    PaymentAlreadyApproved = 1718,
+    PaymentNotPendingRelease = 1719,
+    
+    // Insurance Pool
+    InvalidInsuranceConfig = 1801,
+    InsuranceDisabled = 1802,
+    TaskAlreadyInsured = 1803,
+    TaskNotInsurable = 1804,
+    NoInsuranceClaim = 1805,
+    InvalidInsurancePayout = 1806,
+    InsufficientInsurancePool = 1807,
+    
+    // Arbiter
+    ArbitersDisabled = 1901,
+    InvalidArbiterConfig = 1902,
+    TooManyArbiters = 1903,
+    ArbiterNotBonded = 1904,
+    ArbiterHasOpenDisputes = 1905,
+    NotTaskArbiter = 1906,
+    InvalidArbiter = 1907,
+    ArbitrationNotAccepted = 1908,
+    
+    // Relayed Execution
+    RelayerNotAuthorized = 2001,
+    InvalidPubkey = 2002,
+    SigningKeyNotSet = 2003,
+    InvalidSignedPayload = 2004,
+    InvalidSignature = 2005,
+    InvalidNonce = 2006,
+    ProofAlreadySubmitted = 2007,
+    NoProofRequired = 2008,
+    ProofRequired = 2009,
+    InvalidProofType = 2010,
+    InvalidCategory = 2011,
+    InvalidTags = 2012,
+    ReminderNotAllowed = 2013,
+    ReminderCooldown = 2014,
+    ReminderLimitReached = 2015,
+    
+    // Authorization
+    NotAuthorized = 2101,
+    OnlySenderCanCancel = 2102,
+    OnlyRecipientCanApprove = 2103,
+    AdminReasonRequired = 2104,
+    NoEscrowToRelease = 2105,
+    InvalidIndexCursor = 2106,
+    AlreadyOwner = 2107,
+    NoPendingOwnershipTransfer = 2108,
+    RoleAlreadyGranted = 2109,
+    RoleNotGranted = 2110,
+    
+    // Privacy
+    RecordNotFinal = 2201,
+    AlreadyRedacted = 2202,
+    
+    // Feature Flag
+    PaymentTypeDisabled = 2301,
+    ProofTypeDisabled = 2302,
+    
+    // Policy Contract
+    PolicyDenied = 2401,
+    
+    // Yield Routing
+    InvalidYieldShare = 2501,
+    // 2502 retired: yield shortfalls are paid out pro rata instead of failing
+    
+    // DEX Router
+    InvalidPayoutSwap = 2601,
+    InvalidPayoutSlippage = 2602,
+    
+    // Task System
+    TaskNotFound = 2701,
+    TaskNotAuthorized = 2702,
+    TaskAlreadyCompleted = 2703,
+    TaskAlreadyDisputed = 2704,
+    TaskExpired = 2705,
+    TaskNotInDispute = 2706,
+    DisputeWindowNotElapsed = 2707,
+    DisputeWindowClosed = 2708,
+    TaskNotExpired = 2709,
+    InvalidProof = 2710,
+    ZkTlsVerificationFailed = 2711,
+    OnlyPayerCanApproveSoft = 2712,
+    OnlyPayerCanDispute = 2713,
+    OnlyOwnerCanResolveDispute = 2714,
+    InvalidWorkerShare = 2715,
+    CoApproversRequired = 2716,
+    InvalidCoApprovers = 2717,
+    InvalidApprovalThreshold = 2718,
+    NotCoApprover = 2719,
+    AlreadyCoApproved = 2720,
+    CoApprovalsPending = 2721,
+    CancelAlreadyRequested = 2722,
+    TaskAlreadyStarted = 2723,
+    InvalidCounterOffer = 2724,
+    NoCounterOffer = 2725,
+    InvalidAmendment = 2726,
+    InvalidTaskDependency = 2727,
+    TooManyTaskDependents = 2728,
+    InvalidTaskBatch = 2729,
+    TaskBatchNotFound = 2730,
+    AmendmentPending = 2731,
+    NoAmendmentPending = 2732,
+    AmendmentLimitReached = 2733,
+    CannotCreateTaskWithSelf = 2734,
+    InvalidTaskDeadline = 2735,
+    InvalidReviewWindow = 2736,
+    InvalidReleaseDelay = 2737,
+    
+    // Open Item Limit
+    TooManyOpenFriendRequests = 2801,
+    TooManyOpenPaymentRequests = 2802,
+    TooManyOpenTasks = 2803,
+    InvalidOpenItemLimits = 2804,
 }
@@ -2,11 +2,18 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{
-    to_json_binary, Addr, CosmosMsg, CustomQuery, Querier, QuerierWrapper, StdResult, WasmMsg, WasmQuery,
+    to_json_binary, Addr, Binary, Coin, CosmosMsg, CustomQuery, Querier, QuerierWrapper, StdResult, WasmMsg, WasmQuery,
 };
 
-use crate::msg::{ExecuteMsg, QueryMsg, UserResponse, UsersResponse, FriendsResponse, PaymentResponse, PaymentsResponse};
+use crate::msg::{
+    ExecuteMsg, QueryMsg, UserResponse, UsersResponse, FriendsResponse, PaymentResponse, PaymentsByIdsResponse,
+    PaymentsResponse, TaskResponse, TasksByIdsResponse, TasksResponse,
+};
+#[cfg(any(test, feature = "proofpay-query"))]
+use crate::msg::{AreFriendsResponse, HasUsernameResponse, UsernameResponse, WalletResponse};
 use crate::error::ContractError;
+use crate::state::ProofType;
+use sha2::{Digest, Sha256};
 
 /// SocialPaymentContract is a wrapper around Addr that provides helpers for your contract.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -27,6 +34,100 @@ impl SocialPaymentContract {
         .into())
     }
 
+    /// Build a direct payment, funded with the payment amount
+    pub fn send_direct_payment(
+        &self,
+        to_username: String,
+        amount: Coin,
+        description: String,
+        proof_type: ProofType,
+    ) -> ExecuteBuilder {
+        let funds = vec![amount.clone()];
+        ExecuteBuilder::new(self.addr(), ExecuteMsg::SendDirectPayment {
+            to_username,
+            amount,
+            description,
+            proof_type,
+            category: None,
+            tags: vec![],
+            visibility: None,
+            external_ref: None,
+            idempotency_key: None,
+            release_delay_secs: None,
+        })
+        .with_funds(funds)
+    }
+
+    /// Build a payment request (no funds are needed until it is approved)
+    pub fn create_payment_request(
+        &self,
+        to_username: String,
+        amount: Coin,
+        description: String,
+        proof_type: ProofType,
+    ) -> ExecuteBuilder {
+        ExecuteBuilder::new(self.addr(), ExecuteMsg::CreatePaymentRequest {
+            to_username,
+            amount,
+            description,
+            proof_type,
+            category: None,
+            tags: vec![],
+            visibility: None,
+            price_quote: None,
+            external_ref: None,
+            idempotency_key: None,
+        })
+    }
+
+    /// Build a task, funded with the task amount unless it is a soft task
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_task(
+        &self,
+        to_username: String,
+        amount: Coin,
+        description: String,
+        proof_type: ProofType,
+        deadline_ts: u64,
+        review_window_secs: Option<u64>,
+        endpoint: String,
+    ) -> ExecuteBuilder {
+        let funds = if matches!(proof_type, ProofType::Soft) { vec![] } else { vec![amount.clone()] };
+        ExecuteBuilder::new(self.addr(), ExecuteMsg::CreateTask {
+            to_username,
+            amount,
+            description,
+            proof_type,
+            deadline_ts,
+            review_window_secs,
+            endpoint,
+            category: None,
+            tags: vec![],
+            auto_approve_after_secs: None,
+            escrow_upfront: false,
+            co_approvers: vec![],
+            approvals_required: None,
+            price_quote: None,
+            external_ref: None,
+            idempotency_key: None,
+            arbiter: None,
+            depends_on: None,
+            payout_swap: None,
+            release_delay_secs: None,
+        })
+        .with_funds(funds)
+    }
+
+    /// Build a payment approval; payment requests need the payment amount attached via `with_funds`
+    pub fn approve_payment(&self, payment_id: u64) -> ExecuteBuilder {
+        ExecuteBuilder::new(self.addr(), ExecuteMsg::ApprovePayment { payment_id })
+    }
+
+    /// Build a task approval; unfunded soft tasks need the task amount attached via `with_funds`
+    pub fn approve_task(&self, task_id: u64) -> ExecuteBuilder {
+        ExecuteBuilder::new(self.addr(), ExecuteMsg::ApproveTask { task_id })
+    }
+
     /// Query a user by username
     pub fn get_user_by_username<Q, CQ>(&self, querier: &Q, username: String) -> StdResult<UserResponse>
     where
@@ -65,7 +166,7 @@ impl SocialPaymentContract {
         Q: Querier,
         CQ: CustomQuery,
     {
-        let msg = QueryMsg::SearchUsers { query };
+        let msg = QueryMsg::SearchUsers { query, start_after: None };
         let query = WasmQuery::Smart {
             contract_addr: self.addr().into(),
             msg: to_json_binary(&msg)?,
@@ -91,13 +192,13 @@ impl SocialPaymentContract {
         Ok(res)
     }
 
-    /// Query a payment by ID
-    pub fn get_payment_by_id<Q, CQ>(&self, querier: &Q, payment_id: u64) -> StdResult<PaymentResponse>
+    /// Query a payment by ID, as seen by `viewer` (a username)
+    pub fn get_payment_by_id<Q, CQ>(&self, querier: &Q, payment_id: u64, viewer: Option<String>) -> StdResult<PaymentResponse>
     where
         Q: Querier,
         CQ: CustomQuery,
     {
-        let msg = QueryMsg::GetPaymentById { payment_id };
+        let msg = QueryMsg::GetPaymentById { payment_id, viewer };
         let query = WasmQuery::Smart {
             contract_addr: self.addr().into(),
             msg: to_json_binary(&msg)?,
@@ -107,13 +208,29 @@ impl SocialPaymentContract {
         Ok(res)
     }
 
-    /// Query payment history for a user
-    pub fn get_payment_history<Q, CQ>(&self, querier: &Q, username: String) -> StdResult<PaymentsResponse>
+    /// Query up to 100 payments by ID, as seen by `viewer` (a username)
+    pub fn get_payments_by_ids<Q, CQ>(&self, querier: &Q, ids: Vec<u64>, viewer: Option<String>) -> StdResult<PaymentsByIdsResponse>
     where
         Q: Querier,
         CQ: CustomQuery,
     {
-        let msg = QueryMsg::GetPaymentHistory { username };
+        let msg = QueryMsg::GetPaymentsByIds { ids, viewer };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_json_binary(&msg)?,
+        }
+        .into();
+        let res: PaymentsByIdsResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
+        Ok(res)
+    }
+
+    /// Query payment history for a user, as seen by `viewer` (a username)
+    pub fn get_payment_history<Q, CQ>(&self, querier: &Q, username: String, viewer: Option<String>) -> StdResult<PaymentsResponse>
+    where
+        Q: Querier,
+        CQ: CustomQuery,
+    {
+        let msg = QueryMsg::GetPaymentHistory { username, viewer, start_after: None };
         let query = WasmQuery::Smart {
             contract_addr: self.addr().into(),
             msg: to_json_binary(&msg)?,
@@ -122,6 +239,180 @@ impl SocialPaymentContract {
         let res: PaymentsResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
         Ok(res)
     }
+    /// Query a task by ID
+    pub fn get_task_by_id<Q, CQ>(&self, querier: &Q, task_id: u64) -> StdResult<TaskResponse>
+    where
+        Q: Querier,
+        CQ: CustomQuery,
+    {
+        let msg = QueryMsg::GetTaskById { task_id };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_json_binary(&msg)?,
+        }
+        .into();
+        let res: TaskResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
+        Ok(res)
+    }
+
+    /// Query up to 100 tasks by ID
+    pub fn get_tasks_by_ids<Q, CQ>(&self, querier: &Q, ids: Vec<u64>) -> StdResult<TasksByIdsResponse>
+    where
+        Q: Querier,
+        CQ: CustomQuery,
+    {
+        let msg = QueryMsg::GetTasksByIds { ids };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_json_binary(&msg)?,
+        }
+        .into();
+        let res: TasksByIdsResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
+        Ok(res)
+    }
+
+    /// Query task history for a user
+    pub fn get_task_history<Q, CQ>(&self, querier: &Q, username: String) -> StdResult<TasksResponse>
+    where
+        Q: Querier,
+        CQ: CustomQuery,
+    {
+        let msg = QueryMsg::GetTaskHistory { username, start_after: None };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_json_binary(&msg)?,
+        }
+        .into();
+        let res: TasksResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
+        Ok(res)
+    }
+
+    /// Query open tasks for a user
+    pub fn get_pending_tasks<Q, CQ>(&self, querier: &Q, username: String) -> StdResult<TasksResponse>
+    where
+        Q: Querier,
+        CQ: CustomQuery,
+    {
+        let msg = QueryMsg::GetPendingTasks { username, start_after: None };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_json_binary(&msg)?,
+        }
+        .into();
+        let res: TasksResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
+        Ok(res)
+    }
+}
+
+/// Read-only handle lookups for other contracts, enabled by the `proofpay-query` feature.
+/// Looking up an unregistered wallet or username returns an error.
+#[cfg(any(test, feature = "proofpay-query"))]
+impl SocialPaymentContract {
+    /// Query the username registered to a wallet
+    pub fn get_username_by_wallet<Q, CQ>(&self, querier: &Q, wallet_address: String) -> StdResult<UsernameResponse>
+    where
+        Q: Querier,
+        CQ: CustomQuery,
+    {
+        let msg = QueryMsg::GetUsernameByWallet { wallet_address };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_json_binary(&msg)?,
+        }
+        .into();
+        let res: UsernameResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
+        Ok(res)
+    }
+
+    /// Query the wallet a username resolves to
+    pub fn get_wallet_by_username<Q, CQ>(&self, querier: &Q, username: String) -> StdResult<WalletResponse>
+    where
+        Q: Querier,
+        CQ: CustomQuery,
+    {
+        let msg = QueryMsg::GetWalletByUsername { username };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_json_binary(&msg)?,
+        }
+        .into();
+        let res: WalletResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
+        Ok(res)
+    }
+
+    /// Query whether a wallet has registered a username
+    pub fn has_username<Q, CQ>(&self, querier: &Q, wallet_address: String) -> StdResult<HasUsernameResponse>
+    where
+        Q: Querier,
+        CQ: CustomQuery,
+    {
+        let msg = QueryMsg::HasUsername { wallet_address };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_json_binary(&msg)?,
+        }
+        .into();
+        let res: HasUsernameResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
+        Ok(res)
+    }
+
+    /// Query whether two users are friends
+    pub fn are_friends<Q, CQ>(&self, querier: &Q, username1: String, username2: String) -> StdResult<AreFriendsResponse>
+    where
+        Q: Querier,
+        CQ: CustomQuery,
+    {
+        let msg = QueryMsg::AreFriends { username1, username2 };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_json_binary(&msg)?,
+        }
+        .into();
+        let res: AreFriendsResponse = QuerierWrapper::<CQ>::new(querier).query(&query)?;
+        Ok(res)
+    }
+}
+
+/// An execute message for the contract along with the funds to attach
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecuteBuilder {
+    contract_addr: Addr,
+    msg: ExecuteMsg,
+    funds: Vec<Coin>,
+}
+
+impl ExecuteBuilder {
+    pub fn new(contract_addr: Addr, msg: ExecuteMsg) -> Self {
+        ExecuteBuilder {
+            contract_addr,
+            msg,
+            funds: vec![],
+        }
+    }
+
+    /// Replace the funds attached to the execute
+    pub fn with_funds(mut self, funds: Vec<Coin>) -> Self {
+        self.funds = funds;
+        self
+    }
+
+    pub fn msg(&self) -> &ExecuteMsg {
+        &self.msg
+    }
+
+    pub fn funds(&self) -> &[Coin] {
+        &self.funds
+    }
+
+    /// Convert into a `WasmMsg::Execute` ready to be dispatched
+    pub fn build(self) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: self.contract_addr.into(),
+            msg: to_json_binary(&self.msg)?,
+            funds: self.funds,
+        }
+        .into())
+    }
 }
 
 /// zkTLS verification interface - stubbed for now
@@ -146,8 +437,42 @@ pub fn verify_zktls(proof_blob: &str, endpoint: &str) -> Result<bool, ContractEr
     Ok(is_valid)
 }
 
-/// Hash a piece of data for on-chain storage
+/// Challenge a wallet signs (ADR-36) to prove it holds the key passed to RegisterPubkey
+pub fn pubkey_challenge(chain_id: &str, contract: &Addr, wallet: &Addr) -> Binary {
+    Binary::from(format!("proofpay:register_pubkey:{}:{}:{}", chain_id, contract, wallet).into_bytes())
+}
+
+/// SHA-256 of the ADR-36 sign doc a wallet produces when `signer` signs arbitrary `data`
+pub fn adr36_sign_hash(signer: &str, data: &Binary) -> Vec<u8> {
+    let sign_doc = format!(
+        r#"{{"account_number":"0","chain_id":"","fee":{{"amount":[],"gas":"0"}},"memo":"","msgs":[{{"type":"sign/MsgSignData","value":{{"data":"{}","signer":"{}"}}}}],"sequence":"0"}}"#,
+        data.to_base64(),
+        signer,
+    );
+    Sha256::digest(sign_doc.as_bytes()).to_vec()
+}
+
+/// Hash a piece of data for on-chain storage (hex-encoded SHA-256)
 pub fn hash_data(data: &str) -> String {
-    // Simple hash for now - in production use proper cryptographic hash
-    format!("hash_{}", data.len())
+    Sha256::digest(data.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Hex SHA-256 of a hex-encoded preimage's bytes, as other chains' HTLCs hash it; None if not hex
+pub fn hash_preimage(preimage: &str) -> Option<String> {
+    if preimage.is_empty() || !preimage.len().is_multiple_of(2) || !preimage.is_ascii() {
+        return None;
+    }
+    let bytes = (0..preimage.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&preimage[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(
+        Sha256::digest(&bytes)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    )
 }
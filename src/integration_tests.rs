@@ -1,25 +1,17 @@
 #[cfg(test)]
 mod tests {
     use crate::helpers::SocialPaymentContract;
-    use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+    use crate::msg::{ExecuteMsg, QueryMsg};
     use crate::state::{PaymentStatus, ProofType, TaskStatus};
     use cosmwasm_std::{Addr, Coin, Empty, Uint128};
     use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
 
-    pub fn contract_template() -> Box<dyn Contract<Empty>> {
-        let contract = ContractWrapper::new(
-            crate::contract::execute,
-            crate::contract::instantiate,
-            crate::contract::query,
-        );
-        Box::new(contract)
-    }
-
     const USER1: &str = "user1";
     const USER2: &str = "user2";
     const USER3: &str = "user3";
     const ADMIN: &str = "admin";
     const NATIVE_DENOM: &str = "uxion";
+    const OTHER_DENOM: &str = "uatom";
 
     fn mock_app() -> App {
         AppBuilder::new().build(|router, _, storage| {
@@ -29,10 +21,16 @@ mod tests {
                     .init_balance(
                         storage,
                         &Addr::unchecked(user),
-                        vec![Coin {
-                            denom: NATIVE_DENOM.to_string(),
-                            amount: Uint128::new(10000),
-                        }],
+                        vec![
+                            Coin {
+                                denom: OTHER_DENOM.to_string(),
+                                amount: Uint128::new(10000),
+                            },
+                            Coin {
+                                denom: NATIVE_DENOM.to_string(),
+                                amount: Uint128::new(10000),
+                            },
+                        ],
                     )
                     .unwrap();
             }
@@ -41,47 +39,22 @@ mod tests {
 
     fn proper_instantiate() -> (App, SocialPaymentContract) {
         let mut app = mock_app();
-        let contract_id = app.store_code(contract_template());
-
-        let msg = InstantiateMsg {};
-        let contract_addr = app
-            .instantiate_contract(
-                contract_id,
-                Addr::unchecked(ADMIN),
-                &msg,
-                &[],
-                "social-payment",
-                None,
-            )
-            .unwrap();
-
-        let contract = SocialPaymentContract(contract_addr);
-
+        let contract = crate::testing::instantiate(&mut app, &Addr::unchecked(ADMIN));
         (app, contract)
     }
 
     fn register_users(app: &mut App, contract: &SocialPaymentContract) {
-        // Register users
-        let register_user1 = ExecuteMsg::RegisterUser {
-            username: "alice".to_string(),
-            display_name: "Alice Smith".to_string(),
-        };
-        app.execute_contract(Addr::unchecked(USER1), contract.addr(), &register_user1, &[])
-            .unwrap();
-
-        let register_user2 = ExecuteMsg::RegisterUser {
-            username: "bob".to_string(),
-            display_name: "Bob Jones".to_string(),
-        };
-        app.execute_contract(Addr::unchecked(USER2), contract.addr(), &register_user2, &[])
-            .unwrap();
+        for (wallet, username) in [(USER1, "alice"), (USER2, "bob"), (USER3, "charlie")] {
+            crate::testing::register_user(app, contract, &Addr::unchecked(wallet), username);
+        }
+    }
 
-        let register_user3 = ExecuteMsg::RegisterUser {
-            username: "charlie".to_string(),
-            display_name: "Charlie Brown".to_string(),
-        };
-        app.execute_contract(Addr::unchecked(USER3), contract.addr(), &register_user3, &[])
-            .unwrap();
+    // Two funded users, dana paying and eli working, who are not friends
+    fn dana_and_eli() -> crate::testing::Scenario {
+        crate::testing::ScenarioBuilder::new()
+            .with_user("wallet_dana", "dana", 1000)
+            .with_user("wallet_eli", "eli", 1000)
+            .build()
     }
 
     mod user_management {
@@ -165,6 +138,7 @@ mod tests {
                     contract.addr(),
                     &QueryMsg::SearchUsers {
                         query: "alice".to_string(),
+                        start_after: None,
                     },
                 )
                 .unwrap();
@@ -172,6 +146,60 @@ mod tests {
             assert_eq!(search_response.users.len(), 1);
             assert_eq!(search_response.users[0].username, "alice");
         }
+
+        #[test]
+        fn test_profile_details() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let details = |bio: &str, links: Vec<&str>, country: &str, timezone: &str| ExecuteMsg::UpdateUserProfile {
+                display_name: None,
+                profile_picture: None,
+                require_friendship_for_requests: None,
+                default_visibility: None,
+                bio: Some(bio.to_string()),
+                links: Some(links.into_iter().map(str::to_string).collect()),
+                country: Some(country.to_string()),
+                timezone: Some(timezone.to_string()),
+                preferred_denom: None,
+                payout_slippage_bps: None,
+            };
+            let profile = |app: &App| -> crate::state::User {
+                let response: crate::msg::UserResponse = app
+                    .wrap()
+                    .query_wasm_smart(contract.addr(), &QueryMsg::GetUserByUsername { username: "alice".to_string() })
+                    .unwrap();
+                response.user
+            };
+
+            let update = details("Climber and coffee roaster", vec!["https://alice.example"], "DE", "Europe/Berlin");
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &update, &[])
+                .unwrap();
+            let user = profile(&app);
+            assert_eq!(user.bio, Some("Climber and coffee roaster".to_string()));
+            assert_eq!(user.links, vec!["https://alice.example".to_string()]);
+            assert_eq!(user.country, Some("DE".to_string()));
+            assert_eq!(user.timezone, Some("Europe/Berlin".to_string()));
+
+            let invalid = [
+                (details(&"x".repeat(281), vec![], "", ""), crate::ContractError::InvalidBio {}),
+                (details("", vec!["http://alice.example"], "", ""), crate::ContractError::InvalidProfileLinks {}),
+                (details("", vec![], "Germany", ""), crate::ContractError::InvalidCountryCode {}),
+                (details("", vec![], "", "Europe/Berlin; DROP"), crate::ContractError::InvalidTimezone {}),
+            ];
+            for (update, expected) in invalid {
+                let err = app
+                    .execute_contract(Addr::unchecked(USER1), contract.addr(), &update, &[])
+                    .unwrap_err();
+                assert_eq!(err.downcast::<crate::ContractError>().unwrap(), expected);
+            }
+
+            // Empty values clear the details
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &details("", vec![], "", ""), &[])
+                .unwrap();
+            let user = profile(&app);
+            assert_eq!((user.bio, user.links, user.country, user.timezone), (None, vec![], None, None));
+        }
     }
 
     mod friends_system {
@@ -185,6 +213,7 @@ mod tests {
             // Send friend request
             let send_request = ExecuteMsg::SendFriendRequest {
                 to_username: "bob".to_string(),
+                message: None,
             };
             app.execute_contract(
                 Addr::unchecked(USER1),
@@ -201,6 +230,7 @@ mod tests {
                     contract.addr(),
                     &QueryMsg::GetPendingRequests {
                         username: "bob".to_string(),
+                        start_after: None,
                     },
                 )
                 .unwrap();
@@ -246,6 +276,34 @@ mod tests {
             assert_eq!(friends_list.friends[0], "bob");
         }
 
+        #[test]
+        fn test_friend_request_message() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let too_long = ExecuteMsg::SendFriendRequest {
+                to_username: "bob".to_string(),
+                message: Some("x".repeat(141)),
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &too_long, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<crate::ContractError>().unwrap(), crate::ContractError::InvalidFriendRequestMessage {});
+
+            let send_request = ExecuteMsg::SendFriendRequest {
+                to_username: "bob".to_string(),
+                message: Some("  hey it's Alice from the climbing gym ".to_string()),
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_request, &[])
+                .unwrap();
+
+            let pending: crate::msg::FriendRequestsResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPendingRequests { username: "bob".to_string(), start_after: None })
+                .unwrap();
+            assert_eq!(pending.requests[0].message, Some("hey it's Alice from the climbing gym".to_string()));
+        }
+
         #[test]
         fn test_remove_friend() {
             let (mut app, contract) = proper_instantiate();
@@ -254,6 +312,7 @@ mod tests {
             // Become friends first
             let send_request = ExecuteMsg::SendFriendRequest {
                 to_username: "bob".to_string(),
+                message: None,
             };
             app.execute_contract(
                 Addr::unchecked(USER1),
@@ -277,6 +336,7 @@ mod tests {
             // Remove friend
             let remove_friend = ExecuteMsg::RemoveFriend {
                 username: "bob".to_string(),
+                leave_shared_groups: false,
             };
             app.execute_contract(
                 Addr::unchecked(USER1),
@@ -299,6 +359,203 @@ mod tests {
                 .unwrap();
             assert!(!friends_response.are_friends);
         }
+
+        #[test]
+        fn test_declined_requests_can_be_resent_after_cooldown() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let send_request = ExecuteMsg::SendFriendRequest {
+                to_username: "bob".to_string(),
+                message: None,
+            };
+            let decline_request = ExecuteMsg::DeclineFriendRequest {
+                from_username: "alice".to_string(),
+            };
+            let cooldown = crate::state::FriendRequestPolicy::default().cooldown_secs;
+
+            for round in 1..=3 {
+                app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_request, &[])
+                    .unwrap();
+                app.execute_contract(Addr::unchecked(USER2), contract.addr(), &decline_request, &[])
+                    .unwrap();
+
+                // The declined request is gone
+                let pending: crate::msg::FriendRequestsResponse = app
+                    .wrap()
+                    .query_wasm_smart(contract.addr(), &QueryMsg::GetPendingRequests { username: "bob".to_string(), start_after: None })
+                    .unwrap();
+                assert!(pending.requests.is_empty());
+                let err = app
+                    .execute_contract(Addr::unchecked(USER2), contract.addr(), &decline_request, &[])
+                    .unwrap_err();
+                assert_eq!(err.downcast::<crate::ContractError>().unwrap(), crate::ContractError::FriendRequestNotFound {});
+
+                // Resending waits out the cooldown until the third decline
+                let err = app
+                    .execute_contract(Addr::unchecked(USER1), contract.addr(), &send_request, &[])
+                    .unwrap_err();
+                let expected = if round < 3 {
+                    crate::ContractError::FriendRequestCooldown {}
+                } else {
+                    crate::ContractError::FriendRequestBlocked {}
+                };
+                assert_eq!(err.downcast::<crate::ContractError>().unwrap(), expected);
+                app.update_block(|block| block.time = block.time.plus_seconds(cooldown));
+            }
+
+            // The block outlasts the cooldown
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &send_request, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<crate::ContractError>().unwrap(), crate::ContractError::FriendRequestBlocked {});
+        }
+
+        #[test]
+        fn test_crossing_friend_requests_make_friends() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let alice_request = ExecuteMsg::SendFriendRequest {
+                to_username: "bob".to_string(),
+                message: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &alice_request, &[])
+                .unwrap();
+
+            // Bob requesting alice back accepts her pending request instead of adding a second one
+            let bob_request = ExecuteMsg::SendFriendRequest {
+                to_username: "alice".to_string(),
+                message: None,
+            };
+            let res = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &bob_request, &[])
+                .unwrap();
+            let wasm = res.events.iter().find(|event| event.ty == "wasm").unwrap();
+            assert!(wasm.attributes.iter().any(|attr| attr.key == "action" && attr.value == "accept_friend_request"));
+            assert!(wasm.attributes.iter().any(|attr| attr.key == "auto_accepted" && attr.value == "true"));
+
+            let friends: crate::msg::AreFriendsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::AreFriends {
+                        username1: "alice".to_string(),
+                        username2: "bob".to_string(),
+                    },
+                )
+                .unwrap();
+            assert!(friends.are_friends);
+            for username in ["alice", "bob"] {
+                let pending: crate::msg::FriendRequestsResponse = app
+                    .wrap()
+                    .query_wasm_smart(contract.addr(), &QueryMsg::GetPendingRequests { username: username.to_string(), start_after: None })
+                    .unwrap();
+                assert!(pending.requests.is_empty());
+            }
+        }
+    }
+
+    mod friend_gating {
+        use super::*;
+
+        fn enable_friends_only(app: &mut App, contract: &SocialPaymentContract, wallet: &str) {
+            let update = ExecuteMsg::UpdateUserProfile {
+                display_name: None,
+                profile_picture: None,
+                require_friendship_for_requests: Some(true),
+                default_visibility: None,
+                bio: None,
+                links: None,
+                country: None,
+                timezone: None,
+                preferred_denom: None,
+                payout_slippage_bps: None,
+            };
+            app.execute_contract(Addr::unchecked(wallet), contract.addr(), &update, &[])
+                .unwrap();
+        }
+
+        #[test]
+        fn test_friends_only_blocks_requests_from_strangers() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            enable_friends_only(&mut app, &contract, USER2);
+
+            let user_response: crate::msg::UserResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetUserByUsername {
+                        username: "bob".to_string(),
+                    },
+                )
+                .unwrap();
+            assert!(user_response.user.require_friendship_for_requests);
+
+            let payment_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: Coin::new(100, NATIVE_DENOM),
+                description: "Pay me".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &payment_request, &[])
+                .unwrap_err();
+            assert_eq!(
+                err.downcast::<crate::ContractError>().unwrap(),
+                crate::ContractError::CannotRequestNonFriend {}
+            );
+
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: Coin::new(100, NATIVE_DENOM),
+                description: "Unsolicited gig".to_string(),
+                proof_type: ProofType::Soft,
+                deadline_ts: 2524608000,
+                review_window_secs: None,
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task, &[]);
+            assert!(result.is_err());
+
+            // Once they are friends, requests go through
+            let send_request = ExecuteMsg::SendFriendRequest {
+                to_username: "bob".to_string(),
+                message: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_request, &[])
+                .unwrap();
+            let accept_request = ExecuteMsg::AcceptFriendRequest {
+                from_username: "alice".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &accept_request, &[])
+                .unwrap();
+
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &payment_request, &[])
+                .unwrap();
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task, &[])
+                .unwrap();
+        }
     }
 
     mod payment_system {
@@ -320,6 +577,12 @@ mod tests {
                 amount: payment_amount[0].clone(),
                 description: "Test payment".to_string(),
                 proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
             };
 
             app.execute_contract(
@@ -337,7 +600,7 @@ mod tests {
             // Check payment was created and completed
             let payment_response: crate::msg::PaymentResponse = app
                 .wrap()
-                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1 })
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
                 .unwrap();
 
             assert_eq!(payment_response.payment.from_username, "alice");
@@ -345,6 +608,94 @@ mod tests {
             assert_eq!(payment_response.payment.status, PaymentStatus::Completed);
         }
 
+        #[test]
+        fn test_attached_funds_validated_exactly() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let send_payment = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: Coin {
+                    denom: NATIVE_DENOM.to_string(),
+                    amount: Uint128::new(100),
+                },
+                description: "Overpaid".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+
+            // Unrelated denominations are rejected
+            let result = app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &send_payment,
+                &[
+                    Coin {
+                        denom: OTHER_DENOM.to_string(),
+                        amount: Uint128::new(5),
+                    },
+                    Coin {
+                        denom: NATIVE_DENOM.to_string(),
+                        amount: Uint128::new(100),
+                    },
+                ],
+            );
+            assert!(result.is_err());
+
+            // Surplus of the payment denom is refunded
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &send_payment,
+                &[Coin {
+                    denom: NATIVE_DENOM.to_string(),
+                    amount: Uint128::new(150),
+                }],
+            )
+            .unwrap();
+
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(9900));
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10100));
+            let contract_balance = app.wrap().query_balance(contract.addr(), NATIVE_DENOM).unwrap();
+            assert!(contract_balance.amount.is_zero());
+        }
+
+        #[test]
+        fn test_funds_rejected_where_none_are_taken() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let funds = [Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(10),
+            }];
+            let msgs = [
+                ExecuteMsg::SendFriendRequest { to_username: "bob".to_string(), message: None },
+                ExecuteMsg::CancelTask { task_id: 1 },
+                ExecuteMsg::DisputeTask { task_id: 1, reason: None, reason_hash: None },
+                ExecuteMsg::ResolveDispute { task_id: 1, worker_bps: 0 },
+                ExecuteMsg::ReleaseIfWindowElapsed { task_id: 1 },
+                ExecuteMsg::CancelPayment { payment_id: 1 },
+                ExecuteMsg::ReapExpiredUsernames { limit: None },
+            ];
+            for msg in msgs {
+                let err = app
+                    .execute_contract(Addr::unchecked(USER1), contract.addr(), &msg, &funds)
+                    .unwrap_err();
+                assert_eq!(err.downcast::<crate::ContractError>().unwrap(), crate::ContractError::UnexpectedFunds {});
+            }
+
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(10000));
+        }
+
         #[test]
         fn test_help_request_with_proof() {
             let (mut app, contract) = proper_instantiate();
@@ -361,6 +712,12 @@ mod tests {
                 amount: payment_amount[0].clone(),
                 description: "Help with moving".to_string(),
                 proof_type: ProofType::Photo,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
             };
 
             app.execute_contract(
@@ -401,18 +758,17 @@ mod tests {
             // Check payment status
             let payment_response: crate::msg::PaymentResponse = app
                 .wrap()
-                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1 })
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
                 .unwrap();
             assert_eq!(payment_response.payment.status, PaymentStatus::Completed);
         }
 
-        #[test] 
-        #[ignore] // TODO: PaymentRequest logic doesn't use escrow, so no refund needed
+        #[test]
         fn test_payment_cancellation() {
             let (mut app, contract) = proper_instantiate();
             register_users(&mut app, &contract);
 
-            let payment_amount = vec![Coin {
+            let payment_amount = [Coin {
                 denom: NATIVE_DENOM.to_string(),
                 amount: Uint128::new(150),
             }];
@@ -423,6 +779,12 @@ mod tests {
                 amount: payment_amount[0].clone(),
                 description: "Help with coding".to_string(),
                 proof_type: ProofType::Manual,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
             };
 
             app.execute_contract(
@@ -450,57 +812,414 @@ mod tests {
             // Check payment status
             let payment_response: crate::msg::PaymentResponse = app
                 .wrap()
-                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1 })
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
                 .unwrap();
             assert_eq!(payment_response.payment.status, PaymentStatus::Cancelled);
         }
 
         #[test]
-        fn test_payment_history() {
+        fn test_payment_request_cannot_be_approved_twice() {
             let (mut app, contract) = proper_instantiate();
             register_users(&mut app, &contract);
 
-            let payment_amount = vec![Coin {
+            let payment_amount = Coin {
                 denom: NATIVE_DENOM.to_string(),
-                amount: Uint128::new(50),
-            }];
+                amount: Uint128::new(100),
+            };
+            let payment_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: payment_amount.clone(),
+                description: "Dinner".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &payment_request, &[])
+                .unwrap();
 
-            // Send multiple payments
-            for i in 0..3 {
-                let send_payment = ExecuteMsg::SendDirectPayment {
-                    to_username: "bob".to_string(),
-                    amount: payment_amount[0].clone(),
-                    description: format!("Payment {}", i + 1),
-                    proof_type: ProofType::None,
-                };
+            let approve = ExecuteMsg::ApprovePayment { payment_id: 1 };
+            app.execute_contract(
+                Addr::unchecked(USER2),
+                contract.addr(),
+                &approve,
+                std::slice::from_ref(&payment_amount),
+            )
+            .unwrap();
 
+            // Replaying the approval with funds attached does not pay out again
+            let result = app.execute_contract(
+                Addr::unchecked(USER2),
+                contract.addr(),
+                &approve,
+                std::slice::from_ref(&payment_amount),
+            );
+            assert!(result.is_err());
+
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(10100));
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(9900));
+        }
+
+        #[test]
+        fn test_closed_payments_cannot_be_approved() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_amount = Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(100),
+            };
+            let send_payment = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: payment_amount.clone(),
+                description: "Needs proof".to_string(),
+                proof_type: ProofType::Photo,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            for _ in 0..2 {
                 app.execute_contract(
                     Addr::unchecked(USER1),
                     contract.addr(),
                     &send_payment,
-                    &payment_amount,
+                    std::slice::from_ref(&payment_amount),
                 )
                 .unwrap();
             }
+            for payment_id in [1, 2] {
+                let submit_proof = ExecuteMsg::SubmitProof {
+                    payment_id,
+                    proof_data: "photo_hash".to_string(),
+                };
+                app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_proof, &[])
+                    .unwrap();
+            }
 
-            // Check alice's payment history
-            let history_response: crate::msg::PaymentsResponse = app
-                .wrap()
-                .query_wasm_smart(
-                    contract.addr(),
-                    &QueryMsg::GetPaymentHistory {
-                        username: "alice".to_string(),
-                    },
-                )
+            // Payment 1 is cancelled, payment 2 is rejected; both refund alice
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &ExecuteMsg::CancelPayment { payment_id: 1 },
+                &[],
+            )
+            .unwrap();
+            let reject = ExecuteMsg::RejectPayment { payment_id: 2 };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &reject, &[])
                 .unwrap();
 
-            assert_eq!(history_response.payments.len(), 3);
-            assert_eq!(history_response.payments[0].from_username, "alice");
-        }
-    }
+            // Neither can be approved or rejected again afterwards
+            for payment_id in [1, 2] {
+                let approve = ExecuteMsg::ApprovePayment { payment_id };
+                let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &approve, &[]);
+                assert!(result.is_err());
+            }
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &reject, &[]);
+            assert!(result.is_err());
 
-    mod error_cases {
-        use super::*;
+            // A cancelled payment request cannot be approved by its payer
+            let payment_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: payment_amount.clone(),
+                description: "Withdrawn".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &payment_request, &[])
+                .unwrap();
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &ExecuteMsg::CancelPayment { payment_id: 3 },
+                &[],
+            )
+            .unwrap();
+            let result = app.execute_contract(
+                Addr::unchecked(USER2),
+                contract.addr(),
+                &ExecuteMsg::ApprovePayment { payment_id: 3 },
+                &[payment_amount],
+            );
+            assert!(result.is_err());
+
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(10000));
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10000));
+        }
+
+        #[test]
+        fn test_execute_builders_attach_funds() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_amount = Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(100),
+            };
+
+            // Direct payments are funded automatically
+            let send_payment = contract.send_direct_payment(
+                "bob".to_string(),
+                payment_amount.clone(),
+                "Built".to_string(),
+                ProofType::None,
+            );
+            assert_eq!(send_payment.funds(), std::slice::from_ref(&payment_amount));
+            app.execute(Addr::unchecked(USER1), send_payment.build().unwrap())
+                .unwrap();
+
+            // Payment requests are funded by the approver
+            let request = contract.create_payment_request(
+                "bob".to_string(),
+                payment_amount.clone(),
+                "Built request".to_string(),
+                ProofType::None,
+            );
+            app.execute(Addr::unchecked(USER1), request.build().unwrap())
+                .unwrap();
+            let approve = contract
+                .approve_payment(2)
+                .with_funds(vec![payment_amount])
+                .build()
+                .unwrap();
+            app.execute(Addr::unchecked(USER2), approve).unwrap();
+
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(10000));
+            let payment_response: crate::msg::PaymentResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 2, viewer: None })
+                .unwrap();
+            assert_eq!(payment_response.payment.status, PaymentStatus::Completed);
+        }
+
+        #[test]
+        fn test_payment_history() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_amount = vec![Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(50),
+            }];
+
+            // Send multiple payments
+            for i in 0..3 {
+                let send_payment = ExecuteMsg::SendDirectPayment {
+                    to_username: "bob".to_string(),
+                    amount: payment_amount[0].clone(),
+                    description: format!("Payment {}", i + 1),
+                    proof_type: ProofType::None,
+                    category: None,
+                    tags: vec![],
+                    visibility: None,
+                    external_ref: None,
+                    idempotency_key: None,
+                    release_delay_secs: None,
+                };
+
+                app.execute_contract(
+                    Addr::unchecked(USER1),
+                    contract.addr(),
+                    &send_payment,
+                    &payment_amount,
+                )
+                .unwrap();
+            }
+
+            // Check alice's payment history
+            let history_response: crate::msg::PaymentsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetPaymentHistory {
+                        username: "alice".to_string(),
+                        viewer: None,
+                        start_after: None,
+                    },
+                )
+                .unwrap();
+
+            assert_eq!(history_response.payments.len(), 3);
+            assert_eq!(history_response.payments[0].from_username, "alice");
+        }
+
+        #[test]
+        fn test_payment_history_truncation_markers() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_amount = [Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(1),
+            }];
+
+            // More completed payments than a single query may return or scan
+            for i in 0..1001 {
+                let send_payment = ExecuteMsg::SendDirectPayment {
+                    to_username: "bob".to_string(),
+                    amount: payment_amount[0].clone(),
+                    description: format!("Payment {}", i + 1),
+                    proof_type: ProofType::None,
+                    category: None,
+                    tags: vec![],
+                    visibility: None,
+                    external_ref: None,
+                    idempotency_key: None,
+                    release_delay_secs: None,
+                };
+                app.execute_contract(
+                    Addr::unchecked(USER1),
+                    contract.addr(),
+                    &send_payment,
+                    &payment_amount,
+                )
+                .unwrap();
+            }
+
+            let history_response: crate::msg::PaymentsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetPaymentHistory {
+                        username: "alice".to_string(),
+                        viewer: None,
+                        start_after: None,
+                    },
+                )
+                .unwrap();
+            assert_eq!(history_response.payments.len(), 100);
+            assert!(history_response.truncated);
+            assert!(!history_response.scan_budget_exhausted);
+
+            // None of them are pending, and the filter gives up before reaching the end
+            let pending_response: crate::msg::PaymentsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetPendingPayments {
+                        username: "alice".to_string(),
+                        viewer: None,
+                        start_after: None,
+                    },
+                )
+                .unwrap();
+            assert!(pending_response.payments.is_empty());
+            assert!(!pending_response.truncated);
+            assert!(pending_response.scan_budget_exhausted);
+        }
+
+        #[test]
+        fn test_payment_history_filtered_by_category() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_amount = [Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(25),
+            }];
+
+            for (category, proof_type) in [
+                ("food", ProofType::None),
+                ("food", ProofType::Photo),
+                ("rent", ProofType::None),
+            ] {
+                let send_payment = ExecuteMsg::SendDirectPayment {
+                    to_username: "bob".to_string(),
+                    amount: payment_amount[0].clone(),
+                    description: format!("{} payment", category),
+                    proof_type,
+                    category: Some(category.to_string()),
+                    tags: vec!["roommates".to_string()],
+                    visibility: None,
+                    external_ref: None,
+                    idempotency_key: None,
+                    release_delay_secs: None,
+                };
+                app.execute_contract(
+                    Addr::unchecked(USER1),
+                    contract.addr(),
+                    &send_payment,
+                    &payment_amount,
+                )
+                .unwrap();
+            }
+
+            let food_response: crate::msg::PaymentsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetPaymentHistoryFiltered {
+                        username: "alice".to_string(),
+                        category: Some("food".to_string()),
+                        status: None,
+                        from_ts: None,
+                        to_ts: None,
+                        viewer: None,
+                        start_after: None,
+                    },
+                )
+                .unwrap();
+            assert_eq!(food_response.payments.len(), 2);
+            assert_eq!(food_response.payments[0].tags, vec!["roommates".to_string()]);
+
+            let completed_food: crate::msg::PaymentsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetPaymentHistoryFiltered {
+                        username: "alice".to_string(),
+                        category: Some("food".to_string()),
+                        status: Some(PaymentStatus::Completed),
+                        from_ts: None,
+                        to_ts: None,
+                        viewer: None,
+                        start_after: None,
+                    },
+                )
+                .unwrap();
+            assert_eq!(completed_food.payments.len(), 1);
+            assert_eq!(completed_food.payments[0].id, 1);
+
+            // Too many tags are rejected
+            let send_payment = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: payment_amount[0].clone(),
+                description: "Over-tagged".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: (0..6).map(|i| format!("tag{}", i)).collect(),
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            let result = app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &send_payment,
+                &payment_amount,
+            );
+            assert!(result.is_err());
+        }
+    }
+
+    mod error_cases {
+        use super::*;
 
         #[test]
         fn test_duplicate_username_registration() {
@@ -536,6 +1255,7 @@ mod tests {
             // Try to send friend request to self (should fail)
             let send_request = ExecuteMsg::SendFriendRequest {
                 to_username: "alice".to_string(),
+                message: None,
             };
             let result = app.execute_contract(
                 Addr::unchecked(USER1),
@@ -562,6 +1282,12 @@ mod tests {
                 amount: payment_amount[0].clone(),
                 description: "Self payment".to_string(),
                 proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
             };
 
             let result = app.execute_contract(
@@ -592,6 +1318,12 @@ mod tests {
                 },
                 description: "Insufficient funds test".to_string(),
                 proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
             };
 
             let result = app.execute_contract(
@@ -774,7 +1506,9 @@ mod tests {
 
     mod task_system {
         use super::*;
-        use crate::msg::{TaskResponse, TasksResponse};
+        use crate::msg::{DisputeStatsResponse, TaskResponse, TasksResponse};
+        use crate::state::{DisputeOutcome, DisputeReason};
+        use cosmwasm_std::Timestamp;
 
         fn get_future_timestamp() -> u64 {
             // Return timestamp far in the future (Unix timestamp for year 2050)
@@ -800,6 +1534,19 @@ mod tests {
                 deadline_ts: get_future_timestamp(),
                 review_window_secs: None,
                 endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
             };
 
             app.execute_contract(
@@ -850,79 +1597,302 @@ mod tests {
         }
 
         #[test]
-        fn test_zktls_task_instant_release() {
+        fn test_soft_task_auto_approve_after_payer_silence() {
             let (mut app, contract) = proper_instantiate();
             register_users(&mut app, &contract);
 
-            let task_amount = vec![Coin {
+            let task_amount = Coin {
                 denom: NATIVE_DENOM.to_string(),
-                amount: Uint128::new(200),
-            }];
+                amount: Uint128::new(100),
+            };
 
-            // Create zkTLS task (escrow required)
-            let create_task = ExecuteMsg::CreateTask {
+            // Auto-approval is only offered for soft tasks
+            let create_zktls_task = ExecuteMsg::CreateTask {
                 to_username: "bob".to_string(),
-                amount: task_amount[0].clone(),
-                description: "API integration task".to_string(),
+                amount: task_amount.clone(),
+                description: "Not soft".to_string(),
                 proof_type: ProofType::ZkTLS,
                 deadline_ts: get_future_timestamp(),
                 review_window_secs: None,
-                endpoint: "https://api.example.com/verify".to_string(),
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: Some(3600),
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
             };
-
-            app.execute_contract(
+            let result = app.execute_contract(
                 Addr::unchecked(USER1),
                 contract.addr(),
-                &create_task,
-                &task_amount, // Escrow funds
-            )
-            .unwrap();
+                &create_zktls_task,
+                std::slice::from_ref(&task_amount),
+            );
+            assert!(result.is_err());
 
-            // Submit zkTLS proof with "valid" marker for stub verification
-            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: task_amount.clone(),
+                description: "Design a logo".to_string(),
+                proof_type: ProofType::Soft,
+                deadline_ts: get_future_timestamp(),
+                review_window_secs: None,
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: Some(3600),
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task, &[])
+                .unwrap();
+
+            // The silence window only starts once evidence is submitted
+            let auto_approve = ExecuteMsg::AutoApproveIfSilent { task_id: 1 };
+            let result = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &auto_approve, &[]);
+            assert!(result.is_err());
+
+            let submit_evidence = ExecuteMsg::SubmitSoftEvidence {
                 task_id: 1,
-                proof_blob_or_ref: "valid_zktls_proof_data".to_string(),
-                zk_proof_hash: "zk_proof_hash_456".to_string(),
+                evidence_hash: "logo_hash".to_string(),
             };
-            app.execute_contract(
-                Addr::unchecked(USER2), // Bob submits proof
-                contract.addr(),
-                &submit_proof,
-                &[],
-            )
-            .unwrap();
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_evidence, &[])
+                .unwrap();
+
+            let result = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &auto_approve, &[]);
+            assert!(result.is_err());
+
+            app.update_block(|block| block.time = block.time.plus_seconds(3601));
+            let res = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &auto_approve, &[])
+                .unwrap();
+            assert!(res.events.iter().any(|e| e.ty == "wasm-task_auto_approved"));
 
-            // Check task was immediately released
             let task_response: TaskResponse = app
                 .wrap()
                 .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 1 })
                 .unwrap();
-            assert_eq!(task_response.task.status, TaskStatus::Released);
+            assert_eq!(task_response.task.status, TaskStatus::ApprovedPendingFunding);
+
+            // The payer can still fund the approved task
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &ExecuteMsg::ApproveTask { task_id: 1 },
+                &[task_amount],
+            )
+            .unwrap();
 
-            // Check bob received payment
             let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
-            assert_eq!(bob_balance.amount, Uint128::new(10200)); // 10000 initial + 200 payment
+            assert_eq!(bob_balance.amount, Uint128::new(10100));
         }
 
         #[test]
-        fn test_hybrid_task_with_dispute_window() {
+        fn test_soft_task_escrowed_upfront() {
             let (mut app, contract) = proper_instantiate();
             register_users(&mut app, &contract);
 
-            let task_amount = vec![Coin {
+            let task_amount = Coin {
                 denom: NATIVE_DENOM.to_string(),
-                amount: Uint128::new(300),
-            }];
-
-            // Create hybrid task
-            let create_task = ExecuteMsg::CreateTask {
+                amount: Uint128::new(100),
+            };
+            let create_task = |auto_approve_after_secs| ExecuteMsg::CreateTask {
                 to_username: "bob".to_string(),
-                amount: task_amount[0].clone(),
-                description: "Complex verification task".to_string(),
-                proof_type: ProofType::Hybrid,
-                deadline_ts: get_future_timestamp(),
+                amount: task_amount.clone(),
+                description: "Funded soft task".to_string(),
+                proof_type: ProofType::Soft,
+                deadline_ts: get_future_timestamp(),
+                review_window_secs: None,
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs,
+                escrow_upfront: true,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+
+            // Upfront escrow requires the funds at creation
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task(None), &[]);
+            assert!(result.is_err());
+
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &create_task(None),
+                std::slice::from_ref(&task_amount),
+            )
+            .unwrap();
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &create_task(Some(3600)),
+                std::slice::from_ref(&task_amount),
+            )
+            .unwrap();
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(9800));
+
+            for task_id in [1, 2] {
+                let submit_evidence = ExecuteMsg::SubmitSoftEvidence {
+                    task_id,
+                    evidence_hash: "evidence".to_string(),
+                };
+                app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_evidence, &[])
+                    .unwrap();
+            }
+
+            // Approval releases from escrow without attaching funds
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &ExecuteMsg::ApproveTask { task_id: 1 },
+                &[],
+            )
+            .unwrap();
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10100));
+
+            // Auto-approval of a funded task releases straight away
+            app.update_block(|block| block.time = block.time.plus_seconds(3601));
+            app.execute_contract(
+                Addr::unchecked(USER2),
+                contract.addr(),
+                &ExecuteMsg::AutoApproveIfSilent { task_id: 2 },
+                &[],
+            )
+            .unwrap();
+            let task_response: TaskResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 2 })
+                .unwrap();
+            assert_eq!(task_response.task.status, TaskStatus::Released);
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10200));
+        }
+
+        #[test]
+        fn test_zktls_task_instant_release() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let task_amount = vec![Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(200),
+            }];
+
+            // Create zkTLS task (escrow required)
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: task_amount[0].clone(),
+                description: "API integration task".to_string(),
+                proof_type: ProofType::ZkTLS,
+                deadline_ts: get_future_timestamp(),
+                review_window_secs: None,
+                endpoint: "https://api.example.com/verify".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &create_task,
+                &task_amount, // Escrow funds
+            )
+            .unwrap();
+
+            // Submit zkTLS proof with "valid" marker for stub verification
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: 1,
+                proof_blob_or_ref: "valid_zktls_proof_data".to_string(),
+                zk_proof_hash: "zk_proof_hash_456".to_string(),
+            };
+            app.execute_contract(
+                Addr::unchecked(USER2), // Bob submits proof
+                contract.addr(),
+                &submit_proof,
+                &[],
+            )
+            .unwrap();
+
+            // Check task was immediately released
+            let task_response: TaskResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 1 })
+                .unwrap();
+            assert_eq!(task_response.task.status, TaskStatus::Released);
+
+            // Check bob received payment
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10200)); // 10000 initial + 200 payment
+        }
+
+        #[test]
+        fn test_hybrid_task_with_dispute_window() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let task_amount = vec![Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(300),
+            }];
+
+            // Create hybrid task
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: task_amount[0].clone(),
+                description: "Complex verification task".to_string(),
+                proof_type: ProofType::Hybrid,
+                deadline_ts: get_future_timestamp(),
                 review_window_secs: Some(3600), // 1 hour dispute window
                 endpoint: "https://api.example.com/hybrid".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
             };
 
             app.execute_contract(
@@ -958,10 +1928,32 @@ mod tests {
             let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
             assert_eq!(bob_balance.amount, Uint128::new(10000)); // No payment yet
 
-            // Simulate window elapsed and release
-            // Note: In a real test, we'd call ReleaseIfWindowElapsed after advancing blockchain time
-            // For this stub test, we'll just verify the task is in pending release state
-            // let _release_task = ExecuteMsg::ReleaseIfWindowElapsed { task_id: 1 };
+            // Release is blocked until the window has fully elapsed
+            let release_task = ExecuteMsg::ReleaseIfWindowElapsed { task_id: 1 };
+            app.update_block(|block| block.time = block.time.plus_seconds(3600));
+            let result = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &release_task, &[]);
+            assert!(result.is_err());
+
+            // Once the window has passed the payer can no longer dispute, and anyone can release
+            app.update_block(|block| block.time = block.time.plus_seconds(1));
+            let dispute_task = ExecuteMsg::DisputeTask {
+                task_id: 1,
+                reason: None,
+                reason_hash: None,
+            };
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &dispute_task, &[]);
+            assert!(result.is_err());
+
+            app.execute_contract(Addr::unchecked(USER3), contract.addr(), &release_task, &[])
+                .unwrap();
+
+            let task_response: TaskResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 1 })
+                .unwrap();
+            assert_eq!(task_response.task.status, TaskStatus::Released);
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10300));
         }
 
         #[test]
@@ -983,6 +1975,19 @@ mod tests {
                 deadline_ts: get_future_timestamp(),
                 review_window_secs: Some(3600),
                 endpoint: "https://api.example.com/dispute".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
             };
 
             app.execute_contract(
@@ -1010,6 +2015,7 @@ mod tests {
             // Alice disputes the task
             let dispute_task = ExecuteMsg::DisputeTask {
                 task_id: 1,
+                reason: Some(DisputeReason::QualityIssue),
                 reason_hash: Some("dispute_reason_hash".to_string()),
             };
             app.execute_contract(
@@ -1026,11 +2032,12 @@ mod tests {
                 .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 1 })
                 .unwrap();
             assert_eq!(task_response.task.status, TaskStatus::Disputed);
+            assert_eq!(task_response.task.dispute_reason, Some(DisputeReason::QualityIssue));
 
             // Admin resolves dispute in favor of worker
             let resolve_dispute = ExecuteMsg::ResolveDispute {
                 task_id: 1,
-                decision: true, // Release to worker
+                worker_bps: 10_000, // Release to worker
             };
             app.execute_contract(
                 Addr::unchecked(ADMIN), // Only admin can resolve
@@ -1046,7 +2053,67 @@ mod tests {
         }
 
         #[test]
-        #[ignore] // TODO: This test requires blockchain time manipulation
+        fn test_dispute_split_award() {
+            let mut scenario = crate::testing::ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let task_id = scenario.escrowed_task("alice", "bob", 1000, TaskStatus::Disputed);
+            let owner = scenario.owner.clone();
+
+            let err = scenario
+                .app
+                .execute_contract(
+                    owner.clone(),
+                    scenario.contract.addr(),
+                    &ExecuteMsg::ResolveDispute { task_id, worker_bps: 10_001 },
+                    &[],
+                )
+                .unwrap_err();
+            assert_eq!(err.downcast::<crate::ContractError>().unwrap(), crate::ContractError::InvalidWorkerShare {});
+
+            // 70% to the worker, the rest back to the payer, in one transaction
+            scenario
+                .app
+                .execute_contract(
+                    owner,
+                    scenario.contract.addr(),
+                    &ExecuteMsg::ResolveDispute { task_id, worker_bps: 7_000 },
+                    &[],
+                )
+                .unwrap();
+            let bob = scenario.app.wrap().query_balance(scenario.wallet("bob"), "uxion").unwrap();
+            let alice = scenario.app.wrap().query_balance(scenario.wallet("alice"), "uxion").unwrap();
+            assert_eq!(bob.amount, Uint128::new(700));
+            assert_eq!(alice.amount, Uint128::new(300));
+
+            let task: TaskResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetTaskById { task_id })
+                .unwrap();
+            assert_eq!(task.task.status, TaskStatus::Released);
+            assert_eq!(task.task.dispute_award_bps, Some(7_000));
+            assert_eq!(task.task.dispute_reason, Some(DisputeReason::Other));
+            assert_eq!(task.task.dispute_outcome, Some(DisputeOutcome::Split));
+
+            let stats: DisputeStatsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetDisputeStats {})
+                .unwrap();
+            assert_eq!(stats.reasons.len(), DisputeReason::ALL.len());
+            assert!(stats
+                .reasons
+                .iter()
+                .all(|entry| entry.count == u64::from(entry.reason == DisputeReason::Other)));
+            assert!(stats
+                .outcomes
+                .iter()
+                .all(|entry| entry.count == u64::from(entry.outcome == DisputeOutcome::Split)));
+        }
+
+        #[test]
         fn test_task_expiry_refund() {
             let (mut app, contract) = proper_instantiate();
             register_users(&mut app, &contract);
@@ -1056,16 +2123,27 @@ mod tests {
                 amount: Uint128::new(150),
             }];
 
-            // Create task with past deadline for immediate expiry test
-            // We'll create a task with valid deadline first, then manually set it as expired
             let create_task = ExecuteMsg::CreateTask {
                 to_username: "bob".to_string(),
                 amount: task_amount[0].clone(),
                 description: "Expired task".to_string(),
                 proof_type: ProofType::ZkTLS,
-                deadline_ts: get_future_timestamp(), // Valid deadline initially
+                deadline_ts: get_future_timestamp(),
                 review_window_secs: None,
                 endpoint: "https://api.example.com/expired".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
             };
 
             app.execute_contract(
@@ -1076,8 +2154,13 @@ mod tests {
             )
             .unwrap();
 
-            // Try to refund expired task
+            // Refunds are rejected up to and including the deadline
             let refund_task = ExecuteMsg::RefundIfExpired { task_id: 1 };
+            app.update_block(|block| block.time = Timestamp::from_seconds(get_future_timestamp()));
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &refund_task, &[]);
+            assert!(result.is_err());
+
+            app.update_block(|block| block.time = block.time.plus_seconds(1));
             app.execute_contract(
                 Addr::unchecked(USER1), // Anyone can call refund
                 contract.addr(),
@@ -1098,6 +2181,63 @@ mod tests {
             assert_eq!(task_response.task.status, TaskStatus::Refunded);
         }
 
+        #[test]
+        fn test_verified_task_not_refunded_at_deadline() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let task_amount = Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(200),
+            };
+            let deadline_ts = app.block_info().time.seconds() + 600;
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: task_amount.clone(),
+                description: "Deadline race".to_string(),
+                proof_type: ProofType::Hybrid,
+                deadline_ts,
+                review_window_secs: Some(3600),
+                endpoint: "https://api.example.com/hybrid".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task, &[task_amount])
+                .unwrap();
+
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: 1,
+                proof_blob_or_ref: "valid_hybrid_proof_data".to_string(),
+                zk_proof_hash: "hybrid_proof_hash".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_proof, &[])
+                .unwrap();
+
+            // The deadline passes inside the review window; the verified proof still stands
+            app.update_block(|block| block.time = Timestamp::from_seconds(deadline_ts + 1));
+            let refund_task = ExecuteMsg::RefundIfExpired { task_id: 1 };
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &refund_task, &[]);
+            assert!(result.is_err());
+
+            app.update_block(|block| block.time = block.time.plus_seconds(3600));
+            let release_task = ExecuteMsg::ReleaseIfWindowElapsed { task_id: 1 };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &release_task, &[])
+                .unwrap();
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10200));
+        }
+
         #[test]
         fn test_invalid_zktls_proof() {
             let (mut app, contract) = proper_instantiate();
@@ -1117,6 +2257,19 @@ mod tests {
                 deadline_ts: get_future_timestamp(),
                 review_window_secs: None,
                 endpoint: "https://api.example.com/invalid".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
             };
 
             app.execute_contract(
@@ -1147,7 +2300,7 @@ mod tests {
             let (mut app, contract) = proper_instantiate();
             register_users(&mut app, &contract);
 
-            let task_amount = vec![Coin {
+            let task_amount = [Coin {
                 denom: NATIVE_DENOM.to_string(),
                 amount: Uint128::new(50),
             }];
@@ -1162,6 +2315,19 @@ mod tests {
                     deadline_ts: get_future_timestamp(),
                     review_window_secs: None,
                     endpoint: format!("https://api.example.com/task{}", i + 1),
+                    category: None,
+                    tags: vec![],
+                    auto_approve_after_secs: None,
+                    escrow_upfront: false,
+                    co_approvers: vec![],
+                    approvals_required: None,
+                    price_quote: None,
+                    external_ref: None,
+                    idempotency_key: None,
+                    arbiter: None,
+                    depends_on: None,
+                    payout_swap: None,
+                    release_delay_secs: None,
                 };
                 app.execute_contract(
                     Addr::unchecked(USER1),
@@ -1179,6 +2345,7 @@ mod tests {
                     contract.addr(),
                     &QueryMsg::GetTaskHistory {
                         username: "alice".to_string(),
+                        start_after: None,
                     },
                 )
                 .unwrap();
@@ -1191,6 +2358,7 @@ mod tests {
                     contract.addr(),
                     &QueryMsg::GetPendingTasks {
                         username: "alice".to_string(),
+                        start_after: None,
                     },
                 )
                 .unwrap();
@@ -1224,6 +2392,19 @@ mod tests {
                 deadline_ts: get_future_timestamp(),
                 review_window_secs: Some(3600),
                 endpoint: "https://api.example.com/auth".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
             };
             app.execute_contract(
                 Addr::unchecked(USER1),
@@ -1256,6 +2437,19 @@ mod tests {
                 deadline_ts: get_future_timestamp(),
                 review_window_secs: None,
                 endpoint: "https://api.example.com/soft".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
             };
             app.execute_contract(
                 Addr::unchecked(USER1),
@@ -1294,6 +2488,19 @@ mod tests {
                 deadline_ts: get_future_timestamp(),
                 review_window_secs: None,
                 endpoint: "https://api.example.com/self".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
             };
             let result = app.execute_contract(
                 Addr::unchecked(USER1), // Alice
@@ -1304,4 +2511,7206 @@ mod tests {
             assert!(result.is_err());
         }
     }
+
+    mod admin_intervention {
+        use super::*;
+        use crate::msg::{
+            AdminOutcome, IndexKind, OwnershipResponse, PaymentResponse, RoleGrant, RolesResponse, TaskResponse,
+            UserResponse, UsersResponse,
+        };
+        use crate::state::{AdminRole, Badge};
+        use crate::ContractError;
+
+        #[test]
+        fn test_admin_refunds_escrowed_payment() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_amount = vec![Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(100),
+            }];
+
+            // Direct payment with proof keeps funds in the contract
+            let send_payment = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: payment_amount[0].clone(),
+                description: "Wrong recipient".to_string(),
+                proof_type: ProofType::Photo,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &send_payment,
+                &payment_amount,
+            )
+            .unwrap();
+
+            // Non-admin cannot intervene
+            let resolve = ExecuteMsg::AdminResolvePayment {
+                payment_id: 1,
+                outcome: AdminOutcome::Refund,
+                reason: "support ticket #42".to_string(),
+            };
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &resolve, &[]);
+            assert!(result.is_err());
+
+            // Reason is mandatory
+            let no_reason = ExecuteMsg::AdminResolvePayment {
+                payment_id: 1,
+                outcome: AdminOutcome::Refund,
+                reason: " ".to_string(),
+            };
+            let result = app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &no_reason, &[]);
+            assert!(result.is_err());
+
+            let res = app
+                .execute_contract(Addr::unchecked(ADMIN), contract.addr(), &resolve, &[])
+                .unwrap();
+            let audit = res
+                .events
+                .iter()
+                .find(|e| e.ty == "wasm-admin_intervention")
+                .unwrap();
+            assert!(audit
+                .attributes
+                .iter()
+                .any(|a| a.key == "reason" && a.value == "support ticket #42"));
+
+            // Alice is refunded
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(10000));
+
+            let payment_response: PaymentResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
+                .unwrap();
+            assert_eq!(payment_response.payment.status, PaymentStatus::Cancelled);
+
+            // Resolved payments cannot be resolved again
+            let result = app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &resolve, &[]);
+            assert!(result.is_err());
+        }
+
+        #[test]
+        fn test_admin_cannot_release_unfunded_request() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: Coin {
+                    denom: NATIVE_DENOM.to_string(),
+                    amount: Uint128::new(100),
+                },
+                description: "Dinner".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &payment_request, &[])
+                .unwrap();
+
+            let release = ExecuteMsg::AdminResolvePayment {
+                payment_id: 1,
+                outcome: AdminOutcome::Release,
+                reason: "requested by user".to_string(),
+            };
+            let result = app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &release, &[]);
+            assert!(result.is_err());
+        }
+
+        #[test]
+        fn test_admin_cancel_task_refunds_escrow() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let task_amount = vec![Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(300),
+            }];
+
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: task_amount[0].clone(),
+                description: "Compromised account".to_string(),
+                proof_type: ProofType::Hybrid,
+                deadline_ts: 2524608000,
+                review_window_secs: Some(3600),
+                endpoint: "https://api.example.com/admin".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &create_task,
+                &task_amount,
+            )
+            .unwrap();
+
+            let cancel = ExecuteMsg::AdminCancelTask {
+                task_id: 1,
+                reason: "worker account compromised".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &cancel, &[])
+                .unwrap();
+
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(10000));
+
+            let task_response: TaskResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 1 })
+                .unwrap();
+            assert_eq!(task_response.task.status, TaskStatus::Refunded);
+        }
+
+        #[test]
+        fn test_dispute_resolver_role() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            let resolver = Addr::unchecked("resolver");
+
+            let payment_amount = Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(100),
+            };
+            let send_payment = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: payment_amount.clone(),
+                description: "Escrowed".to_string(),
+                proof_type: ProofType::Photo,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &send_payment,
+                &[payment_amount],
+            )
+            .unwrap();
+
+            let resolve = ExecuteMsg::AdminResolvePayment {
+                payment_id: 1,
+                outcome: AdminOutcome::Refund,
+                reason: "duplicate payment".to_string(),
+            };
+            let result = app.execute_contract(resolver.clone(), contract.addr(), &resolve, &[]);
+            assert!(result.is_err());
+
+            // Only the owner may grant roles
+            let grant = ExecuteMsg::GrantRole {
+                role: AdminRole::DisputeResolver,
+                address: resolver.to_string(),
+            };
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &grant, &[]);
+            assert!(result.is_err());
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &grant, &[])
+                .unwrap();
+
+            let roles: RolesResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetRoles {})
+                .unwrap();
+            assert_eq!(roles.owner, Addr::unchecked(ADMIN));
+            assert_eq!(
+                roles.grants,
+                vec![RoleGrant {
+                    address: resolver.clone(),
+                    role: AdminRole::DisputeResolver,
+                }]
+            );
+
+            // A dispute resolver holds no other privileges
+            let rebuild = ExecuteMsg::RebuildIndex {
+                index: IndexKind::UserPayments,
+                start_after: None,
+                limit: None,
+            };
+            let result = app.execute_contract(resolver.clone(), contract.addr(), &rebuild, &[]);
+            assert!(result.is_err());
+
+            app.execute_contract(resolver.clone(), contract.addr(), &resolve, &[])
+                .unwrap();
+            let payment_response: PaymentResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
+                .unwrap();
+            assert_eq!(payment_response.payment.status, PaymentStatus::Cancelled);
+
+            let revoke = ExecuteMsg::RevokeRole {
+                role: AdminRole::DisputeResolver,
+                address: resolver.to_string(),
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &revoke, &[])
+                .unwrap();
+            let roles: RolesResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetRoles {})
+                .unwrap();
+            assert!(roles.grants.is_empty());
+        }
+
+        #[test]
+        fn test_two_step_ownership_transfer() {
+            let (mut app, contract) = proper_instantiate();
+            let multisig = Addr::unchecked("multisig");
+
+            // Only the owner may propose a new owner
+            let transfer = ExecuteMsg::TransferOwnership {
+                new_owner: multisig.to_string(),
+            };
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &transfer, &[]);
+            assert!(result.is_err());
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &transfer, &[])
+                .unwrap();
+
+            let ownership: OwnershipResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetOwnership {})
+                .unwrap();
+            assert_eq!(ownership.owner, Addr::unchecked(ADMIN));
+            assert_eq!(ownership.pending_owner, Some(multisig.clone()));
+
+            // Only the proposed owner may accept
+            let accept = ExecuteMsg::AcceptOwnership {};
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &accept, &[]);
+            assert!(result.is_err());
+            app.execute_contract(multisig.clone(), contract.addr(), &accept, &[])
+                .unwrap();
+
+            let ownership: OwnershipResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetOwnership {})
+                .unwrap();
+            assert_eq!(ownership.owner, multisig);
+            assert_eq!(ownership.pending_owner, None);
+
+            // Privileged operations now follow the new owner
+            let rebuild = ExecuteMsg::RebuildIndex {
+                index: IndexKind::UserPayments,
+                start_after: None,
+                limit: None,
+            };
+            let result = app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &rebuild, &[]);
+            assert!(result.is_err());
+            app.execute_contract(multisig, contract.addr(), &rebuild, &[])
+                .unwrap();
+        }
+
+        #[test]
+        fn test_attestor_sets_user_badge() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            let attestor = Addr::unchecked("kyc_attestor");
+
+            // Users cannot badge themselves
+            let set_badge = ExecuteMsg::SetUserBadge {
+                username: "alice".to_string(),
+                badge: Some(Badge::Merchant),
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &set_badge, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotAuthorized {});
+
+            // A registered attestor can
+            let grant = ExecuteMsg::GrantRole {
+                role: AdminRole::Attestor,
+                address: attestor.to_string(),
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &grant, &[])
+                .unwrap();
+            app.execute_contract(attestor.clone(), contract.addr(), &set_badge, &[])
+                .unwrap();
+
+            let user: UserResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetUserByUsername {
+                        username: "alice".to_string(),
+                    },
+                )
+                .unwrap();
+            assert_eq!(user.user.badge, Some(Badge::Merchant));
+
+            let search: UsersResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::SearchUsers {
+                        query: "ali".to_string(),
+                        start_after: None,
+                    },
+                )
+                .unwrap();
+            assert_eq!(search.users[0].badge, Some(Badge::Merchant));
+
+            // The owner can clear it again
+            let clear_badge = ExecuteMsg::SetUserBadge {
+                username: "alice".to_string(),
+                badge: None,
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &clear_badge, &[])
+                .unwrap();
+            let user: UserResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetUserByUsername {
+                        username: "alice".to_string(),
+                    },
+                )
+                .unwrap();
+            assert_eq!(user.user.badge, None);
+
+            let missing = ExecuteMsg::SetUserBadge {
+                username: "nobody".to_string(),
+                badge: Some(Badge::Verified),
+            };
+            let err = app
+                .execute_contract(attestor, contract.addr(), &missing, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::UserNotFound {});
+        }
+    }
+
+    mod escrow_accounting {
+        use super::*;
+        use crate::msg::{EscrowLiabilitiesResponse, UserEscrowedResponse};
+
+        fn query_liabilities(app: &App, contract: &SocialPaymentContract) -> Vec<Coin> {
+            let response: EscrowLiabilitiesResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetEscrowLiabilities {})
+                .unwrap();
+            response.liabilities
+        }
+
+        #[test]
+        fn test_escrow_liabilities_track_payments_and_tasks() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_amount = [Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(100),
+            }];
+            let task_amount = [Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(300),
+            }];
+
+            // Escrowed direct payment
+            let send_payment = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: payment_amount[0].clone(),
+                description: "Escrowed payment".to_string(),
+                proof_type: ProofType::Photo,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &send_payment,
+                &payment_amount,
+            )
+            .unwrap();
+
+            // Escrowed task
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: task_amount[0].clone(),
+                description: "Escrowed task".to_string(),
+                proof_type: ProofType::Hybrid,
+                deadline_ts: 2524608000,
+                review_window_secs: Some(3600),
+                endpoint: "https://api.example.com/escrow".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &create_task,
+                &task_amount,
+            )
+            .unwrap();
+
+            let liabilities = query_liabilities(&app, &contract);
+            assert_eq!(liabilities, vec![Coin::new(400, NATIVE_DENOM)]);
+
+            let contract_balance = app
+                .wrap()
+                .query_balance(contract.addr(), NATIVE_DENOM)
+                .unwrap();
+            assert_eq!(contract_balance, liabilities[0]);
+
+            let alice_escrowed: UserEscrowedResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetUserEscrowed {
+                        username: "alice".to_string(),
+                    },
+                )
+                .unwrap();
+            assert_eq!(alice_escrowed.escrowed, vec![Coin::new(400, NATIVE_DENOM)]);
+
+            // Cancelling the payment refunds alice and releases the liability
+            let cancel_payment = ExecuteMsg::CancelPayment { payment_id: 1 };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &cancel_payment, &[])
+                .unwrap();
+
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(9700));
+            assert_eq!(
+                query_liabilities(&app, &contract),
+                vec![Coin::new(300, NATIVE_DENOM)]
+            );
+
+            // Releasing the task clears everything
+            let cancel_task = ExecuteMsg::AdminCancelTask {
+                task_id: 1,
+                reason: "reconciliation test".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &cancel_task, &[])
+                .unwrap();
+            assert!(query_liabilities(&app, &contract).is_empty());
+
+            let bob_escrowed: UserEscrowedResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetUserEscrowed {
+                        username: "bob".to_string(),
+                    },
+                )
+                .unwrap();
+            assert!(bob_escrowed.escrowed.is_empty());
+        }
+    }
+
+    mod maintenance {
+        use super::*;
+        use crate::msg::{IndexKind, IndexReportResponse};
+        use crate::testing::attribute;
+
+        #[test]
+        fn test_rebuild_index_in_batches() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_amount = [Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(10),
+            }];
+            for _ in 0..3 {
+                let send_payment = ExecuteMsg::SendDirectPayment {
+                    to_username: "bob".to_string(),
+                    amount: payment_amount[0].clone(),
+                    description: "Rebuild me".to_string(),
+                    proof_type: ProofType::None,
+                    category: None,
+                    tags: vec![],
+                    visibility: None,
+                    external_ref: None,
+                    idempotency_key: None,
+                    release_delay_secs: None,
+                };
+                app.execute_contract(
+                    Addr::unchecked(USER1),
+                    contract.addr(),
+                    &send_payment,
+                    &payment_amount,
+                )
+                .unwrap();
+            }
+
+            // Only the owner may rebuild
+            let rebuild = ExecuteMsg::RebuildIndex {
+                index: IndexKind::UserPayments,
+                start_after: None,
+                limit: Some(2),
+            };
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &rebuild, &[]);
+            assert!(result.is_err());
+
+            let res = app
+                .execute_contract(Addr::unchecked(ADMIN), contract.addr(), &rebuild, &[])
+                .unwrap();
+            assert_eq!(attribute(&res, "processed").unwrap(), "2");
+            assert_eq!(attribute(&res, "done").unwrap(), "false");
+            let cursor = attribute(&res, "last_key").unwrap();
+            assert_eq!(cursor, "2");
+
+            let rebuild = ExecuteMsg::RebuildIndex {
+                index: IndexKind::UserPayments,
+                start_after: Some(cursor),
+                limit: Some(2),
+            };
+            let res = app
+                .execute_contract(Addr::unchecked(ADMIN), contract.addr(), &rebuild, &[])
+                .unwrap();
+            assert_eq!(attribute(&res, "processed").unwrap(), "1");
+            assert_eq!(attribute(&res, "done").unwrap(), "true");
+
+            // Id-keyed indexes reject non-numeric cursors
+            let rebuild = ExecuteMsg::RebuildIndex {
+                index: IndexKind::UserTasks,
+                start_after: Some("alice".to_string()),
+                limit: None,
+            };
+            let result = app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &rebuild, &[]);
+            assert!(result.is_err());
+
+            // An empty primary map finishes immediately without a cursor
+            let rebuild = ExecuteMsg::RebuildIndex {
+                index: IndexKind::UserTasks,
+                start_after: None,
+                limit: None,
+            };
+            let res = app
+                .execute_contract(Addr::unchecked(ADMIN), contract.addr(), &rebuild, &[])
+                .unwrap();
+            assert_eq!(attribute(&res, "processed").unwrap(), "0");
+            assert_eq!(attribute(&res, "done").unwrap(), "true");
+
+            let report: IndexReportResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetIndexReport {})
+                .unwrap();
+            assert_eq!(report.indexes.len(), 5);
+            assert!(report.indexes.iter().all(|r| r.in_sync));
+            assert_eq!(report.indexes[1].primary_count, 3);
+            assert_eq!(report.indexes[1].index_entries, 6);
+        }
+    }
+
+    mod completion_callbacks {
+        use super::*;
+        use crate::msg::{CallbackExecuteMsg, CallbackPayload};
+        use crate::state::RecordKind;
+        use cosmwasm_std::{
+            to_json_binary, to_json_vec, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError,
+            StdResult,
+        };
+
+        // Receiver that stores the last callback it was sent
+        fn receiver_template() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                |deps: DepsMut, _env: Env, _info: MessageInfo, msg: CallbackExecuteMsg| -> StdResult<Response> {
+                    let CallbackExecuteMsg::ProofpayCallback(payload) = msg;
+                    deps.storage.set(b"last", &to_json_vec(&payload)?);
+                    Ok(Response::new())
+                },
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |deps: Deps, _env: Env, _msg: Empty| -> StdResult<Binary> {
+                    Ok(Binary::from(deps.storage.get(b"last").unwrap_or_default()))
+                },
+            );
+            Box::new(contract)
+        }
+
+        // Receiver that always fails
+        fn failing_receiver_template() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: CallbackExecuteMsg| -> StdResult<Response> {
+                    Err(StdError::generic_err("receiver is broken"))
+                },
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: Deps, _env: Env, _msg: Empty| -> StdResult<Binary> { Ok(Binary::default()) },
+            );
+            Box::new(contract)
+        }
+
+        fn instantiate_receiver(app: &mut App, template: Box<dyn Contract<Empty>>) -> Addr {
+            let code_id = app.store_code(template);
+            app.instantiate_contract(code_id, Addr::unchecked(ADMIN), &Empty {}, &[], "receiver", None)
+                .unwrap()
+        }
+
+        fn create_escrowed_payment(app: &mut App, contract: &SocialPaymentContract) {
+            let payment_amount = [Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(100),
+            }];
+            let send_payment = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: payment_amount[0].clone(),
+                description: "Composable payment".to_string(),
+                proof_type: ProofType::Photo,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &send_payment,
+                &payment_amount,
+            )
+            .unwrap();
+        }
+
+        #[test]
+        fn test_callback_on_payment_completion() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            let receiver = instantiate_receiver(&mut app, receiver_template());
+            create_escrowed_payment(&mut app, &contract);
+
+            // Only the creator can register a callback
+            let register = ExecuteMsg::RegisterCallback {
+                kind: RecordKind::Payment,
+                id: 1,
+                contract_addr: receiver.to_string(),
+                msg: Some(to_json_binary(&"order-17").unwrap()),
+            };
+            let result = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &register, &[]);
+            assert!(result.is_err());
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &register, &[])
+                .unwrap();
+
+            let submit_proof = ExecuteMsg::SubmitProof {
+                payment_id: 1,
+                proof_data: "photo_hash".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_proof, &[])
+                .unwrap();
+            let approve = ExecuteMsg::ApprovePayment { payment_id: 1 };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &approve, &[])
+                .unwrap();
+
+            let payload: CallbackPayload = app
+                .wrap()
+                .query_wasm_smart(receiver, &Empty {})
+                .unwrap();
+            assert_eq!(payload.kind, RecordKind::Payment);
+            assert_eq!(payload.id, 1);
+            assert_eq!(payload.status, "Completed");
+            assert_eq!(payload.amount, Coin::new(100, NATIVE_DENOM));
+            assert_eq!(payload.msg, Some(to_json_binary(&"order-17").unwrap()));
+        }
+
+        #[test]
+        fn test_failing_callback_does_not_block_settlement() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            let receiver = instantiate_receiver(&mut app, failing_receiver_template());
+            create_escrowed_payment(&mut app, &contract);
+
+            let register = ExecuteMsg::RegisterCallback {
+                kind: RecordKind::Payment,
+                id: 1,
+                contract_addr: receiver.to_string(),
+                msg: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &register, &[])
+                .unwrap();
+
+            let cancel = ExecuteMsg::CancelPayment { payment_id: 1 };
+            let res = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &cancel, &[])
+                .unwrap();
+            assert!(res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .any(|a| a.key == "action" && a.value == "callback_failed"));
+
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(10000));
+        }
+    }
+
+    mod analytics_export {
+        use super::*;
+        use crate::msg::ExportEventsResponse;
+
+        #[test]
+        fn test_export_events_incremental_sync() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let send_request = ExecuteMsg::SendFriendRequest {
+                to_username: "bob".to_string(),
+                message: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_request, &[])
+                .unwrap();
+
+            // Failed executes are not logged
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_request, &[]);
+            assert!(result.is_err());
+
+            let first_page: ExportEventsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::ExportEvents {
+                        since_seq: None,
+                        limit: Some(2),
+                    },
+                )
+                .unwrap();
+            assert_eq!(first_page.latest_seq, 4);
+            assert_eq!(first_page.events.len(), 2);
+            assert_eq!(first_page.events[0].seq, 1);
+            assert_eq!(first_page.events[0].action, "register_user");
+            assert_eq!(first_page.events[0].sender, Addr::unchecked(USER1));
+
+            // Continue from the last seen sequence number
+            let second_page: ExportEventsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::ExportEvents {
+                        since_seq: Some(first_page.events[1].seq),
+                        limit: None,
+                    },
+                )
+                .unwrap();
+            let seqs: Vec<u64> = second_page.events.iter().map(|e| e.seq).collect();
+            assert_eq!(seqs, vec![3, 4]);
+            assert_eq!(second_page.events[1].action, "send_friend_request");
+            assert!(second_page.events[1]
+                .attributes
+                .iter()
+                .any(|a| a.key == "to_username" && a.value == "bob"));
+        }
+    }
+
+    mod redaction {
+        use super::*;
+        use crate::helpers::hash_data;
+        use crate::state::RecordKind;
+
+        #[test]
+        fn test_redact_completed_payment_text() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let payment_amount = Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(100),
+            };
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &ExecuteMsg::CreatePaymentRequest {
+                    to_username: "bob".to_string(),
+                    amount: payment_amount.clone(),
+                    description: "Rent for March".to_string(),
+                    proof_type: ProofType::None,
+                    category: None,
+                    tags: vec![],
+                    visibility: None,
+                    price_quote: None,
+                    external_ref: None,
+                    idempotency_key: None,
+                },
+                &[],
+            )
+            .unwrap();
+
+            let redact = ExecuteMsg::RedactRecordText {
+                kind: RecordKind::Payment,
+                id: 1,
+            };
+
+            // Open records cannot be redacted
+            let result = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &redact, &[]);
+            assert!(result.is_err());
+
+            app.execute_contract(
+                Addr::unchecked(USER2),
+                contract.addr(),
+                &ExecuteMsg::RejectPayment { payment_id: 1 },
+                &[],
+            )
+            .unwrap();
+
+            // Only the parties to the payment may redact
+            let result = app.execute_contract(Addr::unchecked(USER3), contract.addr(), &redact, &[]);
+            assert!(result.is_err());
+
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &redact, &[])
+                .unwrap();
+
+            let payment_response: crate::msg::PaymentResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
+                .unwrap();
+            assert!(payment_response.payment.redacted);
+            assert_eq!(payment_response.payment.description, hash_data("Rent for March"));
+            assert_eq!(payment_response.payment.amount, payment_amount);
+            assert_eq!(payment_response.payment.status, PaymentStatus::Rejected);
+
+            // Redaction is one-way
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &redact, &[]);
+            assert!(result.is_err());
+        }
+    }
+
+    mod account_snapshot {
+        use super::*;
+        use crate::msg::AccountSnapshotResponse;
+
+        fn query_snapshot(
+            app: &App,
+            contract: &SocialPaymentContract,
+            payments_before: Option<u64>,
+        ) -> AccountSnapshotResponse {
+            app.wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetAccountSnapshot {
+                        username: "Alice".to_string(),
+                        limit: Some(2),
+                        payments_before,
+                        tasks_before: None,
+                        viewer: None,
+                    },
+                )
+                .unwrap()
+        }
+
+        #[test]
+        fn test_account_snapshot_pages_recent_payments() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let amount = Coin {
+                denom: NATIVE_DENOM.to_string(),
+                amount: Uint128::new(100),
+            };
+            // One escrowed payment followed by two instant ones
+            for proof_type in [ProofType::Photo, ProofType::None, ProofType::None] {
+                let send_payment = ExecuteMsg::SendDirectPayment {
+                    to_username: "bob".to_string(),
+                    amount: amount.clone(),
+                    description: "Snapshot".to_string(),
+                    proof_type,
+                    category: None,
+                    tags: vec![],
+                    visibility: None,
+                    external_ref: None,
+                    idempotency_key: None,
+                    release_delay_secs: None,
+                };
+                app.execute_contract(
+                    Addr::unchecked(USER1),
+                    contract.addr(),
+                    &send_payment,
+                    std::slice::from_ref(&amount),
+                )
+                .unwrap();
+            }
+
+            let send_request = ExecuteMsg::SendFriendRequest {
+                to_username: "alice".to_string(),
+                message: None,
+            };
+            app.execute_contract(Addr::unchecked(USER3), contract.addr(), &send_request, &[])
+                .unwrap();
+
+            let snapshot = query_snapshot(&app, &contract, None);
+            assert_eq!(snapshot.user.username, "alice");
+            assert!(snapshot.friends.friends.is_empty());
+            assert_eq!(snapshot.pending_friend_requests.requests.len(), 1);
+            assert_eq!(snapshot.pending_friend_requests.requests[0].from_username, "charlie");
+            assert_eq!(snapshot.escrowed, vec![amount]);
+            assert!(snapshot.recent_tasks.is_empty());
+            assert_eq!(snapshot.next_tasks_before, None);
+
+            // Newest payments first, with a cursor to the older page
+            let ids: Vec<u64> = snapshot.recent_payments.iter().map(|p| p.id).collect();
+            assert_eq!(ids, vec![3, 2]);
+            assert_eq!(snapshot.next_payments_before, Some(2));
+
+            let older = query_snapshot(&app, &contract, snapshot.next_payments_before);
+            let ids: Vec<u64> = older.recent_payments.iter().map(|p| p.id).collect();
+            assert_eq!(ids, vec![1]);
+            assert_eq!(older.next_payments_before, None);
+        }
+    }
+
+    mod scenario_fixtures {
+        use super::*;
+        use crate::msg::{AreFriendsResponse, TaskResponse};
+        use crate::testing::ScenarioBuilder;
+
+        #[test]
+        fn test_scenario_builder_sets_up_friends_and_tasks() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_dana", "dana", 1000)
+                .with_user("wallet_eli", "eli", 1000)
+                .with_friends("dana", "eli")
+                .build();
+
+            let friends: AreFriendsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::AreFriends {
+                        username1: "dana".to_string(),
+                        username2: "eli".to_string(),
+                    },
+                )
+                .unwrap();
+            assert!(friends.are_friends);
+
+            for status in [TaskStatus::Escrowed, TaskStatus::PendingRelease, TaskStatus::Disputed, TaskStatus::Refunded] {
+                let task_id = scenario.escrowed_task("dana", "eli", 100, status.clone());
+                let task_response: TaskResponse = scenario
+                    .app
+                    .wrap()
+                    .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetTaskById { task_id })
+                    .unwrap();
+                assert_eq!(task_response.task.status, status);
+            }
+
+            // Three tasks are still escrowed; the refunded one was returned
+            let balance = scenario
+                .app
+                .wrap()
+                .query_balance(scenario.wallet("dana"), &scenario.denom)
+                .unwrap();
+            assert_eq!(balance.amount, Uint128::new(700));
+        }
+    }
+
+    mod yield_routing {
+        use super::*;
+        use crate::msg::{
+            AdapterPositionResponse, EscrowLiabilitiesResponse, TaskResponse, YieldAdapterExecuteMsg,
+            YieldAdapterQueryMsg, YieldConfigMsg, YieldPositionResponse,
+        };
+        use crate::state::DisputeReason;
+        use cosmwasm_std::{
+            from_json, to_json_binary, BankMsg, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
+        };
+
+        const TREASURY: &str = "treasury";
+
+        // Adapter that pays out every position at a flat VALUE_PERCENT of its principal
+        fn adapter_template<const VALUE_PERCENT: u128>() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                |deps: DepsMut, _env: Env, info: MessageInfo, msg: YieldAdapterExecuteMsg| -> StdResult<Response> {
+                    match msg {
+                        YieldAdapterExecuteMsg::Deposit { position_id } => {
+                            let key = format!("{}:{}", info.sender, position_id);
+                            deps.storage.set(key.as_bytes(), &to_json_binary(&info.funds[0])?);
+                            Ok(Response::new())
+                        }
+                        YieldAdapterExecuteMsg::Withdraw { position_id } => {
+                            let key = format!("{}:{}", info.sender, position_id);
+                            let principal: Coin = from_json(deps.storage.get(key.as_bytes()).unwrap())?;
+                            deps.storage.remove(key.as_bytes());
+                            Ok(Response::new().add_message(BankMsg::Send {
+                                to_address: info.sender.to_string(),
+                                amount: vec![Coin::new(principal.amount.u128() * VALUE_PERCENT / 100, principal.denom)],
+                            }))
+                        }
+                    }
+                },
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |deps: Deps, _env: Env, msg: YieldAdapterQueryMsg| -> StdResult<Binary> {
+                    let YieldAdapterQueryMsg::Position { depositor, position_id } = msg;
+                    let key = format!("{}:{}", depositor, position_id);
+                    let principal: Coin = from_json(deps.storage.get(key.as_bytes()).unwrap())?;
+                    to_json_binary(&AdapterPositionResponse {
+                        value: Coin::new(principal.amount.u128() * VALUE_PERCENT / 100, principal.denom),
+                    })
+                },
+            );
+            Box::new(contract)
+        }
+
+        // Instantiate a funded adapter and point the contract at it
+        fn setup_adapter(app: &mut App, contract: &SocialPaymentContract, adapter: Box<dyn Contract<Empty>>) -> Addr {
+            let code_id = app.store_code(adapter);
+            let adapter = app
+                .instantiate_contract(code_id, Addr::unchecked(ADMIN), &Empty {}, &[], "adapter", None)
+                .unwrap();
+            app.send_tokens(Addr::unchecked(USER3), adapter.clone(), &[Coin::new(1000, NATIVE_DENOM)])
+                .unwrap();
+
+            let set_config = ExecuteMsg::SetYieldConfig {
+                config: Some(YieldConfigMsg {
+                    adapter: adapter.to_string(),
+                    denom: NATIVE_DENOM.to_string(),
+                    threshold: Uint128::new(500),
+                    treasury: TREASURY.to_string(),
+                    user_share_bps: 5000,
+                }),
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &set_config, &[])
+                .unwrap();
+            adapter
+        }
+
+        fn create_hybrid_task(app: &mut App, contract: &SocialPaymentContract, amount: u128) {
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: Coin::new(amount, NATIVE_DENOM),
+                description: "Large job".to_string(),
+                proof_type: ProofType::Hybrid,
+                deadline_ts: app.block_info().time.seconds() + 86400,
+                review_window_secs: Some(3600),
+                endpoint: "https://api.example.com/hybrid".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(
+                Addr::unchecked(USER1),
+                contract.addr(),
+                &create_task,
+                &[Coin::new(amount, NATIVE_DENOM)],
+            )
+            .unwrap();
+        }
+
+        fn yield_position(app: &App, contract: &SocialPaymentContract, task_id: u64) -> YieldPositionResponse {
+            app.wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetYieldPosition { task_id })
+                .unwrap()
+        }
+
+        #[test]
+        fn test_large_escrow_earns_yield_until_release() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            let adapter = setup_adapter(&mut app, &contract, adapter_template::<110>());
+
+            // Only escrows at or above the threshold are deposited
+            create_hybrid_task(&mut app, &contract, 1000);
+            create_hybrid_task(&mut app, &contract, 100);
+            assert!(yield_position(&app, &contract, 1).position.is_some());
+            assert!(yield_position(&app, &contract, 2).position.is_none());
+            let held = app.wrap().query_balance(contract.addr(), NATIVE_DENOM).unwrap();
+            assert_eq!(held.amount, Uint128::new(100));
+            let adapter_balance = app.wrap().query_balance(&adapter, NATIVE_DENOM).unwrap();
+            assert_eq!(adapter_balance.amount, Uint128::new(2000));
+
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: 1,
+                proof_blob_or_ref: "valid_hybrid_proof_data".to_string(),
+                zk_proof_hash: "hybrid_proof_hash".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_proof, &[])
+                .unwrap();
+            app.update_block(|block| block.time = block.time.plus_seconds(3601));
+            let release_task = ExecuteMsg::ReleaseIfWindowElapsed { task_id: 1 };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &release_task, &[])
+                .unwrap();
+
+            // 100 of yield accrued: half to the worker, half to the treasury
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(11050));
+            let treasury_balance = app.wrap().query_balance(TREASURY, NATIVE_DENOM).unwrap();
+            assert_eq!(treasury_balance.amount, Uint128::new(50));
+            let held = app.wrap().query_balance(contract.addr(), NATIVE_DENOM).unwrap();
+            assert_eq!(held.amount, Uint128::new(100));
+            assert!(yield_position(&app, &contract, 1).position.is_none());
+        }
+
+        #[test]
+        fn test_deposited_escrow_refunds_after_yield_disabled() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            // Yield routing is off by default
+            create_hybrid_task(&mut app, &contract, 1000);
+            assert!(yield_position(&app, &contract, 1).position.is_none());
+
+            // Only config managers can configure routing, and shares are capped
+            let bad_share = ExecuteMsg::SetYieldConfig {
+                config: Some(YieldConfigMsg {
+                    adapter: "adapter".to_string(),
+                    denom: NATIVE_DENOM.to_string(),
+                    threshold: Uint128::zero(),
+                    treasury: TREASURY.to_string(),
+                    user_share_bps: 10_001,
+                }),
+            };
+            let result = app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &bad_share, &[]);
+            assert!(result.is_err());
+            let disable = ExecuteMsg::SetYieldConfig { config: None };
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &disable, &[]);
+            assert!(result.is_err());
+
+            setup_adapter(&mut app, &contract, adapter_template::<110>());
+            create_hybrid_task(&mut app, &contract, 1000);
+            assert!(yield_position(&app, &contract, 2).position.is_some());
+
+            // Disabling stops new deposits, but the open position still withdraws on refund
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &disable, &[])
+                .unwrap();
+            let cancel_task = ExecuteMsg::AdminCancelTask {
+                task_id: 2,
+                reason: "Worker unavailable".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &cancel_task, &[])
+                .unwrap();
+
+            let task_response: TaskResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 2 })
+                .unwrap();
+            assert_eq!(task_response.task.status, TaskStatus::Refunded);
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(9050));
+            let treasury_balance = app.wrap().query_balance(TREASURY, NATIVE_DENOM).unwrap();
+            assert_eq!(treasury_balance.amount, Uint128::new(50));
+        }
+
+        #[test]
+        fn test_vault_loss_is_split_pro_rata_and_recorded() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            setup_adapter(&mut app, &contract, adapter_template::<80>());
+            create_hybrid_task(&mut app, &contract, 1000);
+
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: 1,
+                proof_blob_or_ref: "valid_hybrid_proof_data".to_string(),
+                zk_proof_hash: "hybrid_proof_hash".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_proof, &[])
+                .unwrap();
+            let dispute_task = ExecuteMsg::DisputeTask {
+                task_id: 1,
+                reason: Some(DisputeReason::QualityIssue),
+                reason_hash: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &dispute_task, &[])
+                .unwrap();
+
+            // The vault returns 800 of the 1000 deposited; both parties bear the loss in proportion
+            let resolve_dispute = ExecuteMsg::ResolveDispute { task_id: 1, worker_bps: 7_000 };
+            let res = app
+                .execute_contract(Addr::unchecked(ADMIN), contract.addr(), &resolve_dispute, &[])
+                .unwrap();
+            let shortfall = res.events.iter().find(|e| e.ty == "wasm-yield_shortfall").unwrap();
+            assert!(shortfall
+                .attributes
+                .iter()
+                .any(|a| a.key == "loss" && a.value == format!("200{}", NATIVE_DENOM)));
+
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10560));
+            let alice_balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(alice_balance.amount, Uint128::new(9240));
+            let held = app.wrap().query_balance(contract.addr(), NATIVE_DENOM).unwrap();
+            assert_eq!(held.amount, Uint128::zero());
+
+            let escrow: EscrowLiabilitiesResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetEscrowLiabilities {})
+                .unwrap();
+            assert!(escrow.liabilities.is_empty());
+            assert_eq!(escrow.losses, vec![Coin::new(200, NATIVE_DENOM)]);
+        }
+    }
+
+    mod co_approval {
+        use super::*;
+        use crate::msg::{ConfigResponse, TaskResponse};
+
+        const USER4: &str = "user4";
+        const USER5: &str = "user5";
+
+        fn setup() -> (App, SocialPaymentContract) {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::register_user(&mut app, &contract, &Addr::unchecked(USER4), "dave");
+            crate::testing::register_user(&mut app, &contract, &Addr::unchecked(USER5), "erin");
+
+            let update_config = ExecuteMsg::UpdateConfig {
+                co_approval_thresholds: Some(vec![Coin::new(500, NATIVE_DENOM)]),
+                review_window: None,
+                archive_retention_secs: None,
+                accepted_denoms: None,
+                instant_release: None,
+                friend_requests: None,
+                text_limits: None,
+                open_item_limits: None,
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &update_config, &[])
+                .unwrap();
+            (app, contract)
+        }
+
+        fn create_task_msg(
+            app: &App,
+            proof_type: ProofType,
+            amount: u128,
+            co_approvers: &[&str],
+            approvals_required: Option<u32>,
+        ) -> ExecuteMsg {
+            ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: Coin::new(amount, NATIVE_DENOM),
+                description: "Renovation".to_string(),
+                proof_type,
+                deadline_ts: app.block_info().time.seconds() + 86400,
+                review_window_secs: Some(3600),
+                endpoint: "https://api.example.com/verify".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: co_approvers.iter().map(|username| username.to_string()).collect(),
+                approvals_required,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            }
+        }
+
+        #[test]
+        fn test_high_value_task_needs_m_of_n_co_approvals() {
+            let (mut app, contract) = setup();
+            let funds = [Coin::new(1000, NATIVE_DENOM)];
+
+            let config: ConfigResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetConfig {})
+                .unwrap();
+            assert_eq!(config.config.co_approval_thresholds, vec![Coin::new(500, NATIVE_DENOM)]);
+
+            // Above the threshold the payer must designate co-approvers
+            let no_co_approvers = create_task_msg(&app, ProofType::Hybrid, 1000, &[], None);
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &no_co_approvers, &funds);
+            assert!(result.is_err());
+
+            let create_task = create_task_msg(&app, ProofType::Hybrid, 1000, &["charlie", "dave", "erin"], Some(2));
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task, &funds)
+                .unwrap();
+
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: 1,
+                proof_blob_or_ref: "valid_hybrid_proof_data".to_string(),
+                zk_proof_hash: "hybrid_proof_hash".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_proof, &[])
+                .unwrap();
+            app.update_block(|block| block.time = block.time.plus_seconds(3601));
+
+            // The window has elapsed, but release waits for two co-approvals
+            let release_task = ExecuteMsg::ReleaseIfWindowElapsed { task_id: 1 };
+            let result = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &release_task, &[]);
+            assert!(result.is_err());
+
+            let co_approve = ExecuteMsg::CoApproveTask { task_id: 1 };
+            app.execute_contract(Addr::unchecked(USER3), contract.addr(), &co_approve, &[])
+                .unwrap();
+            let result = app.execute_contract(Addr::unchecked(USER3), contract.addr(), &co_approve, &[]);
+            assert!(result.is_err());
+            let result = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &co_approve, &[]);
+            assert!(result.is_err());
+            let result = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &release_task, &[]);
+            assert!(result.is_err());
+
+            app.execute_contract(Addr::unchecked(USER5), contract.addr(), &co_approve, &[])
+                .unwrap();
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &release_task, &[])
+                .unwrap();
+
+            let task_response: TaskResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 1 })
+                .unwrap();
+            assert_eq!(task_response.task.status, TaskStatus::Released);
+            assert_eq!(task_response.task.approvals, vec!["charlie".to_string(), "erin".to_string()]);
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(11000));
+        }
+
+        #[test]
+        fn test_co_approved_zktls_task_waits_for_approval() {
+            let (mut app, contract) = setup();
+            let funds = [Coin::new(100, NATIVE_DENOM)];
+
+            // Co-approvers must be distinct users other than the parties, with a satisfiable threshold
+            for invalid in [
+                create_task_msg(&app, ProofType::ZkTLS, 100, &["bob"], None),
+                create_task_msg(&app, ProofType::ZkTLS, 100, &["charlie", "charlie"], None),
+                create_task_msg(&app, ProofType::ZkTLS, 100, &["charlie"], Some(0)),
+                create_task_msg(&app, ProofType::ZkTLS, 100, &["charlie"], Some(2)),
+                create_task_msg(&app, ProofType::ZkTLS, 100, &["nobody"], None),
+            ] {
+                let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &invalid, &funds);
+                assert!(result.is_err());
+            }
+
+            // Below the threshold co-approvers are optional but still enforced when designated
+            let create_task = create_task_msg(&app, ProofType::ZkTLS, 100, &["charlie"], None);
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task, &funds)
+                .unwrap();
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: 1,
+                proof_blob_or_ref: "valid_zktls_proof_data".to_string(),
+                zk_proof_hash: "zktls_proof_hash".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_proof, &[])
+                .unwrap();
+
+            let task_response: TaskResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 1 })
+                .unwrap();
+            assert_eq!(task_response.task.status, TaskStatus::PendingRelease);
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10000));
+
+            let co_approve = ExecuteMsg::CoApproveTask { task_id: 1 };
+            app.execute_contract(Addr::unchecked(USER3), contract.addr(), &co_approve, &[])
+                .unwrap();
+            app.update_block(|block| block.time = block.time.plus_seconds(1));
+            let release_task = ExecuteMsg::ReleaseIfWindowElapsed { task_id: 1 };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &release_task, &[])
+                .unwrap();
+            let bob_balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(bob_balance.amount, Uint128::new(10100));
+
+            // Only config managers can change the thresholds
+            let update_config = ExecuteMsg::UpdateConfig {
+                co_approval_thresholds: Some(vec![]),
+                review_window: None,
+                archive_retention_secs: None,
+                accepted_denoms: None,
+                instant_release: None,
+                friend_requests: None,
+                text_limits: None,
+                open_item_limits: None,
+            };
+            let result = app.execute_contract(Addr::unchecked(USER1), contract.addr(), &update_config, &[]);
+            assert!(result.is_err());
+        }
+    }
+
+    mod notifications {
+        use super::*;
+        use crate::msg::NotificationsResponse;
+        use crate::state::NotificationKind;
+
+        fn notifications(
+            app: &App,
+            contract: &SocialPaymentContract,
+            username: &str,
+            start_after: Option<u64>,
+            limit: Option<u32>,
+        ) -> NotificationsResponse {
+            app.wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetNotifications {
+                        username: username.to_string(),
+                        start_after,
+                        limit,
+                    },
+                )
+                .unwrap()
+        }
+
+        #[test]
+        fn test_inbox_collects_key_events_until_acked() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let friend_request = ExecuteMsg::SendFriendRequest {
+                to_username: "bob".to_string(),
+                message: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &friend_request, &[])
+                .unwrap();
+
+            let payment_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: Coin::new(50, NATIVE_DENOM),
+                description: "Concert tickets".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &payment_request, &[])
+                .unwrap();
+
+            let task_amount = [Coin::new(200, NATIVE_DENOM)];
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: task_amount[0].clone(),
+                description: "Build website".to_string(),
+                proof_type: ProofType::Hybrid,
+                deadline_ts: app.block_info().time.seconds() + 86400,
+                review_window_secs: Some(3600),
+                endpoint: "https://api.example.com/hybrid".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task, &task_amount)
+                .unwrap();
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: 1,
+                proof_blob_or_ref: "valid_hybrid_proof_data".to_string(),
+                zk_proof_hash: "hybrid_proof_hash".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit_proof, &[])
+                .unwrap();
+            let dispute_task = ExecuteMsg::DisputeTask {
+                task_id: 1,
+                reason: None,
+                reason_hash: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &dispute_task, &[])
+                .unwrap();
+
+            // The payer is told about the proof, the worker about everything else
+            let alice_inbox = notifications(&app, &contract, "alice", None, None);
+            assert_eq!(alice_inbox.notifications.len(), 1);
+            assert_eq!(alice_inbox.notifications[0].kind, NotificationKind::TaskProofSubmitted);
+            assert_eq!(alice_inbox.notifications[0].from_username, "bob");
+            assert_eq!(alice_inbox.notifications[0].record_id, Some(1));
+
+            let bob_inbox = notifications(&app, &contract, "bob", None, None);
+            let kinds: Vec<NotificationKind> = bob_inbox.notifications.iter().map(|n| n.kind.clone()).collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    NotificationKind::FriendRequestReceived,
+                    NotificationKind::PaymentRequestReceived,
+                    NotificationKind::TaskDisputed,
+                ]
+            );
+
+            // Pages continue after the last id seen
+            let first_page = notifications(&app, &contract, "bob", None, Some(2));
+            assert_eq!(first_page.notifications.len(), 2);
+            let second_page = notifications(&app, &contract, "bob", Some(first_page.notifications[1].id), Some(2));
+            assert_eq!(second_page.notifications, bob_inbox.notifications[2..].to_vec());
+
+            // Acknowledging removes everything up to and including the id
+            let ack = ExecuteMsg::AckNotifications {
+                up_to_id: bob_inbox.notifications[1].id,
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &ack, &[])
+                .unwrap();
+            let bob_inbox_after = notifications(&app, &contract, "bob", None, None);
+            assert_eq!(bob_inbox_after.notifications, bob_inbox.notifications[2..].to_vec());
+            assert_eq!(bob_inbox_after.acked_up_to, bob_inbox.notifications[1].id);
+
+            // Acknowledgements never move backwards
+            let stale_ack = ExecuteMsg::AckNotifications { up_to_id: 0 };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &stale_ack, &[])
+                .unwrap();
+            let bob_inbox_after = notifications(&app, &contract, "bob", None, None);
+            assert_eq!(bob_inbox_after.notifications.len(), 1);
+        }
+    }
+
+    mod payment_visibility {
+        use super::*;
+        use crate::msg::{ActivityFeedResponse, PaymentResponse, PaymentsResponse};
+        use crate::state::Visibility;
+
+        fn send_payment(app: &mut App, contract: &SocialPaymentContract, to_username: &str, visibility: Option<Visibility>) {
+            let amount = [Coin::new(10, NATIVE_DENOM)];
+            let send_payment = ExecuteMsg::SendDirectPayment {
+                to_username: to_username.to_string(),
+                amount: amount[0].clone(),
+                description: "Dinner".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment, &amount)
+                .unwrap();
+        }
+
+        fn history_ids(app: &App, contract: &SocialPaymentContract, viewer: Option<&str>) -> Vec<u64> {
+            let history: PaymentsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetPaymentHistory {
+                        username: "alice".to_string(),
+                        viewer: viewer.map(|v| v.to_string()),
+                        start_after: None,
+                    },
+                )
+                .unwrap();
+            history.payments.iter().map(|p| p.id).collect()
+        }
+
+        fn feed(app: &App, contract: &SocialPaymentContract, username: &str, before: Option<u64>) -> ActivityFeedResponse {
+            app.wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetActivityFeed {
+                        username: username.to_string(),
+                        before,
+                        limit: Some(2),
+                    },
+                )
+                .unwrap()
+        }
+
+        #[test]
+        fn test_visibility_filters_history_and_feed() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER2), "bob"), (&Addr::unchecked(USER3), "charlie"));
+
+            // Payments without an explicit visibility use the sender's preference
+            let update = ExecuteMsg::UpdateUserProfile {
+                display_name: None,
+                profile_picture: None,
+                require_friendship_for_requests: None,
+                default_visibility: Some(Visibility::Friends),
+                bio: None,
+                links: None,
+                country: None,
+                timezone: None,
+                preferred_denom: None,
+                payout_slippage_bps: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &update, &[])
+                .unwrap();
+            send_payment(&mut app, &contract, "bob", None);
+            send_payment(&mut app, &contract, "bob", Some(Visibility::Private));
+            send_payment(&mut app, &contract, "charlie", Some(Visibility::Public));
+
+            // Hidden payments look missing to anyone who may not see them
+            let hidden = app.wrap().query_wasm_smart::<PaymentResponse>(
+                contract.addr(),
+                &QueryMsg::GetPaymentById { payment_id: 1, viewer: None },
+            );
+            assert!(hidden.is_err());
+            let payment: PaymentResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetPaymentById {
+                        payment_id: 1,
+                        viewer: Some("charlie".to_string()),
+                    },
+                )
+                .unwrap();
+            assert_eq!(payment.payment.visibility, Visibility::Friends);
+
+            // Anonymous viewers see public payments, parties see everything,
+            // friends of either party also see friends-visible payments
+            assert_eq!(history_ids(&app, &contract, None), vec![3]);
+            assert_eq!(history_ids(&app, &contract, Some("bob")), vec![1, 2, 3]);
+            assert_eq!(history_ids(&app, &contract, Some("charlie")), vec![1, 3]);
+
+            // Charlie's feed shows bob's friends-visible payment but not his private one
+            let charlie_feed = feed(&app, &contract, "charlie", None);
+            let ids: Vec<u64> = charlie_feed.payments.iter().map(|p| p.id).collect();
+            assert_eq!(ids, vec![1]);
+            assert_eq!(charlie_feed.next_before, None);
+
+            // Bob's feed merges his friends' payments newest first, in pages
+            let first_page = feed(&app, &contract, "bob", None);
+            let ids: Vec<u64> = first_page.payments.iter().map(|p| p.id).collect();
+            assert_eq!(ids, vec![3, 2]);
+            let second_page = feed(&app, &contract, "bob", first_page.next_before);
+            let ids: Vec<u64> = second_page.payments.iter().map(|p| p.id).collect();
+            assert_eq!(ids, vec![1]);
+            assert_eq!(second_page.next_before, None);
+        }
+    }
+
+    mod friend_groups {
+        use super::*;
+        use crate::msg::{GroupResponse, GroupsResponse};
+        use crate::ContractError;
+
+        fn user_groups(app: &App, contract: &SocialPaymentContract, username: &str) -> Vec<u64> {
+            let res: GroupsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetUserGroups {
+                        username: username.to_string(),
+                    },
+                )
+                .unwrap();
+            res.groups.iter().map(|g| g.id).collect()
+        }
+
+        #[test]
+        fn test_group_membership() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+
+            let create = ExecuteMsg::CreateGroup {
+                name: " Roommates ".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create, &[])
+                .unwrap();
+
+            // Names are unique per owner
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &create, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::GroupNameTaken {});
+
+            // Only friends can be added, and only by the owner
+            let add_charlie = ExecuteMsg::AddToGroup {
+                group_id: 1,
+                username: "charlie".to_string(),
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &add_charlie, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotFriends {});
+            let add_bob = ExecuteMsg::AddToGroup {
+                group_id: 1,
+                username: "bob".to_string(),
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &add_bob, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::OnlyGroupOwner {});
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &add_bob, &[])
+                .unwrap();
+
+            let group: GroupResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetGroup { group_id: 1 })
+                .unwrap();
+            assert_eq!(group.group.name, "Roommates");
+            assert_eq!(group.group.members, vec!["alice".to_string(), "bob".to_string()]);
+            assert_eq!(user_groups(&app, &contract, "bob"), vec![1]);
+
+            // The owner stays; members can leave on their own
+            let remove_alice = ExecuteMsg::RemoveFromGroup {
+                group_id: 1,
+                username: "alice".to_string(),
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &remove_alice, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::CannotRemoveGroupOwner {});
+            let leave = ExecuteMsg::RemoveFromGroup {
+                group_id: 1,
+                username: "bob".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &leave, &[])
+                .unwrap();
+            assert!(user_groups(&app, &contract, "bob").is_empty());
+            assert_eq!(user_groups(&app, &contract, "alice"), vec![1]);
+        }
+    }
+
+    mod group_ledger {
+        use super::*;
+        use crate::msg::{GroupLedgerResponse, PaymentResponse};
+        use crate::state::PaymentType;
+        use crate::ContractError;
+
+        fn setup() -> (App, SocialPaymentContract) {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER3), "charlie"));
+            let create = ExecuteMsg::CreateGroup {
+                name: "Trip".to_string(),
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create, &[])
+                .unwrap();
+            for username in ["bob", "charlie"] {
+                let add = ExecuteMsg::AddToGroup {
+                    group_id: 1,
+                    username: username.to_string(),
+                };
+                app.execute_contract(Addr::unchecked(USER1), contract.addr(), &add, &[])
+                    .unwrap();
+            }
+            (app, contract)
+        }
+
+        fn record_expense(app: &mut App, contract: &SocialPaymentContract, payer: &str, amount: u128, split_among: &[&str]) {
+            let msg = ExecuteMsg::RecordGroupExpense {
+                group_id: 1,
+                amount: Coin::new(amount, NATIVE_DENOM),
+                description: "Dinner".to_string(),
+                split_among: split_among.iter().map(|u| u.to_string()).collect(),
+            };
+            app.execute_contract(Addr::unchecked(payer), contract.addr(), &msg, &[])
+                .unwrap();
+        }
+
+        fn ledger(app: &App, contract: &SocialPaymentContract) -> Vec<(String, u128, u128)> {
+            let res: GroupLedgerResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetGroupLedger { group_id: 1 })
+                .unwrap();
+            res.balances
+                .into_iter()
+                .map(|b| (b.username, b.paid.u128(), b.owed.u128()))
+                .collect()
+        }
+
+        #[test]
+        fn test_uneven_split_assigns_remainder() {
+            let (mut app, contract) = setup();
+            record_expense(&mut app, &contract, USER2, 10, &[]);
+            assert_eq!(
+                ledger(&app, &contract),
+                vec![
+                    ("alice".to_string(), 0, 4),
+                    ("bob".to_string(), 10, 3),
+                    ("charlie".to_string(), 0, 3),
+                ]
+            );
+
+            // Only members may share an expense, once each
+            let msg = ExecuteMsg::RecordGroupExpense {
+                group_id: 1,
+                amount: Coin::new(10, NATIVE_DENOM),
+                description: "Taxi".to_string(),
+                split_among: vec!["bob".to_string(), "bob".to_string()],
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidExpenseSplit {});
+        }
+
+        #[test]
+        fn test_settle_up_creates_minimal_requests() {
+            let (mut app, contract) = setup();
+            record_expense(&mut app, &contract, USER1, 90, &[]);
+            record_expense(&mut app, &contract, USER2, 30, &["alice", "bob"]);
+
+            // Members with an open balance cannot leave
+            let leave = ExecuteMsg::RemoveFromGroup {
+                group_id: 1,
+                username: "bob".to_string(),
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &leave, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::GroupBalanceOutstanding {});
+
+            // Alice is owed 45; charlie owes 30 and bob 15
+            let res = app
+                .execute_contract(Addr::unchecked(USER3), contract.addr(), &ExecuteMsg::SettleUp { group_id: 1 }, &[])
+                .unwrap();
+            let settle = res.events.iter().find(|e| e.ty == "wasm").unwrap();
+            assert!(settle.attributes.iter().any(|a| a.key == "payment_ids" && a.value == "1,2"));
+            for (payment_id, debtor, amount) in [(1, "charlie", 30), (2, "bob", 15)] {
+                let payment: PaymentResponse = app
+                    .wrap()
+                    .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id, viewer: Some("alice".to_string()) })
+                    .unwrap();
+                assert_eq!(payment.payment.payment_type, PaymentType::PaymentRequest);
+                assert_eq!(payment.payment.from_username, "alice");
+                assert_eq!(payment.payment.to_username, debtor);
+                assert_eq!(payment.payment.amount, Coin::new(amount, NATIVE_DENOM));
+                assert_eq!(payment.payment.status, PaymentStatus::Pending);
+            }
+
+            // The open requests cover every balance, so there is nothing more to settle, but the
+            // ledger keeps the debts until they are paid
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &ExecuteMsg::SettleUp { group_id: 1 }, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NothingToSettle {});
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &leave, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::GroupBalanceOutstanding {});
+
+            // Paying the request settles bob's part of the ledger, so he may leave
+            app.execute_contract(
+                Addr::unchecked(USER2),
+                contract.addr(),
+                &ExecuteMsg::ApprovePayment { payment_id: 2 },
+                &[Coin::new(15, NATIVE_DENOM)],
+            )
+            .unwrap();
+            assert_eq!(
+                ledger(&app, &contract),
+                vec![
+                    ("alice".to_string(), 90, 60),
+                    ("bob".to_string(), 45, 45),
+                    ("charlie".to_string(), 0, 30),
+                ]
+            );
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &leave, &[])
+                .unwrap();
+        }
+
+        #[test]
+        fn test_rejected_settlement_keeps_debt_on_ledger() {
+            let (mut app, contract) = setup();
+            record_expense(&mut app, &contract, USER1, 60, &["alice", "charlie"]);
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &ExecuteMsg::SettleUp { group_id: 1 }, &[])
+                .unwrap();
+
+            app.execute_contract(Addr::unchecked(USER3), contract.addr(), &ExecuteMsg::RejectPayment { payment_id: 1 }, &[])
+                .unwrap();
+            assert_eq!(
+                ledger(&app, &contract),
+                vec![("alice".to_string(), 60, 30), ("charlie".to_string(), 0, 30)]
+            );
+
+            // The debt can be requested again
+            let res = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &ExecuteMsg::SettleUp { group_id: 1 }, &[])
+                .unwrap();
+            let settle = res.events.iter().find(|e| e.ty == "wasm").unwrap();
+            assert!(settle.attributes.iter().any(|a| a.key == "payment_ids" && a.value == "2"));
+        }
+
+        #[test]
+        fn test_settle_up_respects_request_checks() {
+            let (mut app, contract) = setup();
+            record_expense(&mut app, &contract, USER1, 60, &["alice", "charlie"]);
+
+            // Charlie only takes requests from friends, and alice is one
+            let friends_only = ExecuteMsg::UpdateUserProfile {
+                display_name: None,
+                profile_picture: None,
+                require_friendship_for_requests: Some(true),
+                default_visibility: None,
+                bio: None,
+                links: None,
+                country: None,
+                timezone: None,
+                preferred_denom: None,
+                payout_slippage_bps: None,
+            };
+            app.execute_contract(Addr::unchecked(USER3), contract.addr(), &friends_only, &[])
+                .unwrap();
+            let remove = ExecuteMsg::RemoveFriend { username: "alice".to_string(), leave_shared_groups: false };
+            app.execute_contract(Addr::unchecked(USER3), contract.addr(), &remove, &[])
+                .unwrap();
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &ExecuteMsg::SettleUp { group_id: 1 }, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::CannotRequestNonFriend {});
+        }
+    }
+
+    mod payment_reminders {
+        use super::*;
+        use crate::msg::{NotificationsResponse, PaymentResponse};
+        use crate::state::NotificationKind;
+        use crate::ContractError;
+
+        #[test]
+        fn test_reminders_are_rate_limited() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            let payment_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: Coin::new(100, NATIVE_DENOM),
+                description: "Pay me".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &payment_request, &[])
+                .unwrap();
+
+            // Only the requester may remind
+            let remind = ExecuteMsg::RemindPaymentRequest { payment_id: 1 };
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &remind, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotAuthorized {});
+
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &remind, &[])
+                .unwrap();
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &remind, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ReminderCooldown {});
+
+            let inbox: NotificationsResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    contract.addr(),
+                    &QueryMsg::GetNotifications {
+                        username: "bob".to_string(),
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap();
+            let kinds: Vec<NotificationKind> = inbox.notifications.into_iter().map(|n| n.kind).collect();
+            assert_eq!(kinds, vec![NotificationKind::PaymentRequestReceived, NotificationKind::PaymentRequestReminder]);
+
+            // Four more reminders a day apart use up the allowance
+            for _ in 0..4 {
+                app.update_block(|b| b.time = b.time.plus_seconds(86400));
+                app.execute_contract(Addr::unchecked(USER1), contract.addr(), &remind, &[])
+                    .unwrap();
+            }
+            app.update_block(|b| b.time = b.time.plus_seconds(86400));
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &remind, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ReminderLimitReached {});
+
+            let payment: PaymentResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
+                .unwrap();
+            assert_eq!(payment.payment.reminder_count, 5);
+            assert_eq!(
+                payment.payment.last_reminded_at,
+                Some(app.block_info().time.seconds() - 86400)
+            );
+        }
+    }
+
+    mod campaigns {
+        use super::*;
+        use crate::msg::{CampaignResponse, EscrowLiabilitiesResponse};
+        use crate::state::CampaignStatus;
+        use crate::ContractError;
+
+        fn create_campaign(app: &mut App, contract: &SocialPaymentContract, all_or_nothing: bool) {
+            let msg = ExecuteMsg::CreateCampaign {
+                title: "Community garden".to_string(),
+                goal: Coin::new(1000, NATIVE_DENOM),
+                deadline: app.block_info().time.seconds() + 86400,
+                beneficiary_username: "charlie".to_string(),
+                all_or_nothing,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &msg, &[])
+                .unwrap();
+        }
+
+        fn donate(app: &mut App, contract: &SocialPaymentContract, donor: &str, amount: u128) {
+            let msg = ExecuteMsg::Donate { campaign_id: 1 };
+            app.execute_contract(Addr::unchecked(donor), contract.addr(), &msg, &[Coin::new(amount, NATIVE_DENOM)])
+                .unwrap();
+        }
+
+        fn liabilities(app: &App, contract: &SocialPaymentContract) -> Vec<Coin> {
+            let res: EscrowLiabilitiesResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetEscrowLiabilities {})
+                .unwrap();
+            res.liabilities
+        }
+
+        #[test]
+        fn test_beneficiary_withdraws_once_goal_met() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            create_campaign(&mut app, &contract, false);
+
+            donate(&mut app, &contract, USER1, 600);
+            let withdraw = ExecuteMsg::WithdrawCampaign { campaign_id: 1 };
+            let err = app
+                .execute_contract(Addr::unchecked(USER3), contract.addr(), &withdraw, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::CampaignNotWithdrawable {});
+
+            donate(&mut app, &contract, USER2, 500);
+            assert_eq!(liabilities(&app, &contract), vec![Coin::new(1100, NATIVE_DENOM)]);
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &withdraw, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotAuthorized {});
+            app.execute_contract(Addr::unchecked(USER3), contract.addr(), &withdraw, &[])
+                .unwrap();
+
+            let balance = app.wrap().query_balance(USER3, NATIVE_DENOM).unwrap();
+            assert_eq!(balance.amount, Uint128::new(11100));
+            assert!(liabilities(&app, &contract).is_empty());
+            let campaign: CampaignResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetCampaign { campaign_id: 1 })
+                .unwrap();
+            assert_eq!(campaign.campaign.status, CampaignStatus::Withdrawn);
+
+            // A withdrawn campaign takes no more donations
+            let err = app
+                .execute_contract(
+                    Addr::unchecked(USER1),
+                    contract.addr(),
+                    &ExecuteMsg::Donate { campaign_id: 1 },
+                    &[Coin::new(10, NATIVE_DENOM)],
+                )
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::CampaignClosed {});
+        }
+
+        #[test]
+        fn test_all_or_nothing_refunds_donors() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            create_campaign(&mut app, &contract, true);
+            donate(&mut app, &contract, USER1, 300);
+            donate(&mut app, &contract, USER2, 200);
+
+            // No refunds while the campaign can still reach its goal
+            let claim = ExecuteMsg::ClaimCampaignRefund { campaign_id: 1 };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &claim, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NoCampaignRefund {});
+
+            app.update_block(|b| b.time = b.time.plus_seconds(86401));
+            let withdraw = ExecuteMsg::WithdrawCampaign { campaign_id: 1 };
+            let err = app
+                .execute_contract(Addr::unchecked(USER3), contract.addr(), &withdraw, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::CampaignNotWithdrawable {});
+
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &claim, &[])
+                .unwrap();
+            let balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(balance.amount, Uint128::new(10000));
+            assert_eq!(liabilities(&app, &contract), vec![Coin::new(200, NATIVE_DENOM)]);
+
+            // Each donation is refunded once
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &claim, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NoCampaignRefund {});
+        }
+    }
+
+    mod review_window_bounds {
+        use super::*;
+        use crate::msg::TaskResponse;
+        use crate::state::ReviewWindowBounds;
+        use crate::ContractError;
+
+        fn create_task_msg(app: &App, review_window_secs: Option<u64>) -> ExecuteMsg {
+            ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: Coin::new(100, NATIVE_DENOM),
+                description: "Logo design".to_string(),
+                proof_type: ProofType::Hybrid,
+                deadline_ts: app.block_info().time.seconds() + 86400,
+                review_window_secs,
+                endpoint: "https://api.example.com/verify".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            }
+        }
+
+        #[test]
+        fn test_hybrid_review_window_defaults_and_bounds() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            let funds = [Coin::new(100, NATIVE_DENOM)];
+
+            // The default must fall within the bounds
+            let invalid = ExecuteMsg::UpdateConfig {
+                co_approval_thresholds: None,
+                review_window: Some(ReviewWindowBounds {
+                    default_secs: Some(60),
+                    min_secs: Some(3600),
+                    max_secs: Some(604800),
+                }),
+                archive_retention_secs: None,
+                accepted_denoms: None,
+                instant_release: None,
+                friend_requests: None,
+                text_limits: None,
+                open_item_limits: None,
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(ADMIN), contract.addr(), &invalid, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidReviewWindow {});
+
+            let update_config = ExecuteMsg::UpdateConfig {
+                co_approval_thresholds: None,
+                review_window: Some(ReviewWindowBounds {
+                    default_secs: Some(86400),
+                    min_secs: Some(3600),
+                    max_secs: Some(604800),
+                }),
+                archive_retention_secs: None,
+                accepted_denoms: None,
+                instant_release: None,
+                friend_requests: None,
+                text_limits: None,
+                open_item_limits: None,
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &update_config, &[])
+                .unwrap();
+
+            // Windows that are too short or too long are rejected
+            for review_window_secs in [1, 10 * 365 * 86400] {
+                let msg = create_task_msg(&app, Some(review_window_secs));
+                let err = app
+                    .execute_contract(Addr::unchecked(USER1), contract.addr(), &msg, &funds)
+                    .unwrap_err();
+                assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidReviewWindow {});
+            }
+
+            // Omitting the window applies the default
+            let msg = create_task_msg(&app, None);
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &msg, &funds)
+                .unwrap();
+            let task: TaskResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 1 })
+                .unwrap();
+            assert_eq!(task.task.review_window_secs, Some(86400));
+        }
+    }
+
+    mod task_cancellation {
+        use super::*;
+        use crate::ContractError;
+
+        #[test]
+        fn test_payer_cancels_unstarted_task() {
+            let mut scenario = dana_and_eli();
+            let task_id = scenario.escrowed_task("dana", "eli", 100, TaskStatus::Escrowed);
+            assert_eq!(scenario.balance("dana"), 900);
+
+            let res = scenario.execute_as("dana", &ExecuteMsg::CancelTask { task_id }, &[]);
+            assert!(res.events.iter().any(|e| e.ty == "wasm-task_cancelled"));
+            assert_eq!(scenario.task(task_id).status, TaskStatus::Refunded);
+            assert_eq!(scenario.balance("dana"), 1000);
+        }
+
+        #[test]
+        fn test_started_task_needs_both_parties() {
+            let mut scenario = dana_and_eli();
+            let task_id = scenario.escrowed_task("dana", "eli", 100, TaskStatus::PendingRelease);
+
+            // The payer's request waits for the worker
+            let cancel = ExecuteMsg::CancelTask { task_id };
+            scenario.execute_as("dana", &cancel, &[]);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::PendingRelease);
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("dana"), scenario.contract.addr(), &cancel, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::CancelAlreadyRequested {});
+
+            scenario.execute_as("eli", &cancel, &[]);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::Refunded);
+            assert_eq!(scenario.balance("dana"), 1000);
+        }
+    }
+
+    mod task_acceptance {
+        use super::*;
+        use crate::ContractError;
+
+        #[test]
+        fn test_worker_declines_task() {
+            let mut scenario = dana_and_eli();
+            let task_id = scenario.escrowed_task("dana", "eli", 100, TaskStatus::Escrowed);
+
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("dana"), scenario.contract.addr(), &ExecuteMsg::DeclineTask { task_id }, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TaskNotAuthorized {});
+
+            scenario.execute_as("eli", &ExecuteMsg::DeclineTask { task_id }, &[]);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::Refunded);
+            assert_eq!(scenario.balance("dana"), 1000);
+        }
+
+        #[test]
+        fn test_counter_offer_tops_up_escrow() {
+            let mut scenario = dana_and_eli();
+            let task_id = scenario.escrowed_task("dana", "eli", 100, TaskStatus::Escrowed);
+
+            let lower = ExecuteMsg::CounterOffer { task_id, amount: scenario.coin(80) };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("eli"), scenario.contract.addr(), &lower, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidCounterOffer {});
+            let counter = ExecuteMsg::CounterOffer { task_id, amount: scenario.coin(150) };
+            scenario.execute_as("eli", &counter, &[]);
+
+            // The payer attaches the difference
+            let accept = ExecuteMsg::AcceptCounterOffer { task_id };
+            let short = [scenario.coin(40)];
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("dana"), scenario.contract.addr(), &accept, &short)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InsufficientFunds {});
+            let top_up = [scenario.coin(50)];
+            scenario.execute_as("dana", &accept, &top_up);
+
+            let accepted = scenario.task(task_id);
+            assert_eq!(accepted.amount, scenario.coin(150));
+            assert_eq!(accepted.counter_offer, None);
+            assert!(accepted.accepted_at.is_some());
+            assert_eq!(scenario.balance("dana"), 850);
+
+            // An accepted task can no longer be declined; cancelling refunds the full escrow
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("eli"), scenario.contract.addr(), &ExecuteMsg::DeclineTask { task_id }, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TaskAlreadyStarted {});
+            scenario.execute_as("dana", &ExecuteMsg::CancelTask { task_id }, &[]);
+            assert_eq!(scenario.balance("dana"), 1000);
+        }
+
+        #[test]
+        fn test_counter_offer_lapses_when_task_is_cancelled() {
+            let mut scenario = dana_and_eli();
+            let task_id = scenario.escrowed_task("dana", "eli", 100, TaskStatus::Escrowed);
+            let counter = ExecuteMsg::CounterOffer { task_id, amount: scenario.coin(150) };
+            scenario.execute_as("eli", &counter, &[]);
+
+            scenario.execute_as("dana", &ExecuteMsg::CancelTask { task_id }, &[]);
+            assert_eq!(scenario.task(task_id).counter_offer, None);
+            let top_up = [scenario.coin(50)];
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("dana"), scenario.contract.addr(), &ExecuteMsg::AcceptCounterOffer { task_id }, &top_up)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TaskAlreadyCompleted {});
+            assert_eq!(scenario.balance("dana"), 1000);
+        }
+
+        #[test]
+        fn test_counter_offer_cannot_be_accepted_once_work_starts() {
+            let mut scenario = dana_and_eli();
+            let task_id = scenario.escrowed_task("dana", "eli", 100, TaskStatus::Escrowed);
+            let counter = ExecuteMsg::CounterOffer { task_id, amount: scenario.coin(150) };
+            scenario.execute_as("eli", &counter, &[]);
+
+            // eli submits proof at the original price instead of waiting for an answer
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id,
+                proof_blob_or_ref: "valid_scenario_proof".to_string(),
+                zk_proof_hash: "scenario_proof_hash".to_string(),
+            };
+            scenario.execute_as("eli", &submit_proof, &[]);
+            let accept = ExecuteMsg::AcceptCounterOffer { task_id };
+            let top_up = [scenario.coin(50)];
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("dana"), scenario.contract.addr(), &accept, &top_up)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TaskAlreadyStarted {});
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(86400));
+            scenario.execute_as("eli", &ExecuteMsg::ReleaseIfWindowElapsed { task_id }, &[]);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::Released);
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("dana"), scenario.contract.addr(), &accept, &top_up)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TaskAlreadyCompleted {});
+            assert_eq!(scenario.balance("eli"), 1100);
+        }
+    }
+
+    mod task_amendments {
+        use super::*;
+        use crate::ContractError;
+
+        #[test]
+        fn test_worker_accepts_amendment() {
+            let mut scenario = dana_and_eli();
+            let task_id = scenario.escrowed_task("dana", "eli", 100, TaskStatus::Escrowed);
+            let new_deadline = scenario.app.block_info().time.seconds() + 2 * 86400;
+
+            let amend = ExecuteMsg::AmendTask {
+                task_id,
+                additional_amount: Some(scenario.coin(50)),
+                new_description_hash: Some("scope_v2_hash".to_string()),
+                new_deadline: Some(new_deadline),
+            };
+            let top_up = [scenario.coin(50)];
+            scenario.execute_as("dana", &amend, &top_up);
+            assert_eq!(scenario.balance("dana"), 850);
+
+            // One proposal at a time
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("dana"), scenario.contract.addr(), &amend, &top_up)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::AmendmentPending {});
+
+            scenario.execute_as("eli", &ExecuteMsg::AcceptAmendment { task_id }, &[]);
+            let amended = scenario.task(task_id);
+            assert_eq!(amended.amount, scenario.coin(150));
+            assert_eq!(amended.deadline_ts, new_deadline);
+            assert_eq!(amended.pending_amendment, None);
+            assert_eq!(amended.amendments.len(), 1);
+            assert_eq!(amended.amendments[0].description_hash, Some("scope_v2_hash".to_string()));
+            assert!(amended.amendments[0].accepted_at.is_some());
+
+            // Refunds cover the amended amount
+            scenario.execute_as("dana", &ExecuteMsg::CancelTask { task_id }, &[]);
+            assert_eq!(scenario.balance("dana"), 1000);
+        }
+
+        #[test]
+        fn test_payer_withdraws_unaccepted_amendment() {
+            let mut scenario = dana_and_eli();
+            let task_id = scenario.escrowed_task("dana", "eli", 100, TaskStatus::Escrowed);
+
+            let no_change = ExecuteMsg::AmendTask {
+                task_id,
+                additional_amount: None,
+                new_description_hash: None,
+                new_deadline: None,
+            };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("dana"), scenario.contract.addr(), &no_change, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidAmendment {});
+
+            let amend = ExecuteMsg::AmendTask {
+                task_id,
+                additional_amount: Some(scenario.coin(50)),
+                new_description_hash: None,
+                new_deadline: None,
+            };
+            let top_up = [scenario.coin(50)];
+            scenario.execute_as("dana", &amend, &top_up);
+
+            // The task settles first; the top-up is still returned on withdrawal
+            scenario.execute_as("dana", &ExecuteMsg::CancelTask { task_id }, &[]);
+            assert_eq!(scenario.balance("dana"), 950);
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("eli"), scenario.contract.addr(), &ExecuteMsg::AcceptAmendment { task_id }, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TaskAlreadyCompleted {});
+            scenario.execute_as("dana", &ExecuteMsg::WithdrawAmendment { task_id }, &[]);
+            assert_eq!(scenario.balance("dana"), 1000);
+            assert_eq!(scenario.task(task_id).pending_amendment, None);
+        }
+    }
+
+    mod archival {
+        use super::*;
+        use crate::msg::{ArchiveRootResponse, IndexReportResponse, PaymentResponse};
+        use crate::ContractError;
+
+        fn archive_root(app: &App, contract: &SocialPaymentContract) -> ArchiveRootResponse {
+            app.wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetArchiveRoot {})
+                .unwrap()
+        }
+
+        #[test]
+        fn test_settled_records_are_archived_after_retention() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+
+            // One completed payment and one still-pending request
+            let send_payment = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: Coin::new(100, NATIVE_DENOM),
+                description: "Lunch".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment, &[Coin::new(100, NATIVE_DENOM)])
+                .unwrap();
+            let payment_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: Coin::new(50, NATIVE_DENOM),
+                description: "Tickets".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &payment_request, &[])
+                .unwrap();
+            let completed: PaymentResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
+                .unwrap();
+
+            let archive = ExecuteMsg::ArchiveRecords {
+                up_to_ts: app.block_info().time.seconds(),
+                limit: None,
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &archive, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotAuthorized {});
+
+            // Nothing is archivable within the default 90-day retention
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &archive, &[])
+                .unwrap();
+            assert_eq!(archive_root(&app, &contract).archived_records, 0);
+
+            let update_config = ExecuteMsg::UpdateConfig {
+                co_approval_thresholds: None,
+                review_window: None,
+                archive_retention_secs: Some(3600),
+                accepted_denoms: None,
+                instant_release: None,
+                friend_requests: None,
+                text_limits: None,
+                open_item_limits: None,
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &update_config, &[])
+                .unwrap();
+            app.update_block(|b| b.time = b.time.plus_seconds(3601));
+            let archive = ExecuteMsg::ArchiveRecords {
+                up_to_ts: app.block_info().time.seconds(),
+                limit: None,
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &archive, &[])
+                .unwrap();
+
+            // Only the settled payment is folded into the root and removed
+            let root = archive_root(&app, &contract);
+            assert_eq!(root.archived_records, 1);
+            let expected = crate::helpers::hash_data(&format!(
+                "payment:{}",
+                cosmwasm_std::to_json_string(&completed.payment).unwrap()
+            ));
+            assert_eq!(root.root, expected);
+            assert!(app
+                .wrap()
+                .query_wasm_smart::<PaymentResponse>(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
+                .is_err());
+            let pending: PaymentResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 2, viewer: None })
+                .unwrap();
+            assert_eq!(pending.payment.status, PaymentStatus::Pending);
+
+            let report: IndexReportResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetIndexReport {})
+                .unwrap();
+            assert!(report.indexes.iter().all(|index| index.in_sync));
+        }
+    }
+
+    mod accepted_denoms {
+        use super::*;
+        use crate::msg::AcceptedDenomsResponse;
+        use crate::state::AcceptedDenoms;
+        use crate::ContractError;
+
+        fn set_accepted_denoms(app: &mut App, contract: &SocialPaymentContract, accepted_denoms: AcceptedDenoms) {
+            let update_config = ExecuteMsg::UpdateConfig {
+                co_approval_thresholds: None,
+                review_window: None,
+                archive_retention_secs: None,
+                accepted_denoms: Some(accepted_denoms),
+                instant_release: None,
+                friend_requests: None,
+                text_limits: None,
+                open_item_limits: None,
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &update_config, &[])
+                .unwrap();
+        }
+
+        fn send_payment(denom: &str) -> ExecuteMsg {
+            ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: Coin::new(100, denom),
+                description: "Lunch".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            }
+        }
+
+        #[test]
+        fn test_only_accepted_denoms_can_be_paid() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+
+            set_accepted_denoms(&mut app, &contract, AcceptedDenoms::Only(vec![NATIVE_DENOM.to_string()]));
+            let accepted: AcceptedDenomsResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetAcceptedDenoms {})
+                .unwrap();
+            assert_eq!(accepted.accepted_denoms, AcceptedDenoms::Only(vec![NATIVE_DENOM.to_string()]));
+
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment(OTHER_DENOM), &[Coin::new(100, OTHER_DENOM)])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::DenomNotAccepted {});
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment(NATIVE_DENOM), &[Coin::new(100, NATIVE_DENOM)])
+                .unwrap();
+
+            // Requests funded later are checked when created
+            let payment_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: Coin::new(50, OTHER_DENOM),
+                description: "Tickets".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &payment_request, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::DenomNotAccepted {});
+
+            // Allowing all denoms lifts the restriction
+            set_accepted_denoms(&mut app, &contract, AcceptedDenoms::AllowAll);
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment(OTHER_DENOM), &[Coin::new(100, OTHER_DENOM)])
+                .unwrap();
+        }
+    }
+
+    mod price_quotes {
+        use super::*;
+        use crate::msg::{OraclePriceResponse, OracleQueryMsg, PaymentResponse, PriceOracleResponse, PriceQuoteMsg, TaskResponse};
+        use crate::ContractError;
+        use cosmwasm_std::{to_json_binary, Binary, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+
+        // Oracle quoting 1000 base units per currency unit; EUR prices are ten minutes old
+        fn oracle_template() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: Deps, env: Env, msg: OracleQueryMsg| -> StdResult<Binary> {
+                    let OracleQueryMsg::Price { currency, .. } = msg;
+                    let age = if currency == "EUR" { 600 } else { 0 };
+                    to_json_binary(&OraclePriceResponse {
+                        price: Decimal::from_atomics(1000u128, 0).unwrap(),
+                        publish_time: env.block.time.seconds() - age,
+                    })
+                },
+            );
+            Box::new(contract)
+        }
+
+        fn setup_oracle(app: &mut App, contract: &SocialPaymentContract) -> Addr {
+            let code_id = app.store_code(oracle_template());
+            let oracle = app
+                .instantiate_contract(code_id, Addr::unchecked(ADMIN), &Empty {}, &[], "oracle", None)
+                .unwrap();
+            let set_oracle = ExecuteMsg::SetPriceOracle {
+                oracle: Some(oracle.to_string()),
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &set_oracle, &[])
+                .unwrap();
+            oracle
+        }
+
+        fn quote(currency: &str, amount: &str) -> Option<PriceQuoteMsg> {
+            Some(PriceQuoteMsg {
+                currency: currency.to_string(),
+                amount: amount.parse().unwrap(),
+            })
+        }
+
+        fn quoted_soft_task(app: &App, currency: &str, escrow_upfront: bool) -> ExecuteMsg {
+            ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: Coin::new(1, NATIVE_DENOM),
+                description: "Translate the menu".to_string(),
+                proof_type: ProofType::Soft,
+                deadline_ts: app.block_info().time.seconds() + 86400,
+                review_window_secs: None,
+                endpoint: "".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: quote(currency, "2"),
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            }
+        }
+
+        #[test]
+        fn test_payment_request_settles_at_oracle_price() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+            let oracle = setup_oracle(&mut app, &contract);
+            let configured: PriceOracleResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPriceOracle {})
+                .unwrap();
+            assert_eq!(configured.oracle, Some(oracle));
+
+            let create_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: Coin::new(1, NATIVE_DENOM),
+                description: "Concert ticket".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: quote("USD", "1.5"),
+                external_ref: None,
+                idempotency_key: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_request, &[])
+                .unwrap();
+
+            // The indicative amount is not enough once the quote resolves
+            let approve = ExecuteMsg::ApprovePayment { payment_id: 1 };
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &approve, &[Coin::new(1, NATIVE_DENOM)])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InsufficientFunds {});
+
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &approve, &[Coin::new(1500, NATIVE_DENOM)])
+                .unwrap();
+            let payment: PaymentResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
+                .unwrap();
+            assert_eq!(payment.payment.amount, Coin::new(1500, NATIVE_DENOM));
+            let resolved = payment.payment.price_quote.unwrap();
+            assert_eq!(resolved.resolved_price, Some(Decimal::from_atomics(1000u128, 0).unwrap()));
+            assert!(resolved.resolved_at.is_some());
+            let balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(balance.amount, Uint128::new(11500));
+        }
+
+        #[test]
+        fn test_quotes_need_an_oracle_and_approval_funding() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &quoted_soft_task(&app, "USD", false), &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::PriceOracleNotConfigured {});
+
+            setup_oracle(&mut app, &contract);
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &quoted_soft_task(&app, "USD", true), &[Coin::new(1, NATIVE_DENOM)])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidPriceQuote {});
+
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &quoted_soft_task(&app, "USD", false), &[])
+                .unwrap();
+            let approve = ExecuteMsg::ApproveTask { task_id: 1 };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &approve, &[Coin::new(2000, NATIVE_DENOM)])
+                .unwrap();
+            let task: TaskResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetTaskById { task_id: 1 })
+                .unwrap();
+            assert_eq!(task.task.status, TaskStatus::Released);
+            assert_eq!(task.task.amount, Coin::new(2000, NATIVE_DENOM));
+            let balance = app.wrap().query_balance(USER2, NATIVE_DENOM).unwrap();
+            assert_eq!(balance.amount, Uint128::new(12000));
+        }
+
+        #[test]
+        fn test_stale_price_is_rejected() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+            setup_oracle(&mut app, &contract);
+
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &quoted_soft_task(&app, "EUR", false), &[])
+                .unwrap();
+            let approve = ExecuteMsg::ApproveTask { task_id: 1 };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &approve, &[Coin::new(2000, NATIVE_DENOM)])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::StalePrice {});
+        }
+    }
+
+    mod external_refs {
+        use super::*;
+        use crate::msg::PaymentResponse;
+        use crate::ContractError;
+        use cosmwasm_std::StdResult;
+
+        fn invoice(to_username: &str, external_ref: &str) -> ExecuteMsg {
+            ExecuteMsg::CreatePaymentRequest {
+                to_username: to_username.to_string(),
+                amount: Coin::new(250, NATIVE_DENOM),
+                description: "Consulting, March".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: Some(external_ref.to_string()),
+                idempotency_key: None,
+            }
+        }
+
+        fn payment_by_ref(app: &App, contract: &SocialPaymentContract, creator: &str, external_ref: &str) -> StdResult<PaymentResponse> {
+            app.wrap().query_wasm_smart(
+                contract.addr(),
+                &QueryMsg::GetPaymentByExternalRef {
+                    creator: creator.to_string(),
+                    external_ref: external_ref.to_string(),
+                    viewer: None,
+                },
+            )
+        }
+
+        #[test]
+        fn test_external_refs_are_unique_per_creator() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &invoice("bob", "INV-0001"), &[])
+                .unwrap();
+            let found = payment_by_ref(&app, &contract, "alice", "INV-0001").unwrap();
+            assert_eq!(found.payment.id, 1);
+            assert_eq!(found.payment.external_ref, Some("INV-0001".to_string()));
+            assert!(payment_by_ref(&app, &contract, "bob", "INV-0001").is_err());
+
+            // Replaying the same reference is rejected, even towards another payer
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &invoice("charlie", "INV-0001"), &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ExternalRefTaken {});
+
+            // Other creators have their own namespace
+            app.execute_contract(Addr::unchecked(USER3), contract.addr(), &invoice("bob", "INV-0001"), &[])
+                .unwrap();
+            let found = payment_by_ref(&app, &contract, "charlie", "INV-0001").unwrap();
+            assert_eq!(found.payment.id, 2);
+
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &invoice("bob", &"X".repeat(65)), &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidExternalRef {});
+        }
+
+        #[test]
+        fn test_task_refs_are_separate_from_payment_refs() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &invoice("bob", "PO-7"), &[])
+                .unwrap();
+
+            let create_task = |deadline_ts: u64| ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: Coin::new(100, NATIVE_DENOM),
+                description: "Deliver the order".to_string(),
+                proof_type: ProofType::Soft,
+                deadline_ts,
+                review_window_secs: None,
+                endpoint: "".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: Some("PO-7".to_string()),
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            let deadline_ts = app.block_info().time.seconds() + 86400;
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task(deadline_ts), &[])
+                .unwrap();
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task(deadline_ts), &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ExternalRefTaken {});
+        }
+    }
+
+    mod idempotency_keys {
+        use super::*;
+        use crate::msg::PaymentsResponse;
+        use crate::state::IDEMPOTENCY_KEYS;
+        use crate::testing::attribute;
+        use crate::ContractError;
+
+        fn send_payment(idempotency_key: &str) -> ExecuteMsg {
+            ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: Coin::new(100, NATIVE_DENOM),
+                description: "Rent share".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: Some(idempotency_key.to_string()),
+                release_delay_secs: None,
+            }
+        }
+
+        fn payment_count(app: &App, contract: &SocialPaymentContract) -> usize {
+            let history: PaymentsResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPaymentHistory { username: "alice".to_string(), viewer: Some("alice".to_string()), start_after: None })
+                .unwrap();
+            history.payments.len()
+        }
+
+        #[test]
+        fn test_replayed_key_returns_original_payment() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+
+            let funds = [Coin::new(100, NATIVE_DENOM)];
+            let first = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment("retry-1"), &funds)
+                .unwrap();
+            let retry = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment("retry-1"), &funds)
+                .unwrap();
+            assert_eq!(attribute(&retry, "payment_id"), attribute(&first, "payment_id"));
+            assert_eq!(attribute(&retry, "idempotent_replay"), Some("true".to_string()));
+            assert_eq!(payment_count(&app, &contract), 1);
+
+            // The retry's funds are returned, so bob is only paid once
+            let balance = app.wrap().query_balance(USER1, NATIVE_DENOM).unwrap();
+            assert_eq!(balance.amount, Uint128::new(9900));
+
+            // A key used for a payment cannot be replayed as a task
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: Coin::new(100, NATIVE_DENOM),
+                description: "Fix the sink".to_string(),
+                proof_type: ProofType::Soft,
+                deadline_ts: app.block_info().time.seconds() + 86400,
+                review_window_secs: None,
+                endpoint: "".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: Some("retry-1".to_string()),
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &create_task, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::IdempotencyKeyConflict {});
+        }
+
+        #[test]
+        fn test_key_expires_after_ttl() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+
+            let funds = [Coin::new(100, NATIVE_DENOM)];
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment("monthly"), &funds)
+                .unwrap();
+            // Keys are per sender: bob's message is validated rather than replayed
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &send_payment("monthly"), &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::CannotPaySelf {});
+
+            app.update_block(|block| block.time = block.time.plus_seconds(86400));
+            let res = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment("monthly"), &funds)
+                .unwrap();
+            assert_eq!(attribute(&res, "idempotent_replay"), None);
+            assert_eq!(payment_count(&app, &contract), 2);
+        }
+
+        #[test]
+        fn test_expired_keys_are_deleted_by_the_reaper() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            crate::testing::make_friends(&mut app, &contract, (&Addr::unchecked(USER1), "alice"), (&Addr::unchecked(USER2), "bob"));
+
+            let funds = [Coin::new(100, NATIVE_DENOM)];
+            for key in ["rent", "groceries"] {
+                app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send_payment(key), &funds)
+                    .unwrap();
+            }
+            let reap = ExecuteMsg::ReapExpiredUsernames { limit: None };
+            let res = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &reap, &[]).unwrap();
+            assert_eq!(attribute(&res, "idempotency_keys_removed"), Some("0".to_string()));
+
+            app.update_block(|block| block.time = block.time.plus_seconds(86400));
+            let res = app.execute_contract(Addr::unchecked(USER2), contract.addr(), &reap, &[]).unwrap();
+            assert_eq!(attribute(&res, "idempotency_keys_removed"), Some("2".to_string()));
+            let stored = IDEMPOTENCY_KEYS
+                .query(&app.wrap(), contract.addr(), (Addr::unchecked(USER1), "rent".to_string()))
+                .unwrap();
+            assert!(stored.is_none());
+        }
+    }
+
+    mod relayed_execution {
+        use super::*;
+        use crate::helpers::{adr36_sign_hash, pubkey_challenge};
+        use crate::msg::{FriendRequestsResponse, RelayersResponse, SignedAction, SignedPayload, SignerResponse, UserResponse};
+        use crate::state::UsernameExpiry;
+        use crate::ContractError;
+        use cosmwasm_std::{to_json_binary, Binary};
+        use k256::ecdsa::{signature::hazmat::PrehashSigner, Signature, SigningKey};
+
+        const RELAYER: &str = "relayer";
+
+        fn alice_key() -> SigningKey {
+            SigningKey::from_bytes(&[7u8; 32].into()).unwrap()
+        }
+
+        // Authorize the relayer and register alice's signing key
+        fn setup(app: &mut App, contract: &SocialPaymentContract) {
+            let set_relayer = ExecuteMsg::SetRelayer {
+                address: RELAYER.to_string(),
+                authorized: true,
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &set_relayer, &[])
+                .unwrap();
+            let register = register_pubkey(app, contract, &alice_key(), USER1);
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &register, &[])
+                .unwrap();
+        }
+
+        // RegisterPubkey for `key`, proven by signing the challenge for `wallet`
+        fn register_pubkey(app: &App, contract: &SocialPaymentContract, key: &SigningKey, wallet: &str) -> ExecuteMsg {
+            let challenge = pubkey_challenge(&app.block_info().chain_id, &contract.addr(), &Addr::unchecked(wallet));
+            let signature: Signature = key.sign_prehash(&adr36_sign_hash(wallet, &challenge)).unwrap();
+            ExecuteMsg::RegisterPubkey {
+                pubkey: Binary::from(key.verifying_key().to_encoded_point(true).as_bytes().to_vec()),
+                proof_signature: Binary::from(signature.to_bytes().to_vec()),
+            }
+        }
+
+        fn signed(app: &App, contract: &SocialPaymentContract, key: &SigningKey, nonce: u64, action: SignedAction) -> ExecuteMsg {
+            let payload = to_json_binary(&SignedPayload {
+                signer: "alice".to_string(),
+                nonce,
+                chain_id: app.block_info().chain_id,
+                contract: contract.addr().to_string(),
+                action,
+            })
+            .unwrap();
+            let signature: Signature = key.sign_prehash(&adr36_sign_hash(USER1, &payload)).unwrap();
+            ExecuteMsg::ExecuteSigned {
+                payload,
+                signature: Binary::from(signature.to_bytes().to_vec()),
+            }
+        }
+
+        fn friend_request_to(to_username: &str) -> SignedAction {
+            SignedAction::SendFriendRequest {
+                to_username: to_username.to_string(),
+                message: None,
+            }
+        }
+
+        #[test]
+        fn test_relayer_submits_signed_action() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            setup(&mut app, &contract);
+            let relayers: RelayersResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetRelayers {})
+                .unwrap();
+            assert_eq!(relayers.relayers, vec![Addr::unchecked(RELAYER)]);
+
+            let msg = signed(&app, &contract, &alice_key(), 0, friend_request_to("bob"));
+            app.execute_contract(Addr::unchecked(RELAYER), contract.addr(), &msg, &[])
+                .unwrap();
+            let pending: FriendRequestsResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetPendingRequests { username: "bob".to_string(), start_after: None })
+                .unwrap();
+            assert_eq!(pending.requests[0].from_username, "alice");
+
+            // The same payload cannot be replayed
+            let err = app
+                .execute_contract(Addr::unchecked(RELAYER), contract.addr(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidNonce {});
+            let signer: SignerResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetSigner { username: "alice".to_string() })
+                .unwrap();
+            assert_eq!(signer.next_nonce, 1);
+        }
+
+        #[test]
+        fn test_expired_signer_cannot_act_through_relayer() {
+            let (mut app, contract) = proper_instantiate();
+            let set_expiry = ExecuteMsg::SetUsernameExpiry {
+                config: Some(UsernameExpiry { term_secs: 1000, grace_secs: 500 }),
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &set_expiry, &[])
+                .unwrap();
+            register_users(&mut app, &contract);
+            setup(&mut app, &contract);
+
+            // The relayer has no username of its own, so the signer's registration is checked
+            app.update_block(|block| block.time = block.time.plus_seconds(1000));
+            let msg = signed(&app, &contract, &alice_key(), 0, friend_request_to("bob"));
+            let err = app
+                .execute_contract(Addr::unchecked(RELAYER), contract.addr(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::UsernameExpired {});
+        }
+
+        #[test]
+        fn test_signed_action_needs_relayer_and_valid_signature() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            setup(&mut app, &contract);
+
+            let msg = signed(&app, &contract, &alice_key(), 0, friend_request_to("bob"));
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::RelayerNotAuthorized {});
+
+            let forged_key = SigningKey::from_bytes(&[9u8; 32].into()).unwrap();
+            let forged = signed(&app, &contract, &forged_key, 0, friend_request_to("bob"));
+            let err = app
+                .execute_contract(Addr::unchecked(RELAYER), contract.addr(), &forged, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidSignature {});
+
+            // A payload signed for another contract is rejected
+            let mut other_contract = signed(&app, &contract, &alice_key(), 0, friend_request_to("bob"));
+            if let ExecuteMsg::ExecuteSigned { payload, .. } = &mut other_contract {
+                let mut decoded: SignedPayload = cosmwasm_std::from_json(&*payload).unwrap();
+                decoded.contract = "other".to_string();
+                *payload = to_json_binary(&decoded).unwrap();
+            }
+            let err = app
+                .execute_contract(Addr::unchecked(RELAYER), contract.addr(), &other_contract, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidSignedPayload {});
+        }
+
+        #[test]
+        fn test_register_pubkey_requires_proof_for_own_wallet() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            // A proof made for alice's wallet cannot register the key for bob
+            let stolen = register_pubkey(&app, &contract, &alice_key(), USER1);
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &stolen, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidSignature {});
+
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &stolen, &[])
+                .unwrap();
+            let user: UserResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetUserByUsername { username: "alice".to_string() })
+                .unwrap();
+            let pubkey = alice_key().verifying_key().to_encoded_point(true).as_bytes().to_vec();
+            assert_eq!(user.user.pubkey, Some(Binary::from(pubkey)));
+
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &ExecuteMsg::RemovePubkey {}, &[])
+                .unwrap();
+            let signer: SignerResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetSigner { username: "alice".to_string() })
+                .unwrap();
+            assert_eq!(signer.pubkey, None);
+        }
+    }
+
+    mod insurance_pool {
+        use super::*;
+        use crate::msg::{EscrowLiabilitiesResponse, InsurancePoolResponse, InvariantsResponse};
+        use crate::state::InsuranceConfig;
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        // Scenario with 2% premiums, 50% payout caps and a pool funded by dana
+        fn insured_scenario(pool: u128) -> Scenario {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 5000)
+                .with_user("wallet_bob", "bob", 5000)
+                .with_user("wallet_dana", "dana", 5000)
+                .build();
+            let set_config = ExecuteMsg::SetInsuranceConfig {
+                config: Some(InsuranceConfig {
+                    premium_bps: 200,
+                    max_payout_bps: 5000,
+                }),
+            };
+            let owner = scenario.owner.clone();
+            scenario
+                .app
+                .execute_contract(owner, scenario.contract.addr(), &set_config, &[])
+                .unwrap();
+            let funds = [scenario.coin(pool)];
+            scenario.execute_as("dana", &ExecuteMsg::FundInsurancePool {}, &funds);
+            scenario
+        }
+
+        // Submit proof, dispute and resolve an escrowed hybrid task
+        fn resolve_dispute(scenario: &mut Scenario, task_id: u64, worker_bps: u16) {
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id,
+                proof_blob_or_ref: "valid_scenario_proof".to_string(),
+                zk_proof_hash: "scenario_proof_hash".to_string(),
+            };
+            scenario.execute_as("bob", &submit_proof, &[]);
+            scenario.execute_as("alice", &ExecuteMsg::DisputeTask { task_id, reason: None, reason_hash: None }, &[]);
+            let owner = scenario.owner.clone();
+            scenario
+                .app
+                .execute_contract(owner, scenario.contract.addr(), &ExecuteMsg::ResolveDispute { task_id, worker_bps }, &[])
+                .unwrap();
+        }
+
+        fn pool(scenario: &Scenario) -> InsurancePoolResponse {
+            scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetInsurancePool {})
+                .unwrap()
+        }
+
+        fn pay_claim(scenario: &mut Scenario, task_id: u64, amount: u128) -> Result<(), ContractError> {
+            let msg = ExecuteMsg::PayInsuranceClaim { task_id, amount: Uint128::new(amount) };
+            scenario.try_execute_as(scenario.owner.clone(), &msg, &[]).map(|_| ())
+        }
+
+        #[test]
+        fn test_arbiter_compensates_insured_worker() {
+            let mut scenario = insured_scenario(1000);
+            let task_id = scenario.escrowed_task("alice", "bob", 1000, TaskStatus::Escrowed);
+
+            let funds = [scenario.coin(20)];
+            scenario.execute_as("bob", &ExecuteMsg::InsureTask { task_id }, &funds);
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &ExecuteMsg::InsureTask { task_id }, &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TaskAlreadyInsured {});
+
+            resolve_dispute(&mut scenario, task_id, 0);
+            let pool_state = pool(&scenario);
+            assert_eq!(pool_state.balances, vec![scenario.coin(1020)]);
+            assert_eq!(pool_state.open_claims, vec![scenario.coin(500)]);
+            assert!(pool_state.solvent);
+
+            assert_eq!(pay_claim(&mut scenario, task_id, 600), Err(ContractError::InvalidInsurancePayout {}));
+            pay_claim(&mut scenario, task_id, 300).unwrap();
+            let balance = scenario.app.wrap().query_balance(scenario.wallet("bob"), &scenario.denom).unwrap();
+            assert_eq!(balance.amount, Uint128::new(5000 - 20 + 300));
+            let pool_state = pool(&scenario);
+            assert_eq!(pool_state.balances, vec![scenario.coin(720)]);
+            assert!(pool_state.open_claims.is_empty());
+            assert_eq!(pay_claim(&mut scenario, task_id, 100), Err(ContractError::NoInsuranceClaim {}));
+        }
+
+        #[test]
+        fn test_pool_counts_toward_escrow_liabilities() {
+            let mut scenario = insured_scenario(1000);
+            let task_id = scenario.escrowed_task("alice", "bob", 1000, TaskStatus::Escrowed);
+            let funds = [scenario.coin(20)];
+            scenario.execute_as("bob", &ExecuteMsg::InsureTask { task_id }, &funds);
+            resolve_dispute(&mut scenario, task_id, 0);
+            pay_claim(&mut scenario, task_id, 300).unwrap();
+
+            // The refunded task left escrow; the pool's funding and premium minus the payout remain
+            let escrow: EscrowLiabilitiesResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetEscrowLiabilities {})
+                .unwrap();
+            assert_eq!(escrow.liabilities, vec![scenario.coin(720)]);
+            assert_eq!(scenario.address_balance(scenario.contract.addr()), 720);
+
+            let report: InvariantsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::VerifyInvariants { limit: None })
+                .unwrap();
+            assert!(report.violations.is_empty());
+        }
+
+        #[test]
+        fn test_claims_only_follow_rulings_against_the_worker() {
+            let mut scenario = insured_scenario(100);
+            let funds = [scenario.coin(20)];
+
+            let released = scenario.escrowed_task("alice", "bob", 1000, TaskStatus::Escrowed);
+            scenario.execute_as("alice", &ExecuteMsg::InsureTask { task_id: released }, &funds);
+            resolve_dispute(&mut scenario, released, 10_000);
+            assert_eq!(pay_claim(&mut scenario, released, 100), Err(ContractError::NoInsuranceClaim {}));
+
+            // A claim larger than the pool leaves it insolvent until topped up
+            let refunded = scenario.escrowed_task("alice", "bob", 1000, TaskStatus::Escrowed);
+            scenario.execute_as("alice", &ExecuteMsg::InsureTask { task_id: refunded }, &funds);
+            resolve_dispute(&mut scenario, refunded, 0);
+            assert!(!pool(&scenario).solvent);
+            assert_eq!(pay_claim(&mut scenario, refunded, 500), Err(ContractError::InsufficientInsurancePool {}));
+            pay_claim(&mut scenario, refunded, 0).unwrap();
+            assert!(pool(&scenario).solvent);
+        }
+    }
+
+    mod subscriptions {
+        use super::*;
+        use crate::msg::{SubscriptionResponse, SubscriptionsResponse, UserEscrowedResponse};
+        use crate::state::SubscriptionStatus;
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        const PERIOD: u64 = 30 * 86400;
+
+        // alice prepays bob four periods of 100
+        fn subscribed_scenario() -> (Scenario, u64) {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let create = ExecuteMsg::CreateSubscription {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                period: PERIOD,
+                periods: 4,
+            };
+            let funds = [scenario.coin(450)];
+            scenario.execute_as("alice", &create, &funds);
+            (scenario, 1)
+        }
+
+        fn subscription(scenario: &Scenario, subscription_id: u64) -> SubscriptionResponse {
+            scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetSubscription { subscription_id })
+                .unwrap()
+        }
+
+        fn advance(scenario: &mut Scenario, secs: u64) {
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(secs));
+        }
+
+        #[test]
+        fn test_recipient_claims_matured_periods() {
+            let (mut scenario, subscription_id) = subscribed_scenario();
+            // The surplus over four periods is refunded at creation
+            assert_eq!(scenario.balance("alice"), 600);
+            let claim = ExecuteMsg::ClaimSubscription { subscription_id };
+            assert_eq!(scenario.try_execute_as(scenario.wallet("bob"), &claim, &[]).unwrap_err(), ContractError::NothingToClaim {});
+            assert_eq!(scenario.try_execute_as(scenario.wallet("alice"), &claim, &[]).unwrap_err(), ContractError::NotAuthorized {});
+
+            advance(&mut scenario, PERIOD * 2 + 10);
+            assert_eq!(subscription(&scenario, subscription_id).claimable_periods, 2);
+            scenario.execute_as("bob", &claim, &[]);
+            assert_eq!(scenario.balance("bob"), 200);
+            assert_eq!(scenario.try_execute_as(scenario.wallet("bob"), &claim, &[]).unwrap_err(), ContractError::NothingToClaim {});
+
+            // Periods stop maturing once every funded period has been claimed
+            advance(&mut scenario, PERIOD * 10);
+            assert_eq!(subscription(&scenario, subscription_id).claimable_periods, 2);
+            scenario.execute_as("bob", &claim, &[]);
+            assert_eq!(scenario.balance("bob"), 400);
+            let response = subscription(&scenario, subscription_id);
+            assert_eq!(response.subscription.status, SubscriptionStatus::Completed);
+            assert_eq!(response.subscription.periods_claimed, 4);
+        }
+
+        #[test]
+        fn test_cancel_pays_matured_and_refunds_unmatured_periods() {
+            let (mut scenario, subscription_id) = subscribed_scenario();
+            advance(&mut scenario, PERIOD + 10);
+
+            let cancel = ExecuteMsg::CancelSubscription { subscription_id };
+            scenario.execute_as("alice", &cancel, &[]);
+            assert_eq!(scenario.balance("bob"), 100);
+            assert_eq!(scenario.balance("alice"), 900);
+            let response = subscription(&scenario, subscription_id);
+            assert_eq!(response.subscription.status, SubscriptionStatus::Cancelled);
+            assert_eq!(response.claimable_periods, 0);
+            assert_eq!(scenario.try_execute_as(scenario.wallet("bob"), &cancel, &[]).unwrap_err(), ContractError::SubscriptionNotActive {});
+
+            let escrowed: UserEscrowedResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetUserEscrowed { username: "alice".to_string() })
+                .unwrap();
+            assert!(escrowed.escrowed.is_empty());
+
+            let listed: SubscriptionsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetUserSubscriptions { username: "bob".to_string() })
+                .unwrap();
+            assert_eq!(listed.subscriptions.len(), 1);
+        }
+
+        #[test]
+        fn test_subscription_requires_every_period_funded() {
+            let (mut scenario, _) = subscribed_scenario();
+            let create = ExecuteMsg::CreateSubscription {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                period: PERIOD,
+                periods: 3,
+            };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &create, &[scenario.coin(250)])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InsufficientFunds {});
+
+            let zero_period = ExecuteMsg::CreateSubscription {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                period: 0,
+                periods: 3,
+            };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &zero_period, &[scenario.coin(300)])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidSubscription {});
+        }
+    }
+
+    mod invoices {
+        use super::*;
+        use crate::msg::InvoiceResponse;
+        use crate::state::{InvoiceLineItem, InvoiceStatus};
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        fn line_item(scenario: &Scenario, description: &str, amount: u128) -> InvoiceLineItem {
+            InvoiceLineItem {
+                description: description.to_string(),
+                amount: scenario.coin(amount),
+            }
+        }
+
+        // bob bills alice 300 for design and 200 for hosting
+        fn invoiced_scenario() -> (Scenario, u64) {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let create = ExecuteMsg::CreateInvoice {
+                to_username: "alice".to_string(),
+                line_items: vec![line_item(&scenario, "Design", 300), line_item(&scenario, "Hosting", 200)],
+                due_ts: scenario.app.block_info().time.seconds() + 86400,
+            };
+            scenario.execute_as("bob", &create, &[]);
+            (scenario, 1)
+        }
+
+        fn invoice(scenario: &Scenario, invoice_id: u64) -> InvoiceResponse {
+            scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetInvoice { invoice_id })
+                .unwrap()
+        }
+
+        #[test]
+        fn test_partial_payments_fill_line_items_in_order() {
+            let (mut scenario, invoice_id) = invoiced_scenario();
+            let created = invoice(&scenario, invoice_id);
+            assert_eq!(created.invoice.total, scenario.coin(500));
+            assert_eq!(created.remaining, scenario.coin(500));
+
+            let pay = ExecuteMsg::PayInvoice { invoice_id };
+            let funds = [scenario.coin(350)];
+            scenario.execute_as("alice", &pay, &funds);
+            let partial = invoice(&scenario, invoice_id);
+            assert_eq!(partial.invoice.status, InvoiceStatus::Open);
+            assert_eq!(partial.remaining, scenario.coin(150));
+            assert!(partial.line_items[0].fully_paid);
+            assert_eq!(partial.line_items[1].paid, Uint128::new(50));
+            assert!(!partial.line_items[1].fully_paid);
+            assert_eq!(scenario.balance("bob"), 350);
+
+            // Overpaying the remainder refunds the surplus
+            let funds = [scenario.coin(400)];
+            scenario.execute_as("alice", &pay, &funds);
+            let paid = invoice(&scenario, invoice_id);
+            assert_eq!(paid.invoice.status, InvoiceStatus::Paid);
+            assert!(paid.line_items.iter().all(|item| item.fully_paid));
+            assert_eq!(scenario.balance("bob"), 500);
+            assert_eq!(scenario.balance("alice"), 500);
+
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &pay, &[scenario.coin(10)])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvoiceNotOpen {});
+        }
+
+        #[test]
+        fn test_only_the_billed_user_pays_and_only_the_issuer_cancels() {
+            let (mut scenario, invoice_id) = invoiced_scenario();
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &ExecuteMsg::PayInvoice { invoice_id }, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotAuthorized {});
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &ExecuteMsg::CancelInvoice { invoice_id }, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotAuthorized {});
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(2 * 86400));
+            assert!(invoice(&scenario, invoice_id).overdue);
+            scenario.execute_as("bob", &ExecuteMsg::CancelInvoice { invoice_id }, &[]);
+            let cancelled = invoice(&scenario, invoice_id);
+            assert_eq!(cancelled.invoice.status, InvoiceStatus::Cancelled);
+            assert!(!cancelled.overdue);
+        }
+
+        #[test]
+        fn test_line_items_must_share_a_denom() {
+            let (mut scenario, _) = invoiced_scenario();
+            let mut hosting = line_item(&scenario, "Hosting", 200);
+            hosting.amount.denom = "uatom".to_string();
+            let create = ExecuteMsg::CreateInvoice {
+                to_username: "alice".to_string(),
+                line_items: vec![line_item(&scenario, "Design", 300), hosting],
+                due_ts: scenario.app.block_info().time.seconds() + 86400,
+            };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &create, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidInvoiceLineItems {});
+        }
+    }
+
+    mod receipts {
+        use super::*;
+        use crate::helpers::hash_data;
+        use crate::msg::ReceiptResponse;
+        use crate::state::Visibility;
+        use crate::testing::attribute;
+        use cosmwasm_std::{to_json_string, StdResult};
+
+        fn receipt(app: &App, contract: &SocialPaymentContract, payment_id: u64, viewer: Option<&str>) -> StdResult<ReceiptResponse> {
+            app.wrap().query_wasm_smart(
+                contract.addr(),
+                &QueryMsg::GetReceipt { payment_id, viewer: viewer.map(str::to_string) },
+            )
+        }
+
+        #[test]
+        fn test_settled_payment_receipt_hashes_its_context() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let send = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: Coin::new(300, NATIVE_DENOM),
+                description: "Bike repair".to_string(),
+                proof_type: ProofType::Manual,
+                category: None,
+                tags: vec![],
+                visibility: Some(Visibility::Private),
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &send, &[Coin::new(300, NATIVE_DENOM)])
+                .unwrap();
+            // No receipt until the payment settles
+            assert!(receipt(&app, &contract, 1, Some("alice")).is_err());
+
+            let submit = ExecuteMsg::SubmitProof { payment_id: 1, proof_data: "photo_of_bike".to_string() };
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &submit, &[]).unwrap();
+            let res = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &ExecuteMsg::ApprovePayment { payment_id: 1 }, &[])
+                .unwrap();
+            let attribute_hash = attribute(&res, "receipt_hash").unwrap();
+
+            let response = receipt(&app, &contract, 1, Some("bob")).unwrap();
+            let settled = response.receipt.unwrap();
+            assert_eq!(settled.resolution, PaymentStatus::Completed);
+            assert_eq!(settled.amount, Coin::new(300, NATIVE_DENOM));
+            assert_eq!(settled.proof_hash, Some(hash_data("photo_of_bike")));
+            // Anyone holding the receipt can recompute its hash
+            assert_eq!(response.hash, hash_data(&to_json_string(&settled).unwrap()));
+            assert_eq!(response.hash, attribute_hash);
+
+            // Outsiders only see the hash of a private payment's receipt
+            let outsider = receipt(&app, &contract, 1, Some("charlie")).unwrap();
+            assert_eq!(outsider.receipt, None);
+            assert_eq!(outsider.hash, response.hash);
+        }
+
+        #[test]
+        fn test_cancelled_payment_receipt_records_resolution() {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+
+            let request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: Coin::new(120, NATIVE_DENOM),
+                description: "Dinner".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &request, &[]).unwrap();
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &ExecuteMsg::CancelPayment { payment_id: 1 }, &[])
+                .unwrap();
+
+            let settled = receipt(&app, &contract, 1, None).unwrap().receipt.unwrap();
+            assert_eq!(settled.resolution, PaymentStatus::Cancelled);
+            assert_eq!(settled.proof_hash, None);
+        }
+    }
+
+    mod suggested_friends {
+        use super::*;
+        use crate::msg::SuggestedFriendsResponse;
+        use crate::testing::{Scenario, ScenarioBuilder};
+
+        fn pay(scenario: &mut Scenario, from: &str, to: &str, amount: u128) {
+            let send = ExecuteMsg::SendDirectPayment {
+                to_username: to.to_string(),
+                amount: scenario.coin(amount),
+                description: "Lunch".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            let funds = [scenario.coin(amount)];
+            scenario.execute_as(from, &send, &funds);
+        }
+
+        fn suggestions(scenario: &Scenario, username: &str) -> Vec<String> {
+            let response: SuggestedFriendsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetSuggestedFriends { username: username.to_string(), limit: None },
+                )
+                .unwrap();
+            response.suggestions.into_iter().map(|suggestion| suggestion.username).collect()
+        }
+
+        #[test]
+        fn test_suggests_recent_counterparties_who_are_not_friends() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 5000)
+                .with_user("wallet_bob", "bob", 5000)
+                .with_user("wallet_carol", "carol", 5000)
+                .with_user("wallet_dave", "dave", 5000)
+                .with_friends("alice", "dave")
+                .build();
+
+            pay(&mut scenario, "alice", "bob", 100);
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(60));
+            pay(&mut scenario, "carol", "alice", 100);
+            pay(&mut scenario, "alice", "dave", 100);
+            // Tasks count once the worker is paid
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+            assert_eq!(suggestions(&scenario, "alice"), vec!["carol", "bob"]);
+            assert_eq!(suggestions(&scenario, "bob"), vec!["alice"]);
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(60));
+            scenario.escrowed_task("bob", "alice", 100, TaskStatus::Released);
+            assert_eq!(suggestions(&scenario, "alice"), vec!["bob", "carol"]);
+        }
+    }
+
+    mod username_nfts {
+        use super::*;
+        use crate::msg::{Cw721ReceiveMsg, UserResponse, UsernameTransferResponse};
+        use crate::ContractError;
+        use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, WasmMsg};
+        use cw_storage_plus::{Item, Map};
+        use schemars::JsonSchema;
+        use serde::{Deserialize, Serialize};
+
+        const NEW_WALLET: &str = "new_wallet";
+
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+        #[serde(rename_all = "snake_case")]
+        enum MockCw721Msg {
+            Mint { token_id: String, owner: String, token_uri: Option<String>, extension: Empty },
+            TransferNft { recipient: String, token_id: String },
+            SendNft { contract: String, token_id: String, msg: Binary },
+        }
+
+        const MINTER: Item<String> = Item::new("minter");
+        const OWNERS: Map<String, String> = Map::new("owners");
+
+        // Minimal cw721: only the minter mints, only owners move tokens, SendNft calls ReceiveNft
+        fn collection_template() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                |deps: DepsMut, _env: Env, info: MessageInfo, msg: MockCw721Msg| -> StdResult<Response> {
+                    let ensure_owner = |deps: &DepsMut, token_id: &String| -> StdResult<()> {
+                        match OWNERS.load(deps.storage, token_id.clone())? == info.sender.as_str() {
+                            true => Ok(()),
+                            false => Err(StdError::generic_err("not the token owner")),
+                        }
+                    };
+                    match msg {
+                        MockCw721Msg::Mint { token_id, owner, .. } => {
+                            if MINTER.load(deps.storage)? != info.sender.as_str() {
+                                return Err(StdError::generic_err("not the minter"));
+                            }
+                            OWNERS.save(deps.storage, token_id, &owner)?;
+                            Ok(Response::new())
+                        }
+                        MockCw721Msg::TransferNft { recipient, token_id } => {
+                            ensure_owner(&deps, &token_id)?;
+                            OWNERS.save(deps.storage, token_id, &recipient)?;
+                            Ok(Response::new())
+                        }
+                        MockCw721Msg::SendNft { contract, token_id, msg } => {
+                            ensure_owner(&deps, &token_id)?;
+                            OWNERS.save(deps.storage, token_id.clone(), &contract)?;
+                            let receive = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+                                sender: info.sender.to_string(),
+                                token_id,
+                                msg,
+                            });
+                            Ok(Response::new().add_message(WasmMsg::Execute {
+                                contract_addr: contract,
+                                msg: to_json_binary(&receive)?,
+                                funds: vec![],
+                            }))
+                        }
+                    }
+                },
+                |deps: DepsMut, _env: Env, _info: MessageInfo, minter: String| -> StdResult<Response> {
+                    MINTER.save(deps.storage, &minter)?;
+                    Ok(Response::new())
+                },
+                |deps: Deps, _env: Env, token_id: String| -> StdResult<Binary> {
+                    to_json_binary(&OWNERS.load(deps.storage, token_id)?)
+                },
+            );
+            Box::new(contract)
+        }
+
+        // alice's handle minted in a collection the contract mints for
+        fn tokenized_setup() -> (App, SocialPaymentContract, Addr) {
+            let (mut app, contract) = proper_instantiate();
+            register_users(&mut app, &contract);
+            let code_id = app.store_code(collection_template());
+            let collection = app
+                .instantiate_contract(code_id, Addr::unchecked(ADMIN), &contract.addr().to_string(), &[], "usernames", None)
+                .unwrap();
+            let set_collection = ExecuteMsg::SetUsernameCollection { collection: collection.to_string() };
+            app.execute_contract(Addr::unchecked(ADMIN), contract.addr(), &set_collection, &[])
+                .unwrap();
+            app.execute_contract(Addr::unchecked(USER1), contract.addr(), &ExecuteMsg::TokenizeUsername {}, &[])
+                .unwrap();
+            (app, contract, collection)
+        }
+
+        fn nft_owner(app: &App, collection: &Addr) -> String {
+            app.wrap().query_wasm_smart(collection, &"alice".to_string()).unwrap()
+        }
+
+        fn send_nft(app: &mut App, contract: &SocialPaymentContract, collection: &Addr, holder: &str) -> Result<(), ContractError> {
+            let send = MockCw721Msg::SendNft {
+                contract: contract.addr().to_string(),
+                token_id: "alice".to_string(),
+                msg: Binary::default(),
+            };
+            app.execute_contract(Addr::unchecked(holder), collection.clone(), &send, &[])
+                .map(|_| ())
+                .map_err(|err| err.downcast::<ContractError>().unwrap())
+        }
+
+        #[test]
+        fn test_sold_handle_moves_to_new_holder_after_delay() {
+            let (mut app, contract, collection) = tokenized_setup();
+            assert_eq!(nft_owner(&app, &collection), USER1);
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &ExecuteMsg::TokenizeUsername {}, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::UsernameAlreadyTokenized {});
+
+            // alice sells the NFT; the buyer hands it to the contract to claim the handle
+            let sell = MockCw721Msg::TransferNft { recipient: NEW_WALLET.to_string(), token_id: "alice".to_string() };
+            app.execute_contract(Addr::unchecked(USER1), collection.clone(), &sell, &[]).unwrap();
+            send_nft(&mut app, &contract, &collection, NEW_WALLET).unwrap();
+            let pending: UsernameTransferResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetUsernameTransfer { username: "alice".to_string() })
+                .unwrap();
+            assert_eq!(pending.transfer.unwrap().to_wallet, Addr::unchecked(NEW_WALLET));
+
+            let finalize = ExecuteMsg::FinalizeUsernameTransfer { username: "alice".to_string() };
+            let err = app
+                .execute_contract(Addr::unchecked(USER2), contract.addr(), &finalize, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::UsernameTransferNotReady {});
+
+            app.update_block(|block| block.time = block.time.plus_seconds(3 * 86400));
+            app.execute_contract(Addr::unchecked(USER2), contract.addr(), &finalize, &[])
+                .unwrap();
+            let user: UserResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetUserByUsername { username: "alice".to_string() })
+                .unwrap();
+            assert_eq!(user.user.wallet_address, Addr::unchecked(NEW_WALLET));
+            assert_eq!(nft_owner(&app, &collection), NEW_WALLET);
+            let old_wallet: StdResult<UserResponse> = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetUserByWallet { wallet_address: USER1.to_string() });
+            assert!(old_wallet.is_err());
+        }
+
+        #[test]
+        fn test_registered_wallets_cannot_claim_a_handle() {
+            let (mut app, contract, collection) = tokenized_setup();
+
+            // Re-sending your own handle, or sending it to a wallet that already has one, is rejected
+            let err = send_nft(&mut app, &contract, &collection, USER1).unwrap_err();
+            assert_eq!(err, ContractError::WalletAlreadyRegistered {});
+            let gift = MockCw721Msg::TransferNft { recipient: USER2.to_string(), token_id: "alice".to_string() };
+            app.execute_contract(Addr::unchecked(USER1), collection.clone(), &gift, &[]).unwrap();
+            let err = send_nft(&mut app, &contract, &collection, USER2).unwrap_err();
+            assert_eq!(err, ContractError::WalletAlreadyRegistered {});
+            assert_eq!(nft_owner(&app, &collection), USER2);
+        }
+
+        #[test]
+        fn test_claimant_can_cancel_and_recover_the_nft() {
+            let (mut app, contract, collection) = tokenized_setup();
+            let sell = MockCw721Msg::TransferNft { recipient: NEW_WALLET.to_string(), token_id: "alice".to_string() };
+            app.execute_contract(Addr::unchecked(USER1), collection.clone(), &sell, &[]).unwrap();
+            send_nft(&mut app, &contract, &collection, NEW_WALLET).unwrap();
+
+            let cancel = ExecuteMsg::CancelUsernameTransfer { username: "alice".to_string() };
+            let err = app
+                .execute_contract(Addr::unchecked(USER1), contract.addr(), &cancel, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotAuthorized {});
+            app.execute_contract(Addr::unchecked(NEW_WALLET), contract.addr(), &cancel, &[])
+                .unwrap();
+            assert_eq!(nft_owner(&app, &collection), NEW_WALLET);
+            let user: UserResponse = app
+                .wrap()
+                .query_wasm_smart(contract.addr(), &QueryMsg::GetUserByUsername { username: "alice".to_string() })
+                .unwrap();
+            assert_eq!(user.user.wallet_address, Addr::unchecked(USER1));
+        }
+    }
+
+    mod payment_links {
+        use super::*;
+        use crate::msg::{PaymentLinkResponse, PaymentLinksResponse, PaymentResponse};
+        use crate::state::PaymentStatus;
+        use crate::testing::{attribute, Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        fn merchant_scenario() -> Scenario {
+            ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_shop", "shop", 0)
+                .build()
+        }
+
+        // Create a link as the shop and return its code
+        fn create_link(scenario: &mut Scenario, amount: Option<u128>, max_uses: Option<u32>) -> String {
+            let create = ExecuteMsg::CreatePaymentLink {
+                amount: amount.map(|amount| scenario.coin(amount)),
+                description: "Coffee".to_string(),
+                max_uses,
+                expires_at: None,
+            };
+            let res = scenario.execute_as("shop", &create, &[]);
+            attribute(&res, "code").unwrap()
+        }
+
+        fn link(scenario: &Scenario, code: &str) -> PaymentLinkResponse {
+            scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetPaymentLink { code: code.to_string() })
+                .unwrap()
+        }
+
+        #[test]
+        fn test_fixed_link_creates_payments_until_used_up() {
+            let mut scenario = merchant_scenario();
+            let code = create_link(&mut scenario, Some(150), Some(2));
+
+            // Overpaying refunds the surplus; each use is an ordinary completed payment
+            let pay = ExecuteMsg::PayLink { code: code.clone() };
+            let funds = [scenario.coin(200)];
+            scenario.execute_as("alice", &pay, &funds);
+            assert_eq!(scenario.balance("shop"), 150);
+            assert_eq!(scenario.balance("alice"), 850);
+            let payment: PaymentResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetPaymentById {
+                        payment_id: 1,
+                        viewer: Some("alice".to_string()),
+                    },
+                )
+                .unwrap();
+            assert_eq!(payment.payment.to_username, "shop");
+            assert_eq!(payment.payment.description, "Coffee");
+            assert_eq!(payment.payment.status, PaymentStatus::Completed);
+
+            let funds = [scenario.coin(150)];
+            scenario.execute_as("alice", &pay, &funds);
+            let used_up = link(&scenario, &code);
+            assert_eq!(used_up.link.uses, 2);
+            assert!(!used_up.usable);
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &pay, &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::PaymentLinkInactive {});
+            assert_eq!(scenario.balance("shop"), 300);
+        }
+
+        #[test]
+        fn test_open_link_takes_the_attached_amount() {
+            let mut scenario = merchant_scenario();
+            let code = create_link(&mut scenario, None, None);
+            let pay = ExecuteMsg::PayLink { code: code.clone() };
+
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &pay, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidPaymentAmount {});
+
+            let funds = [scenario.coin(42)];
+            scenario.execute_as("alice", &pay, &funds);
+            assert_eq!(scenario.balance("shop"), 42);
+            assert!(link(&scenario, &code).usable);
+        }
+
+        #[test]
+        fn test_only_the_merchant_disables_a_link() {
+            let mut scenario = merchant_scenario();
+            let code = create_link(&mut scenario, Some(100), None);
+            let disable = ExecuteMsg::DisablePaymentLink { code: code.clone() };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &disable, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotAuthorized {});
+
+            scenario.execute_as("shop", &disable, &[]);
+            let pay = ExecuteMsg::PayLink { code };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &pay, &[scenario.coin(100)])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::PaymentLinkInactive {});
+
+            let links: PaymentLinksResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetUserPaymentLinks {
+                        username: "shop".to_string(),
+                    },
+                )
+                .unwrap();
+            assert_eq!(links.links.len(), 1);
+            assert!(!links.links[0].active);
+        }
+    }
+
+    mod scheduled_payments {
+        use super::*;
+        use crate::msg::{PaymentResponse, ScheduledPaymentResponse, ScheduledPaymentsResponse, UserEscrowedResponse};
+        use crate::state::ScheduledPaymentStatus;
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        const DELAY: u64 = 7 * 86400;
+
+        // alice schedules 250 to bob a week from now
+        fn scheduled_scenario() -> (Scenario, u64) {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let schedule = ExecuteMsg::SchedulePayment {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(250),
+                description: "Birthday".to_string(),
+                execute_at: scenario.app.block_info().time.seconds() + DELAY,
+            };
+            let funds = [scenario.coin(250)];
+            scenario.execute_as("alice", &schedule, &funds);
+            (scenario, 1)
+        }
+
+        fn scheduled(scenario: &Scenario, scheduled_id: u64) -> ScheduledPaymentResponse {
+            scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetScheduledPayment { scheduled_id })
+                .unwrap()
+        }
+
+        fn due(scenario: &Scenario) -> ScheduledPaymentsResponse {
+            scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetDueScheduledPayments {})
+                .unwrap()
+        }
+
+        #[test]
+        fn test_keeper_executes_once_due() {
+            let (mut scenario, scheduled_id) = scheduled_scenario();
+            let keeper = Addr::unchecked("keeper");
+            let execute = ExecuteMsg::ExecuteScheduledPayment { scheduled_id };
+            let err = scenario
+                .app
+                .execute_contract(keeper.clone(), scenario.contract.addr(), &execute, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ScheduledPaymentNotDue {});
+            assert!(due(&scenario).scheduled_payments.is_empty());
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(DELAY));
+            assert!(scheduled(&scenario, scheduled_id).executable);
+            assert_eq!(due(&scenario).scheduled_payments.len(), 1);
+            scenario
+                .app
+                .execute_contract(keeper.clone(), scenario.contract.addr(), &execute, &[])
+                .unwrap();
+            assert_eq!(scenario.balance("bob"), 250);
+            assert_eq!(scenario.balance("alice"), 750);
+
+            let executed = scheduled(&scenario, scheduled_id);
+            assert_eq!(executed.scheduled_payment.status, ScheduledPaymentStatus::Executed);
+            assert!(!executed.executable);
+            let payment: PaymentResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetPaymentById {
+                        payment_id: executed.scheduled_payment.payment_id.unwrap(),
+                        viewer: Some("bob".to_string()),
+                    },
+                )
+                .unwrap();
+            assert_eq!(payment.payment.description, "Birthday");
+            assert!(due(&scenario).scheduled_payments.is_empty());
+
+            let err = scenario
+                .app
+                .execute_contract(keeper, scenario.contract.addr(), &execute, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ScheduledPaymentNotPending {});
+        }
+
+        #[test]
+        fn test_sender_cancels_before_execution() {
+            let (mut scenario, scheduled_id) = scheduled_scenario();
+            let escrowed: UserEscrowedResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetUserEscrowed {
+                        username: "alice".to_string(),
+                    },
+                )
+                .unwrap();
+            assert_eq!(escrowed.escrowed, vec![scenario.coin(250)]);
+
+            let cancel = ExecuteMsg::CancelScheduledPayment { scheduled_id };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &cancel, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotAuthorized {});
+
+            scenario.execute_as("alice", &cancel, &[]);
+            assert_eq!(scenario.balance("alice"), 1000);
+            assert_eq!(
+                scheduled(&scenario, scheduled_id).scheduled_payment.status,
+                ScheduledPaymentStatus::Cancelled
+            );
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(DELAY));
+            assert!(due(&scenario).scheduled_payments.is_empty());
+            let err = scenario
+                .app
+                .execute_contract(
+                    Addr::unchecked("keeper"),
+                    scenario.contract.addr(),
+                    &ExecuteMsg::ExecuteScheduledPayment { scheduled_id },
+                    &[],
+                )
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ScheduledPaymentNotPending {});
+        }
+    }
+
+    mod arbiters {
+        use super::*;
+        use crate::msg::{ArbitersResponse, TaskResponse};
+        use crate::state::{AdminRole, ArbiterConfig};
+        use crate::testing::{attribute, Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        // alice disputes an escrowed task for bob; judge and jury are dispute resolvers who may bond
+        fn arbiter_scenario() -> Scenario {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_user("wallet_judge", "judge", 500)
+                .with_user("wallet_jury", "jury", 500)
+                .build();
+            let owner = scenario.owner.clone();
+            let config = ExecuteMsg::SetArbiterConfig {
+                config: Some(ArbiterConfig {
+                    bond_denom: scenario.denom.clone(),
+                    min_bond: Uint128::new(100),
+                }),
+            };
+            scenario.app.execute_contract(owner.clone(), scenario.contract.addr(), &config, &[]).unwrap();
+            for resolver in ["judge", "jury"] {
+                let grant = ExecuteMsg::GrantRole {
+                    role: AdminRole::DisputeResolver,
+                    address: scenario.wallet(resolver).to_string(),
+                };
+                scenario.app.execute_contract(owner.clone(), scenario.contract.addr(), &grant, &[]).unwrap();
+            }
+            scenario
+        }
+
+        fn assigned_arbiter(scenario: &Scenario, task_id: u64) -> Option<Addr> {
+            let task: TaskResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetTaskById { task_id })
+                .unwrap();
+            task.task.arbiter
+        }
+
+        fn arbiters(scenario: &Scenario) -> ArbitersResponse {
+            scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetArbiters {})
+                .unwrap()
+        }
+
+        #[test]
+        fn test_only_the_assigned_arbiter_resolves() {
+            let mut scenario = arbiter_scenario();
+            let funds = [scenario.coin(200)];
+            scenario.execute_as("judge", &ExecuteMsg::BondArbiter {}, &funds);
+            // Below the minimum bond, so never selected
+            let funds = [scenario.coin(50)];
+            scenario.execute_as("jury", &ExecuteMsg::BondArbiter {}, &funds);
+            let bonded = arbiters(&scenario);
+            assert!(bonded.arbiters[0].eligible);
+            assert!(!bonded.arbiters[1].eligible);
+
+            let task_id = scenario.escrowed_task("alice", "bob", 300, TaskStatus::Disputed);
+            assert_eq!(assigned_arbiter(&scenario, task_id), Some(scenario.wallet("judge")));
+
+            let resolve = ExecuteMsg::ResolveDispute { task_id, worker_bps: 10_000 };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("jury"), scenario.contract.addr(), &resolve, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotTaskArbiter {});
+
+            // The bond stays locked while the dispute is open
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("judge"), scenario.contract.addr(), &ExecuteMsg::UnbondArbiter {}, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ArbiterHasOpenDisputes {});
+
+            scenario.execute_as("judge", &resolve, &[]);
+            assert_eq!(arbiters(&scenario).arbiters[0].arbiter.open_disputes, 0);
+            scenario.execute_as("judge", &ExecuteMsg::UnbondArbiter {}, &[]);
+            let judge = scenario.app.wrap().query_balance(scenario.wallet("judge"), &scenario.denom).unwrap();
+            assert_eq!(judge.amount, Uint128::new(500));
+        }
+
+        #[test]
+        fn test_selection_spreads_over_bonded_arbiters() {
+            let mut scenario = arbiter_scenario();
+            let funds = [scenario.coin(200)];
+            scenario.execute_as("judge", &ExecuteMsg::BondArbiter {}, &funds);
+            scenario.execute_as("jury", &ExecuteMsg::BondArbiter {}, &funds);
+
+            let mut assigned = vec![];
+            for _ in 0..3 {
+                let task_id = scenario.escrowed_task("alice", "bob", 100, TaskStatus::Disputed);
+                assigned.push(assigned_arbiter(&scenario, task_id).unwrap());
+                scenario.app.update_block(|block| block.height += 1);
+            }
+            assert!(assigned.iter().all(|arbiter| [scenario.wallet("judge"), scenario.wallet("jury")].contains(arbiter)));
+            let open: u32 = arbiters(&scenario).arbiters.iter().map(|info| info.arbiter.open_disputes).sum();
+            assert_eq!(open, 3);
+        }
+
+        #[test]
+        fn test_unassigned_disputes_still_go_to_resolvers() {
+            let mut scenario = arbiter_scenario();
+            let task_id = scenario.escrowed_task("alice", "bob", 300, TaskStatus::Disputed);
+            assert_eq!(assigned_arbiter(&scenario, task_id), None);
+            scenario.execute_as("jury", &ExecuteMsg::ResolveDispute { task_id, worker_bps: 0 }, &[]);
+        }
+
+        // alice pays bob 300 on a hybrid task with judge as the agreed arbiter
+        fn create_arbitrated_task(scenario: &mut Scenario) -> u64 {
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(300),
+                description: "Arbitrated task".to_string(),
+                proof_type: ProofType::Hybrid,
+                deadline_ts: scenario.app.block_info().time.seconds() + 86400,
+                review_window_secs: Some(3600),
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: Some(scenario.wallet("judge").to_string()),
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            let funds = [scenario.coin(300)];
+            let res = scenario.execute_as("alice", &create_task, &funds);
+            attribute(&res, "task_id").and_then(|value| value.parse().ok()).unwrap()
+        }
+
+        #[test]
+        fn test_agreed_arbiter_accepts_and_alone_resolves() {
+            let mut scenario = arbiter_scenario();
+            let funds = [scenario.coin(200)];
+            scenario.execute_as("jury", &ExecuteMsg::BondArbiter {}, &funds);
+
+            let task_id = create_arbitrated_task(&mut scenario);
+
+            // No proofs until the arbiter accepts, and only the arbiter can accept
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id,
+                proof_blob_or_ref: "valid_proof".to_string(),
+                zk_proof_hash: "proof_hash".to_string(),
+            };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &submit_proof, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ArbitrationNotAccepted {});
+            let accept = ExecuteMsg::AcceptArbitration { task_id };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("jury"), scenario.contract.addr(), &accept, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotTaskArbiter {});
+            scenario.execute_as("judge", &accept, &[]);
+
+            scenario.execute_as("bob", &submit_proof, &[]);
+            let dispute = ExecuteMsg::DisputeTask { task_id, reason: None, reason_hash: None };
+            scenario.execute_as("alice", &dispute, &[]);
+            // The bonded registry is bypassed
+            assert_eq!(assigned_arbiter(&scenario, task_id), None);
+            assert_eq!(arbiters(&scenario).arbiters[0].arbiter.open_disputes, 0);
+
+            // Neither a dispute resolver nor the owner can override the agreed arbiter
+            let resolve = ExecuteMsg::ResolveDispute { task_id, worker_bps: 10_000 };
+            for sender in [scenario.wallet("jury"), scenario.owner.clone()] {
+                let err = scenario
+                    .app
+                    .execute_contract(sender, scenario.contract.addr(), &resolve, &[])
+                    .unwrap_err();
+                assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::NotTaskArbiter {});
+            }
+            scenario.execute_as("judge", &resolve, &[]);
+            let bob = scenario.app.wrap().query_balance(scenario.wallet("bob"), &scenario.denom).unwrap();
+            assert_eq!(bob.amount, Uint128::new(300));
+        }
+
+        #[test]
+        fn test_agreed_arbiter_must_be_a_third_party_on_hybrid_tasks() {
+            let mut scenario = arbiter_scenario();
+            let create_task = |proof_type: ProofType, arbiter: &Addr, scenario: &Scenario| ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                description: "Arbitrated task".to_string(),
+                proof_type,
+                deadline_ts: scenario.app.block_info().time.seconds() + 86400,
+                review_window_secs: Some(3600),
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: Some(arbiter.to_string()),
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            let cases = [
+                create_task(ProofType::ZkTLS, &scenario.wallet("judge"), &scenario),
+                create_task(ProofType::Hybrid, &scenario.wallet("bob"), &scenario),
+                create_task(ProofType::Hybrid, &scenario.wallet("alice"), &scenario),
+            ];
+            let funds = [scenario.coin(100)];
+            for msg in cases {
+                let err = scenario
+                    .app
+                    .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &msg, &funds)
+                    .unwrap_err();
+                assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidArbiter {});
+            }
+        }
+
+        #[test]
+        fn test_declining_arbiter_refunds_the_payer() {
+            let mut scenario = arbiter_scenario();
+            let alice_before = scenario.balance("alice");
+            let task_id = create_arbitrated_task(&mut scenario);
+
+            let decline = ExecuteMsg::DeclineArbitration { task_id };
+            let err = scenario.try_execute_as(scenario.wallet("jury"), &decline, &[]).unwrap_err();
+            assert_eq!(err, ContractError::NotTaskArbiter {});
+            scenario.execute_as("judge", &decline, &[]);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::Refunded);
+            assert_eq!(scenario.balance("alice"), alice_before);
+
+            // A declined task can no longer be accepted
+            let accept = ExecuteMsg::AcceptArbitration { task_id };
+            let err = scenario.try_execute_as(scenario.wallet("judge"), &accept, &[]).unwrap_err();
+            assert_eq!(err, ContractError::TaskNotAuthorized {});
+        }
+    }
+
+    mod instant_release {
+        use super::*;
+        use crate::msg::ReputationResponse;
+        use crate::state::InstantReleaseConfig;
+        use crate::testing::{Scenario, ScenarioBuilder};
+
+        fn reputation(scenario: &Scenario, username: &str) -> u64 {
+            let res: ReputationResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetReputation { username: username.to_string() })
+                .unwrap();
+            res.reputation
+        }
+
+        #[test]
+        fn test_trusted_workers_skip_the_review_window() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 5000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+
+            // Tasks paid in full build reputation
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Released);
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Released);
+            assert_eq!(reputation(&scenario, "bob"), 2);
+
+            // Nothing releases instantly until configured
+            let task_id = scenario.escrowed_task("alice", "bob", 100, TaskStatus::PendingRelease);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::PendingRelease);
+
+            let update_config = ExecuteMsg::UpdateConfig {
+                co_approval_thresholds: None,
+                review_window: None,
+                archive_retention_secs: None,
+                accepted_denoms: None,
+                instant_release: Some(InstantReleaseConfig {
+                    min_reputation: 2,
+                    max_amounts: vec![scenario.coin(500)],
+                }),
+                friend_requests: None,
+                text_limits: None,
+                open_item_limits: None,
+            };
+            let owner = scenario.owner.clone();
+            scenario.app.execute_contract(owner, scenario.contract.addr(), &update_config, &[]).unwrap();
+
+            let task_id = scenario.escrowed_task("alice", "bob", 300, TaskStatus::PendingRelease);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::Released);
+            let bob = scenario.app.wrap().query_balance(scenario.wallet("bob"), &scenario.denom).unwrap();
+            assert_eq!(bob.amount, Uint128::new(500));
+            assert_eq!(reputation(&scenario, "bob"), 3);
+
+            // Larger tasks keep the review window
+            let task_id = scenario.escrowed_task("alice", "bob", 600, TaskStatus::PendingRelease);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::PendingRelease);
+
+            // Losing part of a dispute restarts reputation, and the window applies again
+            scenario.execute_as("alice", &ExecuteMsg::DisputeTask { task_id, reason: None, reason_hash: None }, &[]);
+            let owner = scenario.owner.clone();
+            let resolve = ExecuteMsg::ResolveDispute { task_id, worker_bps: 5_000 };
+            scenario.app.execute_contract(owner, scenario.contract.addr(), &resolve, &[]).unwrap();
+            assert_eq!(reputation(&scenario, "bob"), 0);
+            let task_id = scenario.escrowed_task("alice", "bob", 300, TaskStatus::PendingRelease);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::PendingRelease);
+        }
+    }
+
+    mod tasks_by_status {
+        use super::*;
+        use crate::msg::TasksByStatusResponse;
+        use crate::testing::{Scenario, ScenarioBuilder};
+
+        fn task_ids(scenario: &Scenario, query: &QueryMsg) -> Vec<u64> {
+            let res: TasksByStatusResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), query)
+                .unwrap();
+            res.tasks.iter().map(|task| task.id).collect()
+        }
+
+        #[test]
+        fn test_tasks_are_indexed_by_current_status() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 1000)
+                .with_user("wallet_carol", "carol", 0)
+                .build();
+            let first = scenario.escrowed_task("alice", "carol", 100, TaskStatus::PendingRelease);
+            let second = scenario.escrowed_task("bob", "carol", 100, TaskStatus::PendingRelease);
+            let third = scenario.escrowed_task("alice", "bob", 100, TaskStatus::Disputed);
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+
+            let pending = |start_after, limit| QueryMsg::GetTasksByStatus {
+                status: TaskStatus::PendingRelease,
+                start_after,
+                limit,
+            };
+            assert_eq!(task_ids(&scenario, &pending(None, None)), vec![first, second]);
+            assert_eq!(task_ids(&scenario, &pending(None, Some(1))), vec![first]);
+            assert_eq!(task_ids(&scenario, &pending(Some(first), None)), vec![second]);
+            let disputed = QueryMsg::GetTasksByStatus { status: TaskStatus::Disputed, start_after: None, limit: None };
+            assert_eq!(task_ids(&scenario, &disputed), vec![third]);
+
+            // Either party sees the task under its status
+            let alice_pending = QueryMsg::GetUserTasksByStatus {
+                username: "alice".to_string(),
+                status: TaskStatus::PendingRelease,
+                start_after: None,
+                limit: None,
+            };
+            assert_eq!(task_ids(&scenario, &alice_pending), vec![first]);
+            let bob_disputed = QueryMsg::GetUserTasksByStatus {
+                username: "bob".to_string(),
+                status: TaskStatus::Disputed,
+                start_after: None,
+                limit: None,
+            };
+            assert_eq!(task_ids(&scenario, &bob_disputed), vec![third]);
+
+            // Settling moves the task to its new status
+            let owner = scenario.owner.clone();
+            let resolve = ExecuteMsg::ResolveDispute { task_id: third, worker_bps: 10_000 };
+            scenario.app.execute_contract(owner, scenario.contract.addr(), &resolve, &[]).unwrap();
+            assert!(task_ids(&scenario, &disputed).is_empty());
+            assert!(task_ids(&scenario, &bob_disputed).is_empty());
+            let released = QueryMsg::GetTasksByStatus { status: TaskStatus::Released, start_after: None, limit: None };
+            assert_eq!(task_ids(&scenario, &released), vec![third]);
+        }
+    }
+
+    mod block_heights {
+        use super::*;
+        use crate::msg::TaskResponse;
+        use crate::testing::ScenarioBuilder;
+
+        #[test]
+        fn test_records_carry_creation_and_update_heights() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let created_height = scenario.app.block_info().height;
+            let task_id = scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+
+            scenario.app.update_block(|block| block.height += 5);
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id,
+                proof_blob_or_ref: "valid_proof".to_string(),
+                zk_proof_hash: "proof_hash".to_string(),
+            };
+            scenario.execute_as("bob", &submit_proof, &[]);
+
+            let res: TaskResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetTaskById { task_id })
+                .unwrap();
+            // Same timestamp, different heights
+            assert_eq!(res.task.created_at, res.task.updated_at);
+            assert_eq!(res.task.created_at_height, created_height);
+            assert_eq!(res.task.updated_at_height, created_height + 5);
+        }
+    }
+
+    mod unfriend_cascade {
+        use super::*;
+        use crate::msg::{GroupResponse, PaymentResponse};
+        use crate::state::PaymentStatus;
+        use crate::testing::{Scenario, ScenarioBuilder};
+
+        fn request_payment(scenario: &mut Scenario, from: &str, to: &str) {
+            request_payment_with_proof(scenario, from, to, ProofType::None);
+        }
+
+        fn request_payment_with_proof(scenario: &mut Scenario, from: &str, to: &str, proof_type: ProofType) {
+            let request = ExecuteMsg::CreatePaymentRequest {
+                to_username: to.to_string(),
+                amount: scenario.coin(50),
+                description: "Dinner".to_string(),
+                proof_type,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            scenario.execute_as(from, &request, &[]);
+        }
+
+        fn payment_status(scenario: &Scenario, payment_id: u64) -> PaymentStatus {
+            let res: PaymentResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetPaymentById { payment_id, viewer: Some("alice".to_string()) },
+                )
+                .unwrap();
+            res.payment.status
+        }
+
+        fn group_members(scenario: &Scenario, group_id: u64) -> Vec<String> {
+            let res: GroupResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetGroup { group_id })
+                .unwrap();
+            res.group.members
+        }
+
+        #[test]
+        fn test_unfriending_cancels_requests_and_leaves_groups() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 1000)
+                .with_user("wallet_carol", "carol", 1000)
+                .with_friends("alice", "bob")
+                .with_friends("alice", "carol")
+                .build();
+            request_payment(&mut scenario, "alice", "bob");
+            request_payment(&mut scenario, "bob", "alice");
+            request_payment(&mut scenario, "alice", "carol");
+
+            // Each owns a group the other belongs to
+            scenario.execute_as("alice", &ExecuteMsg::CreateGroup { name: "Climbing".to_string() }, &[]);
+            scenario.execute_as("alice", &ExecuteMsg::AddToGroup { group_id: 1, username: "bob".to_string() }, &[]);
+            scenario.execute_as("alice", &ExecuteMsg::AddToGroup { group_id: 1, username: "carol".to_string() }, &[]);
+            scenario.execute_as("bob", &ExecuteMsg::CreateGroup { name: "Book club".to_string() }, &[]);
+            scenario.execute_as("bob", &ExecuteMsg::AddToGroup { group_id: 2, username: "alice".to_string() }, &[]);
+
+            let remove_friend = ExecuteMsg::RemoveFriend {
+                username: "bob".to_string(),
+                leave_shared_groups: true,
+            };
+            let res = scenario.execute_as("alice", &remove_friend, &[]);
+            assert_eq!(res.events.iter().filter(|event| event.ty == "wasm-payment_request_cancelled").count(), 2);
+            assert_eq!(res.events.iter().filter(|event| event.ty == "wasm-group_member_removed").count(), 2);
+
+            assert_eq!(payment_status(&scenario, 1), PaymentStatus::Cancelled);
+            assert_eq!(payment_status(&scenario, 2), PaymentStatus::Cancelled);
+            // Requests with other friends are untouched
+            assert_eq!(payment_status(&scenario, 3), PaymentStatus::Pending);
+
+            assert_eq!(group_members(&scenario, 1), vec!["alice".to_string(), "carol".to_string()]);
+            assert_eq!(group_members(&scenario, 2), vec!["bob".to_string()]);
+        }
+
+        #[test]
+        fn test_unfriending_cancels_requests_awaiting_proof_review() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 1000)
+                .with_user("wallet_carol", "carol", 1000)
+                .with_friends("alice", "bob")
+                .with_friends("alice", "carol")
+                .build();
+            request_payment_with_proof(&mut scenario, "alice", "bob", ProofType::Photo);
+            let submit = ExecuteMsg::SubmitProof { payment_id: 1, proof_data: "receipt.jpg".to_string() };
+            scenario.execute_as("bob", &submit, &[]);
+            assert_eq!(payment_status(&scenario, 1), PaymentStatus::ProofSubmitted);
+
+            request_payment(&mut scenario, "alice", "carol");
+
+            let remove_friend = ExecuteMsg::RemoveFriend {
+                username: "bob".to_string(),
+                leave_shared_groups: false,
+            };
+            scenario.execute_as("alice", &remove_friend, &[]);
+            assert_eq!(payment_status(&scenario, 1), PaymentStatus::Cancelled);
+            assert_eq!(payment_status(&scenario, 2), PaymentStatus::Pending);
+        }
+    }
+
+    mod invariants {
+        use super::*;
+        use crate::msg::{InstantiateMsg, InvariantsResponse};
+        use crate::state::{Friendship, ESCROW_TOTALS, FRIENDSHIPS};
+        use crate::testing::ScenarioBuilder;
+        use cosmwasm_std::from_json;
+        use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+        #[test]
+        fn test_live_state_satisfies_invariants() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 1000)
+                .with_user("wallet_carol", "carol", 1000)
+                .with_friends("alice", "bob")
+                .build();
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+            scenario.escrowed_task("bob", "alice", 200, TaskStatus::Disputed);
+            scenario.escrowed_task("alice", "bob", 300, TaskStatus::Released);
+
+            let report: InvariantsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::VerifyInvariants { limit: None })
+                .unwrap();
+            assert!(report.complete);
+            assert!(report.violations.is_empty());
+
+            // Too small a slice to cover every user
+            let report: InvariantsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::VerifyInvariants { limit: Some(2) })
+                .unwrap();
+            assert!(!report.complete);
+        }
+
+        #[test]
+        fn test_reports_broken_state() {
+            let mut deps = mock_dependencies();
+            crate::contract::instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), InstantiateMsg {}).unwrap();
+            let friendship = Friendship {
+                user1: "alice".to_string(),
+                user2: "bob".to_string(),
+                created_at: 0,
+            };
+            FRIENDSHIPS.save(deps.as_mut().storage, ("alice".to_string(), "bob".to_string()), &friendship).unwrap();
+            ESCROW_TOTALS.save(deps.as_mut().storage, "uxion".to_string(), &Uint128::new(50)).unwrap();
+
+            let res = crate::contract::query(deps.as_ref(), mock_env(), QueryMsg::VerifyInvariants { limit: None }).unwrap();
+            let report: InvariantsResponse = from_json(res).unwrap();
+            let broken: Vec<(&str, &str)> = report
+                .violations
+                .iter()
+                .map(|violation| (violation.invariant.as_str(), violation.key.as_str()))
+                .collect();
+            assert_eq!(broken, vec![("escrow_totals", "uxion"), ("friendship_symmetry", "alice:bob")]);
+        }
+    }
+
+    mod address_validation {
+        use super::*;
+        use crate::msg::{RolesResponse, UserResponse};
+        use crate::state::AdminRole;
+        use crate::testing::ScenarioBuilder;
+        use crate::ContractError;
+
+        #[test]
+        fn test_addresses_are_validated_and_stored_canonically() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .build();
+            let owner = scenario.owner.clone();
+
+            let grant = |address: &str| ExecuteMsg::GrantRole {
+                role: AdminRole::DisputeResolver,
+                address: address.to_string(),
+            };
+            let err = scenario
+                .app
+                .execute_contract(owner.clone(), scenario.contract.addr(), &grant("ab"), &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidAddress {});
+
+            // Other casings of the same address are stored in canonical form
+            scenario
+                .app
+                .execute_contract(owner, scenario.contract.addr(), &grant("Wallet_Alice"), &[])
+                .unwrap();
+            let roles: RolesResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetRoles {})
+                .unwrap();
+            assert_eq!(roles.grants[0].address, Addr::unchecked("wallet_alice"));
+
+            let user: UserResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetUserByWallet { wallet_address: "WALLET_ALICE".to_string() },
+                )
+                .unwrap();
+            assert_eq!(user.user.username, "alice");
+            let err = scenario
+                .app
+                .wrap()
+                .query_wasm_smart::<UserResponse>(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetUserByWallet { wallet_address: "ab".to_string() },
+                )
+                .unwrap_err();
+            assert!(err.to_string().contains("Invalid address"));
+        }
+    }
+
+    mod text_limits {
+        use super::*;
+        use crate::state::TextLimits;
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        fn set_limits(scenario: &mut Scenario, limits: TextLimits) -> Result<cw_multi_test::AppResponse, ContractError> {
+            let update_config = ExecuteMsg::UpdateConfig {
+                co_approval_thresholds: None,
+                review_window: None,
+                archive_retention_secs: None,
+                accepted_denoms: None,
+                instant_release: None,
+                friend_requests: None,
+                text_limits: Some(limits),
+                open_item_limits: None,
+            };
+            scenario.try_execute_as(scenario.owner.clone(), &update_config, &[])
+        }
+
+        fn send_payment(description: String, scenario: &Scenario) -> ExecuteMsg {
+            ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(10),
+                description,
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            }
+        }
+
+        #[test]
+        fn test_free_text_is_bounded_by_configured_limits() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build();
+            let funds = [scenario.coin(10)];
+
+            // Defaults apply before any limits are configured
+            let default_max = TextLimits::default().description as usize;
+            let msg = send_payment("x".repeat(default_max + 1), &scenario);
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &msg, &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::DescriptionTooLong {});
+            let msg = send_payment("x".repeat(default_max), &scenario);
+            scenario.execute_as("alice", &msg, &funds);
+
+            let err = set_limits(&mut scenario, TextLimits { description: 0, evidence: 8, endpoint: 16 }).unwrap_err();
+            assert_eq!(err, ContractError::InvalidTextLimits {});
+            set_limits(&mut scenario, TextLimits { description: 100, evidence: 8, endpoint: 16 }).unwrap();
+
+            // The scenario task endpoint is longer than 16 characters
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                description: "Fetch the report".to_string(),
+                proof_type: ProofType::Hybrid,
+                deadline_ts: scenario.app.block_info().time.seconds() + 86400,
+                review_window_secs: Some(3600),
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            let funds = [scenario.coin(100)];
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &create_task, &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::EndpointTooLong {});
+
+            set_limits(&mut scenario, TextLimits { description: 100, evidence: 8, endpoint: 64 }).unwrap();
+            scenario.execute_as("alice", &create_task, &funds);
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: 1,
+                proof_blob_or_ref: "valid_proof".to_string(),
+                zk_proof_hash: "hash".to_string(),
+            };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &submit_proof, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::EvidenceTooLong {});
+        }
+    }
+
+    mod registration_fees {
+        use super::*;
+        use crate::msg::{AreFriendsResponse, RegistrationFeeResponse, RegistrationFeesMsg, UsernameAvailableResponse};
+        use crate::state::RegistrationFeeTier;
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        fn set_fees(scenario: &mut Scenario, tiers: Vec<RegistrationFeeTier>) -> Result<cw_multi_test::AppResponse, ContractError> {
+            let msg = ExecuteMsg::SetRegistrationFees {
+                config: Some(RegistrationFeesMsg {
+                    tiers,
+                    treasury: "treasury".to_string(),
+                }),
+            };
+            scenario.try_execute_as(scenario.owner.clone(), &msg, &[])
+        }
+
+        fn register(scenario: &mut Scenario, wallet: &str, username: &str, funds: &[Coin]) -> Result<cw_multi_test::AppResponse, ContractError> {
+            let msg = ExecuteMsg::RegisterUser {
+                username: username.to_string(),
+                display_name: username.to_string(),
+            };
+            scenario.try_execute_as(wallet, &msg, funds)
+        }
+
+        fn fee_scenario() -> Scenario {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build();
+            for wallet in ["wallet_new", "wallet_other"] {
+                let funds = [scenario.coin(200)];
+                scenario.app.send_tokens(scenario.wallet("alice"), Addr::unchecked(wallet), &funds).unwrap();
+            }
+            let tiers = vec![
+                RegistrationFeeTier { max_length: 4, amount: scenario.coin(100), refundable: false },
+                RegistrationFeeTier { max_length: 8, amount: scenario.coin(50), refundable: true },
+            ];
+            set_fees(&mut scenario, tiers).unwrap();
+            scenario
+        }
+
+        #[test]
+        fn test_fee_tiers_by_username_length() {
+            let mut scenario = fee_scenario();
+
+            let unordered = vec![
+                RegistrationFeeTier { max_length: 8, amount: scenario.coin(50), refundable: true },
+                RegistrationFeeTier { max_length: 4, amount: scenario.coin(100), refundable: false },
+            ];
+            assert_eq!(set_fees(&mut scenario, unordered).unwrap_err(), ContractError::InvalidRegistrationFees {});
+
+            let quote: RegistrationFeeResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetRegistrationFee { username: "Ace".to_string() })
+                .unwrap();
+            assert_eq!(quote.tier.unwrap().amount, scenario.coin(100));
+
+            // Short names pay a fee to the treasury; any surplus is returned
+            let err = register(&mut scenario, "wallet_new", "ace", &[]).unwrap_err();
+            assert_eq!(err, ContractError::InsufficientFunds {});
+            let funds = [scenario.coin(120)];
+            register(&mut scenario, "wallet_new", "ace", &funds).unwrap();
+            assert_eq!(scenario.address_balance("treasury"), 100);
+            assert_eq!(scenario.address_balance("wallet_new"), 100);
+
+            // Names longer than every tier register free
+            register(&mut scenario, "wallet_other", "long_username", &[]).unwrap();
+            assert_eq!(scenario.address_balance("wallet_other"), 200);
+        }
+
+        #[test]
+        fn test_deposit_refunded_on_deactivation() {
+            let mut scenario = fee_scenario();
+            let funds = [scenario.coin(50)];
+            register(&mut scenario, "wallet_new", "carol", &funds).unwrap();
+            assert_eq!(scenario.address_balance("treasury"), 0);
+            let make_friends = ExecuteMsg::SendFriendRequest { to_username: "carol".to_string(), message: None };
+            scenario.execute_as("alice", &make_friends, &[]);
+            let accept = ExecuteMsg::AcceptFriendRequest { from_username: "alice".to_string() };
+            scenario.app.execute_contract(Addr::unchecked("wallet_new"), scenario.contract.addr(), &accept, &[]).unwrap();
+
+            let quote: RegistrationFeeResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetRegistrationFee { username: "carol".to_string() })
+                .unwrap();
+            assert_eq!(quote.deposit_held, Some(scenario.coin(50)));
+
+            // Users with escrowed funds cannot deactivate
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &ExecuteMsg::DeactivateUser {}, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::DeactivationBlocked {});
+
+            scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_new"), scenario.contract.addr(), &ExecuteMsg::DeactivateUser {}, &[])
+                .unwrap();
+            assert_eq!(scenario.address_balance("wallet_new"), 200);
+            let friends: AreFriendsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::AreFriends { username1: "alice".to_string(), username2: "carol".to_string() },
+                )
+                .unwrap();
+            assert!(!friends.are_friends);
+
+            // The username is retired; the wallet can register a new one
+            let available: UsernameAvailableResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::IsUsernameAvailable { username: "carol".to_string() })
+                .unwrap();
+            assert!(!available.available);
+            let funds = [scenario.coin(50)];
+            let err = register(&mut scenario, "wallet_other", "carol", &funds).unwrap_err();
+            assert_eq!(err, ContractError::UsernameAlreadyTaken {});
+            register(&mut scenario, "wallet_new", "carol_two", &funds).unwrap();
+        }
+    }
+
+    mod username_expiry {
+        use super::*;
+        use crate::msg::{RegistrationFeesMsg, UserResponse, UsernameAvailableResponse};
+        use crate::state::{RegistrationFeeTier, UsernameExpiry};
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        const TERM_SECS: u64 = 1000;
+        const GRACE_SECS: u64 = 500;
+
+        fn expiry_scenario() -> Scenario {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            for wallet in ["wallet_carol", "wallet_other"] {
+                let funds = [scenario.coin(100)];
+                scenario.app.send_tokens(scenario.wallet("alice"), Addr::unchecked(wallet), &funds).unwrap();
+            }
+            let set_expiry = ExecuteMsg::SetUsernameExpiry {
+                config: Some(UsernameExpiry { term_secs: TERM_SECS, grace_secs: GRACE_SECS }),
+            };
+            let owner = scenario.owner.clone();
+            scenario.app.execute_contract(owner, scenario.contract.addr(), &set_expiry, &[]).unwrap();
+            scenario
+        }
+
+        fn register(username: &str) -> ExecuteMsg {
+            ExecuteMsg::RegisterUser {
+                username: username.to_string(),
+                display_name: username.to_string(),
+            }
+        }
+
+        fn available(scenario: &Scenario, username: &str) -> bool {
+            let response: UsernameAvailableResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::IsUsernameAvailable { username: username.to_string() })
+                .unwrap();
+            response.available
+        }
+
+        #[test]
+        fn test_expired_username_renews_within_grace() {
+            let mut scenario = expiry_scenario();
+            scenario.try_execute_as("wallet_carol", &register("carol"), &[]).unwrap();
+            let registered_at = scenario.app.block_info().time.seconds();
+            let user: UserResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetUserByUsername { username: "carol".to_string() })
+                .unwrap();
+            assert_eq!(user.user.expires_at, Some(registered_at + TERM_SECS));
+
+            // Users registered before expiry was enabled keep their usernames
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &ExecuteMsg::RenewUsername {}, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::UsernameDoesNotExpire {});
+
+            // Once expired, the owner can only renew
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(TERM_SECS));
+            let send_request = ExecuteMsg::SendFriendRequest { to_username: "bob".to_string(), message: None };
+            let err = scenario.try_execute_as("wallet_carol", &send_request, &[]).unwrap_err();
+            assert_eq!(err, ContractError::UsernameExpired {});
+            assert!(!available(&scenario, "carol"));
+
+            // Renewal after expiry runs a full term from now; the fee tier is charged again
+            let set_fees = ExecuteMsg::SetRegistrationFees {
+                config: Some(RegistrationFeesMsg {
+                    tiers: vec![RegistrationFeeTier { max_length: 8, amount: scenario.coin(30), refundable: false }],
+                    treasury: "treasury".to_string(),
+                }),
+            };
+            let owner = scenario.owner.clone();
+            scenario.app.execute_contract(owner, scenario.contract.addr(), &set_fees, &[]).unwrap();
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(GRACE_SECS - 1));
+            let funds = [scenario.coin(30)];
+            scenario.try_execute_as("wallet_carol", &ExecuteMsg::RenewUsername {}, &funds).unwrap();
+            let now = scenario.app.block_info().time.seconds();
+            let user: UserResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetUserByUsername { username: "carol".to_string() })
+                .unwrap();
+            assert_eq!(user.user.expires_at, Some(now + TERM_SECS));
+            assert_eq!(scenario.app.wrap().query_balance("treasury", &scenario.denom).unwrap().amount.u128(), 30);
+            scenario.try_execute_as("wallet_carol", &send_request, &[]).unwrap();
+        }
+
+        #[test]
+        fn test_username_released_after_grace() {
+            let mut scenario = expiry_scenario();
+            let set_fees = ExecuteMsg::SetRegistrationFees {
+                config: Some(RegistrationFeesMsg {
+                    tiers: vec![RegistrationFeeTier { max_length: 8, amount: scenario.coin(40), refundable: true }],
+                    treasury: "treasury".to_string(),
+                }),
+            };
+            let owner = scenario.owner.clone();
+            scenario.app.execute_contract(owner, scenario.contract.addr(), &set_fees, &[]).unwrap();
+            let funds = [scenario.coin(40)];
+            scenario.try_execute_as("wallet_carol", &register("carol"), &funds).unwrap();
+            scenario.try_execute_as("wallet_other", &register("dave"), &funds).unwrap();
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(TERM_SECS + GRACE_SECS));
+            let err = scenario.try_execute_as("wallet_carol", &ExecuteMsg::RenewUsername {}, &[]).unwrap_err();
+            assert_eq!(err, ContractError::UsernameExpired {});
+
+            // Lookups treat the usernames as released before the reaper runs
+            assert!(available(&scenario, "carol"));
+            let lookup: Result<UserResponse, _> = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetWalletByUsername { username: "carol".to_string() });
+            assert!(lookup.is_err());
+
+            // The reaper releases both usernames and refunds their deposits
+            let res = scenario.try_execute_as("wallet_bob", &ExecuteMsg::ReapExpiredUsernames { limit: None }, &[]).unwrap();
+            assert_eq!(res.events.iter().filter(|event| event.ty == "wasm-username_released").count(), 2);
+            assert_eq!(scenario.app.wrap().query_balance("wallet_carol", &scenario.denom).unwrap().amount.u128(), 100);
+
+            // Anyone can register a released username
+            scenario.try_execute_as("wallet_other", &register("carol"), &funds).unwrap();
+            assert!(!available(&scenario, "carol"));
+        }
+
+        #[test]
+        fn test_username_with_unsettled_records_is_not_released() {
+            let mut scenario = expiry_scenario();
+            scenario.try_execute_as("wallet_carol", &register("carol"), &[]).unwrap();
+            let task_id = scenario.escrowed_task("alice", "carol", 100, TaskStatus::Escrowed);
+            let request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(10),
+                description: "Lunch".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            scenario.try_execute_as("wallet_carol", &request, &[]).unwrap();
+
+            // carol is the worker on an open task, so the username stays hers past grace
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(TERM_SECS + GRACE_SECS));
+            assert!(!available(&scenario, "carol"));
+            let err = scenario.try_execute_as("wallet_other", &register("carol"), &[]).unwrap_err();
+            assert_eq!(err, ContractError::UsernameAlreadyTaken {});
+
+            // Refunding the task still leaves her open payment request
+            let cancel_task = ExecuteMsg::AdminCancelTask { task_id, reason: "Worker expired".to_string() };
+            let owner = scenario.owner.clone();
+            scenario.app.execute_contract(owner, scenario.contract.addr(), &cancel_task, &[]).unwrap();
+            let res = scenario.try_execute_as("wallet_bob", &ExecuteMsg::ReapExpiredUsernames { limit: None }, &[]).unwrap();
+            assert!(!res.events.iter().any(|event| event.ty == "wasm-username_released"));
+            assert!(!available(&scenario, "carol"));
+
+            scenario.try_execute_as("wallet_bob", &ExecuteMsg::RejectPayment { payment_id: 1 }, &[]).unwrap();
+            assert!(available(&scenario, "carol"));
+            scenario.try_execute_as("wallet_other", &register("carol"), &[]).unwrap();
+        }
+    }
+
+    mod query_helpers {
+        use super::*;
+        use crate::testing::ScenarioBuilder;
+
+        #[test]
+        fn test_typed_handle_lookups() {
+            let scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .with_user("wallet_bob", "bob", 0)
+                .with_user("wallet_carol", "carol", 0)
+                .with_friends("alice", "bob")
+                .build();
+            let contract = &scenario.contract;
+
+            let username = contract.get_username_by_wallet::<_, Empty>(&scenario.app, "wallet_alice".to_string()).unwrap();
+            assert_eq!(username.username, "alice");
+            let wallet = contract.get_wallet_by_username::<_, Empty>(&scenario.app, "bob".to_string()).unwrap();
+            assert_eq!(wallet.wallet_address, "wallet_bob");
+            assert!(contract.get_wallet_by_username::<_, Empty>(&scenario.app, "nobody".to_string()).is_err());
+
+            assert!(contract.has_username::<_, Empty>(&scenario.app, "wallet_carol".to_string()).unwrap().has_username);
+            assert!(!contract.has_username::<_, Empty>(&scenario.app, "wallet_dave".to_string()).unwrap().has_username);
+
+            let friends = contract.are_friends::<_, Empty>(&scenario.app, "alice".to_string(), "bob".to_string()).unwrap();
+            assert!(friends.are_friends);
+            let friends = contract.are_friends::<_, Empty>(&scenario.app, "alice".to_string(), "carol".to_string()).unwrap();
+            assert!(!friends.are_friends);
+        }
+    }
+
+    mod wallet_payment_requests {
+        use super::*;
+        use crate::msg::{PaymentResponse, PaymentsResponse};
+        use crate::testing::ScenarioBuilder;
+        use crate::ContractError;
+
+        fn wallet_request(to_wallet: &str, amount: Coin) -> ExecuteMsg {
+            ExecuteMsg::CreateWalletPaymentRequest {
+                to_wallet: to_wallet.to_string(),
+                amount,
+                description: "Consulting invoice".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            }
+        }
+
+        #[test]
+        fn test_request_binds_when_wallet_registers() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let funds = [scenario.coin(500)];
+            scenario.app.send_tokens(scenario.wallet("alice"), Addr::unchecked("wallet_client"), &funds).unwrap();
+
+            // Registered wallets get an ordinary request to their username
+            let msg = wallet_request("wallet_bob", scenario.coin(10));
+            scenario.execute_as("alice", &msg, &[]);
+            let payment: PaymentResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
+                .unwrap();
+            assert_eq!(payment.payment.to_username, "bob");
+            assert_eq!(payment.payment.to_wallet, None);
+
+            let msg = wallet_request("wallet_client", scenario.coin(100));
+            scenario.execute_as("alice", &msg, &[]);
+            scenario.execute_as("alice", &msg, &[]);
+            let waiting: PaymentsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetWalletPaymentRequests { wallet_address: "wallet_client".to_string(), start_after: None },
+                )
+                .unwrap();
+            assert_eq!(waiting.payments.len(), 2);
+
+            // Only the addressed wallet can approve without a username
+            let approve = ExecuteMsg::ApprovePayment { payment_id: 2 };
+            let err = scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_stranger"), scenario.contract.addr(), &approve, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::UserNotRegistered {});
+            let funds = [scenario.coin(100)];
+            scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_client"), scenario.contract.addr(), &approve, &funds)
+                .unwrap();
+            let alice_balance = scenario.app.wrap().query_balance(scenario.wallet("alice"), &scenario.denom).unwrap();
+            assert_eq!(alice_balance.amount.u128(), 600);
+
+            // Registering binds both requests to the new username
+            let register = ExecuteMsg::RegisterUser {
+                username: "client".to_string(),
+                display_name: "Client".to_string(),
+            };
+            let res = scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_client"), scenario.contract.addr(), &register, &[])
+                .unwrap();
+            assert!(res.events.iter().flat_map(|event| event.attributes.iter())
+                .any(|attr| attr.key == "payment_requests_bound" && attr.value == "2"));
+            let history: PaymentsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    scenario.contract.addr(),
+                    &QueryMsg::GetPaymentHistory { username: "client".to_string(), viewer: Some("client".to_string()), start_after: None },
+                )
+                .unwrap();
+            assert_eq!(history.payments.len(), 2);
+            assert!(history.payments.iter().all(|payment| payment.to_username == "client" && payment.to_wallet.is_none()));
+
+            scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_client"), scenario.contract.addr(), &ExecuteMsg::ApprovePayment { payment_id: 3 }, &funds)
+                .unwrap();
+        }
+
+        #[test]
+        fn test_unregistered_wallet_rejects_and_is_reminded() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let msg = wallet_request("wallet_client", scenario.coin(100));
+            scenario.execute_as("alice", &msg, &[]);
+
+            let res = scenario.execute_as("alice", &ExecuteMsg::RemindPaymentRequest { payment_id: 1 }, &[]);
+            assert!(res.events.iter().flat_map(|event| event.attributes.iter())
+                .any(|attr| attr.key == "to" && attr.value == "wallet_client"));
+
+            // Only the addressed wallet can reject without a username
+            let reject = ExecuteMsg::RejectPayment { payment_id: 1 };
+            let err = scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_stranger"), scenario.contract.addr(), &reject, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::UserNotRegistered {});
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &reject, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::PaymentNotAuthorized {});
+            scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_client"), scenario.contract.addr(), &reject, &[])
+                .unwrap();
+            let payment: PaymentResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetPaymentById { payment_id: 1, viewer: None })
+                .unwrap();
+            assert_eq!(payment.payment.status, PaymentStatus::Rejected);
+        }
+
+        #[test]
+        fn test_wallet_request_cap_is_per_requester() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let msg = wallet_request("wallet_client", scenario.coin(100));
+            for _ in 0..20 {
+                scenario.execute_as("alice", &msg, &[]);
+            }
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TooManyWalletPaymentRequests {});
+
+            // alice filling her cap does not stop bob from billing the same wallet
+            scenario.execute_as("bob", &msg, &[]);
+
+            // Settled requests no longer count
+            scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_client"), scenario.contract.addr(), &ExecuteMsg::RejectPayment { payment_id: 1 }, &[])
+                .unwrap();
+            scenario.execute_as("alice", &msg, &[]);
+        }
+    }
+
+    mod gift_codes {
+        use super::*;
+        use crate::helpers::hash_data;
+        use crate::msg::GiftCodeResponse;
+        use crate::state::GiftCodeStatus;
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        const EXPIRY_SECS: u64 = 86400;
+
+        fn create_gift_code(scenario: &mut Scenario, code: &str, amount: u128) -> Result<cw_multi_test::AppResponse, ContractError> {
+            let msg = ExecuteMsg::CreateGiftCode {
+                amount: scenario.coin(amount),
+                code_hash: hash_data(code),
+                expires_at: scenario.app.block_info().time.seconds() + EXPIRY_SECS,
+            };
+            let funds = [scenario.coin(amount)];
+            scenario.try_execute_as(scenario.wallet("alice"), &msg, &funds)
+        }
+
+        fn claim(scenario: &mut Scenario, wallet: &str, code: &str) -> Result<cw_multi_test::AppResponse, ContractError> {
+            let msg = ExecuteMsg::ClaimGiftCode { code: code.to_string() };
+            scenario.try_execute_as(wallet, &msg, &[])
+        }
+
+        #[test]
+        fn test_claim_with_code() {
+            let mut scenario = ScenarioBuilder::new().with_user("wallet_alice", "alice", 1000).build();
+            create_gift_code(&mut scenario, "happy-birthday", 250).unwrap();
+            let err = create_gift_code(&mut scenario, "happy-birthday", 10).unwrap_err();
+            assert_eq!(err, ContractError::GiftCodeHashInUse {});
+
+            // Anyone holding the code can claim, without a username
+            assert_eq!(claim(&mut scenario, "wallet_friend", "happy-bday").unwrap_err(), ContractError::GiftCodeNotFound {});
+            claim(&mut scenario, "wallet_friend", "happy-birthday").unwrap();
+            let balance = scenario.app.wrap().query_balance("wallet_friend", &scenario.denom).unwrap();
+            assert_eq!(balance.amount.u128(), 250);
+            assert_eq!(claim(&mut scenario, "wallet_other", "happy-birthday").unwrap_err(), ContractError::GiftCodeNotFound {});
+
+            let gift: GiftCodeResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetGiftCode { gift_id: 1 })
+                .unwrap();
+            assert_eq!(gift.gift_code.status, GiftCodeStatus::Claimed);
+            assert_eq!(gift.gift_code.claimed_by, Some(Addr::unchecked("wallet_friend")));
+        }
+
+        #[test]
+        fn test_refund_after_expiry() {
+            let mut scenario = ScenarioBuilder::new().with_user("wallet_alice", "alice", 1000).build();
+            create_gift_code(&mut scenario, "airdrop-42", 300).unwrap();
+
+            let refund = ExecuteMsg::RefundGiftCode { gift_id: 1 };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &refund, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::GiftCodeNotExpired {});
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(EXPIRY_SECS));
+            assert_eq!(claim(&mut scenario, "wallet_friend", "airdrop-42").unwrap_err(), ContractError::GiftCodeExpired {});
+            scenario.execute_as("alice", &refund, &[]);
+            let balance = scenario.app.wrap().query_balance(scenario.wallet("alice"), &scenario.denom).unwrap();
+            assert_eq!(balance.amount.u128(), 1000);
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &refund, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::GiftCodeNotActive {});
+        }
+    }
+
+    mod htlc {
+        use super::*;
+        use crate::helpers::hash_preimage;
+        use crate::msg::HtlcResponse;
+        use crate::state::HtlcStatus;
+        use crate::testing::{attribute, Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        const TIMELOCK_SECS: u64 = 7200;
+        const PREIMAGE: &str = "616263"; // "abc"
+        const HASHLOCK: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+        fn create_htlc(scenario: &mut Scenario) -> u64 {
+            let msg = ExecuteMsg::CreateHtlc {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(400),
+                hashlock: HASHLOCK.to_uppercase(),
+                timelock: scenario.app.block_info().time.seconds() + TIMELOCK_SECS,
+            };
+            let funds = [scenario.coin(400)];
+            let res = scenario.execute_as("alice", &msg, &funds);
+            attribute(&res, "htlc_id").and_then(|value| value.parse().ok()).unwrap()
+        }
+
+        #[test]
+        fn test_preimage_hashes_bytes() {
+            assert_eq!(hash_preimage(PREIMAGE).as_deref(), Some(HASHLOCK));
+            assert_eq!(hash_preimage("abc"), None);
+            assert_eq!(hash_preimage("zz"), None);
+        }
+
+        #[test]
+        fn test_claim_with_preimage() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let htlc_id = create_htlc(&mut scenario);
+
+            let wrong = ExecuteMsg::ClaimHtlc { htlc_id, preimage: "616264".to_string() };
+            assert_eq!(scenario.try_execute_as(scenario.wallet("bob"), &wrong, &[]).unwrap_err(), ContractError::InvalidPreimage {});
+            let claim = ExecuteMsg::ClaimHtlc { htlc_id, preimage: PREIMAGE.to_string() };
+            assert_eq!(scenario.try_execute_as(scenario.wallet("alice"), &claim, &[]).unwrap_err(), ContractError::NotAuthorized {});
+
+            let res = scenario.execute_as("bob", &claim, &[]);
+            assert!(res.events.iter().any(|event| event
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "preimage" && attr.value == PREIMAGE)));
+            let balance = scenario.app.wrap().query_balance(scenario.wallet("bob"), &scenario.denom).unwrap();
+            assert_eq!(balance.amount.u128(), 400);
+            assert_eq!(scenario.try_execute_as(scenario.wallet("bob"), &claim, &[]).unwrap_err(), ContractError::HtlcNotLocked {});
+
+            let htlc: HtlcResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetHtlc { htlc_id })
+                .unwrap();
+            assert_eq!(htlc.htlc.status, HtlcStatus::Claimed);
+            assert_eq!(htlc.htlc.hashlock, HASHLOCK);
+            assert_eq!(htlc.htlc.preimage.as_deref(), Some(PREIMAGE));
+        }
+
+        #[test]
+        fn test_refund_after_timelock() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let htlc_id = create_htlc(&mut scenario);
+
+            let refund = ExecuteMsg::RefundHtlc { htlc_id };
+            assert_eq!(scenario.try_execute_as(scenario.wallet("alice"), &refund, &[]).unwrap_err(), ContractError::HtlcNotExpired {});
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(TIMELOCK_SECS));
+            let claim = ExecuteMsg::ClaimHtlc { htlc_id, preimage: PREIMAGE.to_string() };
+            assert_eq!(scenario.try_execute_as(scenario.wallet("bob"), &claim, &[]).unwrap_err(), ContractError::HtlcExpired {});
+            assert_eq!(scenario.try_execute_as(scenario.wallet("bob"), &refund, &[]).unwrap_err(), ContractError::NotAuthorized {});
+            scenario.execute_as("alice", &refund, &[]);
+            let balance = scenario.app.wrap().query_balance(scenario.wallet("alice"), &scenario.denom).unwrap();
+            assert_eq!(balance.amount.u128(), 1000);
+        }
+    }
+
+    mod task_dependencies {
+        use super::*;
+        use crate::state::{ProofType, TaskStatus};
+        use crate::testing::{Scenario, ScenarioBuilder, SCENARIO_REVIEW_WINDOW_SECS};
+        use crate::ContractError;
+
+        const DURATION_SECS: u64 = 86400;
+
+        fn scenario() -> Scenario {
+            ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_user("wallet_carol", "carol", 1000)
+                .with_friends("alice", "bob")
+                .with_friends("carol", "bob")
+                .build()
+        }
+
+        fn create_task_msg(scenario: &Scenario, proof_type: ProofType, depends_on: u64) -> ExecuteMsg {
+            ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(300),
+                description: "Second milestone".to_string(),
+                proof_type,
+                deadline_ts: scenario.app.block_info().time.seconds() + DURATION_SECS,
+                review_window_secs: None,
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: Some(depends_on),
+                payout_swap: None,
+                release_delay_secs: None,
+            }
+        }
+
+        #[test]
+        fn test_dependent_task_starts_when_dependency_released() {
+            let mut scenario = scenario();
+            let first = scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+            let msg = create_task_msg(&scenario, ProofType::Hybrid, first);
+            let funds = [scenario.coin(300)];
+            scenario.execute_as("alice", &msg, &funds);
+            let second = first + 1;
+            let created = scenario.task(second);
+            assert_eq!(created.status, TaskStatus::Blocked);
+
+            // The worker cannot start on it yet
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: second,
+                proof_blob_or_ref: "valid_scenario_proof".to_string(),
+                zk_proof_hash: "scenario_proof_hash".to_string(),
+            };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &submit_proof, &[])
+                .unwrap_err();
+            assert!(err.downcast::<ContractError>().is_ok());
+
+            let first_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: first,
+                proof_blob_or_ref: "valid_scenario_proof".to_string(),
+                zk_proof_hash: "scenario_proof_hash".to_string(),
+            };
+            scenario.execute_as("bob", &first_proof, &[]);
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(SCENARIO_REVIEW_WINDOW_SECS + 1));
+            scenario.execute_as("bob", &ExecuteMsg::ReleaseIfWindowElapsed { task_id: first }, &[]);
+
+            // Activated with its deadline pushed back by the time spent blocked
+            let activated = scenario.task(second);
+            assert_eq!(activated.status, TaskStatus::Escrowed);
+            assert_eq!(activated.deadline_ts, created.deadline_ts + SCENARIO_REVIEW_WINDOW_SECS + 1);
+            scenario.execute_as("bob", &submit_proof, &[]);
+            assert_eq!(scenario.task(second).status, TaskStatus::PendingRelease);
+        }
+
+        #[test]
+        fn test_dependent_task_refundable_when_dependency_refunded() {
+            let mut scenario = scenario();
+            let first = scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+            let msg = create_task_msg(&scenario, ProofType::Hybrid, first);
+            let funds = [scenario.coin(300)];
+            scenario.execute_as("alice", &msg, &funds);
+            let second = first + 1;
+            scenario.execute_as("bob", &ExecuteMsg::AcceptTask { task_id: second }, &[]);
+
+            // Never started, so the payer can take the escrow back once the dependency is refunded
+            scenario.execute_as("alice", &ExecuteMsg::CancelTask { task_id: first }, &[]);
+            assert_eq!(scenario.task(first).status, TaskStatus::Refunded);
+            scenario.execute_as("alice", &ExecuteMsg::CancelTask { task_id: second }, &[]);
+            assert_eq!(scenario.task(second).status, TaskStatus::Refunded);
+            let balance = scenario.app.wrap().query_balance(scenario.wallet("alice"), &scenario.denom).unwrap();
+            assert_eq!(balance.amount.u128(), 1000);
+        }
+
+        #[test]
+        fn test_invalid_dependencies() {
+            let mut scenario = scenario();
+            let first = scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+
+            // Another payer's task, and soft tasks funded at approval, cannot be chained
+            let msg = create_task_msg(&scenario, ProofType::Hybrid, first);
+            let funds = [scenario.coin(300)];
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("carol"), scenario.contract.addr(), &msg, &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidTaskDependency {});
+            let msg = create_task_msg(&scenario, ProofType::Soft, first);
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidTaskDependency {});
+        }
+    }
+
+    mod task_batches {
+        use super::*;
+        use crate::msg::{TaskBatchResponse, TaskTemplate};
+        use crate::state::{ProofType, TaskStatus};
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        fn scenario() -> Scenario {
+            ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_user("wallet_carol", "carol", 0)
+                .with_user("wallet_dave", "dave", 0)
+                .with_friends("alice", "bob")
+                .with_friends("alice", "carol")
+                .with_friends("alice", "dave")
+                .build()
+        }
+
+        fn batch_msg(scenario: &Scenario, workers: &[&str]) -> ExecuteMsg {
+            ExecuteMsg::CreateTaskBatch {
+                template: TaskTemplate {
+                    amount: scenario.coin(100),
+                    description: "Hand out flyers".to_string(),
+                    proof_type: ProofType::Hybrid,
+                    deadline_ts: scenario.app.block_info().time.seconds() + 86400,
+                    review_window_secs: None,
+                    endpoint: "https://api.example.com".to_string(),
+                    category: None,
+                    tags: vec![],
+                    escrow_upfront: false,
+                },
+                workers: workers.iter().map(|worker| worker.to_string()).collect(),
+            }
+        }
+
+        fn batch(scenario: &Scenario, batch_id: u64) -> TaskBatchResponse {
+            scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetTaskBatch { batch_id })
+                .unwrap()
+        }
+
+        #[test]
+        fn test_batch_creates_one_escrowed_task_per_worker() {
+            let mut scenario = scenario();
+            let msg = batch_msg(&scenario, &["bob", "carol", "dave"]);
+            let funds = [scenario.coin(350)];
+            scenario.execute_as("alice", &msg, &funds);
+            assert_eq!(scenario.balance("alice"), 700);
+
+            let response = batch(&scenario, 1);
+            assert_eq!(response.batch.payer, "alice");
+            assert_eq!(response.tasks.len(), 3);
+            let workers: Vec<&str> = response.tasks.iter().map(|task| task.worker.as_str()).collect();
+            assert_eq!(workers, ["bob", "carol", "dave"]);
+            assert!(response.tasks.iter().all(|task| task.batch_id == Some(1) && task.status == TaskStatus::Escrowed));
+        }
+
+        #[test]
+        fn test_cancel_batch_keeps_claimed_tasks() {
+            let mut scenario = scenario();
+            let msg = batch_msg(&scenario, &["bob", "carol", "dave"]);
+            let funds = [scenario.coin(300)];
+            scenario.execute_as("alice", &msg, &funds);
+            let task_ids = batch(&scenario, 1).batch.task_ids;
+
+            scenario.execute_as("bob", &ExecuteMsg::AcceptTask { task_id: task_ids[0] }, &[]);
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: task_ids[1],
+                proof_blob_or_ref: "valid_batch_proof".to_string(),
+                zk_proof_hash: "batch_proof_hash".to_string(),
+            };
+            scenario.execute_as("carol", &submit_proof, &[]);
+
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &ExecuteMsg::CancelTaskBatch { batch_id: 1 }, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TaskNotAuthorized {});
+            scenario.execute_as("alice", &ExecuteMsg::CancelTaskBatch { batch_id: 1 }, &[]);
+
+            let statuses: Vec<TaskStatus> = batch(&scenario, 1).tasks.into_iter().map(|task| task.status).collect();
+            assert_eq!(statuses, [TaskStatus::Escrowed, TaskStatus::PendingRelease, TaskStatus::Refunded]);
+            assert_eq!(scenario.balance("alice"), 800);
+        }
+
+        #[test]
+        fn test_archived_batch_tasks_are_reported_missing() {
+            let mut scenario = scenario();
+            let msg = batch_msg(&scenario, &["bob", "carol", "dave"]);
+            let funds = [scenario.coin(300)];
+            scenario.execute_as("alice", &msg, &funds);
+            let task_ids = batch(&scenario, 1).batch.task_ids;
+
+            // bob's declined task settles and is archived once the retention period passes
+            scenario.execute_as("bob", &ExecuteMsg::DeclineTask { task_id: task_ids[0] }, &[]);
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(90 * 86400 + 1));
+            let archive = ExecuteMsg::ArchiveRecords { up_to_ts: scenario.app.block_info().time.seconds(), limit: None };
+            let owner = scenario.owner.clone();
+            scenario.app.execute_contract(owner, scenario.contract.addr(), &archive, &[]).unwrap();
+
+            let response = batch(&scenario, 1);
+            assert_eq!(response.missing, vec![task_ids[0]]);
+            assert_eq!(response.tasks.len(), 2);
+
+            let res = scenario.execute_as("alice", &ExecuteMsg::CancelTaskBatch { batch_id: 1 }, &[]);
+            let attributes: Vec<(String, String)> = res.events.iter()
+                .flat_map(|event| event.attributes.iter())
+                .map(|attr| (attr.key.clone(), attr.value.clone()))
+                .collect();
+            assert!(attributes.contains(&("cancelled".to_string(), "2".to_string())));
+            assert!(attributes.contains(&("kept".to_string(), "0".to_string())));
+            assert!(attributes.contains(&("not_found".to_string(), task_ids[0].to_string())));
+            assert_eq!(scenario.balance("alice"), 1000);
+        }
+
+        #[test]
+        fn test_invalid_batches() {
+            let mut scenario = scenario();
+            let msg = batch_msg(&scenario, &["bob", "Bob"]);
+            let funds = [scenario.coin(200)];
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &msg, &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidTaskBatch {});
+
+            // The attached funds must cover every worker's task
+            let msg = batch_msg(&scenario, &["bob", "carol"]);
+            let funds = [scenario.coin(150)];
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &msg, &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InsufficientFunds {});
+        }
+    }
+
+    mod feature_flags {
+        use super::*;
+        use crate::msg::FeatureFlagsResponse;
+        use crate::state::{FeatureFlags, PaymentType, ProofType, TaskStatus};
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        fn set_flags(scenario: &mut Scenario, payment_types: Vec<PaymentType>, proof_types: Vec<ProofType>) {
+            let msg = ExecuteMsg::SetFeatureFlags {
+                disabled_payment_types: payment_types,
+                disabled_proof_types: proof_types,
+            };
+            scenario
+                .app
+                .execute_contract(scenario.owner.clone(), scenario.contract.addr(), &msg, &[])
+                .unwrap();
+        }
+
+        fn submit_proof_msg(task_id: u64) -> ExecuteMsg {
+            ExecuteMsg::SubmitZkTlsProof {
+                task_id,
+                proof_blob_or_ref: "valid_flag_proof".to_string(),
+                zk_proof_hash: "flag_proof_hash".to_string(),
+            }
+        }
+
+        #[test]
+        fn test_disabled_flows_are_rejected() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build();
+            let open_task = scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+
+            let msg = ExecuteMsg::SetFeatureFlags {
+                disabled_payment_types: vec![],
+                disabled_proof_types: vec![ProofType::Hybrid],
+            };
+            let result = scenario.app.execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &msg, &[]);
+            assert!(result.is_err());
+            set_flags(&mut scenario, vec![PaymentType::DirectPayment], vec![ProofType::Hybrid]);
+            let flags: FeatureFlagsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetFeatureFlags {})
+                .unwrap();
+            assert_eq!(flags.flags.disabled_proof_types, vec![ProofType::Hybrid]);
+
+            let send = ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(50),
+                description: "Lunch".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            };
+            let funds = [scenario.coin(50)];
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &send, &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::PaymentTypeDisabled {});
+
+            // Tasks already open cannot take proofs of a disabled type either
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &submit_proof_msg(open_task), &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::ProofTypeDisabled {});
+
+            set_flags(&mut scenario, vec![], vec![]);
+            let flags: FeatureFlagsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetFeatureFlags {})
+                .unwrap();
+            assert_eq!(flags.flags, FeatureFlags::default());
+            scenario.execute_as("bob", &submit_proof_msg(open_task), &[]);
+            scenario.execute_as("alice", &send, &funds);
+        }
+    }
+
+    mod pagination {
+        use super::*;
+        use crate::msg::{FriendRequestsResponse, PaymentsResponse, TasksResponse, UsersResponse};
+        use crate::state::{ProofType, TaskStatus};
+        use crate::testing::{Scenario, ScenarioBuilder};
+
+        fn payment_history(scenario: &Scenario, viewer: Option<&str>, start_after: Option<u64>) -> PaymentsResponse {
+            let msg = QueryMsg::GetPaymentHistory {
+                username: "alice".to_string(),
+                viewer: viewer.map(str::to_string),
+                start_after,
+            };
+            scenario.app.wrap().query_wasm_smart(scenario.contract.addr(), &msg).unwrap()
+        }
+
+        fn pending_requests(scenario: &Scenario, start_after: Option<&str>) -> FriendRequestsResponse {
+            let msg = QueryMsg::GetPendingRequests {
+                username: "bob".to_string(),
+                start_after: start_after.map(str::to_string),
+            };
+            scenario.app.wrap().query_wasm_smart(scenario.contract.addr(), &msg).unwrap()
+        }
+
+        #[test]
+        fn test_payment_history_resumes_from_cursor() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            for i in 0..101 {
+                let send = ExecuteMsg::SendDirectPayment {
+                    to_username: "bob".to_string(),
+                    amount: scenario.coin(1),
+                    description: format!("Payment {}", i),
+                    proof_type: ProofType::None,
+                    category: None,
+                    tags: vec![],
+                    visibility: None,
+                    external_ref: None,
+                    idempotency_key: None,
+                    release_delay_secs: None,
+                };
+                let funds = [scenario.coin(1)];
+                scenario.execute_as("alice", &send, &funds);
+            }
+
+            let first = payment_history(&scenario, Some("alice"), None);
+            assert_eq!(first.payments.len(), 100);
+            assert!(first.truncated);
+            assert_eq!(first.total, Some(101));
+            let cursor = first.next_start_after.unwrap();
+            assert_eq!(cursor, first.payments[99].id);
+
+            let second = payment_history(&scenario, Some("alice"), Some(cursor));
+            assert_eq!(second.payments.len(), 1);
+            assert!(!second.truncated);
+            assert_eq!(second.next_start_after, None);
+
+            // Other viewers may be shown fewer payments than the user holds
+            assert_eq!(payment_history(&scenario, None, None).total, None);
+        }
+
+        #[test]
+        fn test_pending_request_total_follows_replies() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_bob", "bob", 0)
+                .with_user("wallet_carol", "carol", 0)
+                .with_user("wallet_dave", "dave", 0)
+                .with_user("wallet_erin", "erin", 0)
+                .build();
+            for sender in ["carol", "dave", "erin"] {
+                let request = ExecuteMsg::SendFriendRequest { to_username: "bob".to_string(), message: None };
+                scenario.execute_as(sender, &request, &[]);
+            }
+
+            let all = pending_requests(&scenario, None);
+            assert_eq!(all.requests.len(), 3);
+            assert_eq!(all.total, Some(3));
+            assert_eq!(all.next_start_after, None);
+            let rest = pending_requests(&scenario, Some("carol"));
+            let senders: Vec<String> = rest.requests.into_iter().map(|r| r.from_username).collect();
+            assert_eq!(senders, vec!["dave".to_string(), "erin".to_string()]);
+
+            scenario.execute_as("bob", &ExecuteMsg::DeclineFriendRequest { from_username: "dave".to_string() }, &[]);
+            assert_eq!(pending_requests(&scenario, None).total, Some(2));
+            scenario.execute_as("bob", &ExecuteMsg::AcceptFriendRequest { from_username: "erin".to_string() }, &[]);
+            let remaining = pending_requests(&scenario, None);
+            assert_eq!(remaining.total, Some(1));
+            assert_eq!(remaining.requests[0].from_username, "carol");
+        }
+
+        #[test]
+        fn test_task_and_user_totals() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_user("wallet_carol", "carol", 0)
+                .with_friends("alice", "bob")
+                .build();
+            let first = scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Released);
+
+            let msg = QueryMsg::GetTaskHistory { username: "bob".to_string(), start_after: Some(first) };
+            let history: TasksResponse = scenario.app.wrap().query_wasm_smart(scenario.contract.addr(), &msg).unwrap();
+            assert_eq!(history.tasks.len(), 1);
+            assert_eq!(history.total, Some(2));
+
+            let search = |query: &str| -> UsersResponse {
+                let msg = QueryMsg::SearchUsers { query: query.to_string(), start_after: None };
+                scenario.app.wrap().query_wasm_smart(scenario.contract.addr(), &msg).unwrap()
+            };
+            assert_eq!(search("").total, Some(3));
+            assert_eq!(search("car").total, None);
+        }
+    }
+
+    mod contract_info {
+        use super::*;
+        use crate::msg::ContractInfoResponse;
+        use crate::state::{PaymentType, TaskStatus};
+        use crate::testing::ScenarioBuilder;
+
+        #[test]
+        fn test_contract_info_summarizes_contract() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build();
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+            let flags = ExecuteMsg::SetFeatureFlags {
+                disabled_payment_types: vec![PaymentType::PaymentRequest],
+                disabled_proof_types: vec![],
+            };
+            scenario
+                .app
+                .execute_contract(scenario.owner.clone(), scenario.contract.addr(), &flags, &[])
+                .unwrap();
+
+            let info: ContractInfoResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetContractInfo {})
+                .unwrap();
+            assert_eq!(info.contract, "crates.io:social-payment-contract");
+            assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+            assert_eq!(info.owner, scenario.owner);
+            assert_eq!(info.feature_flags.disabled_payment_types, vec![PaymentType::PaymentRequest]);
+            assert_eq!(info.registration_fees, None);
+            assert_eq!(info.users, 2);
+            assert_eq!(info.payments, 0);
+            assert_eq!(info.tasks, 1);
+        }
+    }
+
+    mod policy_contract {
+        use super::*;
+        use crate::msg::{PolicyCheckResponse, PolicyContractResponse, PolicyQueryMsg};
+        use crate::state::ProofType;
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+        use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+
+        // Policy denying any execute signed by or paying out to mallory's wallet
+        fn policy_template() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: Deps, _env: Env, msg: PolicyQueryMsg| -> StdResult<Binary> {
+                    let PolicyQueryMsg::CheckTransfer { sender, transfers, .. } = msg;
+                    let sanctioned = sender == "wallet_mallory"
+                        || transfers.iter().any(|transfer| transfer.recipient == "wallet_mallory");
+                    to_json_binary(&PolicyCheckResponse { allowed: !sanctioned })
+                },
+            );
+            Box::new(contract)
+        }
+
+        fn set_policy(scenario: &mut Scenario, policy: Option<String>) {
+            let msg = ExecuteMsg::SetPolicyContract { policy };
+            scenario
+                .app
+                .execute_contract(scenario.owner.clone(), scenario.contract.addr(), &msg, &[])
+                .unwrap();
+        }
+
+        fn send_msg(to_username: &str, scenario: &Scenario) -> ExecuteMsg {
+            ExecuteMsg::SendDirectPayment {
+                to_username: to_username.to_string(),
+                amount: scenario.coin(10),
+                description: "Lunch".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs: None,
+            }
+        }
+
+        #[test]
+        fn test_policy_contract_gates_fund_movements() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_user("wallet_mallory", "mallory", 1_000)
+                .build();
+            let code_id = scenario.app.store_code(policy_template());
+            let policy = scenario
+                .app
+                .instantiate_contract(code_id, scenario.owner.clone(), &Empty {}, &[], "policy", None)
+                .unwrap();
+            set_policy(&mut scenario, Some(policy.to_string()));
+            let configured: PolicyContractResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetPolicyContract {})
+                .unwrap();
+            assert_eq!(configured.policy, Some(policy));
+
+            let funds = [scenario.coin(10)];
+            scenario.execute_as("alice", &send_msg("bob", &scenario), &funds);
+
+            // Sanctioned senders cannot pay and sanctioned recipients cannot be paid
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("mallory"), scenario.contract.addr(), &send_msg("bob", &scenario), &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::PolicyDenied {});
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &send_msg("mallory", &scenario), &funds)
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::PolicyDenied {});
+
+            // Executes that move no funds are not checked
+            let request = ExecuteMsg::SendFriendRequest { to_username: "alice".to_string(), message: None };
+            scenario.execute_as("mallory", &request, &[]);
+
+            set_policy(&mut scenario, None);
+            scenario.execute_as("mallory", &send_msg("bob", &scenario), &funds);
+        }
+    }
+
+    mod payout_swaps {
+        use super::*;
+        use crate::msg::{DexRouterExecuteMsg, DexRouterResponse, OraclePriceResponse, OracleQueryMsg, PolicyCheckResponse, PolicyQueryMsg};
+        use crate::ContractError;
+        use crate::state::{PayoutSwap, ProofType};
+        use crate::testing::{attribute, Scenario, ScenarioBuilder, SCENARIO_TASK_DURATION_SECS};
+        use cosmwasm_std::{to_json_binary, BankMsg, Binary, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult};
+
+        // Router paying out min_out of the asked denom, or failing when asked for more than 1000
+        fn router_template() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, msg: DexRouterExecuteMsg| -> StdResult<Response> {
+                    let DexRouterExecuteMsg::Swap { ask_denom, min_out, to } = msg;
+                    if min_out > Uint128::new(1000) {
+                        return Err(StdError::generic_err("slippage exceeded"));
+                    }
+                    Ok(Response::new().add_message(BankMsg::Send {
+                        to_address: to,
+                        amount: vec![Coin { denom: ask_denom, amount: min_out }],
+                    }))
+                },
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: Deps, _env: Env, _msg: Empty| -> StdResult<Binary> {
+                    Err(StdError::generic_err("no queries"))
+                },
+            );
+            Box::new(contract)
+        }
+
+        // Oracle pricing a USD at 1000 uxion and 2000 uusdc
+        fn oracle_template() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: Deps, env: Env, msg: OracleQueryMsg| -> StdResult<Binary> {
+                    let OracleQueryMsg::Price { denom, .. } = msg;
+                    let price = if denom == "uusdc" { 2000u128 } else { 1000u128 };
+                    to_json_binary(&OraclePriceResponse {
+                        price: Decimal::from_atomics(price, 0).unwrap(),
+                        publish_time: env.block.time.seconds(),
+                    })
+                },
+            );
+            Box::new(contract)
+        }
+
+        fn setup_oracle(scenario: &mut Scenario) {
+            let code_id = scenario.app.store_code(oracle_template());
+            let oracle = scenario
+                .app
+                .instantiate_contract(code_id, scenario.owner.clone(), &Empty {}, &[], "oracle", None)
+                .unwrap();
+            let set_oracle = ExecuteMsg::SetPriceOracle { oracle: Some(oracle.to_string()) };
+            scenario
+                .app
+                .execute_contract(scenario.owner.clone(), scenario.contract.addr(), &set_oracle, &[])
+                .unwrap();
+        }
+
+        fn prefer_usdc(payout_slippage_bps: Option<u16>) -> ExecuteMsg {
+            ExecuteMsg::UpdateUserProfile {
+                display_name: None,
+                profile_picture: None,
+                require_friendship_for_requests: None,
+                default_visibility: None,
+                bio: None,
+                links: None,
+                country: None,
+                timezone: None,
+                preferred_denom: Some("uusdc".to_string()),
+                payout_slippage_bps,
+            }
+        }
+
+        fn setup() -> Scenario {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build();
+            let code_id = scenario.app.store_code(router_template());
+            let router = scenario
+                .app
+                .instantiate_contract(code_id, scenario.owner.clone(), &Empty {}, &[], "router", None)
+                .unwrap();
+            scenario
+                .app
+                .init_modules(|app_router, _, storage| {
+                    app_router.bank.init_balance(storage, &router, vec![Coin::new(10_000, "uusdc")])
+                })
+                .unwrap();
+            let set_router = ExecuteMsg::SetDexRouter { router: Some(router.to_string()) };
+            scenario
+                .app
+                .execute_contract(scenario.owner.clone(), scenario.contract.addr(), &set_router, &[])
+                .unwrap();
+            let configured: DexRouterResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetDexRouter {})
+                .unwrap();
+            assert_eq!(configured.router, Some(router));
+            scenario
+        }
+
+        // Create a zkTLS task paying 100, with a payout swap if `min_out` is set; the valid proof releases it at once
+        fn release_swapped_task(scenario: &mut Scenario, min_out: Option<u128>) -> cw_multi_test::AppResponse {
+            let task_id = create_swapped_task(scenario, min_out);
+            scenario.execute_as("bob", &submit_proof(task_id), &[])
+        }
+
+        fn create_swapped_task(scenario: &mut Scenario, min_out: Option<u128>) -> u64 {
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                description: "Design a logo".to_string(),
+                proof_type: ProofType::ZkTLS,
+                deadline_ts: scenario.app.block_info().time.seconds() + SCENARIO_TASK_DURATION_SECS,
+                review_window_secs: None,
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: min_out.map(|min_out| PayoutSwap { ask_denom: "uusdc".to_string(), min_out: Uint128::new(min_out) }),
+                release_delay_secs: None,
+            };
+            let funds = [scenario.coin(100)];
+            let res = scenario.execute_as("alice", &create_task, &funds);
+            attribute(&res, "task_id").and_then(|value| value.parse().ok()).unwrap()
+        }
+
+        fn submit_proof(task_id: u64) -> ExecuteMsg {
+            ExecuteMsg::SubmitZkTlsProof {
+                task_id,
+                proof_blob_or_ref: "valid_proof".to_string(),
+                zk_proof_hash: "proof_hash".to_string(),
+            }
+        }
+
+        // Policy denying payouts to bob's wallet; with FALLBACK_ONLY, only a failed swap's fallback payout
+        fn policy_template<const FALLBACK_ONLY: bool>() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                    Ok(Response::new())
+                },
+                |_deps: Deps, _env: Env, msg: PolicyQueryMsg| -> StdResult<Binary> {
+                    let PolicyQueryMsg::CheckTransfer { action, transfers, .. } = msg;
+                    let to_bob = transfers.iter().any(|transfer| transfer.recipient == "wallet_bob");
+                    let checked = !FALLBACK_ONLY || action == "payout_swap_failed";
+                    to_json_binary(&PolicyCheckResponse { allowed: !(to_bob && checked) })
+                },
+            );
+            Box::new(contract)
+        }
+
+        fn setup_policy(scenario: &mut Scenario, template: Box<dyn Contract<Empty>>) {
+            let code_id = scenario.app.store_code(template);
+            let policy = scenario
+                .app
+                .instantiate_contract(code_id, scenario.owner.clone(), &Empty {}, &[], "policy", None)
+                .unwrap();
+            let set_policy = ExecuteMsg::SetPolicyContract { policy: Some(policy.to_string()) };
+            scenario
+                .app
+                .execute_contract(scenario.owner.clone(), scenario.contract.addr(), &set_policy, &[])
+                .unwrap();
+        }
+
+        fn balance(scenario: &Scenario, denom: &str) -> u128 {
+            scenario.app.wrap().query_balance(scenario.wallet("bob"), denom).unwrap().amount.u128()
+        }
+
+        #[test]
+        fn test_policy_checks_swap_routed_payouts() {
+            let mut scenario = setup();
+            setup_policy(&mut scenario, policy_template::<false>());
+
+            // The router pays bob, so the swap is checked as a transfer to him
+            let task_id = create_swapped_task(&mut scenario, Some(90));
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &submit_proof(task_id), &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::PolicyDenied {});
+            assert_eq!(balance(&scenario, "uusdc"), 0);
+        }
+
+        #[test]
+        fn test_policy_checks_failed_swap_fallback() {
+            let mut scenario = setup();
+            setup_policy(&mut scenario, policy_template::<true>());
+
+            let task_id = create_swapped_task(&mut scenario, Some(5_000));
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &submit_proof(task_id), &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::PolicyDenied {});
+            assert_eq!(balance(&scenario, "uxion"), 0);
+        }
+
+        #[test]
+        fn test_release_swaps_escrow_into_ask_denom() {
+            let mut scenario = setup();
+            release_swapped_task(&mut scenario, Some(90));
+            assert_eq!(balance(&scenario, "uusdc"), 90);
+            assert_eq!(balance(&scenario, "uxion"), 0);
+        }
+
+        #[test]
+        fn test_failed_swap_pays_escrow_unconverted() {
+            let mut scenario = setup();
+            let res = release_swapped_task(&mut scenario, Some(5_000));
+            assert!(res.events.iter().any(|event| event
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "action" && attr.value == "payout_swap_failed")));
+            assert_eq!(balance(&scenario, "uusdc"), 0);
+            assert_eq!(balance(&scenario, "uxion"), 100);
+        }
+
+        #[test]
+        fn test_release_swaps_into_preferred_denom_within_slippage() {
+            let mut scenario = setup();
+            setup_oracle(&mut scenario);
+            scenario.execute_as("bob", &prefer_usdc(None), &[]);
+
+            // The escrow is worth 200 uusdc at oracle prices; the default 1% slippage leaves a floor of 198
+            release_swapped_task(&mut scenario, None);
+            assert_eq!(balance(&scenario, "uusdc"), 198);
+            assert_eq!(balance(&scenario, "uxion"), 0);
+        }
+
+        #[test]
+        fn test_preferred_denom_swap_uses_worker_slippage() {
+            let mut scenario = setup();
+            setup_oracle(&mut scenario);
+            scenario.execute_as("bob", &prefer_usdc(Some(500)), &[]);
+
+            release_swapped_task(&mut scenario, None);
+            assert_eq!(balance(&scenario, "uusdc"), 190);
+
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &prefer_usdc(Some(1001)), &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidPayoutSlippage {});
+        }
+
+        #[test]
+        fn test_preferred_denom_without_oracle_price_pays_unconverted() {
+            let mut scenario = setup();
+            scenario.execute_as("bob", &prefer_usdc(None), &[]);
+
+            let res = release_swapped_task(&mut scenario, None);
+            assert!(!res.events.iter().any(|event| event.ty == "wasm" && event
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "action" && attr.value.starts_with("payout_swap"))));
+            assert_eq!(balance(&scenario, "uusdc"), 0);
+            assert_eq!(balance(&scenario, "uxion"), 100);
+        }
+    }
+
+    mod error_codes {
+        use super::*;
+        use crate::msg::ErrorCodesResponse;
+        use crate::testing::ScenarioBuilder;
+        use crate::ContractError;
+
+        #[test]
+        fn test_error_codes_are_unique_and_match_failures() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .build();
+            let catalog: ErrorCodesResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetErrorCodes {})
+                .unwrap();
+            let mut codes: Vec<u32> = catalog.errors.iter().map(|error| error.code).collect();
+            codes.dedup();
+            assert_eq!(codes.len(), catalog.errors.len());
+            assert!(codes.windows(2).all(|pair| pair[0] < pair[1]));
+
+            // A failed execute maps back to its catalog entry
+            let register = ExecuteMsg::RegisterUser {
+                username: "alice".to_string(),
+                display_name: "Alice".to_string(),
+            };
+            let err = scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_other"), scenario.contract.addr(), &register, &[])
+                .unwrap_err()
+                .downcast::<ContractError>()
+                .unwrap();
+            let entry = catalog.errors.iter().find(|error| error.code == err.code()).unwrap();
+            assert_eq!(entry.name, "UsernameAlreadyTaken");
+            assert_eq!(entry.message, err.to_string());
+            assert_eq!(err.code(), 101);
+
+            // Each message carries its code, so clients can read it from the failure text
+            assert!(err.to_string().starts_with("[E0101] "));
+            assert!(catalog
+                .errors
+                .iter()
+                .all(|error| error.message.starts_with(&format!("[E{:04}] ", error.code))));
+        }
+    }
+
+    mod open_item_limits {
+        use super::*;
+        use crate::msg::OpenItemsResponse;
+        use crate::state::{OpenItemLimits, TaskStatus, FRIEND_REQUESTS};
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        fn set_limits(scenario: &mut Scenario, limits: OpenItemLimits) -> Result<(), ContractError> {
+            let update_config = ExecuteMsg::UpdateConfig {
+                co_approval_thresholds: None,
+                review_window: None,
+                archive_retention_secs: None,
+                accepted_denoms: None,
+                instant_release: None,
+                friend_requests: None,
+                text_limits: None,
+                open_item_limits: Some(limits),
+            };
+            scenario.try_execute_as(scenario.owner.clone(), &update_config, &[]).map(|_| ())
+        }
+
+        fn open_items(scenario: &Scenario, username: &str) -> OpenItemsResponse {
+            let query = QueryMsg::GetOpenItems {
+                username: username.to_string(),
+            };
+            scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &query)
+                .unwrap()
+        }
+
+        fn limits(friend_requests: u64, payment_requests: u64, tasks: u64) -> OpenItemLimits {
+            OpenItemLimits {
+                friend_requests,
+                payment_requests,
+                tasks,
+            }
+        }
+
+        #[test]
+        fn test_zero_limit_rejected() {
+            let mut scenario = ScenarioBuilder::new().build();
+            let err = set_limits(&mut scenario, limits(1, 0, 1)).unwrap_err();
+            assert_eq!(err, ContractError::InvalidOpenItemLimits {});
+            assert_eq!(open_items(&scenario, "alice").limits, OpenItemLimits::default());
+        }
+
+        #[test]
+        fn test_open_friend_requests_capped_until_answered() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .with_user("wallet_bob", "bob", 0)
+                .with_user("wallet_carol", "carol", 0)
+                .build();
+            set_limits(&mut scenario, limits(1, 100, 100)).unwrap();
+            let to_bob = ExecuteMsg::SendFriendRequest {
+                to_username: "bob".to_string(),
+                message: None,
+            };
+            scenario.execute_as("alice", &to_bob, &[]);
+            assert_eq!(open_items(&scenario, "alice").friend_requests, 1);
+
+            let to_carol = ExecuteMsg::SendFriendRequest {
+                to_username: "carol".to_string(),
+                message: None,
+            };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &to_carol, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TooManyOpenFriendRequests {});
+
+            let decline = ExecuteMsg::DeclineFriendRequest {
+                from_username: "alice".to_string(),
+            };
+            scenario.execute_as("bob", &decline, &[]);
+            assert_eq!(open_items(&scenario, "alice").friend_requests, 0);
+            scenario.execute_as("alice", &to_carol, &[]);
+        }
+
+        #[test]
+        fn test_deactivation_releases_requests_sent_to_the_user() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .with_user("wallet_bob", "bob", 0)
+                .build();
+            let to_bob = ExecuteMsg::SendFriendRequest {
+                to_username: "bob".to_string(),
+                message: None,
+            };
+            scenario.execute_as("alice", &to_bob, &[]);
+            assert_eq!(open_items(&scenario, "alice").friend_requests, 1);
+
+            scenario.execute_as("bob", &ExecuteMsg::DeactivateUser {}, &[]);
+            assert_eq!(open_items(&scenario, "alice").friend_requests, 0);
+            let request = FRIEND_REQUESTS
+                .query(&scenario.app.wrap(), scenario.contract.addr(), ("alice".to_string(), "bob".to_string()))
+                .unwrap();
+            assert!(request.is_none());
+        }
+
+        #[test]
+        fn test_open_payment_requests_capped_until_settled() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build();
+            set_limits(&mut scenario, limits(100, 1, 100)).unwrap();
+            let request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                description: "Pay me".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            scenario.execute_as("alice", &request, &[]);
+            assert_eq!(open_items(&scenario, "alice").payment_requests, 1);
+
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &request, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TooManyOpenPaymentRequests {});
+
+            scenario.execute_as("alice", &ExecuteMsg::CancelPayment { payment_id: 1 }, &[]);
+            assert_eq!(open_items(&scenario, "alice").payment_requests, 0);
+            scenario.execute_as("alice", &request, &[]);
+        }
+
+        #[test]
+        fn test_open_tasks_capped_until_released() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build();
+            set_limits(&mut scenario, limits(100, 100, 1)).unwrap();
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Released);
+            assert_eq!(open_items(&scenario, "alice").tasks, 0);
+
+            scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+            assert_eq!(open_items(&scenario, "alice").tasks, 1);
+            assert_eq!(open_items(&scenario, "bob").tasks, 0);
+
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                description: "Another task".to_string(),
+                proof_type: ProofType::Soft,
+                deadline_ts: scenario.app.block_info().time.seconds() + 3600,
+                review_window_secs: None,
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &create_task, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::TooManyOpenTasks {});
+        }
+    }
+
+    mod action_items {
+        use super::*;
+        use crate::msg::{ActionItem, ActionItemKind, ActionItemsResponse};
+        use crate::state::TaskStatus;
+        use crate::testing::{attribute, Scenario, ScenarioBuilder, SCENARIO_REVIEW_WINDOW_SECS, SCENARIO_TASK_DURATION_SECS};
+
+        fn action_items(scenario: &Scenario, username: &str) -> Vec<ActionItem> {
+            let query = QueryMsg::GetActionItems {
+                username: username.to_string(),
+            };
+            let res: ActionItemsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &query)
+                .unwrap();
+            assert!(!res.truncated && !res.scan_budget_exhausted);
+            res.items
+        }
+
+        #[test]
+        fn test_action_items_list_what_awaits_each_user() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 1_000)
+                .with_user("wallet_carol", "carol", 0)
+                .with_friends("alice", "bob")
+                .build();
+            let now = scenario.app.block_info().time.seconds();
+            let friend_request = ExecuteMsg::SendFriendRequest {
+                to_username: "alice".to_string(),
+                message: None,
+            };
+            scenario.execute_as("carol", &friend_request, &[]);
+            let proof_due = scenario.escrowed_task("bob", "alice", 100, TaskStatus::Escrowed);
+            let proof_review = scenario.escrowed_task("alice", "bob", 200, TaskStatus::PendingRelease);
+            let disputed = scenario.escrowed_task("bob", "alice", 300, TaskStatus::Disputed);
+            let payment_request = ExecuteMsg::CreatePaymentRequest {
+                to_username: "alice".to_string(),
+                amount: scenario.coin(50),
+                description: "Dinner".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            scenario.execute_as("bob", &payment_request, &[]);
+
+            let item = |kind, id, counterparty: &str, amount: Option<u128>, deadline| ActionItem {
+                kind,
+                id,
+                counterparty: counterparty.to_string(),
+                amount: amount.map(|amount| scenario.coin(amount)),
+                deadline,
+            };
+            assert_eq!(
+                action_items(&scenario, "alice"),
+                vec![
+                    item(ActionItemKind::FriendRequest, None, "carol", None, None),
+                    item(ActionItemKind::ProofDue, Some(proof_due), "bob", Some(100), Some(now + SCENARIO_TASK_DURATION_SECS)),
+                    item(ActionItemKind::ProofReview, Some(proof_review), "bob", Some(200), Some(now + SCENARIO_REVIEW_WINDOW_SECS)),
+                    item(ActionItemKind::Dispute, Some(disputed), "bob", Some(300), None),
+                    item(ActionItemKind::PaymentRequest, Some(1), "bob", Some(50), None),
+                ]
+            );
+            assert_eq!(
+                action_items(&scenario, "bob"),
+                vec![item(ActionItemKind::Dispute, Some(disputed), "alice", Some(300), None)]
+            );
+            assert_eq!(action_items(&scenario, "carol"), vec![]);
+        }
+
+        // alice asks bob for unescrowed soft work that auto-approves after an hour of silence
+        fn soft_task(scenario: &mut Scenario) -> u64 {
+            let create_task = ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                description: "Write documentation".to_string(),
+                proof_type: ProofType::Soft,
+                deadline_ts: scenario.app.block_info().time.seconds() + SCENARIO_TASK_DURATION_SECS,
+                review_window_secs: None,
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: Some(3600),
+                escrow_upfront: false,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs: None,
+            };
+            let res = scenario.execute_as("alice", &create_task, &[]);
+            attribute(&res, "task_id").and_then(|value| value.parse().ok()).unwrap()
+        }
+
+        fn soft_scenario() -> Scenario {
+            ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build()
+        }
+
+        fn submit_evidence(scenario: &mut Scenario, task_id: u64) {
+            let submit_evidence = ExecuteMsg::SubmitSoftEvidence {
+                task_id,
+                evidence_hash: "evidence_hash".to_string(),
+            };
+            scenario.execute_as("bob", &submit_evidence, &[]);
+        }
+
+        #[test]
+        fn test_soft_task_waits_on_worker_until_evidence() {
+            let mut scenario = soft_scenario();
+            let now = scenario.app.block_info().time.seconds();
+            let task_id = soft_task(&mut scenario);
+
+            let kinds = |scenario: &Scenario, username| -> Vec<ActionItemKind> {
+                action_items(scenario, username).into_iter().map(|item| item.kind).collect()
+            };
+            assert_eq!(kinds(&scenario, "alice"), vec![]);
+            let due = action_items(&scenario, "bob");
+            assert_eq!(due.len(), 1);
+            assert_eq!(due[0].kind, ActionItemKind::ProofDue);
+            assert_eq!(due[0].deadline, Some(now + SCENARIO_TASK_DURATION_SECS));
+
+            submit_evidence(&mut scenario, task_id);
+            assert_eq!(kinds(&scenario, "alice"), vec![ActionItemKind::ProofReview]);
+            assert_eq!(kinds(&scenario, "bob"), vec![]);
+        }
+
+        #[test]
+        fn test_approved_unfunded_task_waits_on_payer() {
+            let mut scenario = soft_scenario();
+            let task_id = soft_task(&mut scenario);
+            submit_evidence(&mut scenario, task_id);
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(3601));
+            scenario.execute_as("bob", &ExecuteMsg::AutoApproveIfSilent { task_id }, &[]);
+            assert_eq!(scenario.task(task_id).status, TaskStatus::ApprovedPendingFunding);
+
+            let items = action_items(&scenario, "alice");
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].kind, ActionItemKind::FundingDue);
+            assert_eq!(items[0].id, Some(task_id));
+            assert_eq!(items[0].counterparty, "bob");
+            assert_eq!(action_items(&scenario, "bob"), vec![]);
+        }
+
+        #[test]
+        fn test_wallet_payment_requests_are_action_items_for_the_wallet() {
+            let mut scenario = soft_scenario();
+            let request = ExecuteMsg::CreateWalletPaymentRequest {
+                to_wallet: "wallet_client".to_string(),
+                amount: scenario.coin(75),
+                description: "Consulting invoice".to_string(),
+                proof_type: ProofType::None,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+            };
+            scenario.execute_as("alice", &request, &[]);
+
+            let wallet_items = |scenario: &Scenario, wallet: &str| -> Vec<ActionItem> {
+                let query = QueryMsg::GetWalletActionItems { wallet_address: wallet.to_string() };
+                let res: ActionItemsResponse = scenario.app.wrap().query_wasm_smart(scenario.contract.addr(), &query).unwrap();
+                res.items
+            };
+            assert_eq!(
+                wallet_items(&scenario, "wallet_client"),
+                vec![ActionItem {
+                    kind: ActionItemKind::PaymentRequest,
+                    id: Some(1),
+                    counterparty: "alice".to_string(),
+                    amount: Some(scenario.coin(75)),
+                    deadline: None,
+                }]
+            );
+            assert_eq!(wallet_items(&scenario, "wallet_other"), vec![]);
+            assert_eq!(action_items(&scenario, "alice"), vec![]);
+
+            // Settled requests drop off the list
+            scenario
+                .app
+                .execute_contract(Addr::unchecked("wallet_client"), scenario.contract.addr(), &ExecuteMsg::RejectPayment { payment_id: 1 }, &[])
+                .unwrap();
+            assert_eq!(wallet_items(&scenario, "wallet_client"), vec![]);
+        }
+    }
+
+    mod records_by_ids {
+        use super::*;
+        use crate::msg::{PaymentsByIdsResponse, TasksByIdsResponse};
+        use crate::state::{TaskStatus, Visibility};
+        use crate::testing::ScenarioBuilder;
+
+        #[test]
+        fn test_payments_by_ids_report_hidden_and_missing() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 0)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build();
+            for visibility in [Visibility::Public, Visibility::Private] {
+                let request = ExecuteMsg::CreatePaymentRequest {
+                    to_username: "bob".to_string(),
+                    amount: scenario.coin(100),
+                    description: "Rent".to_string(),
+                    proof_type: ProofType::None,
+                    category: None,
+                    tags: vec![],
+                    visibility: Some(visibility),
+                    price_quote: None,
+                    external_ref: None,
+                    idempotency_key: None,
+                };
+                scenario.execute_as("alice", &request, &[]);
+            }
+
+            let query = |viewer: Option<&str>| QueryMsg::GetPaymentsByIds {
+                ids: vec![2, 99, 1],
+                viewer: viewer.map(str::to_string),
+            };
+            let public: PaymentsByIdsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &query(None))
+                .unwrap();
+            assert_eq!(public.payments.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1]);
+            assert_eq!(public.missing, vec![2, 99]);
+
+            let party: PaymentsByIdsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &query(Some("bob")))
+                .unwrap();
+            assert_eq!(party.payments.iter().map(|p| p.id).collect::<Vec<_>>(), vec![2, 1]);
+            assert_eq!(party.missing, vec![99]);
+        }
+
+        #[test]
+        fn test_tasks_by_ids_in_request_order() {
+            let mut scenario = ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build();
+            let first = scenario.escrowed_task("alice", "bob", 100, TaskStatus::Escrowed);
+            let second = scenario.escrowed_task("alice", "bob", 200, TaskStatus::Escrowed);
+
+            let res: TasksByIdsResponse = scenario
+                .app
+                .wrap()
+                .query_wasm_smart(scenario.contract.addr(), &QueryMsg::GetTasksByIds { ids: vec![second, 7, first] })
+                .unwrap();
+            assert_eq!(res.tasks.iter().map(|t| t.id).collect::<Vec<_>>(), vec![second, first]);
+            assert_eq!(res.missing, vec![7]);
+        }
+
+        #[test]
+        fn test_too_many_ids_rejected() {
+            let scenario = ScenarioBuilder::new().build();
+            let query = QueryMsg::GetTasksByIds { ids: (1..=101).collect() };
+            let err = scenario
+                .app
+                .wrap()
+                .query_wasm_smart::<TasksByIdsResponse>(scenario.contract.addr(), &query)
+                .unwrap_err();
+            assert!(err.to_string().contains("At most 100 ids"));
+        }
+    }
+
+    mod release_delay {
+        use super::*;
+        use crate::testing::{Scenario, ScenarioBuilder, SCENARIO_TASK_DURATION_SECS};
+        use crate::ContractError;
+
+        const DELAY_SECS: u64 = 600;
+
+        fn setup() -> Scenario {
+            ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build()
+        }
+
+        fn create_task(scenario: &Scenario, proof_type: ProofType, escrow_upfront: bool, release_delay_secs: Option<u64>) -> ExecuteMsg {
+            ExecuteMsg::CreateTask {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                description: "Write the docs".to_string(),
+                proof_type,
+                deadline_ts: scenario.app.block_info().time.seconds() + SCENARIO_TASK_DURATION_SECS,
+                review_window_secs: None,
+                endpoint: "https://api.example.com".to_string(),
+                category: None,
+                tags: vec![],
+                auto_approve_after_secs: None,
+                escrow_upfront,
+                co_approvers: vec![],
+                approvals_required: None,
+                price_quote: None,
+                external_ref: None,
+                idempotency_key: None,
+                arbiter: None,
+                depends_on: None,
+                payout_swap: None,
+                release_delay_secs,
+            }
+        }
+
+        #[test]
+        fn test_invalid_release_delay_rejected() {
+            let mut scenario = setup();
+            let funds = [scenario.coin(100)];
+            for (msg, funds) in [
+                (create_task(&scenario, ProofType::ZkTLS, false, Some(0)), &funds[..]),
+                (create_task(&scenario, ProofType::ZkTLS, false, Some(31 * 86400)), &funds[..]),
+                (create_task(&scenario, ProofType::Soft, false, Some(DELAY_SECS)), &[][..]),
+            ] {
+                let err = scenario
+                    .app
+                    .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &msg, funds)
+                    .unwrap_err();
+                assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidReleaseDelay {});
+            }
+        }
+
+        #[test]
+        fn test_valid_proof_releases_after_delay() {
+            let mut scenario = setup();
+            let funds = [scenario.coin(100)];
+            scenario.execute_as("alice", &create_task(&scenario, ProofType::ZkTLS, false, Some(DELAY_SECS)), &funds);
+            let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+                task_id: 1,
+                proof_blob_or_ref: "valid_proof".to_string(),
+                zk_proof_hash: "proof_hash".to_string(),
+            };
+            scenario.execute_as("bob", &submit_proof, &[]);
+            assert_eq!(scenario.task(1).status, TaskStatus::PendingRelease);
+            assert_eq!(scenario.balance("bob"), 0);
+
+            let release = ExecuteMsg::ReleaseIfWindowElapsed { task_id: 1 };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &release, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::DisputeWindowNotElapsed {});
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(DELAY_SECS + 1));
+            scenario.execute_as("bob", &release, &[]);
+            assert_eq!(scenario.task(1).status, TaskStatus::Released);
+            assert_eq!(scenario.balance("bob"), 100);
+        }
+
+        #[test]
+        fn test_payer_freezes_approval_by_disputing() {
+            let mut scenario = setup();
+            let funds = [scenario.coin(100)];
+            scenario.execute_as("alice", &create_task(&scenario, ProofType::Soft, true, Some(DELAY_SECS)), &funds);
+            scenario.execute_as("alice", &ExecuteMsg::ApproveTask { task_id: 1 }, &[]);
+            assert_eq!(scenario.task(1).status, TaskStatus::PendingRelease);
+            assert_eq!(scenario.balance("bob"), 0);
+
+            let dispute = ExecuteMsg::DisputeTask {
+                task_id: 1,
+                reason: None,
+                reason_hash: None,
+            };
+            scenario.execute_as("alice", &dispute, &[]);
+            assert_eq!(scenario.task(1).status, TaskStatus::Disputed);
+
+            let resolve = ExecuteMsg::ResolveDispute { task_id: 1, worker_bps: 0 };
+            scenario
+                .app
+                .execute_contract(scenario.owner.clone(), scenario.contract.addr(), &resolve, &[])
+                .unwrap();
+            assert_eq!(scenario.task(1).status, TaskStatus::Refunded);
+            assert_eq!(scenario.balance("bob"), 0);
+        }
+    }
+
+    mod payment_release_delay {
+        use super::*;
+        use crate::msg::AdminOutcome;
+        use crate::state::PaymentStatus;
+        use crate::testing::{Scenario, ScenarioBuilder};
+        use crate::ContractError;
+
+        const DELAY_SECS: u64 = 600;
+
+        fn setup() -> Scenario {
+            ScenarioBuilder::new()
+                .with_user("wallet_alice", "alice", 1_000)
+                .with_user("wallet_bob", "bob", 0)
+                .with_friends("alice", "bob")
+                .build()
+        }
+
+        fn send_payment(scenario: &Scenario, proof_type: ProofType, release_delay_secs: Option<u64>) -> ExecuteMsg {
+            ExecuteMsg::SendDirectPayment {
+                to_username: "bob".to_string(),
+                amount: scenario.coin(100),
+                description: "Design review".to_string(),
+                proof_type,
+                category: None,
+                tags: vec![],
+                visibility: None,
+                external_ref: None,
+                idempotency_key: None,
+                release_delay_secs,
+            }
+        }
+
+        // Helper function to send a delayed payment, submit its proof and approve it
+        fn approved_payment(scenario: &mut Scenario) {
+            let funds = [scenario.coin(100)];
+            scenario.execute_as("alice", &send_payment(scenario, ProofType::Photo, Some(DELAY_SECS)), &funds);
+            let submit_proof = ExecuteMsg::SubmitProof {
+                payment_id: 1,
+                proof_data: "photo_hash".to_string(),
+            };
+            scenario.execute_as("bob", &submit_proof, &[]);
+            scenario.execute_as("alice", &ExecuteMsg::ApprovePayment { payment_id: 1 }, &[]);
+        }
+
+        #[test]
+        fn test_invalid_release_delay_rejected() {
+            let mut scenario = setup();
+            let funds = [scenario.coin(100)];
+            for msg in [
+                send_payment(&scenario, ProofType::Photo, Some(0)),
+                send_payment(&scenario, ProofType::Photo, Some(31 * 86400)),
+                send_payment(&scenario, ProofType::None, Some(DELAY_SECS)),
+            ] {
+                let err = scenario
+                    .app
+                    .execute_contract(scenario.wallet("alice"), scenario.contract.addr(), &msg, &funds)
+                    .unwrap_err();
+                assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidReleaseDelay {});
+            }
+        }
+
+        #[test]
+        fn test_approved_payment_releases_after_delay() {
+            let mut scenario = setup();
+            approved_payment(&mut scenario);
+            assert_eq!(scenario.payment(1).status, PaymentStatus::PendingRelease);
+            assert_eq!(scenario.balance("bob"), 0);
+
+            let release = ExecuteMsg::ReleasePayment { payment_id: 1 };
+            let err = scenario
+                .app
+                .execute_contract(scenario.wallet("bob"), scenario.contract.addr(), &release, &[])
+                .unwrap_err();
+            assert_eq!(err.downcast::<ContractError>().unwrap(), ContractError::DisputeWindowNotElapsed {});
+
+            scenario.app.update_block(|block| block.time = block.time.plus_seconds(DELAY_SECS + 1));
+            scenario.execute_as("bob", &release, &[]);
+            assert_eq!(scenario.payment(1).status, PaymentStatus::Completed);
+            assert_eq!(scenario.balance("bob"), 100);
+        }
+
+        #[test]
+        fn test_sender_freezes_approval_by_disputing() {
+            let mut scenario = setup();
+            approved_payment(&mut scenario);
+
+            let dispute = ExecuteMsg::DisputePayment { payment_id: 1 };
+            scenario.execute_as("alice", &dispute, &[]);
+            assert_eq!(scenario.payment(1).status, PaymentStatus::Disputed);
+
+            let resolve = ExecuteMsg::AdminResolvePayment {
+                payment_id: 1,
+                outcome: AdminOutcome::Refund,
+                reason: "Work was not delivered".to_string(),
+            };
+            scenario
+                .app
+                .execute_contract(scenario.owner.clone(), scenario.contract.addr(), &resolve, &[])
+                .unwrap();
+            assert_eq!(scenario.payment(1).status, PaymentStatus::Cancelled);
+            assert_eq!(scenario.balance("alice"), 1_000);
+            assert_eq!(scenario.balance("bob"), 0);
+        }
+    }
 }
@@ -4,5 +4,7 @@ pub mod helpers;
 pub mod integration_tests;
 pub mod msg;
 pub mod state;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 pub use crate::error::ContractError;
@@ -1,7 +1,7 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use crate::state::{User, FriendRequest, Payment, ProofType, Task};
-use cosmwasm_std::Coin;
+use crate::state::{AcceptedDenoms, ActivityEntry, AdminRole, Arbiter, ArbiterConfig, Badge, Campaign, Config, DisputeOutcome, FeatureFlags, FriendRequestPolicy, DisputeReason, GiftCode, Htlc, InsuranceConfig, InstantReleaseConfig, OpenItemLimits, ReviewWindowBounds, User, FriendGroup, FriendRequest, GroupBalance, Invoice, InvoiceLineItem, GroupExpense, Notification, Payment, PaymentLink, PaymentStatus, PaymentType, PayoutSwap, ProofType, Receipt, RecordKind, RegistrationFeeTier, RegistrationFees, ScheduledPayment, Subscription, Task, TaskBatch, TaskStatus, TextLimits, UsernameExpiry, UsernameTransfer, Visibility, YieldConfig, YieldPosition};
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Empty, Uint128};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -17,12 +17,31 @@ pub enum ExecuteMsg {
     },
     UpdateUserProfile { 
         display_name: Option<String>, 
-        profile_picture: Option<String> 
+        profile_picture: Option<String>,
+        require_friendship_for_requests: Option<bool>,
+        default_visibility: Option<Visibility>,
+        bio: Option<String>,           // empty clears
+        links: Option<Vec<String>>,    // replaces the list; empty clears
+        country: Option<String>,       // empty clears
+        timezone: Option<String>,      // empty clears
+        preferred_denom: Option<String>, // accepted denom task payouts are swapped into; empty clears
+        payout_slippage_bps: Option<u16>, // most a preferred-denom swap may fall below the oracle price, at most 1000
+    },
+    RegisterPubkey {
+        pubkey: Binary,          // compressed or uncompressed secp256k1 key
+        proof_signature: Binary, // ADR-36 signature over the pubkey challenge with this key
+    },
+    RemovePubkey {},
+    DeactivateUser {}, // Frees the wallet, retires the username and refunds any registration deposit
+    RenewUsername {},  // Extends the registration by one term, paying the fee tier again unless it is a deposit
+    ReapExpiredUsernames {
+        limit: Option<u32>, // expiry entries scanned per call, and expired idempotency keys deleted
     },
     
     // Friends System
     SendFriendRequest { 
-        to_username: String 
+        to_username: String,
+        message: Option<String>, // shown to the recipient; at most 140 characters
     },
     AcceptFriendRequest { 
         from_username: String 
@@ -31,7 +50,32 @@ pub enum ExecuteMsg {
         from_username: String 
     },
     RemoveFriend { 
-        username: String 
+        username: String,
+        #[serde(default)]
+        leave_shared_groups: bool, // also remove each of you from groups the other owns
+    },
+    
+    // Friend Groups
+    CreateGroup {
+        name: String,
+    },
+    AddToGroup {
+        group_id: u64,
+        username: String,
+    },
+    RemoveFromGroup {
+        group_id: u64,
+        username: String, // members may remove themselves
+    },
+    RecordGroupExpense {
+        group_id: u64,
+        amount: Coin,
+        description: String,
+        #[serde(default)]
+        split_among: Vec<String>, // empty = all current members
+    },
+    SettleUp {
+        group_id: u64,
     },
     
     // Payment System
@@ -39,13 +83,40 @@ pub enum ExecuteMsg {
         to_username: String, 
         amount: Coin,
         description: String, 
-        proof_type: ProofType 
+        proof_type: ProofType,
+        category: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        visibility: Option<Visibility>, // defaults to the sender's default_visibility
+        external_ref: Option<String>, // e.g. an invoice number; unique per sender
+        idempotency_key: Option<String>, // retries with the same key within 24 hours return the original payment
+        release_delay_secs: Option<u64>, // payments with proof only: hold the funds this long after approval; you can dispute meanwhile
     },
     CreatePaymentRequest { 
         to_username: String, 
         amount: Coin,
         description: String, 
-        proof_type: ProofType 
+        proof_type: ProofType,
+        category: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        visibility: Option<Visibility>, // defaults to the requester's default_visibility
+        price_quote: Option<PriceQuoteMsg>, // amount.denom settles; the amount is resolved at approval
+        external_ref: Option<String>, // e.g. an invoice number; unique per requester
+        idempotency_key: Option<String>, // retries with the same key within 24 hours return the original payment
+    },
+    CreateWalletPaymentRequest {
+        to_wallet: String, // payer without a username; the request binds to the username it registers
+        amount: Coin,
+        description: String,
+        proof_type: ProofType,
+        category: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        visibility: Option<Visibility>,
+        price_quote: Option<PriceQuoteMsg>,
+        external_ref: Option<String>,
+        idempotency_key: Option<String>,
     },
     // Task System
     CreateTask {
@@ -56,6 +127,28 @@ pub enum ExecuteMsg {
         deadline_ts: u64,
         review_window_secs: Option<u64>,
         endpoint: String,
+        category: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        auto_approve_after_secs: Option<u64>, // Soft only: approve if the payer stays silent after evidence
+        #[serde(default)]
+        escrow_upfront: bool, // Soft only: fund at creation instead of at approval
+        #[serde(default)]
+        co_approvers: Vec<String>, // usernames whose approval is needed before release
+        approvals_required: Option<u32>, // defaults to every co-approver
+        price_quote: Option<PriceQuoteMsg>, // Soft tasks funded at approval only
+        external_ref: Option<String>, // e.g. a purchase order number; unique per payer
+        idempotency_key: Option<String>, // retries with the same key within 24 hours return the original task
+        arbiter: Option<String>, // Hybrid only: address agreed to resolve disputes; must accept before proofs are taken
+        depends_on: Option<u64>, // your task that must be released first; escrowed now, the deadline clock starts then
+        payout_swap: Option<PayoutSwap>, // convert the escrow through the DEX router when released to the worker
+        release_delay_secs: Option<u64>, // escrowed tasks only: hold the release this long after proof or approval; you can dispute meanwhile
+    },
+    CoApproveTask {
+        task_id: u64,
+    },
+    AutoApproveIfSilent {
+        task_id: u64,
     },
     SubmitSoftEvidence {
         task_id: u64,
@@ -71,11 +164,12 @@ pub enum ExecuteMsg {
     },
     DisputeTask {
         task_id: u64,
+        reason: Option<DisputeReason>, // recorded as Other when omitted
         reason_hash: Option<String>,
     },
     ResolveDispute {
         task_id: u64,
-        decision: bool, // true = release to worker, false = refund to payer
+        worker_bps: u16, // worker's share of the escrow; the rest is refunded to the payer
     },
     RefundIfExpired {
         task_id: u64,
@@ -83,6 +177,41 @@ pub enum ExecuteMsg {
     ReleaseIfWindowElapsed {
         task_id: u64,
     },
+    CancelTask {
+        task_id: u64,
+    },
+    CreateTaskBatch {
+        template: TaskTemplate, // attach the template amount once per worker
+        workers: Vec<String>,   // up to 20 distinct usernames, one escrowed task each
+    },
+    CancelTaskBatch {
+        batch_id: u64, // payer only: cancels the batch's tasks no worker has accepted or started
+    },
+    AcceptTask {
+        task_id: u64,
+    },
+    DeclineTask {
+        task_id: u64,
+    },
+    CounterOffer {
+        task_id: u64,
+        amount: Coin,
+    },
+    AcceptCounterOffer {
+        task_id: u64, // attach the difference for escrowed tasks
+    },
+    AmendTask {
+        task_id: u64,
+        additional_amount: Option<Coin>, // attach it for escrowed tasks
+        new_description_hash: Option<String>,
+        new_deadline: Option<u64>,
+    },
+    AcceptAmendment {
+        task_id: u64,
+    },
+    WithdrawAmendment {
+        task_id: u64,
+    },
     SubmitProof { 
         payment_id: u64, 
         proof_data: String 
@@ -96,6 +225,369 @@ pub enum ExecuteMsg {
     CancelPayment { 
         payment_id: u64 
     },
+    DisputePayment {
+        payment_id: u64, // sender only: freeze an approved payment during its release delay
+    },
+    ReleasePayment {
+        payment_id: u64, // anyone, once the release delay has passed
+    },
+    RemindPaymentRequest {
+        payment_id: u64,
+    },
+    
+    // Campaigns
+    CreateCampaign {
+        title: String,
+        goal: Coin,
+        deadline: u64,
+        beneficiary_username: String,
+        #[serde(default)]
+        all_or_nothing: bool,
+    },
+    Donate {
+        campaign_id: u64, // attach the donation in the goal's denom
+    },
+    WithdrawCampaign {
+        campaign_id: u64,
+    },
+    ClaimCampaignRefund {
+        campaign_id: u64,
+    },
+    
+    // Subscriptions
+    CreateSubscription {
+        to_username: String,
+        amount: Coin,   // paid per period
+        period: u64,    // seconds between payments
+        periods: u32,   // prepaid up front; attach amount * periods
+    },
+    ClaimSubscription {
+        subscription_id: u64,
+    },
+    CancelSubscription {
+        subscription_id: u64,
+    },
+    
+    // Invoices
+    CreateInvoice {
+        to_username: String,
+        line_items: Vec<InvoiceLineItem>, // all in one denom
+        due_ts: u64,
+    },
+    PayInvoice {
+        invoice_id: u64, // attach up to the remaining balance; any surplus is refunded
+    },
+    CancelInvoice {
+        invoice_id: u64,
+    },
+    
+    // Payment links
+    CreatePaymentLink {
+        amount: Option<Coin>, // None lets the payer choose the amount
+        description: String,
+        max_uses: Option<u32>,
+        expires_at: Option<u64>,
+    },
+    PayLink {
+        code: String, // attach the link amount, or any amount for open links
+    },
+    DisablePaymentLink {
+        code: String,
+    },
+    
+    // Scheduled payments
+    SchedulePayment {
+        to_username: String,
+        amount: Coin, // attach now; held in escrow until execution
+        description: String,
+        execute_at: u64,
+    },
+    ExecuteScheduledPayment {
+        scheduled_id: u64, // anyone, once execute_at has passed
+    },
+    CancelScheduledPayment {
+        scheduled_id: u64,
+    },
+    
+    // Gift codes
+    CreateGiftCode {
+        amount: Coin,      // attach now; held in escrow until claimed or refunded
+        code_hash: String, // hex SHA-256 of the code shared off-chain
+        expires_at: u64,
+    },
+    ClaimGiftCode {
+        code: String, // anyone holding the code, registered or not, before expiry
+    },
+    RefundGiftCode {
+        gift_id: u64, // creator only, once expired
+    },
+    
+    // Hash-timelocked payments
+    CreateHtlc {
+        to_username: String,
+        amount: Coin,     // attach now; held in escrow until claimed or refunded
+        hashlock: String, // hex SHA-256 of the preimage's bytes
+        timelock: u64,
+    },
+    ClaimHtlc {
+        htlc_id: u64,
+        preimage: String, // hex; recipient only, before the timelock
+    },
+    RefundHtlc {
+        htlc_id: u64, // sender only, once the timelock has passed
+    },
+    
+    // Admin
+    AdminResolvePayment {
+        payment_id: u64,
+        outcome: AdminOutcome,
+        reason: String,
+    },
+    AdminCancelTask {
+        task_id: u64,
+        reason: String,
+    },
+    TransferOwnership {
+        new_owner: String,
+    },
+    AcceptOwnership {},
+    CancelOwnershipTransfer {},
+    GrantRole {
+        role: AdminRole,
+        address: String,
+    },
+    RevokeRole {
+        role: AdminRole,
+        address: String,
+    },
+    SetUserBadge {
+        username: String,
+        badge: Option<Badge>, // None clears the badge
+    },
+    UpdateConfig {
+        co_approval_thresholds: Option<Vec<Coin>>,
+        review_window: Option<ReviewWindowBounds>,
+        archive_retention_secs: Option<u64>,
+        accepted_denoms: Option<AcceptedDenoms>,
+        instant_release: Option<InstantReleaseConfig>,
+        friend_requests: Option<FriendRequestPolicy>,
+        text_limits: Option<TextLimits>,
+        open_item_limits: Option<OpenItemLimits>,
+    },
+    SetYieldConfig {
+        config: Option<YieldConfigMsg>, // None disables deposits; open positions still withdraw
+    },
+    SetPriceOracle {
+        oracle: Option<String>, // None stops new price-quoted records; open ones cannot resolve
+    },
+    SetRegistrationFees {
+        config: Option<RegistrationFeesMsg>, // None makes registration free; deposits already held stay refundable
+    },
+    SetUsernameExpiry {
+        config: Option<UsernameExpiry>, // None stops registrations from expiring; recorded expiries apply again if re-enabled
+    },
+    SetFeatureFlags {
+        disabled_payment_types: Vec<PaymentType>, // empty lists re-enable everything
+        disabled_proof_types: Vec<ProofType>,
+    },
+    SetPolicyContract {
+        policy: Option<String>, // None restores permissionless fund movements
+    },
+    SetDexRouter {
+        router: Option<String>, // None pays payout swaps unconverted
+    },
+    
+    // Notifications
+    AckNotifications {
+        up_to_id: u64, // inclusive
+    },
+    
+    // Privacy
+    RedactRecordText {
+        kind: RecordKind,
+        id: u64,
+    },
+    
+    // Completion Callbacks
+    RegisterCallback {
+        kind: RecordKind,
+        id: u64,
+        contract_addr: String,
+        msg: Option<Binary>,
+    },
+    RebuildIndex {
+        index: IndexKind,
+        start_after: Option<String>, // primary key of the last processed record
+        limit: Option<u32>,
+    },
+    ArchiveRecords {
+        up_to_ts: u64,      // only records settled at or before this time
+        limit: Option<u32>, // payments and tasks scanned per call, each
+    },
+    
+    // Insurance Pool
+    SetInsuranceConfig {
+        config: Option<InsuranceConfig>, // None stops new cover; open claims can still be paid
+    },
+    FundInsurancePool {},
+    InsureTask {
+        task_id: u64,
+    },
+    PayInsuranceClaim {
+        task_id: u64,
+        amount: Uint128, // 0 closes the claim without compensation
+    },
+    
+    // Arbiters
+    SetArbiterConfig {
+        config: Option<ArbiterConfig>, // None stops assigning arbiters to new disputes
+    },
+    BondArbiter {},   // dispute resolver: attach bond_denom funds to add to your bond
+    UnbondArbiter {}, // withdraw your whole bond once no assigned disputes are open
+    AcceptArbitration {
+        task_id: u64, // the arbiter named at task creation
+    },
+    DeclineArbitration {
+        task_id: u64, // the arbiter named at task creation; refunds the payer
+    },
+    
+    // Relayed Execution
+    SetRelayer {
+        address: String,
+        authorized: bool,
+    },
+    ExecuteSigned {
+        payload: Binary,   // JSON-encoded SignedPayload
+        signature: Binary, // 64-byte secp256k1 signature over the payload's ADR-36 sign doc
+    },
+    
+    // Username NFTs
+    SetUsernameCollection {
+        collection: String, // cw721 collection this contract may mint in; set once
+    },
+    TokenizeUsername {},
+    ReceiveNft(Cw721ReceiveMsg), // a holder sending a username NFT claims the handle for their wallet
+    FinalizeUsernameTransfer {
+        username: String,
+    },
+    CancelUsernameTransfer {
+        username: String,
+    },
+}
+
+/// Off-chain payload a user signs for a relayer to submit with ExecuteSigned
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SignedPayload {
+    pub signer: String,   // username
+    pub nonce: u64,       // must equal the signer's next nonce
+    pub chain_id: String,
+    pub contract: String, // address of this contract
+    pub action: SignedAction,
+}
+
+/// Social actions a relayer may submit on a user's behalf; none of them move funds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignedAction {
+    UpdateUserProfile {
+        display_name: Option<String>,
+        profile_picture: Option<String>,
+        require_friendship_for_requests: Option<bool>,
+        default_visibility: Option<Visibility>,
+        bio: Option<String>,           // empty clears
+        links: Option<Vec<String>>,    // replaces the list; empty clears
+        country: Option<String>,       // empty clears
+        timezone: Option<String>,      // empty clears
+        preferred_denom: Option<String>, // accepted denom task payouts are swapped into; empty clears
+        payout_slippage_bps: Option<u16>, // most a preferred-denom swap may fall below the oracle price, at most 1000
+    },
+    SendFriendRequest { to_username: String, message: Option<String> },
+    AcceptFriendRequest { from_username: String },
+    DeclineFriendRequest { from_username: String },
+    SubmitProof { payment_id: u64, proof_data: String },
+    SubmitSoftEvidence { task_id: u64, evidence_hash: String },
+}
+
+impl From<SignedAction> for ExecuteMsg {
+    fn from(action: SignedAction) -> Self {
+        match action {
+            SignedAction::UpdateUserProfile {
+                display_name,
+                profile_picture,
+                require_friendship_for_requests,
+                default_visibility,
+                bio,
+                links,
+                country,
+                timezone,
+                preferred_denom,
+                payout_slippage_bps,
+            } => ExecuteMsg::UpdateUserProfile {
+                display_name,
+                profile_picture,
+                require_friendship_for_requests,
+                default_visibility,
+                bio,
+                links,
+                country,
+                timezone,
+                preferred_denom,
+                payout_slippage_bps,
+            },
+            SignedAction::SendFriendRequest { to_username, message } => {
+                ExecuteMsg::SendFriendRequest { to_username, message }
+            }
+            SignedAction::AcceptFriendRequest { from_username } => ExecuteMsg::AcceptFriendRequest { from_username },
+            SignedAction::DeclineFriendRequest { from_username } => ExecuteMsg::DeclineFriendRequest { from_username },
+            SignedAction::SubmitProof { payment_id, proof_data } => ExecuteMsg::SubmitProof { payment_id, proof_data },
+            SignedAction::SubmitSoftEvidence { task_id, evidence_hash } => {
+                ExecuteMsg::SubmitSoftEvidence { task_id, evidence_hash }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminOutcome {
+    Release,          // Send escrowed funds to the recipient
+    Refund,           // Return escrowed funds to the sender
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RegistrationFeesMsg {
+    pub tiers: Vec<RegistrationFeeTier>,
+    pub treasury: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct YieldConfigMsg {
+    pub adapter: String,
+    pub denom: String,
+    pub threshold: Uint128,
+    pub treasury: String,
+    pub user_share_bps: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionItemKind {
+    FriendRequest,  // Incoming friend request to accept or decline
+    PaymentRequest, // Payment request addressed to the user, to approve or reject
+    ProofDue,       // Task the user works on, or direct payment they receive, waiting for their proof
+    ProofReview,    // Proof submitted on the user's task or direct payment, waiting for their approval
+    Dispute,        // Disputed task the user is a party to, waiting for the arbiter to resolve it
+    FundingDue,     // Approved task the user pays for that was not escrowed, waiting for them to fund it
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexKind {
+    UsersByWallet,    // Derived from users by username
+    UserPayments,     // Derived from payments
+    UserTasks,        // Derived from tasks
+    TasksByStatus,    // Derived from tasks
+    UserTasksByStatus, // Derived from tasks
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -112,7 +604,8 @@ pub enum QueryMsg {
         username: String 
     },
     SearchUsers { 
-        query: String 
+        query: String,
+        start_after: Option<String>, // exclusive username
     },
     
     // New username-specific queries
@@ -131,34 +624,267 @@ pub enum QueryMsg {
         username: String 
     },
     GetPendingRequests { 
-        username: String 
+        username: String,
+        start_after: Option<String>, // exclusive sender username
     },
     AreFriends { 
         username1: String, 
         username2: String 
     },
+    GetSuggestedFriends {
+        username: String,
+        limit: Option<u32>,
+    },
+    
+    // Friend Groups
+    GetGroup {
+        group_id: u64,
+    },
+    GetUserGroups {
+        username: String,
+    },
+    GetGroupLedger {
+        group_id: u64,
+    },
+    GetGroupExpenses {
+        group_id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
     
     // Payment System
+    // Payments are filtered by visibility for `viewer` (a username); omit it to see public payments only
     GetPaymentById { 
-        payment_id: u64 
+        payment_id: u64,
+        viewer: Option<String>,
+    },
+    GetPaymentsByIds {
+        ids: Vec<u64>, // at most 100; hidden payments are reported missing
+        viewer: Option<String>,
+    },
+    GetPaymentByExternalRef {
+        creator: String, // username of the sender or requester
+        external_ref: String,
+        viewer: Option<String>,
+    },
+    GetReceipt {
+        payment_id: u64, // settled payments only
+        viewer: Option<String>,
     },
     GetPaymentHistory { 
-        username: String 
+        username: String,
+        viewer: Option<String>,
+        start_after: Option<u64>, // exclusive payment id
     },
     GetPendingPayments { 
-        username: String 
+        username: String,
+        viewer: Option<String>,
+        start_after: Option<u64>, // exclusive payment id
+    },
+    GetWalletPaymentRequests {
+        wallet_address: String, // requests awaiting this wallet's registration; private ones are omitted
+        start_after: Option<u64>, // exclusive payment id
+    },
+    GetPaymentHistoryFiltered {
+        username: String,
+        viewer: Option<String>,
+        category: Option<String>,
+        status: Option<PaymentStatus>,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+        start_after: Option<u64>, // exclusive payment id
+    },
+    GetActivityFeed {
+        username: String,             // payments of this user's friends, as visible to them
+        before: Option<u64>,          // exclusive payment id; omit for the newest payments
+        limit: Option<u32>,
     },
     
     // Task System
     GetTaskById {
         task_id: u64,
     },
+    GetTasksByIds {
+        ids: Vec<u64>, // at most 100
+    },
     GetTaskHistory {
         username: String,
+        start_after: Option<u64>, // exclusive task id
     },
     GetPendingTasks {
         username: String,
+        start_after: Option<u64>, // exclusive task id
     },
+    GetTasksByStatus {
+        status: TaskStatus,
+        start_after: Option<u64>, // exclusive task id
+        limit: Option<u32>,
+    },
+    GetUserTasksByStatus {
+        username: String,         // tasks this user pays for or works on
+        status: TaskStatus,
+        start_after: Option<u64>, // exclusive task id
+        limit: Option<u32>,
+    },
+    GetDisputeStats {},
+    GetReputation {
+        username: String,
+    },
+    GetTaskBatch {
+        batch_id: u64,
+    },
+    
+    // Campaigns
+    GetCampaign {
+        campaign_id: u64,
+    },
+    GetCampaignDonation {
+        campaign_id: u64,
+        username: String,
+    },
+    
+    // Subscriptions
+    GetSubscription {
+        subscription_id: u64,
+    },
+    GetUserSubscriptions {
+        username: String,
+    },
+    
+    // Invoices
+    GetInvoice {
+        invoice_id: u64,
+    },
+    GetUserInvoices {
+        username: String,
+    },
+    
+    // Payment links
+    GetPaymentLink {
+        code: String,
+    },
+    GetUserPaymentLinks {
+        username: String,
+    },
+    
+    // Scheduled payments
+    GetScheduledPayment {
+        scheduled_id: u64,
+    },
+    GetUserScheduledPayments {
+        username: String,
+    },
+    GetDueScheduledPayments {},
+    
+    // Gift codes
+    GetGiftCode {
+        gift_id: u64,
+    },
+    GetUserGiftCodes {
+        username: String,
+    },
+    
+    // Hash-timelocked payments
+    GetHtlc {
+        htlc_id: u64,
+    },
+    GetUserHtlcs {
+        username: String,
+    },
+    
+    // Admin
+    GetOwnership {},
+    GetRoles {},
+    GetConfig {},
+    GetAcceptedDenoms {},
+    GetPriceOracle {},
+    GetFeatureFlags {},
+    GetPolicyContract {},
+    GetDexRouter {},
+    
+    // Insurance Pool
+    GetInsurancePool {},
+    
+    // Arbiters
+    GetArbiters {},
+    
+    // Relayed Execution
+    GetSigner {
+        username: String,
+    },
+    GetRelayers {},
+    
+    // Username NFTs
+    GetUsernameCollection {},
+    GetUsernameTransfer {
+        username: String,
+    },
+    
+    // Registration Fees
+    GetRegistrationFees {},
+    GetRegistrationFee {
+        username: String,
+    },
+    GetUsernameExpiry {},
+    
+    // Yield Routing
+    GetYieldConfig {},
+    GetYieldPosition {
+        task_id: u64,
+    },
+    
+    // Escrow Accounting
+    GetEscrowLiabilities {},
+    GetUserEscrowed {
+        username: String,
+    },
+    
+    // Maintenance
+    GetIndexReport {},
+    GetArchiveRoot {},
+    #[cfg(any(test, feature = "invariants"))]
+    VerifyInvariants {
+        limit: Option<u32>, // entries scanned per check
+    },
+    
+    // Analytics
+    ExportEvents {
+        since_seq: Option<u64>, // exclusive; omit to start from the beginning
+        limit: Option<u32>,
+    },
+    
+    // Notifications
+    GetNotifications {
+        username: String,
+        start_after: Option<u64>, // exclusive notification id
+        limit: Option<u32>,
+    },
+    
+    // Account Snapshot
+    GetAccountSnapshot {
+        username: String,
+        viewer: Option<String>,       // filters recent payments by visibility
+        limit: Option<u32>,           // recent payments and tasks per page
+        payments_before: Option<u64>, // exclusive; omit for the newest payments
+        tasks_before: Option<u64>,    // exclusive; omit for the newest tasks
+    },
+    
+    // Open Item Limits
+    GetOpenItems {
+        username: String,
+    },
+    
+    // Action Items
+    GetActionItems {
+        username: String,
+    },
+    GetWalletActionItems {
+        wallet_address: String, // payer without a username; lists the payment requests waiting on it
+    },
+    
+    // Contract Metadata
+    GetContractInfo {},
+    GetErrorCodes {},
 }
 
 // Response Types
@@ -170,6 +896,10 @@ pub struct UserResponse {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct UsersResponse {
     pub users: Vec<User>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+    pub total: Option<u64>,          // all matching records, where a counter tracks them; None for filtered queries
+    pub next_start_after: Option<String>, // pass as start_after for the next page; None on the last page
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -195,11 +925,28 @@ pub struct HasUsernameResponse {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct FriendsResponse {
     pub friends: Vec<String>, // usernames
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SuggestedFriendsResponse {
+    pub suggestions: Vec<SuggestedFriend>, // most recent counterparty first
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SuggestedFriend {
+    pub username: String,
+    pub last_transacted_at: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct FriendRequestsResponse {
     pub requests: Vec<FriendRequest>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+    pub total: Option<u64>,          // all matching records, where a counter tracks them; None for filtered queries
+    pub next_start_after: Option<String>, // pass as start_after for the next page; None on the last page
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -207,14 +954,183 @@ pub struct AreFriendsResponse {
     pub are_friends: bool,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GroupResponse {
+    pub group: FriendGroup,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GroupsResponse {
+    pub groups: Vec<FriendGroup>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GroupLedgerResponse {
+    pub balances: Vec<GroupBalance>, // unsettled entries, by username then denom
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GroupExpensesResponse {
+    pub expenses: Vec<GroupExpense>, // oldest first
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CampaignResponse {
+    pub campaign: Campaign,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DisputeStatsResponse {
+    pub reasons: Vec<DisputeReasonCount>,   // every reason, including those never used
+    pub outcomes: Vec<DisputeOutcomeCount>, // every outcome, including those never reached
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TasksByStatusResponse {
+    pub tasks: Vec<Task>, // ascending task id; pass the last id as start_after for the next page
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReputationResponse {
+    pub username: String,
+    pub reputation: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DisputeReasonCount {
+    pub reason: DisputeReason,
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DisputeOutcomeCount {
+    pub outcome: DisputeOutcome,
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CampaignDonationResponse {
+    pub amount: Coin, // donated and not refunded
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvoiceResponse {
+    pub invoice: Invoice,
+    pub line_items: Vec<InvoiceLineItemStatus>,
+    pub remaining: Coin,
+    pub overdue: bool,               // open past its due date
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvoiceLineItemStatus {
+    pub description: String,
+    pub amount: Coin,
+    pub paid: Uint128,               // payments fill line items in order
+    pub fully_paid: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvoicesResponse {
+    pub invoices: Vec<Invoice>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PaymentLinkResponse {
+    pub link: PaymentLink,
+    pub usable: bool,                // active, unexpired and not used up
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PaymentLinksResponse {
+    pub links: Vec<PaymentLink>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GiftCodeResponse {
+    pub gift_code: GiftCode,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GiftCodesResponse {
+    pub gift_codes: Vec<GiftCode>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HtlcResponse {
+    pub htlc: Htlc,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HtlcsResponse {
+    pub htlcs: Vec<Htlc>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ScheduledPaymentResponse {
+    pub scheduled_payment: ScheduledPayment,
+    pub executable: bool,            // pending and due
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ScheduledPaymentsResponse {
+    pub scheduled_payments: Vec<ScheduledPayment>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SubscriptionResponse {
+    pub subscription: Subscription,
+    pub claimable_periods: u32, // matured and not yet claimed
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SubscriptionsResponse {
+    pub subscriptions: Vec<Subscription>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PaymentResponse {
     pub payment: Payment,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PaymentsByIdsResponse {
+    pub payments: Vec<Payment>, // in request order
+    pub missing: Vec<u64>,      // requested ids with no payment visible to the viewer
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptResponse {
+    pub receipt: Option<Receipt>, // None when the payment is not visible to the viewer
+    pub hash: String,             // hex SHA-256 of the receipt's JSON encoding
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PaymentsResponse {
     pub payments: Vec<Payment>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+    pub total: Option<u64>,          // all matching records, where a counter tracks them; None for filtered queries
+    pub next_start_after: Option<u64>, // pass as start_after for the next page; None on the last page
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActivityFeedResponse {
+    pub payments: Vec<Payment>,       // newest first
+    pub next_before: Option<u64>,     // set when older payments may remain
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -222,7 +1138,382 @@ pub struct TaskResponse {
     pub task: Task,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TasksByIdsResponse {
+    pub tasks: Vec<Task>,  // in request order
+    pub missing: Vec<u64>, // requested ids with no task, including archived ones
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TaskBatchResponse {
+    pub batch: TaskBatch,
+    pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub missing: Vec<u64>, // batch tasks archived since, e.g. settled ones
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct TasksResponse {
     pub tasks: Vec<Task>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+    pub total: Option<u64>,          // all matching records, where a counter tracks them; None for filtered queries
+    pub next_start_after: Option<u64>, // pass as start_after for the next page; None on the last page
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnershipResponse {
+    pub owner: Addr,
+    pub pending_owner: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoleGrant {
+    pub address: Addr,
+    pub role: AdminRole,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RolesResponse {
+    pub owner: Addr,                 // implicitly holds every role
+    pub grants: Vec<RoleGrant>,
+    pub truncated: bool,             // more results exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    pub config: Config,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RegistrationFeesResponse {
+    pub config: Option<RegistrationFees>, // None while registration is free
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RegistrationFeeResponse {
+    pub username: String,
+    pub tier: Option<RegistrationFeeTier>, // Tier charged to register the username now; None if free
+    pub deposit_held: Option<Coin>,        // Refundable deposit held for the username if registered
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UsernameExpiryResponse {
+    pub config: Option<UsernameExpiry>, // None while registrations do not expire
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct YieldConfigResponse {
+    pub config: Option<YieldConfig>, // None while yield routing is disabled
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct YieldPositionResponse {
+    pub position: Option<YieldPosition>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EscrowLiabilitiesResponse {
+    pub liabilities: Vec<Coin>, // total held in escrow per denom
+    #[serde(default)]
+    pub losses: Vec<Coin>, // escrow principal yield adapters failed to return, per denom
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UserEscrowedResponse {
+    pub username: String,
+    pub escrowed: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IndexReport {
+    pub index: IndexKind,
+    pub primary_count: u64,
+    pub expected_entries: u64,
+    pub index_entries: u64,
+    pub in_sync: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AcceptedDenomsResponse {
+    pub accepted_denoms: AcceptedDenoms,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ArchiveRootResponse {
+    pub root: String, // empty until the first record is archived
+    pub archived_records: u64,
+    pub last_archived_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IndexReportResponse {
+    pub indexes: Vec<IndexReport>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvariantsResponse {
+    pub complete: bool, // every check scanned its whole map; otherwise raise the limit to check the rest
+    pub violations: Vec<InvariantViolation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvariantViolation {
+    pub invariant: String, // escrow_totals, friendship_symmetry or username_maps
+    pub key: String,       // denom, username pair or username the violation was found at
+    pub detail: String,
+}
+
+/// Message sent to a registered callback contract when its record reaches a terminal state
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CallbackExecuteMsg {
+    ProofpayCallback(CallbackPayload),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CallbackPayload {
+    pub kind: RecordKind,
+    pub id: u64,
+    pub status: String,
+    pub amount: Coin,
+    pub msg: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InsurancePoolResponse {
+    pub config: Option<InsuranceConfig>,
+    pub balances: Vec<Coin>,
+    pub open_claims: Vec<Coin>, // Largest possible payout of claims awaiting a decision, per denom
+    pub solvent: bool,          // Every open claim could be paid in full
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ArbitersResponse {
+    pub config: Option<ArbiterConfig>,
+    pub arbiters: Vec<ArbiterInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ArbiterInfo {
+    pub address: Addr,
+    pub arbiter: Arbiter,
+    pub eligible: bool, // would be considered for newly opened disputes
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SignerResponse {
+    pub pubkey: Option<Binary>,
+    pub next_nonce: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RelayersResponse {
+    pub relayers: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeatureFlagsResponse {
+    pub flags: FeatureFlags,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PolicyContractResponse {
+    pub policy: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DexRouterResponse {
+    pub router: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TaskTemplate {
+    pub amount: Coin, // per worker
+    pub description: String,
+    pub proof_type: ProofType,
+    pub deadline_ts: u64,
+    pub review_window_secs: Option<u64>,
+    pub endpoint: String,
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub escrow_upfront: bool, // required for soft tasks, since batch tasks are always escrowed
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceQuoteMsg {
+    pub currency: String, // e.g. "USD"
+    pub amount: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UsernameCollectionResponse {
+    pub collection: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UsernameTransferResponse {
+    pub transfer: Option<UsernameTransfer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceOracleResponse {
+    pub oracle: Option<Addr>,
+}
+
+/// Subset of the cw721 interface the username collection must implement, with this contract as minter
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw721ExecuteMsg {
+    Mint { token_id: String, owner: String, token_uri: Option<String>, extension: Empty },
+    TransferNft { recipient: String, token_id: String },
+}
+
+/// Hook a cw721 collection calls on this contract when a token is sent to it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw721ReceiveMsg {
+    pub sender: String,
+    pub token_id: String,
+    pub msg: Binary,
+}
+
+/// Interface a price oracle adapter (e.g. over Pyth or Slinky) must implement
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OracleQueryMsg {
+    Price { currency: String, denom: String }, // Returns OraclePriceResponse
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OraclePriceResponse {
+    pub price: Decimal,    // Base units of the denom per one unit of the currency
+    pub publish_time: u64, // Unix seconds the price was published
+}
+
+/// Interface a compliance policy contract must implement
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyQueryMsg {
+    // Returns PolicyCheckResponse
+    CheckTransfer {
+        sender: String,                   // wallet that signed the execute; the contract for a failed swap's fallback payout
+        action: String,                   // the execute's `action` attribute, e.g. "send_direct_payment"
+        funds: Vec<Coin>,                 // attached to the execute
+        transfers: Vec<PolicyTransfer>,   // bank sends and payout swaps the execute pays out, by final recipient
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PolicyTransfer {
+    pub recipient: String,
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PolicyCheckResponse {
+    pub allowed: bool,
+}
+
+/// Interface a DEX router contract must implement
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DexRouterExecuteMsg {
+    // Swap the attached funds into ask_denom and send the output to `to`; fail if it is below min_out
+    Swap { ask_denom: String, min_out: Uint128, to: String },
+}
+
+/// Interface a yield adapter contract must implement
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum YieldAdapterExecuteMsg {
+    Deposit { position_id: u64 },  // Funds attached to the message are the principal
+    Withdraw { position_id: u64 }, // Send the full position value back to the caller and close it
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum YieldAdapterQueryMsg {
+    Position { depositor: String, position_id: u64 }, // Returns AdapterPositionResponse
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdapterPositionResponse {
+    pub value: Coin, // Principal plus accrued yield, as paid out by Withdraw
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportEventsResponse {
+    pub events: Vec<ActivityEntry>,
+    pub latest_seq: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NotificationsResponse {
+    pub notifications: Vec<Notification>, // unacknowledged, oldest first
+    pub acked_up_to: u64,                 // highest acknowledged id; 0 if none
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AccountSnapshotResponse {
+    pub user: User,
+    pub friends: FriendsResponse,
+    pub pending_friend_requests: FriendRequestsResponse,
+    pub recent_payments: Vec<Payment>,         // newest first
+    pub next_payments_before: Option<u64>,     // set when older payments remain
+    pub recent_tasks: Vec<Task>,               // newest first
+    pub next_tasks_before: Option<u64>,        // set when older tasks remain
+    pub escrowed: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OpenItemsResponse {
+    pub friend_requests: u64,  // pending friend requests the user sent
+    pub payment_requests: u64, // payment requests the user created that are pending or awaiting proof review
+    pub tasks: u64,            // tasks the user pays for that are not yet released or refunded
+    pub limits: OpenItemLimits,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActionItem {
+    pub kind: ActionItemKind,
+    pub id: Option<u64>,          // payment or task id; None for friend requests, which are keyed by the counterparty
+    pub counterparty: String,     // username on the other side of the item
+    pub amount: Option<Coin>,     // None for friend requests
+    pub deadline: Option<u64>,    // when the item lapses or settles without the user, if it does
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActionItemsResponse {
+    pub items: Vec<ActionItem>,      // friend requests, then tasks, then payments newest first
+    pub truncated: bool,             // more items exist beyond the query cap
+    pub scan_budget_exhausted: bool, // the query stopped scanning before reaching the end
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ContractInfoResponse {
+    pub contract: String, // cw2 contract name
+    pub version: String,  // cw2 contract version
+    pub owner: Addr,
+    pub pending_owner: Option<Addr>,
+    pub compiled_features: Vec<String>, // optional cargo features this build was compiled with
+    pub feature_flags: FeatureFlags,
+    pub registration_fees: Option<RegistrationFees>, // None while registration is free
+    pub users: u64,    // registered users
+    pub payments: u64, // payments ever created, including archived ones
+    pub tasks: u64,    // tasks ever created, including archived ones
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ErrorCode {
+    pub code: u32,
+    pub name: String,    // ContractError variant, e.g. "UserNotFound"
+    pub message: String, // text of the failed transaction's error, prefixed with the code as "[E0102] "
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ErrorCodesResponse {
+    pub errors: Vec<ErrorCode>, // in code order; wrapped standard errors are code 1 and not listed
 }
@@ -1,25 +1,180 @@
-use cosmwasm_std::{Addr, Coin};
+use cosmwasm_std::{Addr, Attribute, Binary, Coin, Decimal, Uint128};
 use cw_storage_plus::{Item, Map};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
-    pub owner: Addr,
+    pub owner: Addr,             // May be a multisig (e.g. cw3) contract address
+    #[serde(default)]
+    pub pending_owner: Option<Addr>, // Proposed owner awaiting acceptance
     pub next_payment_id: u64,
     pub next_task_id: u64,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Config {
+    #[serde(default)]
+    pub co_approval_thresholds: Vec<Coin>, // Tasks above these amounts (per denom) need payer-designated co-approvers
+    #[serde(default)]
+    pub review_window: ReviewWindowBounds,
+    #[serde(default)]
+    pub archive_retention_secs: Option<u64>, // Settled records become archivable after this long; None = 90 days
+    #[serde(default)]
+    pub accepted_denoms: AcceptedDenoms,
+    #[serde(default)]
+    pub instant_release: InstantReleaseConfig,
+    #[serde(default)]
+    pub friend_requests: FriendRequestPolicy,
+    #[serde(default)]
+    pub text_limits: TextLimits,
+    #[serde(default)]
+    pub open_item_limits: OpenItemLimits,
+}
+
+// Most open records one user may have at a time, so a single account cannot bloat state
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OpenItemLimits {
+    pub friend_requests: u64,  // Pending friend requests sent
+    pub payment_requests: u64, // Payment requests created and not yet settled
+    pub tasks: u64,            // Tasks paid for that are not yet released or refunded
+}
+
+impl Default for OpenItemLimits {
+    fn default() -> Self {
+        OpenItemLimits {
+            friend_requests: 50,
+            payment_requests: 100,
+            tasks: 100,
+        }
+    }
+}
+
+// Maximum characters of free text stored with payments and tasks
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TextLimits {
+    pub description: u32, // Payment, task, link, schedule, group expense and invoice line item descriptions
+    pub evidence: u32,    // Proof data, proof references and hashes, soft evidence and dispute reason hashes
+    pub endpoint: u32,    // Task endpoints
+}
+
+impl Default for TextLimits {
+    fn default() -> Self {
+        TextLimits {
+            description: 500,
+            evidence: 2048,
+            endpoint: 256,
+        }
+    }
+}
+
+// Declined friend requests can be sent again after a cooldown, until too many are declined
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FriendRequestPolicy {
+    pub cooldown_secs: u64, // Wait after a decline before the sender can request again
+    pub max_declines: u32,  // Declines after which the sender can no longer request
+}
+
+impl Default for FriendRequestPolicy {
+    fn default() -> Self {
+        FriendRequestPolicy {
+            cooldown_secs: 7 * 24 * 60 * 60,
+            max_declines: 3,
+        }
+    }
+}
+
+// Hybrid tasks from trusted workers skip the review window; no max amounts disables it
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct InstantReleaseConfig {
+    pub min_reputation: u64,   // Worker reputation needed to qualify
+    pub max_amounts: Vec<Coin>, // Largest task amount per denom that releases instantly
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AcceptedDenoms {
+    #[default]
+    AllowAll,         // Any denom may be paid or escrowed
+    Only(Vec<String>), // Payments, tasks and campaigns must use one of these denoms
+}
+
+// Applied to hybrid tasks at creation; None leaves that side unconstrained
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ReviewWindowBounds {
+    pub default_secs: Option<u64>, // Used when CreateTask omits review_window_secs
+    pub min_secs: Option<u64>,
+    pub max_secs: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct User {
     pub wallet_address: Addr,
     pub username: String,
     pub display_name: String,
     pub profile_picture: Option<String>,
+    #[serde(default)]
+    pub require_friendship_for_requests: bool, // Reject payment requests and tasks from non-friends
+    #[serde(default)]
+    pub default_visibility: Visibility, // Applied to payments created without an explicit visibility
+    #[serde(default)]
+    pub pubkey: Option<Binary>,  // secp256k1 key proven with RegisterPubkey; signs relayed actions
+    #[serde(default)]
+    pub tokenized: bool,         // Minted in the username collection; the NFT can move the handle to another wallet
+    #[serde(default)]
+    pub badge: Option<Badge>,    // Set by an attestor; shown next to the username
+    #[serde(default)]
+    pub bio: Option<String>,
+    #[serde(default)]
+    pub links: Vec<String>,      // https:// URLs, e.g. website and social profiles
+    #[serde(default)]
+    pub country: Option<String>, // ISO 3166-1 alpha-2 code, e.g. "DE"
+    #[serde(default)]
+    pub timezone: Option<String>, // IANA name, e.g. "Europe/Berlin"
+    #[serde(default)]
+    pub preferred_denom: Option<String>, // Task payouts in other denoms are swapped into it when a DEX router is set
+    #[serde(default)]
+    pub payout_slippage_bps: Option<u16>, // Most a preferred-denom swap may fall below the oracle price; None takes the default
+    #[serde(default)]
+    pub expires_at: Option<u64>, // Registration expiry; None never expires
     pub created_at: u64,
     pub updated_at: u64,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub enum Badge {
+    Verified,         // Identity confirmed
+    Merchant,         // Known business accepting payments
+}
+
+// Fee or refundable deposit charged to register a username, by username length
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RegistrationFees {
+    pub tiers: Vec<RegistrationFeeTier>, // Ascending by max_length; usernames longer than every tier register free
+    pub treasury: Addr,                  // Receives non-refundable fees
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RegistrationFeeTier {
+    pub max_length: u32,         // Applies to usernames of up to this many characters not covered by a shorter tier
+    pub amount: Coin,
+    pub refundable: bool,        // Held by the contract and returned on deactivation instead of sent to the treasury
+}
+
+// Registrations last a term and can be renewed until the grace period after expiry ends
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UsernameExpiry {
+    pub term_secs: u64,          // Length of a registration or renewal
+    pub grace_secs: u64,         // After expiry the owner can only renew; then the username is released
+}
+
+// A tokenized username handed to the contract by a new holder, re-pointed once the delay passes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UsernameTransfer {
+    pub to_wallet: Addr,
+    pub effective_at: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Friendship {
     pub user1: String, // username
@@ -32,10 +187,19 @@ pub struct FriendRequest {
     pub from_username: String,
     pub to_username: String,
     pub status: FriendRequestStatus,
+    #[serde(default)]
+    pub message: Option<String>, // Note from the sender, e.g. where you met
     pub created_at: u64,
     pub updated_at: u64,
 }
 
+// Kept when a declined request is deleted, so repeat requests can be limited
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FriendRequestDeclines {
+    pub count: u32,
+    pub last_declined_at: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub enum FriendRequestStatus {
     Pending,
@@ -44,18 +208,273 @@ pub enum FriendRequestStatus {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct Payment {
+pub struct FriendGroup {
+    pub id: u64,
+    pub owner: String,           // username
+    pub name: String,            // unique per owner
+    pub members: Vec<String>,    // usernames, including the owner
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GroupExpense {
+    pub id: u64,
+    pub group_id: u64,
+    pub payer: String,           // username
+    pub amount: Coin,
+    pub description: String,
+    pub participants: Vec<String>, // usernames sharing the cost, possibly including the payer
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GroupBalance {
+    pub username: String,
+    pub denom: String,
+    pub paid: Uint128,           // paid on behalf of the group since the last settlement
+    pub owed: Uint128,           // share of the group's expenses since the last settlement
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Campaign {
+    pub id: u64,
+    pub creator: String,         // username
+    pub beneficiary: String,     // username
+    pub title: String,
+    pub goal: Coin,
+    pub raised: Uint128,         // donations currently held, net of refunds
+    pub deadline: u64,
+    pub all_or_nothing: bool,    // donors are refunded if the goal is missed by the deadline
+    pub status: CampaignStatus,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum CampaignStatus {
+    Active,           // Accepting donations until the deadline
+    Withdrawn,        // Funds paid out to the beneficiary
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Subscription {
+    pub id: u64,
+    pub payer: String,           // username
+    pub recipient: String,       // username
+    pub amount: Coin,            // paid per period
+    pub period: u64,             // seconds
+    pub periods_funded: u32,     // prepaid into escrow at creation
+    pub periods_claimed: u32,    // paid out to the recipient
+    pub start_ts: u64,           // period i matures at start_ts + i * period
+    pub status: SubscriptionStatus,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum SubscriptionStatus {
+    Active,           // Periods mature and can be claimed
+    Cancelled,        // Cancelled; unmatured periods refunded to the payer
+    Completed,        // Every funded period has been claimed
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Invoice {
+    pub id: u64,
+    pub issuer: String,          // username being paid
+    pub payer: String,           // username billed
+    pub line_items: Vec<InvoiceLineItem>,
+    pub total: Coin,             // sum of the line items
+    pub paid: Uint128,           // applied to line items in order
+    pub due_ts: u64,
+    pub status: InvoiceStatus,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvoiceLineItem {
+    pub description: String,
+    pub amount: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum InvoiceStatus {
+    Open,             // Accepting payments until paid in full
+    Paid,             // Paid in full
+    Cancelled,        // Cancelled by the issuer; earlier partial payments stay with the issuer
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PaymentLink {
+    pub code: String,            // handed out, e.g. as a QR code, in place of the merchant's username
+    pub merchant: String,        // username being paid
+    pub amount: Option<Coin>,    // None lets the payer choose the amount
+    pub description: String,     // copied onto every payment made through the link
+    pub max_uses: Option<u32>,   // None allows unlimited uses
+    pub uses: u32,
+    pub expires_at: Option<u64>,
+    pub active: bool,            // false once disabled by the merchant
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ScheduledPayment {
     pub id: u64,
     pub from_username: String,
     pub to_username: String,
+    pub amount: Coin,            // escrowed when scheduled
+    pub description: String,
+    pub execute_at: u64,         // anyone may execute the payment from this time
+    pub status: ScheduledPaymentStatus,
+    pub payment_id: Option<u64>, // payment record created on execution
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+// Funds escrowed behind a secret code, claimable by whoever presents it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GiftCode {
+    pub id: u64,
+    pub creator: String,         // username; escrowed under the creator until claimed or refunded
+    pub amount: Coin,
+    pub code_hash: String,       // hex SHA-256 of the code; the code itself is only revealed by the claim
+    pub expires_at: u64,         // claimable before this, refundable to the creator from it
+    pub status: GiftCodeStatus,
+    pub claimed_by: Option<Addr>, // wallet paid by the claim, registered or not
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum GiftCodeStatus {
+    Active,           // Escrowed until claimed, or refunded after expiry
+    Claimed,
+    Refunded,
+}
+
+// Hash-timelocked payment, one leg of an atomic swap with another chain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Htlc {
+    pub id: u64,
+    pub from_username: String,   // escrowed under the sender until claimed or refunded
+    pub to_username: String,
+    pub amount: Coin,
+    pub hashlock: String,        // hex SHA-256 of the preimage's bytes
+    pub timelock: u64,           // claimable before this, refundable to the sender from it
+    pub status: HtlcStatus,
+    pub preimage: Option<String>, // hex preimage revealed by the claim, for the other leg of the swap
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum HtlcStatus {
+    Locked,           // Escrowed until claimed with the preimage, or refunded after the timelock
+    Claimed,
+    Refunded,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum ScheduledPaymentStatus {
+    Pending,          // Escrowed until executed or cancelled
+    Executed,         // Paid out to the recipient
+    Cancelled,        // Cancelled by the sender and refunded
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Payment {
+    pub id: u64,
+    pub from_username: String,
+    pub to_username: String,     // Empty while a request to `to_wallet` waits for the wallet to register
+    #[serde(default)]
+    pub to_wallet: Option<Addr>, // Payment requests to a wallet without a username; cleared once bound
     pub amount: Coin,
     pub description: String,
     pub payment_type: PaymentType,
     pub proof_type: ProofType,
     pub proof_data: Option<String>,
     pub status: PaymentStatus,
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub redacted: bool,          // Free text replaced by its SHA-256 hash
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(default)]
+    pub price_quote: Option<PriceQuote>, // Payment requests: amount is resolved from this quote at approval
+    #[serde(default)]
+    pub external_ref: Option<String>, // Creator-supplied reference, e.g. an invoice number; unique per creator
+    #[serde(default)]
+    pub reminder_count: u32,     // Reminders sent for a pending payment request
+    #[serde(default)]
+    pub last_reminded_at: Option<u64>,
+    #[serde(default)]
+    pub release_delay_secs: Option<u64>, // Sender's cooling-off period between approval and release of escrowed funds
+    #[serde(default)]
+    pub release_at: Option<u64>, // Set at approval when a delay holds the funds; the sender can dispute until then
     pub created_at: u64,
     pub updated_at: u64,
+    #[serde(default)]
+    pub created_at_height: u64,  // Block heights order records created or updated in the same second
+    #[serde(default)]
+    pub updated_at_height: u64,
+}
+
+// Conversion of a task's escrow into another denom through the DEX router when it is released to the worker
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PayoutSwap {
+    pub ask_denom: String, // Denom the worker receives
+    pub min_out: Uint128,  // Least of ask_denom the worker accepts; below it the escrow is paid unconverted
+}
+
+// A payout swap dispatched to the DEX router, waiting for its reply
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingPayoutSwap {
+    pub task_id: u64,
+    pub recipient: Addr,
+    pub amount: Coin, // Escrow offered to the router, paid to the recipient as-is if the swap fails
+}
+
+// Price set in a reference currency; the record's denom is the settlement token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceQuote {
+    pub currency: String,        // Reference currency, e.g. "USD"
+    pub amount: Decimal,         // Price in the reference currency
+    pub resolved_price: Option<Decimal>, // Oracle price (denom base units per currency unit) used at approval
+    pub resolved_at: Option<u64>,
+}
+
+// Canonical record of a settled payment; its receipt hash is the SHA-256 of its JSON encoding
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Receipt {
+    pub payment_id: u64,
+    pub from_username: String,
+    pub to_username: String,
+    pub amount: Coin,
+    pub created_at: u64,
+    pub settled_at: u64,
+    pub proof_hash: Option<String>, // Hex SHA-256 of the submitted proof
+    pub resolution: PaymentStatus,  // Completed, Rejected or Cancelled
+    pub visibility: Visibility,     // Who GetReceipt reveals the receipt to; the hash is public
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, JsonSchema)]
+pub enum Visibility {
+    #[default]
+    Public,           // Anyone
+    Friends,          // The two parties and friends of either, e.g. in the activity feed
+    Private,          // Only the two parties
+}
+
+// Flows switched off by ops, e.g. after an incident, without pausing the whole contract
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct FeatureFlags {
+    pub disabled_payment_types: Vec<PaymentType>, // Rejected when a payment or request is created
+    pub disabled_proof_types: Vec<ProofType>,     // Rejected when a payment or task is created or a proof submitted
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -71,6 +490,8 @@ pub enum PaymentStatus {
     Completed,        // Payment completed
     Rejected,         // Payment rejected
     Cancelled,        // Payment cancelled
+    PendingRelease,   // Approved; escrow is held until the sender's release delay passes
+    Disputed,         // Frozen by the sender during the release delay, waiting for a dispute resolver
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -91,8 +512,80 @@ pub enum TaskStatus {
     ProofSubmitted,   // Proof submitted, waiting for processing
     PendingRelease,   // Hybrid mode: waiting for dispute window to expire
     Released,         // Task completed, payment sent
+    ApprovedPendingFunding, // Soft mode: auto-approved after payer silence, awaiting payer funds
     Disputed,         // Task under dispute
     Refunded,         // Task expired/cancelled, funds returned
+    Blocked,          // Escrowed, waiting for the task it depends on to be released
+}
+
+impl TaskStatus {
+    pub fn key(&self) -> &'static str {
+        match self {
+            TaskStatus::Escrowed => "escrowed",
+            TaskStatus::ProofSubmitted => "proof_submitted",
+            TaskStatus::PendingRelease => "pending_release",
+            TaskStatus::Released => "released",
+            TaskStatus::ApprovedPendingFunding => "approved_pending_funding",
+            TaskStatus::Disputed => "disputed",
+            TaskStatus::Refunded => "refunded",
+            TaskStatus::Blocked => "blocked",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub enum DisputeReason {
+    NonDelivery,      // The work was never delivered
+    QualityIssue,     // Delivered, but not to the agreed standard
+    WrongEndpoint,    // The proof verifies a different endpoint than agreed
+    FraudulentProof,  // The proof was fabricated or replayed
+    Other,
+}
+
+impl DisputeReason {
+    pub const ALL: [DisputeReason; 5] = [
+        DisputeReason::NonDelivery,
+        DisputeReason::QualityIssue,
+        DisputeReason::WrongEndpoint,
+        DisputeReason::FraudulentProof,
+        DisputeReason::Other,
+    ];
+    
+    pub fn key(&self) -> &'static str {
+        match self {
+            DisputeReason::NonDelivery => "non_delivery",
+            DisputeReason::QualityIssue => "quality_issue",
+            DisputeReason::WrongEndpoint => "wrong_endpoint",
+            DisputeReason::FraudulentProof => "fraudulent_proof",
+            DisputeReason::Other => "other",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub enum DisputeOutcome {
+    WorkerAwarded,    // The whole escrow went to the worker
+    PayerRefunded,    // The whole escrow went back to the payer
+    Split,            // The escrow was divided between both parties
+    AdminCancelled,   // Cancelled by an admin intervention and refunded
+}
+
+impl DisputeOutcome {
+    pub const ALL: [DisputeOutcome; 4] = [
+        DisputeOutcome::WorkerAwarded,
+        DisputeOutcome::PayerRefunded,
+        DisputeOutcome::Split,
+        DisputeOutcome::AdminCancelled,
+    ];
+    
+    pub fn key(&self) -> &'static str {
+        match self {
+            DisputeOutcome::WorkerAwarded => "worker_awarded",
+            DisputeOutcome::PayerRefunded => "payer_refunded",
+            DisputeOutcome::Split => "split",
+            DisputeOutcome::AdminCancelled => "admin_cancelled",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -107,29 +600,375 @@ pub struct Task {
     pub review_window_secs: Option<u64>, // For hybrid mode dispute window
     pub endpoint: String,        // API endpoint for zkTLS verification
     pub evidence_hash: Option<String>,   // Hash of evidence for soft mode
+    #[serde(default)]
+    pub evidence_submitted_at: Option<u64>, // When soft mode evidence was last submitted
+    #[serde(default)]
+    pub auto_approve_after_secs: Option<u64>, // Soft mode: payer silence window after evidence
+    #[serde(default)]
+    pub escrow_upfront: bool,    // Soft mode: funded at creation and held in escrow
+    #[serde(default)]
+    pub co_approvers: Vec<String>,       // usernames the payer designated to co-approve release
+    #[serde(default)]
+    pub approvals_required: u32,         // Co-approvals needed before release (0 = none)
+    #[serde(default)]
+    pub approvals: Vec<String>,          // Co-approvers who have approved so far
+    #[serde(default)]
+    pub cancel_requested_by: Option<String>, // Party waiting for the other to agree to cancel
+    #[serde(default)]
+    pub accepted_at: Option<u64>,        // When the worker accepted the task or its counter-offer
+    #[serde(default)]
+    pub counter_offer: Option<Coin>,     // Higher amount the worker asked for, pending payer acceptance
+    #[serde(default)]
+    pub price_quote: Option<PriceQuote>, // Soft tasks funded at approval: amount is resolved from this quote
+    #[serde(default)]
+    pub external_ref: Option<String>, // Payer-supplied reference, e.g. a purchase order number; unique per payer
+    #[serde(default)]
+    pub pending_amendment: Option<TaskAmendment>, // Scope change proposed by the payer
+    #[serde(default)]
+    pub amendments: Vec<TaskAmendment>,  // Accepted scope changes, oldest first
+    #[serde(default)]
+    pub insurance: Option<TaskInsurance>, // Cover bought from the insurance pool
+    #[serde(default)]
+    pub dispute_award_bps: Option<u16>,  // Worker's share of the escrow awarded when a dispute was resolved
+    #[serde(default)]
+    pub dispute_reason: Option<DisputeReason>,
+    #[serde(default)]
+    pub dispute_outcome: Option<DisputeOutcome>,
+    #[serde(default)]
+    pub arbiter: Option<Addr>,           // Bonded arbiter assigned to resolve the dispute
+    #[serde(default)]
+    pub agreed_arbiter: Option<Addr>,    // Arbiter named at creation; the only one who can resolve its dispute
+    #[serde(default)]
+    pub arbiter_accepted_at: Option<u64>, // Proofs are refused until the agreed arbiter accepts
+    #[serde(default)]
+    pub depends_on: Option<u64>,         // Task of the same payer that must be released before this one starts
+    #[serde(default)]
+    pub batch_id: Option<u64>,           // Batch the task was created in by CreateTaskBatch
+    #[serde(default)]
+    pub payout_swap: Option<PayoutSwap>, // Convert the escrow when released to the worker in full
+    #[serde(default)]
+    pub release_delay_secs: Option<u64>, // Payer's cooling-off period between proof or approval and release
     pub zk_proof_hash: Option<String>,   // Hash of zkTLS proof
     pub verified_at: Option<u64>,        // When proof was verified
     pub verifier_id: Option<String>,     // ID of verifier (if any)
     pub description: String,
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub redacted: bool,          // Free text replaced by its SHA-256 hash
     pub created_at: u64,
     pub updated_at: u64,
+    #[serde(default)]
+    pub created_at_height: u64,  // 0 on tasks stored before heights were recorded
+    #[serde(default)]
+    pub updated_at_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TaskAmendment {
+    pub additional_amount: Option<Coin>, // Added to the task amount; escrowed tasks hold it from proposal
+    pub description_hash: Option<String>, // Hash of the revised scope
+    pub deadline_ts: Option<u64>,        // Replaces the task deadline
+    pub proposed_at: u64,
+    pub accepted_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TaskInsurance {
+    pub premium: Coin,           // Paid into the insurance pool
+    pub max_payout: Coin,        // Claim cap, fixed when the task was insured
+    pub claim_open: bool,        // Dispute resolved against the worker; awaiting the arbiter's decision
+    pub payout: Option<Coin>,    // Compensation paid to the worker, if any
+}
+
+// Tasks created together from one template by CreateTaskBatch
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TaskBatch {
+    pub id: u64,
+    pub payer: String,           // username
+    pub task_ids: Vec<u64>,      // one per worker, in the order the workers were given
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ArbiterConfig {
+    pub bond_denom: String,      // Arbiters bond in this denom; their bond is their selection weight
+    pub min_bond: Uint128,       // Smaller bonds are not selected
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Arbiter {
+    pub bond: Coin,
+    pub open_disputes: u32,      // Disputes assigned and not yet settled; the bond is locked while any are open
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InsuranceConfig {
+    pub premium_bps: u16,        // Premium charged on the task amount when insuring
+    pub max_payout_bps: u16,     // Largest compensation, as a share of the task amount
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminRole {
+    DisputeResolver,  // Resolve task disputes and intervene in open payments and tasks
+    ConfigManager,    // Update contract configuration
+    DenylistManager,  // Manage denylisted users and addresses
+    Treasurer,        // Withdraw collected fees
+    Attestor,         // Set and clear user badges, e.g. a KYC or merchant attestation contract
+}
+
+impl AdminRole {
+    pub fn key(&self) -> &'static str {
+        match self {
+            AdminRole::DisputeResolver => "dispute_resolver",
+            AdminRole::ConfigManager => "config_manager",
+            AdminRole::DenylistManager => "denylist_manager",
+            AdminRole::Treasurer => "treasurer",
+            AdminRole::Attestor => "attestor",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct YieldConfig {
+    pub adapter: Addr,           // Contract implementing the yield adapter interface
+    pub denom: String,           // Only escrows in this denom are deposited
+    pub threshold: Uint128,      // Minimum task amount deposited to the adapter
+    pub treasury: Addr,          // Receives the protocol share of accrued yield
+    pub user_share_bps: u16,     // Share of yield paid to the recipient of the principal
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct YieldPosition {
+    pub adapter: Addr,           // Adapter holding the funds; positions keep the terms they were opened with
+    pub principal: Coin,
+    pub treasury: Addr,
+    pub user_share_bps: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub enum RecordKind {
+    Payment,
+    Task,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IdempotencyRecord {
+    pub kind: RecordKind,
+    pub id: u64,                 // Payment or task created with the key
+    pub expires_at: u64,         // The key may create a new record after this
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Callback {
+    pub contract_addr: Addr,
+    pub msg: Option<Binary>,     // Opaque data passed back to the receiver
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum NotificationKind {
+    FriendRequestReceived,
+    PaymentRequestReceived,
+    PaymentRequestReminder,
+    PaymentProofSubmitted,
+    TaskProofSubmitted,
+    TaskDisputed,
+    InvoiceReceived,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Notification {
+    pub id: u64,                 // Global, monotonically increasing
+    pub kind: NotificationKind,
+    pub from_username: String,   // User whose action triggered the notification
+    pub record_id: Option<u64>,  // Payment, task or invoice id, per kind
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ArchiveState {
+    pub root: String,            // Hex SHA-256 hash chain over archived records; empty before the first
+    pub archived_records: u64,
+    pub last_archived_at: Option<u64>,
+    pub payments_cursor: u64,    // Last payment id scanned; 0 starts over from the first
+    pub tasks_cursor: u64,       // Last task id scanned; 0 starts over from the first
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActivityEntry {
+    pub seq: u64,                // Global, monotonically increasing sequence number
+    pub action: String,
+    pub sender: Addr,
+    pub attributes: Vec<Attribute>,
+    pub block_height: u64,
+    pub timestamp: u64,
 }
 
 // Storage Maps
 pub const STATE: Item<State> = Item::new("state");
+pub const CONFIG: Item<Config> = Item::new("config"); // absent until first updated; load with unwrap_or_default
 
 // User Management
 pub const USERS_BY_USERNAME: Map<String, User> = Map::new("users_by_username");
 pub const USERS_BY_WALLET: Map<Addr, String> = Map::new("users_by_wallet"); // wallet -> username
+pub const RETIRED_USERNAMES: Map<String, u64> = Map::new("retired_usernames"); // username -> deactivated at; never reissued
+pub const USER_COUNT: Item<u64> = Item::new("user_count"); // registered users
+
+// Registration Fees (registration is free until the schedule is set)
+pub const REGISTRATION_FEES: Item<RegistrationFees> = Item::new("registration_fees");
+pub const REGISTRATION_DEPOSITS: Map<String, Coin> = Map::new("registration_deposits"); // username -> refundable deposit held
+
+// Username Expiry (registrations do not expire until the policy is set)
+pub const USERNAME_EXPIRY: Item<UsernameExpiry> = Item::new("username_expiry");
+pub const USERNAME_EXPIRIES: Map<(u64, String), bool> = Map::new("username_expiries"); // (expires_at, username) -> exists while the registration can expire
+
+// Username NFTs (usernames cannot be tokenized until the collection is set)
+pub const USERNAME_COLLECTION: Item<Addr> = Item::new("username_collection");
+pub const USERNAME_TRANSFERS: Map<String, UsernameTransfer> = Map::new("username_transfers"); // username -> pending re-point
 
 // Friends System
 pub const FRIENDSHIPS: Map<(String, String), Friendship> = Map::new("friendships");
 pub const FRIEND_REQUESTS: Map<(String, String), FriendRequest> = Map::new("friend_requests");
+pub const FRIEND_REQUEST_DECLINES: Map<(String, String), FriendRequestDeclines> = Map::new("friend_request_declines"); // (from, to) -> declines so far
+pub const PENDING_FRIEND_REQUESTS: Map<(String, String), bool> = Map::new("pending_friend_requests"); // (to, from) -> exists while pending
+pub const PENDING_FRIEND_REQUEST_COUNTS: Map<String, u64> = Map::new("pending_friend_request_counts"); // recipient -> pending requests
+pub const SENT_FRIEND_REQUEST_COUNTS: Map<String, u64> = Map::new("sent_friend_request_counts"); // sender -> pending requests
+pub const COUNTERPARTIES: Map<(String, String), u64> = Map::new("counterparties"); // (username, counterparty) -> last completed payment or task between them
+
+// Friend Groups
+pub const GROUP_SEQ: Item<u64> = Item::new("group_seq"); // last assigned group id
+pub const GROUPS: Map<u64, FriendGroup> = Map::new("groups");
+pub const GROUP_NAMES: Map<(String, String), u64> = Map::new("group_names"); // (owner, name) -> group_id
+pub const USER_GROUPS: Map<(String, u64), bool> = Map::new("user_groups"); // (member username, group_id) -> exists
+pub const GROUP_EXPENSE_SEQ: Item<u64> = Item::new("group_expense_seq"); // last assigned expense id
+pub const GROUP_EXPENSES: Map<(u64, u64), GroupExpense> = Map::new("group_expenses"); // (group_id, expense_id)
+pub const GROUP_BALANCES: Map<(u64, String, String), GroupBalance> = Map::new("group_balances"); // (group_id, username, denom)
+pub const GROUP_SETTLEMENTS: Map<(u64, u64), bool> = Map::new("group_settlements"); // (group_id, payment_id) -> open settle-up request
+pub const SETTLEMENT_GROUPS: Map<u64, u64> = Map::new("settlement_groups"); // payment_id -> group_id
+
+// Campaigns
+pub const CAMPAIGN_SEQ: Item<u64> = Item::new("campaign_seq"); // last assigned campaign id
+pub const CAMPAIGNS: Map<u64, Campaign> = Map::new("campaigns");
+pub const CAMPAIGN_DONATIONS: Map<(u64, String), Uint128> = Map::new("campaign_donations"); // (campaign_id, donor) -> amount
+
+// Subscriptions
+pub const SUBSCRIPTION_SEQ: Item<u64> = Item::new("subscription_seq"); // last assigned subscription id
+pub const SUBSCRIPTIONS: Map<u64, Subscription> = Map::new("subscriptions");
+pub const USER_SUBSCRIPTIONS: Map<(String, u64), bool> = Map::new("user_subscriptions"); // (payer or recipient, subscription_id) -> exists
+
+// Invoices
+pub const INVOICE_SEQ: Item<u64> = Item::new("invoice_seq"); // last assigned invoice id
+pub const INVOICES: Map<u64, Invoice> = Map::new("invoices");
+pub const USER_INVOICES: Map<(String, u64), bool> = Map::new("user_invoices"); // (issuer or payer, invoice_id) -> exists
+
+// Payment Links
+pub const PAYMENT_LINK_SEQ: Item<u64> = Item::new("payment_link_seq"); // last assigned link number, hashed into the code
+pub const PAYMENT_LINKS: Map<String, PaymentLink> = Map::new("payment_links"); // code -> link
+pub const USER_PAYMENT_LINKS: Map<(String, String), bool> = Map::new("user_payment_links"); // (merchant, code) -> exists
+
+// Scheduled Payments
+pub const SCHEDULED_PAYMENT_SEQ: Item<u64> = Item::new("scheduled_payment_seq"); // last assigned scheduled payment id
+pub const SCHEDULED_PAYMENTS: Map<u64, ScheduledPayment> = Map::new("scheduled_payments");
+pub const USER_SCHEDULED_PAYMENTS: Map<(String, u64), bool> = Map::new("user_scheduled_payments"); // (sender or recipient, scheduled_id) -> exists
+pub const PENDING_SCHEDULED_PAYMENTS: Map<(u64, u64), bool> = Map::new("pending_scheduled_payments"); // (execute_at, scheduled_id) -> exists while pending
+
+// Gift Codes
+pub const GIFT_CODE_SEQ: Item<u64> = Item::new("gift_code_seq"); // last assigned gift code id
+pub const GIFT_CODES: Map<u64, GiftCode> = Map::new("gift_codes");
+pub const ACTIVE_GIFT_CODE_HASHES: Map<String, u64> = Map::new("active_gift_code_hashes"); // code hash -> gift id while active
+pub const USER_GIFT_CODES: Map<(String, u64), bool> = Map::new("user_gift_codes"); // (creator, gift id) -> exists
+pub const HTLC_SEQ: Item<u64> = Item::new("htlc_seq"); // last assigned HTLC id
+pub const HTLCS: Map<u64, Htlc> = Map::new("htlcs");
+pub const USER_HTLCS: Map<(String, u64), bool> = Map::new("user_htlcs"); // (sender or recipient, htlc id) -> exists
 
 // Payment System
 pub const PAYMENTS: Map<u64, Payment> = Map::new("payments");
 pub const USER_PAYMENTS: Map<(String, u64), bool> = Map::new("user_payments"); // (username, payment_id) -> exists
+pub const USER_PAYMENT_COUNTS: Map<String, u64> = Map::new("user_payment_counts"); // username -> entries in user_payments
+pub const WALLET_PAYMENT_REQUESTS: Map<(Addr, u64), bool> = Map::new("wallet_payment_requests"); // (unregistered payer wallet, payment_id) -> awaiting its username
+pub const WALLET_PAYMENT_REQUEST_COUNTS: Map<(String, Addr), u64> = Map::new("wallet_payment_request_counts"); // (requester username, unregistered payer wallet) -> open requests
+pub const PAYMENT_EXTERNAL_REFS: Map<(String, String), u64> = Map::new("payment_external_refs"); // (creator username, external_ref) -> payment_id
+pub const RECEIPTS: Map<u64, Receipt> = Map::new("receipts"); // kept when the payment is archived
+pub const RECEIPT_HASHES: Map<u64, String> = Map::new("receipt_hashes"); // payment_id -> hex receipt hash
+pub const OPEN_PAYMENT_REQUEST_COUNTS: Map<String, u64> = Map::new("open_payment_request_counts"); // creator -> pending or proof-submitted requests
+pub const OPEN_PAYMENTS: Map<(String, String, u64), bool> = Map::new("open_payments"); // (party username, counterparty username, payment_id) -> pending or proof-submitted payment
 
 // Task System
 pub const TASKS: Map<u64, Task> = Map::new("tasks");
 pub const USER_TASKS: Map<(String, u64), bool> = Map::new("user_tasks"); // (username, task_id) -> exists
+pub const USER_TASK_COUNTS: Map<String, u64> = Map::new("user_task_counts"); // username -> entries in user_tasks
+pub const OPEN_TASK_COUNTS: Map<String, u64> = Map::new("open_task_counts"); // payer -> tasks not yet released or refunded
+pub const TASKS_BY_STATUS: Map<(String, u64), bool> = Map::new("tasks_by_status"); // (status key, task_id) -> exists
+pub const USER_TASKS_BY_STATUS: Map<(String, String, u64), bool> = Map::new("user_tasks_by_status"); // (username, status key, task_id) -> exists
+pub const TASK_DEPENDENTS: Map<(u64, u64), bool> = Map::new("task_dependents"); // (task_id, blocked dependent task_id) -> exists
+pub const TASK_BATCH_SEQ: Item<u64> = Item::new("task_batch_seq"); // last assigned task batch id
+pub const TASK_BATCHES: Map<u64, TaskBatch> = Map::new("task_batches");
+pub const TASK_EXTERNAL_REFS: Map<(String, String), u64> = Map::new("task_external_refs"); // (payer username, external_ref) -> task_id
+
+// Dispute Stats
+pub const DISPUTE_REASON_COUNTS: Map<String, u64> = Map::new("dispute_reason_counts"); // reason key -> disputes opened
+pub const DISPUTE_OUTCOME_COUNTS: Map<String, u64> = Map::new("dispute_outcome_counts"); // outcome key -> disputes settled
+
+// Worker Reputation
+pub const WORKER_REPUTATION: Map<String, u64> = Map::new("worker_reputation"); // worker username -> tasks paid in full since the last dispute lost
+
+// Admin Roles
+pub const ADMIN_ROLES: Map<(Addr, String), AdminRole> = Map::new("admin_roles"); // (address, role key) -> role
+
+// Escrow Accounting
+pub const ESCROW_TOTALS: Map<String, Uint128> = Map::new("escrow_totals"); // denom -> amount held
+pub const USER_ESCROWED: Map<(String, String), Uint128> = Map::new("user_escrowed"); // (username, denom) -> amount held
+pub const ESCROW_LOSSES: Map<String, Uint128> = Map::new("escrow_losses"); // denom -> principal lost by yield adapters
+
+// Price Oracle (price-quoted records cannot be created while unset)
+pub const PRICE_ORACLE: Item<Addr> = Item::new("price_oracle");
+
+// Feature Flags (every payment and proof type is enabled while unset)
+pub const FEATURE_FLAGS: Item<FeatureFlags> = Item::new("feature_flags");
+
+// Policy Contract (fund movements are permissionless while unset)
+pub const POLICY_CONTRACT: Item<Addr> = Item::new("policy_contract");
+
+// DEX Router (payout swaps are paid unconverted while unset)
+pub const DEX_ROUTER: Item<Addr> = Item::new("dex_router");
+pub const PENDING_PAYOUT_SWAPS: Map<u64, PendingPayoutSwap> = Map::new("pending_payout_swaps_by_reply"); // reply_id -> swap
+pub const PAYOUT_SWAP_REPLY_SEQ: Item<u64> = Item::new("payout_swap_reply_seq");
+
+// Yield Routing (disabled while no config is set)
+pub const YIELD_CONFIG: Item<YieldConfig> = Item::new("yield_config");
+pub const YIELD_POSITIONS: Map<u64, YieldPosition> = Map::new("yield_positions"); // task_id -> deposited escrow
+
+// Idempotency Keys
+pub const IDEMPOTENCY_KEYS: Map<(Addr, String), IdempotencyRecord> = Map::new("idempotency_keys"); // (sender, key) -> record created
+pub const IDEMPOTENCY_EXPIRIES: Map<(u64, Addr, String), bool> = Map::new("idempotency_expiries"); // (expires_at, sender, key) -> stored key
+
+// Insurance Pool (no new cover while no config is set)
+pub const INSURANCE_CONFIG: Item<InsuranceConfig> = Item::new("insurance_config");
+pub const INSURANCE_POOL: Map<String, Uint128> = Map::new("insurance_pool"); // denom -> pool balance
+pub const INSURANCE_OPEN_CLAIMS: Map<String, Uint128> = Map::new("insurance_open_claims"); // denom -> max payout of open claims
+
+// Arbiters (disputes go to the owner and dispute resolvers while no config is set)
+pub const ARBITER_CONFIG: Item<ArbiterConfig> = Item::new("arbiter_config");
+pub const ARBITERS: Map<Addr, Arbiter> = Map::new("arbiters"); // bonded dispute resolvers
+
+// Relayed Execution
+pub const RELAYERS: Map<Addr, bool> = Map::new("relayers"); // addresses allowed to submit ExecuteSigned
+pub const SIGNER_NONCES: Map<String, u64> = Map::new("signer_nonces"); // username -> next expected nonce
+
+// Completion Callbacks
+pub const PAYMENT_CALLBACKS: Map<u64, Callback> = Map::new("payment_callbacks");
+pub const TASK_CALLBACKS: Map<u64, Callback> = Map::new("task_callbacks");
+
+// Archive
+pub const ARCHIVE: Item<ArchiveState> = Item::new("archive");
+
+// Notifications
+pub const NOTIFICATION_SEQ: Item<u64> = Item::new("notification_seq"); // last assigned notification id
+pub const NOTIFICATIONS: Map<(String, u64), Notification> = Map::new("notifications"); // (username, id) -> unacknowledged notification
+pub const NOTIFICATIONS_ACKED: Map<String, u64> = Map::new("notifications_acked"); // username -> highest acknowledged id
+
+// Activity Log
+pub const ACTIVITY_SEQ: Item<u64> = Item::new("activity_seq"); // last assigned sequence number
+pub const ACTIVITY_LOG: Map<u64, ActivityEntry> = Map::new("activity_log");
@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Addr, Coin, Empty, Uint128};
+use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
+
+use crate::helpers::SocialPaymentContract;
+use crate::ContractError;
+use crate::msg::{ExecuteMsg, InstantiateMsg, PaymentResponse, QueryMsg, TaskResponse};
+use crate::state::{Payment, ProofType, Task, TaskStatus};
+
+/// Review window given to tasks created by scenarios
+pub const SCENARIO_REVIEW_WINDOW_SECS: u64 = 3600;
+/// Time until the deadline of tasks created by scenarios
+pub const SCENARIO_TASK_DURATION_SECS: u64 = 86400;
+
+/// Wrap the contract entry points for cw-multi-test
+pub fn contract_template() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        crate::contract::execute,
+        crate::contract::instantiate,
+        crate::contract::query,
+    )
+    .with_reply(crate::contract::reply);
+    Box::new(contract)
+}
+
+/// Store and instantiate the contract with `owner` as its owner
+pub fn instantiate(app: &mut App, owner: &Addr) -> SocialPaymentContract {
+    let code_id = app.store_code(contract_template());
+    let contract_addr = app
+        .instantiate_contract(code_id, owner.clone(), &InstantiateMsg {}, &[], "social-payment", None)
+        .unwrap();
+    SocialPaymentContract(contract_addr)
+}
+
+/// Register `wallet` under `username`, using the username as display name
+pub fn register_user(app: &mut App, contract: &SocialPaymentContract, wallet: &Addr, username: &str) {
+    let msg = ExecuteMsg::RegisterUser {
+        username: username.to_string(),
+        display_name: username.to_string(),
+    };
+    app.execute_contract(wallet.clone(), contract.addr(), &msg, &[])
+        .unwrap();
+}
+
+/// Value of the first `key` attribute in any event of `res`
+pub fn attribute(res: &AppResponse, key: &str) -> Option<String> {
+    res.events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == key)
+        .map(|attr| attr.value.clone())
+}
+
+/// Make two registered users friends by sending and accepting a friend request
+pub fn make_friends(
+    app: &mut App,
+    contract: &SocialPaymentContract,
+    (from_wallet, from_username): (&Addr, &str),
+    (to_wallet, to_username): (&Addr, &str),
+) {
+    let send_request = ExecuteMsg::SendFriendRequest {
+        to_username: to_username.to_string(),
+        message: None,
+    };
+    app.execute_contract(from_wallet.clone(), contract.addr(), &send_request, &[])
+        .unwrap();
+
+    let accept_request = ExecuteMsg::AcceptFriendRequest {
+        from_username: from_username.to_string(),
+    };
+    app.execute_contract(to_wallet.clone(), contract.addr(), &accept_request, &[])
+        .unwrap();
+}
+
+/// Builds a `Scenario`: funded and registered users, optionally already friends
+pub struct ScenarioBuilder {
+    owner: Addr,
+    denom: String,
+    users: Vec<(Addr, String, u128)>,
+    friendships: Vec<(String, String)>,
+}
+
+impl Default for ScenarioBuilder {
+    fn default() -> Self {
+        ScenarioBuilder {
+            owner: Addr::unchecked("owner"),
+            denom: "uxion".to_string(),
+            users: vec![],
+            friendships: vec![],
+        }
+    }
+}
+
+impl ScenarioBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_owner(mut self, owner: &str) -> Self {
+        self.owner = Addr::unchecked(owner);
+        self
+    }
+
+    pub fn with_denom(mut self, denom: &str) -> Self {
+        self.denom = denom.to_string();
+        self
+    }
+
+    /// Fund `wallet` with `balance` of the scenario denom and register it as `username`
+    pub fn with_user(mut self, wallet: &str, username: &str, balance: u128) -> Self {
+        self.users.push((Addr::unchecked(wallet), username.to_string(), balance));
+        self
+    }
+
+    /// Make two users added with `with_user` friends
+    pub fn with_friends(mut self, username_a: &str, username_b: &str) -> Self {
+        self.friendships.push((username_a.to_string(), username_b.to_string()));
+        self
+    }
+
+    pub fn build(self) -> Scenario {
+        let mut app = AppBuilder::new().build(|router, _, storage| {
+            for (wallet, _, balance) in &self.users {
+                router
+                    .bank
+                    .init_balance(storage, wallet, vec![Coin::new(*balance, self.denom.clone())])
+                    .unwrap();
+            }
+        });
+        let contract = instantiate(&mut app, &self.owner);
+
+        let mut wallets = BTreeMap::new();
+        for (wallet, username, _) in self.users {
+            register_user(&mut app, &contract, &wallet, &username);
+            wallets.insert(username, wallet);
+        }
+
+        let mut scenario = Scenario {
+            app,
+            contract,
+            owner: self.owner,
+            denom: self.denom,
+            wallets,
+        };
+        for (username_a, username_b) in self.friendships {
+            let wallet_a = scenario.wallet(&username_a);
+            let wallet_b = scenario.wallet(&username_b);
+            make_friends(
+                &mut scenario.app,
+                &scenario.contract,
+                (&wallet_a, &username_a),
+                (&wallet_b, &username_b),
+            );
+        }
+        scenario
+    }
+}
+
+/// A running multi-test app with the contract instantiated and users registered
+pub struct Scenario {
+    pub app: App,
+    pub contract: SocialPaymentContract,
+    pub owner: Addr,
+    pub denom: String,
+    wallets: BTreeMap<String, Addr>, // username -> wallet
+}
+
+impl Scenario {
+    /// Wallet of a user added with `ScenarioBuilder::with_user`
+    pub fn wallet(&self, username: &str) -> Addr {
+        self.wallets
+            .get(username)
+            .unwrap_or_else(|| panic!("unknown scenario user {}", username))
+            .clone()
+    }
+
+    /// A coin of the scenario denom
+    pub fn coin(&self, amount: u128) -> Coin {
+        Coin {
+            denom: self.denom.clone(),
+            amount: Uint128::new(amount),
+        }
+    }
+
+    /// Balance of the scenario denom held by `username`'s wallet
+    pub fn balance(&self, username: &str) -> u128 {
+        self.address_balance(self.wallet(username))
+    }
+
+    /// Balance of the scenario denom held by any address, e.g. a treasury
+    pub fn address_balance(&self, address: impl Into<String>) -> u128 {
+        self.app
+            .wrap()
+            .query_balance(address, &self.denom)
+            .unwrap()
+            .amount
+            .u128()
+    }
+
+    /// Load a task through the contract's `GetTaskById` query
+    pub fn task(&self, task_id: u64) -> Task {
+        let res: TaskResponse = self
+            .app
+            .wrap()
+            .query_wasm_smart(self.contract.addr(), &QueryMsg::GetTaskById { task_id })
+            .unwrap();
+        res.task
+    }
+
+    /// Load a payment through the contract's `GetPaymentById` query
+    pub fn payment(&self, payment_id: u64) -> Payment {
+        let res: PaymentResponse = self
+            .app
+            .wrap()
+            .query_wasm_smart(self.contract.addr(), &QueryMsg::GetPaymentById { payment_id, viewer: None })
+            .unwrap();
+        res.payment
+    }
+
+    /// Execute a message as `username`, attaching `funds`; panics if the execute fails
+    pub fn execute_as(&mut self, username: &str, msg: &ExecuteMsg, funds: &[Coin]) -> AppResponse {
+        let wallet = self.wallet(username);
+        self.app
+            .execute_contract(wallet, self.contract.addr(), msg, funds)
+            .unwrap()
+    }
+
+    /// Execute a message from any `sender` address, e.g. `wallet(username)`, the owner or an
+    /// unregistered wallet, attaching `funds`; returns the contract error if the execute fails
+    pub fn try_execute_as(&mut self, sender: impl Into<String>, msg: &ExecuteMsg, funds: &[Coin]) -> Result<AppResponse, ContractError> {
+        self.app
+            .execute_contract(Addr::unchecked(sender), self.contract.addr(), msg, funds)
+            .map_err(|err| err.downcast::<ContractError>().unwrap())
+    }
+
+    /// Create an escrowed hybrid task from `payer` to `worker` and drive it to `status`.
+    /// Supports Escrowed, PendingRelease, Disputed, Released and Refunded; returns the task id.
+    pub fn escrowed_task(&mut self, payer: &str, worker: &str, amount: u128, status: TaskStatus) -> u64 {
+        let create_task = ExecuteMsg::CreateTask {
+            to_username: worker.to_string(),
+            amount: self.coin(amount),
+            description: "Scenario task".to_string(),
+            proof_type: ProofType::Hybrid,
+            deadline_ts: self.app.block_info().time.seconds() + SCENARIO_TASK_DURATION_SECS,
+            review_window_secs: Some(SCENARIO_REVIEW_WINDOW_SECS),
+            endpoint: "https://api.example.com".to_string(),
+            category: None,
+            tags: vec![],
+            auto_approve_after_secs: None,
+            escrow_upfront: false,
+            co_approvers: vec![],
+            approvals_required: None,
+            price_quote: None,
+            external_ref: None,
+            idempotency_key: None,
+            arbiter: None,
+            depends_on: None,
+            payout_swap: None,
+            release_delay_secs: None,
+        };
+        let funds = [self.coin(amount)];
+        let res = self.execute_as(payer, &create_task, &funds);
+        let task_id: u64 = attribute(&res, "task_id").and_then(|value| value.parse().ok()).unwrap();
+
+        if matches!(status, TaskStatus::Escrowed) {
+            return task_id;
+        }
+        let submit_proof = ExecuteMsg::SubmitZkTlsProof {
+            task_id,
+            proof_blob_or_ref: "valid_scenario_proof".to_string(),
+            zk_proof_hash: "scenario_proof_hash".to_string(),
+        };
+        self.execute_as(worker, &submit_proof, &[]);
+
+        if matches!(status, TaskStatus::PendingRelease) {
+            return task_id;
+        }
+        let dispute = ExecuteMsg::DisputeTask {
+            task_id,
+            reason: None,
+            reason_hash: None,
+        };
+        self.execute_as(payer, &dispute, &[]);
+
+        let worker_bps = match status {
+            TaskStatus::Disputed => return task_id,
+            TaskStatus::Released => 10_000,
+            TaskStatus::Refunded => 0,
+            other => panic!("escrowed scenario tasks cannot be driven to {:?}", other),
+        };
+        let resolve = ExecuteMsg::ResolveDispute { task_id, worker_bps };
+        self.app
+            .execute_contract(self.owner.clone(), self.contract.addr(), &resolve, &[])
+            .unwrap();
+        task_id
+    }
+}